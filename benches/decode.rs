@@ -0,0 +1,66 @@
+//! Compares the ways of decoding opcodes: bit level indexing, shift and mask accessors, and the
+//! precomputed decode table.
+use bitvec::prelude::*;
+use chirp::{
+    instructions::{DecodeTable, Instruction},
+    opcode::OpCode,
+};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::convert::TryFrom;
+
+/// The opcodes of Tetris, a realistic mix of instructions
+fn opcodes() -> Vec<OpCode> {
+    let rom = std::fs::read("./games/tetris.ch8").expect("failed to read tetris.ch8");
+    rom.chunks_exact(2)
+        .map(|w| OpCode::new(u16::from_be_bytes([w[0], w[1]])))
+        .collect()
+}
+
+fn fields(c: &mut Criterion) {
+    let opcodes = opcodes();
+    let mut group = c.benchmark_group("fields");
+    group.bench_function("bitvec", |b| {
+        b.iter(|| {
+            opcodes.iter().fold(0u32, |acc, op| {
+                let x = op[8..12].load::<u8>();
+                let y = op[4..8].load::<u8>();
+                let nnn = op[0..12].load::<u16>();
+                acc.wrapping_add(u32::from(x) + u32::from(y) + u32::from(nnn))
+            })
+        })
+    });
+    group.bench_function("shift", |b| {
+        b.iter(|| {
+            opcodes.iter().fold(0u32, |acc, op| {
+                let (x, y, nnn) = (op.x(), op.y(), op.nnn());
+                acc.wrapping_add(u32::from(x) + u32::from(y) + u32::from(nnn))
+            })
+        })
+    });
+    group.finish();
+}
+
+fn decode(c: &mut Criterion) {
+    let opcodes = opcodes();
+    let table = DecodeTable::new();
+    let mut group = c.benchmark_group("decode");
+    group.bench_function("match", |b| {
+        b.iter(|| {
+            for &op in &opcodes {
+                black_box(Instruction::try_from(black_box(op)).ok());
+            }
+        })
+    });
+    group.bench_function("table", |b| {
+        b.iter(|| {
+            for &op in &opcodes {
+                black_box(table.decode(black_box(op)));
+            }
+        })
+    });
+    group.finish();
+    c.bench_function("decode table construction", |b| b.iter(DecodeTable::new));
+}
+
+criterion_group!(benches, fields, decode);
+criterion_main!(benches);
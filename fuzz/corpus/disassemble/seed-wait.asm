@@ -0,0 +1,7 @@
+; Draw a sprite and wait
+main:   LD I, sprite
+        LD V0, 0x08
+        ld v1, 8
+        DRW V0, V1, 3
+loop:   JP loop
+sprite: DB 0xF0, 0x90, 0xF0
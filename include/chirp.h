@@ -0,0 +1,195 @@
+/* chirp, a CHIP-8 emulator: BSD-3-Clause */
+
+#ifndef CHIRP_H
+#define CHIRP_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The result of a call. Every failure also sets the message returned by
+ * `chirp_machine_last_error`.
+ */
+typedef enum ChirpStatus {
+  CHIRP_STATUS_OK = 0,
+  /**
+   * A pointer argument was null
+   */
+  CHIRP_STATUS_NULL_POINTER = 1,
+  /**
+   * No ROM was loaded
+   */
+  CHIRP_STATUS_NO_ROM = 2,
+  /**
+   * The ROM does not fit in memory
+   */
+  CHIRP_STATUS_ROM_TOO_LARGE = 3,
+  /**
+   * The ROM was loaded outside of the program area
+   */
+  CHIRP_STATUS_INVALID_ADDRESS = 4,
+  /**
+   * The ROM could not be read
+   */
+  CHIRP_STATUS_IO = 5,
+  /**
+   * The program called too many nested subroutines
+   */
+  CHIRP_STATUS_STACK_OVERFLOW = 6,
+  /**
+   * The program returned with an empty stack
+   */
+  CHIRP_STATUS_STACK_UNDERFLOW = 7,
+  /**
+   * The program accessed memory past its end
+   */
+  CHIRP_STATUS_OUT_OF_BOUNDS_ACCESS = 8,
+  /**
+   * The program ran into an unknown opcode
+   */
+  CHIRP_STATUS_UNKNOWN_OP_CODE = 9,
+  /**
+   * The program exited
+   */
+  CHIRP_STATUS_EXIT = 10,
+  /**
+   * The save state is invalid
+   */
+  CHIRP_STATUS_INVALID_STATE = 11,
+  /**
+   * The buffer is too small for the save state
+   */
+  CHIRP_STATUS_BUFFER_TOO_SMALL = 12,
+  /**
+   * There is no such key
+   */
+  CHIRP_STATUS_INVALID_KEY = 13,
+  /**
+   * The Octo cartridge is invalid
+   */
+  CHIRP_STATUS_INVALID_CARTRIDGE = 14,
+} ChirpStatus;
+
+/**
+ * A machine, along with the message describing the last failure
+ */
+typedef struct ChirpMachine ChirpMachine;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Create a machine, with no ROM loaded. Free it with `chirp_machine_free`.
+ */
+struct ChirpMachine *chirp_machine_new(void);
+
+/**
+ * Free a machine created by `chirp_machine_new`, which may be null
+ *
+ * # Safety
+ *
+ * `m` must come from `chirp_machine_new`, and must not be used again.
+ */
+void chirp_machine_free(struct ChirpMachine *m);
+
+/**
+ * Load the `len` bytes at `rom` as a ROM, resetting the machine and using the ROM's recommended
+ * settings
+ *
+ * # Safety
+ *
+ * `m` must be a live machine, and `rom` must point to `len` readable bytes.
+ */
+enum ChirpStatus chirp_machine_load(struct ChirpMachine *m, const uint8_t *rom, size_t len);
+
+/**
+ * Execute `cycles` instructions, stopping at the first fault
+ *
+ * # Safety
+ *
+ * `m` must be a live machine.
+ */
+enum ChirpStatus chirp_machine_run_cycles(struct ChirpMachine *m, uint32_t cycles);
+
+/**
+ * Run a 60Hz frame
+ *
+ * # Safety
+ *
+ * `m` must be a live machine.
+ */
+enum ChirpStatus chirp_machine_run_frame(struct ChirpMachine *m);
+
+/**
+ * The framebuffer, one byte per pixel and row by row, 1 when the pixel is lit. Its dimensions
+ * are written to `width` and `height`, which may be null. Returns null if no ROM was loaded.
+ *
+ * The framebuffer is valid until the machine runs again.
+ *
+ * # Safety
+ *
+ * `m` must be a live machine, and `width` and `height` must be writable if not null.
+ */
+const uint8_t *chirp_machine_framebuffer(const struct ChirpMachine *m,
+                                         uint32_t *width,
+                                         uint32_t *height);
+
+/**
+ * Press `key` (0 through 15) if `pressed` is non-zero, release it otherwise
+ *
+ * # Safety
+ *
+ * `m` must be a live machine.
+ */
+enum ChirpStatus chirp_machine_set_key(struct ChirpMachine *m, uint8_t key, int pressed);
+
+/**
+ * The size of a save state of the machine in bytes, 0 if no ROM was loaded
+ *
+ * # Safety
+ *
+ * `m` must be a live machine.
+ */
+size_t chirp_machine_state_size(const struct ChirpMachine *m);
+
+/**
+ * Write a save state of the machine to the `len` bytes at `buf`, which must hold at least
+ * `chirp_machine_state_size` bytes
+ *
+ * # Safety
+ *
+ * `m` must be a live machine, and `buf` must point to `len` writable bytes.
+ */
+enum ChirpStatus chirp_machine_save_state(struct ChirpMachine *m, uint8_t *buf, size_t len);
+
+/**
+ * Restore the save state in the `len` bytes at `buf`, written by `chirp_machine_save_state`
+ * with the same ROM loaded
+ *
+ * # Safety
+ *
+ * `m` must be a live machine, and `buf` must point to `len` readable bytes.
+ */
+enum ChirpStatus chirp_machine_load_state(struct ChirpMachine *m, const uint8_t *buf, size_t len);
+
+/**
+ * The message describing the last failure, or null if nothing failed yet. The message is valid
+ * until the next call failing on the machine.
+ *
+ * # Safety
+ *
+ * `m` must be a live machine.
+ */
+const char *chirp_machine_last_error(const struct ChirpMachine *m);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* CHIRP_H */
@@ -0,0 +1,165 @@
+//! Static control flow analysis of CHIP-8 programs.
+//!
+//! Starting from the entry point, the analysis follows every statically known path through the
+//! program: fall through, jumps, calls and both outcomes of skips. Words that are reached but are
+//! not valid CHIP-8 instructions are kept, as they may be instructions of an extension, and
+//! execution is assumed to carry on after them. `JP V0, addr` is recorded as an indirect jump, as
+//! its targets depend on the value of V0 at runtime.
+use crate::{instructions::Instruction, opcode::OpCode};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+};
+
+/// A reachable word of the program
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Word {
+    pub opcode: OpCode,
+    /// The decoded instruction, if the word is a valid one
+    pub instruction: Option<Instruction>,
+}
+
+impl Word {
+    /// The size of the instruction in bytes. XO-CHIP's `F000 nnnn` is followed by a 16-bit
+    /// address, every other instruction is a single word.
+    pub fn size(&self) -> u16 {
+        if u16::from(self.opcode) == 0xF000 {
+            4
+        } else {
+            2
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Analysis {
+    /// The address the program was loaded at, which is also its entry point
+    pub origin: u16,
+    /// Every reachable word, keyed by address
+    pub code: BTreeMap<u16, Word>,
+    /// The entry point of every subroutine, with the addresses calling it
+    pub subroutines: BTreeMap<u16, BTreeSet<u16>>,
+    /// The target of every `JP addr`, with the addresses jumping to it
+    pub jumps: BTreeMap<u16, BTreeSet<u16>>,
+    /// The address of every `JP V0, addr`
+    pub indirect_jumps: BTreeSet<u16>,
+}
+
+impl Analysis {
+    /// The addresses execution may continue at after the reachable instruction `word` at `addr`
+    pub fn successors(&self, addr: u16, word: &Word) -> Vec<u16> {
+        let following = self.code.get(&addr.wrapping_add(word.size()));
+        successors(addr, word, following)
+    }
+}
+
+/// The addresses execution may continue at after the instruction at `addr`. A skip jumps over
+/// the `following` word, which is 4 bytes long when it is XO-CHIP's `F000 nnnn`.
+pub fn successors(addr: u16, word: &Word, following: Option<&Word>) -> Vec<u16> {
+    use Instruction::*;
+
+    let next = addr.wrapping_add(word.size());
+    match word.instruction {
+        Some(Jump(target)) => vec![target],
+        Some(Call(target)) => vec![target, next],
+        Some(Return) | Some(Exit) | Some(JumpImmediate(_)) => vec![],
+        Some(i) if i.is_skip() => {
+            let skipped = following.map_or(2, Word::size);
+            vec![next, next.wrapping_add(skipped)]
+        }
+        _ => vec![next],
+    }
+}
+
+/// Analyze `bytes`, loaded in memory at `origin`
+pub fn analyze(bytes: &[u8], origin: u16) -> Analysis {
+    let mut analysis = Analysis {
+        origin,
+        ..Analysis::default()
+    };
+
+    let fetch = |addr: u16| -> Option<Word> {
+        let offset = usize::from(addr.checked_sub(origin)?);
+        let hi = *bytes.get(offset)?;
+        let lo = *bytes.get(offset + 1)?;
+        let opcode = OpCode::new(u16::from_be_bytes([hi, lo]));
+        Some(Word {
+            opcode,
+            instruction: Instruction::try_from(opcode).ok(),
+        })
+    };
+
+    let mut pending = vec![origin];
+    while let Some(addr) = pending.pop() {
+        if analysis.code.contains_key(&addr) {
+            continue;
+        }
+        let word = match fetch(addr) {
+            Some(word) => word,
+            None => continue,
+        };
+        analysis.code.insert(addr, word);
+
+        match word.instruction {
+            Some(Instruction::Call(target)) => {
+                analysis.subroutines.entry(target).or_default().insert(addr);
+            }
+            Some(Instruction::Jump(target)) => {
+                analysis.jumps.entry(target).or_default().insert(addr);
+            }
+            Some(Instruction::JumpImmediate(_)) => {
+                analysis.indirect_jumps.insert(addr);
+            }
+            _ => (),
+        }
+
+        let following = fetch(addr.wrapping_add(word.size()));
+        pending.extend(successors(addr, &word, following.as_ref()));
+    }
+
+    analysis
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::analyze;
+
+    #[test]
+    fn test_analyze() {
+        #[rustfmt::skip]
+        let rom = [
+            0x22, 0x08, // 0x200: CALL 0x208
+            0x30, 0x01, // 0x202: SE V0, 0x01
+            0x12, 0x02, // 0x204: JP 0x202
+            0x00, 0xFD, // 0x206: EXIT
+            0x00, 0xEE, // 0x208: RET
+            0xF0, 0x90, // 0x20A: sprite data
+        ];
+        let analysis = analyze(&rom, 0x200);
+        assert_eq!(
+            vec![0x200, 0x202, 0x204, 0x206, 0x208],
+            analysis.code.keys().copied().collect::<Vec<_>>()
+        );
+        assert!(analysis.subroutines[&0x208].contains(&0x200));
+        assert!(analysis.jumps[&0x202].contains(&0x204));
+    }
+
+    #[test]
+    fn test_skip_long() {
+        #[rustfmt::skip]
+        let rom = [
+            0x30, 0x01,             // 0x200: SE V0, 0x01
+            0xF0, 0x00, 0x12, 0x34, // 0x202: I := long 0x1234
+            0x00, 0xFD,             // 0x206: EXIT
+        ];
+        let analysis = analyze(&rom, 0x200);
+        assert_eq!(
+            vec![0x200, 0x202, 0x206],
+            analysis.code.keys().copied().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![0x202, 0x206],
+            analysis.successors(0x200, &analysis.code[&0x200])
+        );
+    }
+}
@@ -0,0 +1,406 @@
+//! A two pass assembler for the syntax produced by the disassembler.
+//!
+//! Every line holds an optional `label:`, an optional statement and an optional `;` comment.
+//! Statements are either one of the instructions from the table in [`crate::instructions`], using
+//! the same mnemonics and operands the disassembler prints, or one of the data directives:
+//!
+//! | Directive          | Effect                                    |
+//! | ------------------ | ----------------------------------------- |
+//! | `DB byte, ...`     | Emit the given bytes                      |
+//! | `DW word, ...`     | Emit the given words, most significant first |
+//!
+//! Numbers may be written in decimal, hexadecimal (`0x`) or binary (`0b`), and addresses may be
+//! given as labels. Mnemonics and register names are case insensitive, labels are not.
+use crate::{
+    instructions::Instruction,
+    opcode::OpCode,
+    source_map::{Location, SourceMap},
+    symbols::{parse_number, DataRegion, SymbolTable},
+};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AssemblerError {
+    #[error("{0}: unknown mnemonic `{1}`")]
+    UnknownMnemonic(Location, String),
+    #[error("{0}: invalid operands for `{1}`")]
+    InvalidOperands(Location, String),
+    #[error("{0}: undefined label `{1}`")]
+    UndefinedLabel(Location, String),
+    #[error("{0}: label `{1}` is already defined")]
+    DuplicateLabel(Location, String),
+    #[error("{0}: value {1:#X} is out of range")]
+    OutOfRange(Location, u16),
+}
+
+/// The output of the assembler
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Assembly {
+    /// The address the program is meant to be loaded at
+    pub origin: u16,
+    pub bytes: Vec<u8>,
+    /// Every label, plus a data region for each `DB`/`DW` directive
+    pub symbols: SymbolTable,
+    pub source_map: SourceMap,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand<'a> {
+    V(u8),
+    I,
+    IndirectI,
+    DT,
+    ST,
+    K,
+    F,
+    B,
+    R,
+    Value(&'a str),
+}
+
+impl<'a> Operand<'a> {
+    fn parse(s: &'a str) -> Self {
+        match s.to_ascii_uppercase().as_str() {
+            "I" => Operand::I,
+            "[I]" => Operand::IndirectI,
+            "DT" => Operand::DT,
+            "ST" => Operand::ST,
+            "K" => Operand::K,
+            "F" => Operand::F,
+            "B" => Operand::B,
+            "R" => Operand::R,
+            reg if reg.len() == 2 && reg.starts_with('V') => {
+                match u8::from_str_radix(&reg[1..], 16) {
+                    Ok(x) => Operand::V(x),
+                    Err(_) => Operand::Value(s),
+                }
+            }
+            _ => Operand::Value(s),
+        }
+    }
+}
+
+/// A single source line, split into its parts
+struct Statement<'a> {
+    location: Location,
+    label: Option<&'a str>,
+    mnemonic: Option<String>,
+    operands: Vec<Operand<'a>>,
+}
+
+impl<'a> Statement<'a> {
+    fn parse(line: &'a str, location: Location) -> Self {
+        let line = line.split(';').next().unwrap_or_default().trim();
+        let (label, rest) = match line.find(':') {
+            Some(idx) => (Some(line[..idx].trim()), line[idx + 1..].trim()),
+            None => (None, line),
+        };
+
+        let mut parts = rest.splitn(2, char::is_whitespace);
+        let mnemonic = parts
+            .next()
+            .filter(|m| !m.is_empty())
+            .map(str::to_ascii_uppercase);
+        let operands = parts
+            .next()
+            .map(|ops| ops.split(',').map(str::trim).map(Operand::parse).collect())
+            .unwrap_or_default();
+
+        Self {
+            location,
+            label,
+            mnemonic,
+            operands,
+        }
+    }
+
+    /// The amount of bytes the statement assembles to
+    fn size(&self) -> usize {
+        match self.mnemonic.as_deref() {
+            None => 0,
+            Some("DB") => self.operands.len(),
+            Some("DW") => 2 * self.operands.len(),
+            Some(_) => 2,
+        }
+    }
+}
+
+struct Assembler<'a> {
+    labels: &'a HashMap<&'a str, u16>,
+}
+
+impl Assembler<'_> {
+    fn value(&self, stmt: &Statement, s: &str, max: u16) -> Result<u16, AssemblerError> {
+        let value = match parse_number(s).or_else(|| self.labels.get(s).copied()) {
+            Some(value) => value,
+            None if s.starts_with(|c: char| c.is_ascii_digit()) => {
+                return Err(self.invalid(stmt));
+            }
+            None => {
+                return Err(AssemblerError::UndefinedLabel(
+                    stmt.location.clone(),
+                    s.to_string(),
+                ))
+            }
+        };
+
+        if value > max {
+            Err(AssemblerError::OutOfRange(stmt.location.clone(), value))
+        } else {
+            Ok(value)
+        }
+    }
+
+    fn addr(&self, stmt: &Statement, s: &str) -> Result<u16, AssemblerError> {
+        self.value(stmt, s, 0xFFF)
+    }
+
+    fn byte(&self, stmt: &Statement, s: &str) -> Result<u8, AssemblerError> {
+        self.value(stmt, s, 0xFF).map(|v| v as u8)
+    }
+
+    fn nibble(&self, stmt: &Statement, s: &str) -> Result<u8, AssemblerError> {
+        self.value(stmt, s, 0xF).map(|v| v as u8)
+    }
+
+    fn invalid(&self, stmt: &Statement) -> AssemblerError {
+        AssemblerError::InvalidOperands(
+            stmt.location.clone(),
+            stmt.mnemonic.clone().unwrap_or_default(),
+        )
+    }
+
+    fn instruction(&self, stmt: &Statement) -> Result<Instruction, AssemblerError> {
+        use Instruction::*;
+        use Operand::*;
+
+        let mnemonic = stmt.mnemonic.as_deref().unwrap_or_default();
+        let instruction = match (mnemonic, stmt.operands.as_slice()) {
+            ("SCD", [Value(k)]) => ScrollDown(self.nibble(stmt, k)?),
+            ("SCR", []) => ScrollRight,
+            ("SCL", []) => ScrollLeft,
+            ("EXIT", []) => Exit,
+            ("LOW", []) => LowRes,
+            ("HIGH", []) => HighRes,
+            ("CLS", []) => ClearScreen,
+            ("RET", []) => Return,
+            ("JP", [Value(addr)]) => Jump(self.addr(stmt, addr)?),
+            ("JP", [V(0), Value(addr)]) => JumpImmediate(self.addr(stmt, addr)?),
+            ("CALL", [Value(addr)]) => Call(self.addr(stmt, addr)?),
+            ("SE", [V(x), Value(kk)]) => SkipEqualImmediate(*x, self.byte(stmt, kk)?),
+            ("SE", [V(x), V(y)]) => SkipEqual(*x, *y),
+            ("SNE", [V(x), Value(kk)]) => SkipNotEqualImmediate(*x, self.byte(stmt, kk)?),
+            ("SNE", [V(x), V(y)]) => SkipNotEqual(*x, *y),
+            ("LD", [V(x), Value(kk)]) => LoadImmediate(*x, self.byte(stmt, kk)?),
+            ("LD", [V(x), V(y)]) => Load(*x, *y),
+            ("LD", [I, Value(addr)]) => LoadI(self.addr(stmt, addr)?),
+            ("LD", [V(x), DT]) => LoadDTIntoV(*x),
+            ("LD", [V(x), K]) => LoadKey(*x),
+            ("LD", [DT, V(x)]) => LoadVIntoDT(*x),
+            ("LD", [ST, V(x)]) => LoadVIntoST(*x),
+            ("LD", [F, V(x)]) => LoadSpriteIntoI(*x),
+            ("LD", [B, V(x)]) => LoadBCDIntoI(*x),
+            ("LD", [IndirectI, V(x)]) => LoadVIntoMem(*x),
+            ("LD", [V(x), IndirectI]) => LoadMemIntoV(*x),
+            ("LD", [R, V(x)]) => LoadVIntoFlags(*x),
+            ("LD", [V(x), R]) => LoadFlagsIntoV(*x),
+            ("ADD", [V(x), Value(kk)]) => AddImmediate(*x, self.byte(stmt, kk)?),
+            ("ADD", [V(x), V(y)]) => Add(*x, *y),
+            ("ADD", [I, V(x)]) => AddI(*x),
+            ("OR", [V(x), V(y)]) => Or(*x, *y),
+            ("AND", [V(x), V(y)]) => And(*x, *y),
+            ("XOR", [V(x), V(y)]) => Xor(*x, *y),
+            ("SUB", [V(x), V(y)]) => Sub(*x, *y),
+            ("SUBN", [V(x), V(y)]) => SubNumeric(*x, *y),
+            ("SHR", [V(x)]) => ShiftRight(*x, *x),
+            ("SHR", [V(x), V(y)]) => ShiftRight(*x, *y),
+            ("SHL", [V(x)]) => ShiftLeft(*x, *x),
+            ("SHL", [V(x), V(y)]) => ShiftLeft(*x, *y),
+            ("RND", [V(x), Value(kk)]) => Random(*x, self.byte(stmt, kk)?),
+            ("DRW", [V(x), V(y), Value(k)]) => Draw(*x, *y, self.nibble(stmt, k)?),
+            ("SKP", [V(x)]) => SkipOnKey(*x),
+            ("SNKP", [V(x)]) | ("SKNP", [V(x)]) => SkipNotOnKey(*x),
+            (
+                "SCD" | "SCR" | "SCL" | "EXIT" | "LOW" | "HIGH" | "CLS" | "RET" | "JP" | "CALL"
+                | "SE" | "SNE" | "LD" | "ADD" | "OR" | "AND" | "XOR" | "SUB" | "SUBN" | "SHR"
+                | "SHL" | "RND" | "DRW" | "SKP" | "SNKP" | "SKNP",
+                _,
+            ) => return Err(self.invalid(stmt)),
+            _ => {
+                return Err(AssemblerError::UnknownMnemonic(
+                    stmt.location.clone(),
+                    mnemonic.to_string(),
+                ))
+            }
+        };
+
+        Ok(instruction)
+    }
+
+    fn emit(&self, stmt: &Statement, bytes: &mut Vec<u8>) -> Result<(), AssemblerError> {
+        match stmt.mnemonic.as_deref() {
+            None => (),
+            Some("DB") => {
+                for op in &stmt.operands {
+                    match op {
+                        Operand::Value(v) => bytes.push(self.byte(stmt, v)?),
+                        _ => return Err(self.invalid(stmt)),
+                    }
+                }
+            }
+            Some("DW") => {
+                for op in &stmt.operands {
+                    match op {
+                        Operand::Value(v) => {
+                            bytes.extend_from_slice(&self.value(stmt, v, 0xFFFF)?.to_be_bytes())
+                        }
+                        _ => return Err(self.invalid(stmt)),
+                    }
+                }
+            }
+            Some(_) => {
+                let opcode = OpCode::from(self.instruction(stmt)?);
+                bytes.extend_from_slice(&u16::from(opcode).to_be_bytes());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Assemble `source`, read from `file`, into a program loaded at 0x200
+pub fn assemble(source: &str, file: &str) -> Result<Assembly, AssemblerError> {
+    const ORIGIN: u16 = 0x200;
+
+    let statements: Vec<Statement> = source
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| {
+            let location = Location {
+                file: file.to_string(),
+                line: idx as u32 + 1,
+            };
+            Statement::parse(line, location)
+        })
+        .collect();
+
+    // First pass: lay out the program to find the address of every label
+    let mut labels = HashMap::new();
+    let mut symbols = SymbolTable::new();
+    let mut addr = ORIGIN;
+    for stmt in &statements {
+        if let Some(label) = stmt.label {
+            if labels.insert(label, addr).is_some() {
+                return Err(AssemblerError::DuplicateLabel(
+                    stmt.location.clone(),
+                    label.to_string(),
+                ));
+            }
+            symbols.insert_label(addr, label);
+        }
+        addr = addr.wrapping_add(stmt.size() as u16);
+    }
+
+    // Second pass: emit the program
+    let assembler = Assembler { labels: &labels };
+    let mut bytes = Vec::new();
+    let mut source_map = SourceMap::new();
+    for stmt in &statements {
+        let addr = ORIGIN.wrapping_add(bytes.len() as u16);
+        assembler.emit(stmt, &mut bytes)?;
+
+        match stmt.mnemonic.as_deref() {
+            None => (),
+            Some("DB") | Some("DW") => symbols.insert_data(DataRegion {
+                start: addr,
+                length: stmt.size() as u16,
+                name: None,
+            }),
+            Some(_) => source_map.insert(addr, stmt.location.clone()),
+        }
+    }
+
+    Ok(Assembly {
+        origin: ORIGIN,
+        bytes,
+        symbols,
+        source_map,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        assembler::{assemble, AssemblerError},
+        disassembler::disassemble,
+        source_map::Location,
+    };
+
+    const PROGRAM: &str = "\
+; Draw a sprite and wait
+main:   LD I, sprite
+        LD V0, 0x08
+        ld v1, 8
+        DRW V0, V1, 3
+loop:   JP loop
+sprite: DB 0xF0, 0x90, 0xF0
+";
+
+    #[test]
+    fn test_assemble() {
+        let assembly = assemble(PROGRAM, "wait.asm").unwrap();
+        assert_eq!(
+            vec![0xA2, 0x0A, 0x60, 0x08, 0x61, 0x08, 0xD0, 0x13, 0x12, 0x08, 0xF0, 0x90, 0xF0],
+            assembly.bytes
+        );
+        assert_eq!(Some(0x20A), assembly.symbols.address("sprite"));
+        assert!(assembly.symbols.data(0x20C).is_some());
+        assert_eq!(
+            Some(&Location {
+                file: "wait.asm".to_string(),
+                line: 6
+            }),
+            assembly.source_map.get(0x208)
+        );
+    }
+
+    #[test]
+    fn test_disassembly_round_trip() {
+        let assembly = assemble(PROGRAM, "wait.asm").unwrap();
+        let source: String = disassemble(&assembly.bytes[..10], 0x200)
+            .iter()
+            .map(|line| format!("{}\n", line.instruction.unwrap()))
+            .collect();
+        assert_eq!(
+            &assembly.bytes[..10],
+            &assemble(&source, "round-trip.asm").unwrap().bytes[..]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let location = |line| Location {
+            file: "bad.asm".to_string(),
+            line,
+        };
+        assert_eq!(
+            Err(AssemblerError::UnknownMnemonic(location(1), "MOV".into())),
+            assemble("MOV V0, V1", "bad.asm")
+        );
+        assert_eq!(
+            Err(AssemblerError::InvalidOperands(location(2), "LD".into())),
+            assemble("CLS\nLD DT, 5", "bad.asm")
+        );
+        assert_eq!(
+            Err(AssemblerError::UndefinedLabel(
+                location(1),
+                "nowhere".into()
+            )),
+            assemble("JP nowhere", "bad.asm")
+        );
+        assert_eq!(
+            Err(AssemblerError::OutOfRange(location(1), 0x100)),
+            assemble("LD V0, 256", "bad.asm")
+        );
+    }
+}
@@ -0,0 +1,394 @@
+//! Running many independent machines at once.
+//!
+//! A [`Batch`] splits its machines across threads and runs them all for the same frames, one frame
+//! at a time in lockstep. The machines share nothing, so each of them ends up exactly where it
+//! would have had it run alone, whatever the amount of threads.
+//!
+//! A snapshot of a whole batch can be taken as [`Lanes`], a structure of arrays holding one field
+//! of every machine per array, with the variable sized parts such as memories, displays and stacks
+//! packed into a single buffer each at a fixed stride per machine. That is the compact form to
+//! hand observations or snapshots over to something processing the whole batch at once. Machines
+//! do not run from lanes, they are only a snapshot format: [`Batch::load_lanes`] restores the
+//! machines from one.
+use crate::{
+    display::Display,
+    keypad::Keypad,
+    machine::{Machine, MachineError},
+    memory::Memory,
+    register::Register,
+    state::State,
+};
+use std::{num::NonZeroUsize, sync::Barrier, thread};
+
+/// Machines running in lockstep across threads
+#[derive(Clone, Debug)]
+pub struct Batch {
+    machines: Vec<Machine>,
+    /// The amount of instructions each machine executed
+    instructions: Vec<u64>,
+    threads: usize,
+}
+
+impl Batch {
+    /// A batch of `machines`, using as many threads as there are CPUs
+    pub fn new(machines: Vec<Machine>) -> Self {
+        Self {
+            instructions: vec![0; machines.len()],
+            machines,
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        }
+    }
+
+    /// Split the machines across `threads` threads
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.machines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.machines.is_empty()
+    }
+
+    pub fn machines(&self) -> &[Machine] {
+        &self.machines
+    }
+
+    pub fn machines_mut(&mut self) -> &mut [Machine] {
+        &mut self.machines
+    }
+
+    /// The amount of instructions executed by each machine
+    pub fn instructions(&self) -> &[u64] {
+        &self.instructions
+    }
+
+    /// Run every machine for `frames` frames. Before each frame, `input` is given the index of
+    /// the machine and of the frame and returns the keys held down, one bit per key. The
+    /// machines run in lockstep: every machine finishes a frame before any of them starts the
+    /// next one, so `input` may depend on where the other machines are. A machine that halts
+    /// stays halted for the remaining frames.
+    pub fn run<F>(&mut self, frames: u32, input: F)
+    where
+        F: Fn(usize, u32) -> u16 + Sync,
+    {
+        let chunk = self.machines.len().div_ceil(self.threads).max(1);
+        let input = &input;
+        let barrier = &Barrier::new(self.machines.len().div_ceil(chunk));
+        thread::scope(|scope| {
+            let chunks = self
+                .machines
+                .chunks_mut(chunk)
+                .zip(self.instructions.chunks_mut(chunk))
+                .enumerate();
+            for (n, (machines, instructions)) in chunks {
+                scope.spawn(move || {
+                    let mut halted = vec![false; machines.len()];
+                    for frame in 0..frames {
+                        let machines = machines.iter_mut().zip(instructions.iter_mut());
+                        for (offset, (machine, count)) in machines.enumerate() {
+                            if halted[offset] {
+                                continue;
+                            }
+                            machine
+                                .keypad_mut()
+                                .set_bits(input(n * chunk + offset, frame));
+                            let result = machine.run_frame_with(|machine| {
+                                *count += 1;
+                                machine.step()
+                            });
+                            if result.is_err() {
+                                // The failed step didn't execute anything
+                                *count -= 1;
+                                halted[offset] = true;
+                            }
+                        }
+                        barrier.wait();
+                    }
+                });
+            }
+        });
+    }
+
+    /// Run a single frame on every machine, holding down `keys[n]` on machine `n`
+    pub fn run_frame(&mut self, keys: &[u16]) {
+        assert_eq!(self.machines.len(), keys.len());
+        self.run(1, |index, _| keys[index]);
+    }
+
+    /// The fault that halted each machine, if any
+    pub fn faults(&self) -> Vec<Option<MachineError>> {
+        self.machines
+            .iter()
+            .map(|machine| machine.fault().copied())
+            .collect()
+    }
+
+    /// The state of every machine
+    pub fn lanes(&self) -> Lanes {
+        let states: Vec<State> = self.machines.iter().map(Machine::save_state).collect();
+        Lanes::from_states(&states)
+    }
+
+    /// Restore the state of every machine from `lanes`, which must hold as many machines as the
+    /// batch
+    pub fn load_lanes(&mut self, lanes: &Lanes) {
+        assert_eq!(self.machines.len(), lanes.len());
+        for (index, machine) in self.machines.iter_mut().enumerate() {
+            machine.load_state(lanes.state(index));
+        }
+    }
+}
+
+/// A snapshot of many machines as a structure of arrays, holding the same as a [`State`] for each.
+/// Each field is a single buffer whatever the amount of machines.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Lanes {
+    pub v: Vec<[u8; 0x10]>,
+    pub i: Vec<u16>,
+    pub pc: Vec<u16>,
+    pub dt: Vec<u8>,
+    pub st: Vec<u8>,
+    pub sp: Vec<usize>,
+    /// The depth of the stack of each machine
+    pub stack_depth: Vec<usize>,
+    /// The return addresses per machine in `stacks`, the depth of the deepest stack
+    pub stack_stride: usize,
+    /// `stack_stride` return addresses per machine, padded with zeros
+    pub stacks: Vec<u16>,
+    /// `Memory::MEMORY_SIZE` bytes per machine
+    pub memory: Vec<u8>,
+    pub hires: Vec<bool>,
+    /// `Lanes::PIXELS` bytes per machine, padded like in a save state when in low resolution
+    pub pixels: Vec<u8>,
+    /// One bit per key held down
+    pub keys: Vec<u16>,
+    /// The amount of user flags of each machine
+    pub flag_count: Vec<usize>,
+    /// The user flags per machine in `flags`, the most any machine has
+    pub flag_stride: usize,
+    /// `flag_stride` bytes per machine, padded with zeros
+    pub flags: Vec<u8>,
+    pub rng: Vec<u64>,
+    pub vblank_wait: Vec<bool>,
+    pub fault: Vec<Option<MachineError>>,
+}
+
+impl Lanes {
+    /// The amount of pixels kept for each machine
+    pub const PIXELS: usize = Display::HIRES_WIDTH * Display::HIRES_HEIGHT;
+
+    pub fn from_states(states: &[State]) -> Self {
+        let stack_stride = states.iter().map(|s| s.register.stack.len()).max();
+        let flag_stride = states.iter().map(|s| s.flags.len()).max();
+        let mut lanes = Self {
+            stack_stride: stack_stride.unwrap_or(0),
+            flag_stride: flag_stride.unwrap_or(0),
+            memory: Vec::with_capacity(states.len() * Memory::MEMORY_SIZE),
+            pixels: Vec::with_capacity(states.len() * Self::PIXELS),
+            ..Self::default()
+        };
+        for state in states {
+            let r = &state.register;
+            lanes.v.push(r.v);
+            lanes.i.push(r.i);
+            lanes.pc.push(r.pc);
+            lanes.dt.push(r.dt);
+            lanes.st.push(r.st);
+            lanes.sp.push(r.sp);
+            lanes.stack_depth.push(r.stack.len());
+            lanes.stacks.extend_from_slice(&r.stack);
+            let padded = lanes.stacks.len() + lanes.stack_stride - r.stack.len();
+            lanes.stacks.resize(padded, 0);
+            lanes.memory.extend_from_slice(state.memory.as_bytes());
+            lanes.hires.push(state.display.is_hires());
+            let pixels = state.display.pixels();
+            lanes.pixels.extend_from_slice(pixels);
+            let padded = lanes.pixels.len() + Self::PIXELS - pixels.len();
+            lanes.pixels.resize(padded, 0);
+            lanes.keys.push(state.keypad.bits());
+            lanes.flag_count.push(state.flags.len());
+            lanes.flags.extend_from_slice(&state.flags);
+            let padded = lanes.flags.len() + lanes.flag_stride - state.flags.len();
+            lanes.flags.resize(padded, 0);
+            lanes.rng.push(state.rng);
+            lanes.vblank_wait.push(state.vblank_wait);
+            lanes.fault.push(state.fault);
+        }
+        lanes
+    }
+
+    pub fn len(&self) -> usize {
+        self.pc.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pc.is_empty()
+    }
+
+    /// The memory of machine `index`
+    pub fn memory(&self, index: usize) -> &[u8] {
+        &self.memory[index * Memory::MEMORY_SIZE..][..Memory::MEMORY_SIZE]
+    }
+
+    /// The pixels of machine `index`, one byte per pixel and row by row, at its resolution
+    pub fn pixels(&self, index: usize) -> &[u8] {
+        let len = if self.hires[index] {
+            Self::PIXELS
+        } else {
+            Display::WIDTH * Display::HEIGHT
+        };
+        &self.pixels[index * Self::PIXELS..][..len]
+    }
+
+    /// The stack of machine `index`
+    pub fn stack(&self, index: usize) -> &[u16] {
+        &self.stacks[index * self.stack_stride..][..self.stack_depth[index]]
+    }
+
+    /// The user flags of machine `index`
+    pub fn flags(&self, index: usize) -> &[u8] {
+        &self.flags[index * self.flag_stride..][..self.flag_count[index]]
+    }
+
+    /// The state of machine `index`
+    pub fn state(&self, index: usize) -> State {
+        let mut memory = Memory::new();
+        for (address, byte) in self.memory(index).iter().enumerate() {
+            memory
+                .poke(address, *byte)
+                .expect("the image is the size of memory");
+        }
+        let mut keypad = Keypad::new();
+        keypad.set_bits(self.keys[index]);
+        State {
+            register: Register {
+                v: self.v[index],
+                i: self.i[index],
+                dt: self.dt[index],
+                st: self.st[index],
+                pc: self.pc[index],
+                sp: self.sp[index],
+                stack: self.stack(index).to_vec(),
+            },
+            memory,
+            display: Display::from_pixels(self.hires[index], self.pixels(index).to_vec()),
+            keypad,
+            flags: self.flags(index).to_vec(),
+            rng: self.rng[index],
+            vblank_wait: self.vblank_wait[index],
+            fault: self.fault[index],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        batch::Batch,
+        loader::Loader,
+        machine::{Engine, Machine},
+    };
+    use std::{
+        num::NonZeroUsize,
+        sync::atomic::{AtomicBool, AtomicU32, Ordering},
+    };
+
+    fn machines() -> Vec<Machine> {
+        let loader = Loader::new();
+        ["./games/tetris.ch8", "./games/space-invaders.ch8"]
+            .iter()
+            .cycle()
+            .take(6)
+            .enumerate()
+            .map(|(n, path)| {
+                let mut machine = Machine::from_rom(loader.load_path(path).unwrap());
+                machine.seed(n as u64);
+                if n == 1 {
+                    machine = machine.stack_depth(NonZeroUsize::new(24).unwrap());
+                }
+                if n % 3 == 0 {
+                    machine.engine(Engine::Cached)
+                } else {
+                    machine
+                }
+            })
+            .collect()
+    }
+
+    fn input(index: usize, frame: u32) -> u16 {
+        // Hold down a different key every few frames
+        1 << ((index as u32 + frame / 8) % 0x10)
+    }
+
+    #[test]
+    fn test_identical() {
+        let mut alone = machines();
+        for (index, machine) in alone.iter_mut().enumerate() {
+            for frame in 0..300 {
+                machine.keypad_mut().set_bits(input(index, frame));
+                if machine.run_frame().is_err() {
+                    break;
+                }
+            }
+        }
+
+        for threads in 1..=4 {
+            let mut batch = Batch::new(machines()).threads(threads);
+            batch.run(300, input);
+            for (machine, alone) in batch.machines().iter().zip(&alone) {
+                assert_eq!(alone.save_state(), machine.save_state());
+            }
+            assert!(batch.instructions().iter().all(|&n| n > 0));
+        }
+    }
+
+    #[test]
+    fn test_lockstep() {
+        let len = machines().len();
+        let progress: Vec<AtomicU32> = (0..len).map(|_| AtomicU32::new(0)).collect();
+        let out_of_step = AtomicBool::new(false);
+        let mut batch = Batch::new(machines()).threads(3);
+        batch.run(120, |index, frame| {
+            progress[index].store(frame, Ordering::SeqCst);
+            // Every other machine is done with the previous frame and waits for this one. Panicking
+            // here would leave the other threads waiting forever, so only take note.
+            for other in progress.iter() {
+                let other = other.load(Ordering::SeqCst);
+                if other + 1 < frame || other > frame {
+                    out_of_step.store(true, Ordering::SeqCst);
+                }
+            }
+            input(index, frame)
+        });
+        assert!(!out_of_step.load(Ordering::SeqCst));
+        assert!(progress.iter().all(|p| p.load(Ordering::SeqCst) == 119));
+    }
+
+    #[test]
+    fn test_lanes() {
+        let mut batch = Batch::new(machines()).threads(2);
+        batch.run(120, input);
+        let lanes = batch.lanes();
+        assert_eq!(batch.len(), lanes.len());
+        for (index, machine) in batch.machines().iter().enumerate() {
+            assert_eq!(machine.save_state(), lanes.state(index));
+            assert_eq!(machine.display().pixels(), lanes.pixels(index));
+            assert_eq!(machine.memory().as_bytes(), lanes.memory(index));
+            assert_eq!(machine.flags(), lanes.flags(index));
+        }
+        assert_eq!(24, lanes.stack_stride);
+        assert_eq!(16, lanes.stack(0).len());
+        assert_eq!(lanes.len() * lanes.stack_stride, lanes.stacks.len());
+        assert_eq!(lanes.len() * lanes.flag_stride, lanes.flags.len());
+
+        let mut restored = Batch::new(machines());
+        restored.load_lanes(&lanes);
+        batch.run(120, input);
+        restored.run(120, input);
+        assert_eq!(batch.lanes(), restored.lanes());
+    }
+}
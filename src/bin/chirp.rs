@@ -1,12 +1,294 @@
-use chirp::memory;
+use chirp::{
+    batch::Batch,
+    cartridge::{self, Cartridge, Options},
+    database::Database,
+    debugger::Debugger,
+    flags::FileFlagStore,
+    lint,
+    loader::Loader,
+    machine::{Engine, FaultPolicy, Machine},
+    recompiler,
+    symbols::SymbolTable,
+};
+use std::{
+    error::Error,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    sync::Arc,
+    time::Instant,
+};
+use structopt::StructOpt;
 
-use std::convert::TryFrom;
+#[derive(StructOpt)]
+#[structopt(about = "A CHIP-8 emulator")]
+enum Command {
+    /// Show what is known about a ROM and the settings it will run with
+    Info {
+        #[structopt(parse(from_os_str))]
+        rom: PathBuf,
+        /// A chip-8-database programs.json to look the ROM up in, on top of the embedded one
+        #[structopt(long, parse(from_os_str))]
+        database: Option<PathBuf>,
+    },
+    /// Dump the memory with the ROM loaded
+    Dump {
+        #[structopt(parse(from_os_str))]
+        rom: PathBuf,
+    },
+    /// Check a ROM for common programming mistakes
+    Lint {
+        #[structopt(parse(from_os_str))]
+        rom: PathBuf,
+        /// Print the diagnostics as JSON
+        #[structopt(long)]
+        json: bool,
+        /// The address the ROM is loaded at
+        #[structopt(long, default_value = "0x200", parse(try_from_str = parse_address))]
+        address: u16,
+    },
+    /// Run a ROM without a window for a number of frames, then show the screen
+    Run {
+        #[structopt(parse(from_os_str))]
+        rom: PathBuf,
+        /// The amount of 60Hz frames to run for
+        #[structopt(long, default_value = "600")]
+        frames: u32,
+        /// What to do when the program faults: halt, wrap or ignore
+        #[structopt(long, default_value = "halt")]
+        fault_policy: FaultPolicy,
+        /// How to execute instructions: interpreter, cached or table
+        #[structopt(long, default_value = "interpreter")]
+        engine: Engine,
+        /// Profile the run, printing a report after the screen
+        #[structopt(long)]
+        profile: bool,
+        /// Also write the call stacks of the profile in the folded format used by flamegraphs
+        #[structopt(long, parse(from_os_str), requires = "profile")]
+        folded: Option<PathBuf>,
+        /// Track code coverage, printing an annotated disassembly after the screen
+        #[structopt(long)]
+        coverage: bool,
+    },
+    /// Debug a ROM, reading commands such as `break <label>`, `step` and `continue` from the
+    /// standard input
+    Debug {
+        #[structopt(parse(from_os_str))]
+        rom: PathBuf,
+        /// A symbol file naming the addresses of the ROM
+        #[structopt(long, parse(from_os_str))]
+        symbols: Option<PathBuf>,
+    },
+    /// Recompile a ROM to a Rust module running it on the machine
+    Recompile {
+        #[structopt(parse(from_os_str))]
+        rom: PathBuf,
+        /// Where to write the module, instead of the standard output
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+        /// The address the ROM is loaded at
+        #[structopt(long, default_value = "0x200", parse(try_from_str = parse_address))]
+        address: u16,
+    },
+    /// Write an Octo cartridge holding a ROM, to run with the settings it is known to need
+    Cartridge {
+        #[structopt(parse(from_os_str))]
+        rom: PathBuf,
+        /// Where to write the GIF image
+        #[structopt(parse(from_os_str))]
+        output: PathBuf,
+    },
+    /// Measure how many instructions per second a batch of machines runs
+    Bench {
+        /// The ROMs to run, the bundled games by default
+        #[structopt(parse(from_os_str))]
+        roms: Vec<PathBuf>,
+        /// The amount of machines running each ROM
+        #[structopt(long, default_value = "64")]
+        machines: usize,
+        /// The amount of 60Hz frames each machine runs for
+        #[structopt(long, default_value = "600")]
+        frames: u32,
+        /// The amount of threads, as many as there are CPUs by default
+        #[structopt(long)]
+        threads: Option<usize>,
+        /// How to execute instructions: interpreter, cached or table
+        #[structopt(long, default_value = "interpreter")]
+        engine: Engine,
+    },
+}
+
+/// Parse an address in decimal or `0x` prefixed hexadecimal notation
+fn parse_address(s: &str) -> Result<u16, std::num::ParseIntError> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+}
 
 fn main() {
-    let memory = memory::Memory::default();
-    memory.dump();
-    let memory = memory::Memory::try_from("./games/tetris.ch8").unwrap();
-    memory.dump();
+    if let Err(e) = run(Command::from_args()) {
+        // Show the whole chain of errors, the cause is often the interesting part
+        eprintln!("Error: {}", e);
+        let mut source = e.source();
+        while let Some(e) = source {
+            eprintln!("  caused by: {}", e);
+            source = e.source();
+        }
+        std::process::exit(1);
+    }
+}
+
+fn run(command: Command) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Info { rom, database } => {
+            let mut db = Database::embedded();
+            if let Some(path) = database {
+                db.merge(Database::from_path(path)?);
+            }
+            let rom = Loader::new().database(db).load_path(rom)?;
+            print!("{}", rom);
+        }
+        Command::Dump { rom } => {
+            let rom = Loader::new().load_path(rom)?;
+            rom.memory.dump();
+        }
+        Command::Lint { rom, json, address } => {
+            let rom = Loader::new().address(address).load_path(rom)?;
+            let start = usize::from(address);
+            let bytes = &rom.memory.as_bytes()[start..start + rom.info.size];
+            let diagnostics = lint::lint(bytes, address);
+            if json {
+                println!("{}", lint::to_json(&diagnostics));
+            } else {
+                for diagnostic in &diagnostics {
+                    println!("{}", diagnostic);
+                }
+            }
+        }
+        Command::Run {
+            rom,
+            frames,
+            fault_policy,
+            engine,
+            profile,
+            folded,
+            coverage,
+        } => {
+            let rom = Loader::new().load_path(rom)?;
+            let sha1 = rom.info.sha1.clone();
+            let (origin, start) = (rom.info.address, usize::from(rom.info.address));
+            let bytes = rom.memory.as_bytes()[start..start + rom.info.size].to_vec();
+            let mut machine = Machine::from_rom(rom).policy(fault_policy).engine(engine);
+            if profile {
+                machine = machine.enable_profiler();
+            }
+            if coverage {
+                machine = machine.enable_coverage();
+            }
+            if let Some(dir) = FileFlagStore::default_dir() {
+                machine = machine.flag_store(Arc::new(FileFlagStore::new(dir)), &sha1);
+            }
+            for _ in 0..frames {
+                if machine.run_frame().is_err() {
+                    break;
+                }
+            }
+            print!("{}", machine);
+            if let Some(profiler) = machine.profiler() {
+                print!("\n{}", profiler);
+                if let Some(path) = folded {
+                    let f = std::fs::File::create(path)?;
+                    profiler.write_folded(io::BufWriter::new(f))?;
+                }
+            }
+            if let Some(coverage) = machine.coverage() {
+                println!();
+                coverage.write_listing(&bytes, origin, io::stdout().lock())?;
+            }
+        }
+        Command::Debug { rom, symbols } => {
+            let symbols = match symbols {
+                Some(path) => SymbolTable::parse(&std::fs::read_to_string(path)?)?,
+                None => SymbolTable::new(),
+            };
+            let rom = Loader::new().load_path(rom)?;
+            let mut debugger = Debugger::new(Machine::from_rom(rom), symbols);
+            let stdout = io::stdout();
+            debugger.command("where", stdout.lock())?;
+            loop {
+                print!("(chirp) ");
+                io::stdout().flush()?;
+                let mut line = String::new();
+                if io::stdin().lock().read_line(&mut line)? == 0 || line.trim() == "quit" {
+                    break;
+                }
+                if let Err(e) = debugger.command(&line, stdout.lock()) {
+                    println!("{}", e);
+                }
+            }
+        }
+        Command::Recompile {
+            rom,
+            output,
+            address,
+        } => {
+            let name = rom
+                .file_name()
+                .map_or_else(|| rom.display().to_string(), |n| n.to_string_lossy().into());
+            let rom = Loader::new().address(address).load_path(rom)?;
+            match output {
+                Some(path) => {
+                    let f = std::fs::File::create(path)?;
+                    recompiler::write_module(&rom, &name, io::BufWriter::new(f))?;
+                }
+                None => recompiler::write_module(&rom, &name, io::stdout().lock())?,
+            }
+        }
+        Command::Cartridge { rom, output } => {
+            let bytes = std::fs::read(&rom)?;
+            let profile = Loader::new().load(&bytes)?.profile;
+            let cartridge = Cartridge::from_rom(&bytes, Options::from_profile(&profile));
+            let f = std::fs::File::create(output)?;
+            cartridge::write_gif(&cartridge, io::BufWriter::new(f))?;
+        }
+        Command::Bench {
+            mut roms,
+            machines,
+            frames,
+            threads,
+            engine,
+        } => {
+            if roms.is_empty() {
+                roms = vec!["games/tetris.ch8".into(), "games/space-invaders.ch8".into()];
+            }
+            for path in roms {
+                let rom = Loader::new().load_path(&path)?;
+                let machines = (0..machines)
+                    .map(|n| {
+                        let mut machine = Machine::from_rom(rom.clone()).engine(engine);
+                        machine.seed(n as u64);
+                        machine
+                    })
+                    .collect();
+                let mut batch = Batch::new(machines);
+                if let Some(threads) = threads {
+                    batch = batch.threads(threads);
+                }
+
+                let start = Instant::now();
+                batch.run(frames, |_, _| 0);
+                let elapsed = start.elapsed().as_secs_f64();
+                let instructions: u64 = batch.instructions().iter().sum();
+                println!(
+                    "{}: {} instructions in {:.3}s, {:.0} instructions per second",
+                    path.display(),
+                    instructions,
+                    elapsed,
+                    instructions as f64 / elapsed
+                );
+            }
+        }
+    }
 
-    println!("Chirp!")
+    Ok(())
 }
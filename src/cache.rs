@@ -0,0 +1,220 @@
+//! A cache of decoded basic blocks.
+//!
+//! Decoding an opcode is the most expensive part of interpreting an instruction, so the cached
+//! engine decodes each basic block once, the first time execution reaches it, and replays the
+//! decoded instructions from then on. A block runs from its entry point up to and including the
+//! first instruction that may transfer control elsewhere.
+//!
+//! Programs commonly modify their own code, so every write to memory covered by a block throws the
+//! block away, and it is decoded again the next time it is reached.
+use crate::{instructions::Instruction, memory::Memory, opcode::OpCode};
+use std::convert::TryFrom;
+
+/// A decoded instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Op {
+    pub pc: u16,
+    pub opcode: OpCode,
+    /// The decoded instruction, `None` for unknown opcodes
+    pub instruction: Option<Instruction>,
+}
+
+/// A straight line sequence of instructions, only the last of which may branch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Block {
+    pub ops: Vec<Op>,
+}
+
+impl Block {
+    /// Decode the block starting at `pc`, which is empty when `pc` is not in memory
+    fn decode(memory: &Memory, pc: u16) -> Self {
+        let bytes = memory.as_bytes();
+        let mut ops = Vec::new();
+        let mut pc = usize::from(pc);
+
+        while pc + 1 < bytes.len() {
+            let opcode = OpCode::new(u16::from_be_bytes([bytes[pc], bytes[pc + 1]]));
+            let instruction = Instruction::try_from(opcode).ok();
+            ops.push(Op {
+                pc: pc as u16,
+                opcode,
+                instruction,
+            });
+            match instruction {
+                Some(instruction) if !ends_block(instruction) => pc += 2,
+                _ => break,
+            }
+        }
+
+        Self { ops }
+    }
+}
+
+/// Whether execution may continue anywhere but the next instruction after `instruction`
+fn ends_block(instruction: Instruction) -> bool {
+    use Instruction::*;
+    instruction.is_skip()
+        || matches!(
+            instruction,
+            Jump(_) | Call(_) | Return | JumpImmediate(_) | Exit | LoadKey(_)
+        )
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockCache {
+    /// The block starting at each address
+    blocks: Vec<Option<Block>>,
+    /// The entry points of the blocks covering each address
+    owners: Vec<Vec<u16>>,
+    /// The entry point of the block being executed, and the index of the next instruction in it
+    cursor: Option<(u16, usize)>,
+}
+
+impl Default for BlockCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlockCache {
+    pub fn new() -> Self {
+        Self {
+            blocks: vec![None; Memory::MEMORY_SIZE],
+            owners: vec![Vec::new(); Memory::MEMORY_SIZE],
+            cursor: None,
+        }
+    }
+
+    /// The decoded instruction at `pc`, or `None` if `pc` is not in memory
+    pub fn get(&mut self, memory: &Memory, pc: u16) -> Option<Op> {
+        if let Some((start, index)) = self.cursor {
+            let op = self.blocks[usize::from(start)]
+                .as_ref()
+                .and_then(|block| block.ops.get(index))
+                .filter(|op| op.pc == pc);
+            if let Some(op) = op {
+                let op = *op;
+                self.cursor = Some((start, index + 1));
+                return Some(op);
+            }
+        }
+
+        let slot = usize::from(pc);
+        if slot >= Memory::MEMORY_SIZE {
+            return None;
+        }
+        if self.blocks[slot].is_none() {
+            let block = Block::decode(memory, pc);
+            for op in &block.ops {
+                let addr = usize::from(op.pc);
+                self.owners[addr].push(pc);
+                self.owners[addr + 1].push(pc);
+            }
+            self.blocks[slot] = Some(block);
+        }
+
+        let op = *self.blocks[slot].as_ref()?.ops.first()?;
+        self.cursor = Some((pc, 1));
+        Some(op)
+    }
+
+    /// Throw away every block covering `address`, after it was written to
+    pub fn invalidate(&mut self, address: usize) {
+        let starts = match self.owners.get_mut(address) {
+            Some(owners) => std::mem::take(owners),
+            None => return,
+        };
+        for start in starts {
+            self.remove(start);
+        }
+    }
+
+    /// Throw away every block, keeping the tables around
+    pub fn clear(&mut self) {
+        self.blocks.iter_mut().for_each(|block| *block = None);
+        self.owners.iter_mut().for_each(Vec::clear);
+        self.cursor = None;
+    }
+
+    /// Throw away the block starting at `start`, and forget it owns the addresses it covers
+    fn remove(&mut self, start: u16) {
+        if let Some(block) = self.blocks[usize::from(start)].take() {
+            for op in &block.ops {
+                let addr = usize::from(op.pc);
+                for owners in &mut self.owners[addr..addr + 2] {
+                    owners.retain(|owner| *owner != start);
+                }
+            }
+        }
+    }
+
+    /// The amount of blocks decoded
+    pub fn len(&self) -> usize {
+        self.blocks.iter().filter(|b| b.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{cache::BlockCache, instructions::Instruction, memory::Memory};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_blocks() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x01, // 0x200: LD V0, 0x01
+            0x30, 0x01, // 0x202: SE V0, 0x01
+            0x12, 0x00, // 0x204: JP 0x200
+        ];
+        let mut memory = Memory::try_from(&rom[..]).unwrap();
+        let mut cache = BlockCache::new();
+
+        let op = cache.get(&memory, 0x200).unwrap();
+        assert_eq!(Some(Instruction::LoadImmediate(0, 1)), op.instruction);
+        assert_eq!(0x202, cache.get(&memory, 0x202).unwrap().pc);
+        assert_eq!(1, cache.len());
+
+        // The skip ends the block
+        cache.get(&memory, 0x206);
+        assert_eq!(2, cache.len());
+
+        *memory.get_mut(0x203).unwrap() = 0x02;
+        cache.invalidate(0x203);
+        assert_eq!(1, cache.len());
+        cache.get(&memory, 0x200).unwrap();
+        assert_eq!(
+            Some(Instruction::SkipEqualImmediate(0, 2)),
+            cache.get(&memory, 0x202).unwrap().instruction
+        );
+    }
+
+    #[test]
+    fn test_owners() {
+        // A loop rewriting its own operand, as self-modifying programs do every frame
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x01, // 0x200: LD V0, 0x01
+            0x12, 0x00, // 0x202: JP 0x200
+        ];
+        let memory = Memory::try_from(&rom[..]).unwrap();
+        let mut cache = BlockCache::new();
+        for _ in 0..100 {
+            cache.get(&memory, 0x200).unwrap();
+            cache.get(&memory, 0x202).unwrap();
+            cache.invalidate(0x201);
+        }
+        assert!(cache.is_empty());
+        assert!(cache.owners.iter().all(Vec::is_empty));
+
+        cache.get(&memory, 0x200).unwrap();
+        assert_eq!(vec![0x200], cache.owners[0x203]);
+        cache.clear();
+        assert!(cache.is_empty());
+        assert!(cache.owners.iter().all(Vec::is_empty));
+    }
+}
@@ -0,0 +1,347 @@
+//! Octo cartridges: GIF images carrying a program and the settings to run it with.
+//!
+//! Octo shares programs as GIFs with the payload hidden in the pixels. Every color of the
+//! palette is repeated sixteen times, so the low nibble of each pixel's color index can hold
+//! data without changing the image. The pixels of all frames, in order, store one nibble
+//! each, high nibble first. The payload starts with its length as a big endian 32-bit number
+//! and is JSON holding the Octo source of the program along with its options:
+//!
+//! ```json
+//! { "program": ": main ...", "options": { "tickrate": 20, "shiftQuirks": false, ... } }
+//! ```
+//!
+//! Loading a cartridge assembles its source with the [Octo assembler](crate::octo), so it
+//! fails on programs using Octo features the assembler doesn't support.
+use crate::{
+    loader::Profile,
+    octo::{self, OctoError},
+    quirks::{Platform, Quirks},
+};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CartridgeError {
+    #[error("Failed to decode the cartridge image")]
+    Decode(#[from] gif::DecodingError),
+    #[error("The payload is {0} bytes, but the image only holds {1}")]
+    Truncated(usize, usize),
+    #[error("Invalid cartridge payload")]
+    Payload(#[from] serde_json::Error),
+    #[error("Failed to assemble the cartridge program, XO-CHIP programs and `:stringmode` are not supported")]
+    Assemble(#[from] OctoError),
+}
+
+/// The size of each frame of the cartridges written, in pixels
+const WIDTH: u16 = 128;
+const HEIGHT: u16 = 64;
+
+/// The colors Octo displays a program with, as `#RRGGBB` strings
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Palette {
+    #[serde(rename = "backgroundColor")]
+    pub background: String,
+    /// The color of pixels lit in the first plane
+    #[serde(rename = "fillColor")]
+    pub fill: String,
+    /// The color of pixels lit in the second XO-CHIP plane
+    #[serde(rename = "fillColor2")]
+    pub fill2: String,
+    /// The color of pixels lit in both XO-CHIP planes
+    #[serde(rename = "blendColor")]
+    pub blend: String,
+    /// The color of the border while the buzzer sounds
+    #[serde(rename = "buzzColor")]
+    pub buzz: String,
+    /// The color of the border while the buzzer is quiet
+    #[serde(rename = "quietColor")]
+    pub quiet: String,
+}
+
+impl Default for Palette {
+    /// Octo's default palette
+    fn default() -> Self {
+        Self {
+            background: "#996600".to_string(),
+            fill: "#FFCC00".to_string(),
+            fill2: "#FF6600".to_string(),
+            blend: "#662200".to_string(),
+            buzz: "#FFAA00".to_string(),
+            quiet: "#000000".to_string(),
+        }
+    }
+}
+
+/// The options of an Octo cartridge, named like Octo names them
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Options {
+    /// The amount of instructions executed per 60Hz frame
+    pub tickrate: u32,
+    #[serde(flatten)]
+    pub palette: Palette,
+    /// `8xy6`/`8xyE` shift Vx in place
+    pub shift_quirks: bool,
+    /// `Fx55`/`Fx65` leave I unchanged
+    pub load_store_quirks: bool,
+    /// VF is set before the result of `8xy_` is stored, which chirp doesn't emulate
+    pub vf_order_quirks: bool,
+    /// Sprites are clipped at the edges of the screen rather than wrapping around
+    pub clip_quirks: bool,
+    /// `Dxyk` waits for the vertical blank interrupt
+    pub v_blank_quirks: bool,
+    /// `Bxkk` jumps to `xkk + Vx`
+    pub jump_quirks: bool,
+    /// `8xy1`/`8xy2`/`8xy3` reset VF
+    pub logic_quirks: bool,
+    /// The rotation of the screen in degrees
+    pub screen_rotation: u32,
+    /// The largest program Octo accepts, which is how it tells the platforms apart
+    pub max_size: u32,
+    pub touch_input_mode: String,
+    pub font_style: String,
+}
+
+impl Default for Options {
+    /// Octo's defaults
+    fn default() -> Self {
+        Self {
+            tickrate: 20,
+            palette: Palette::default(),
+            shift_quirks: false,
+            load_store_quirks: false,
+            vf_order_quirks: false,
+            clip_quirks: false,
+            v_blank_quirks: false,
+            jump_quirks: false,
+            logic_quirks: false,
+            screen_rotation: 0,
+            max_size: 3584,
+            touch_input_mode: "none".to_string(),
+            font_style: "octo".to_string(),
+        }
+    }
+}
+
+impl Options {
+    /// Options running a program like `profile` does
+    pub fn from_profile(profile: &Profile) -> Self {
+        let max_size = match profile.platform {
+            Platform::Chip8 | Platform::HybridVip | Platform::Chip8X => 3216,
+            Platform::Chip48 | Platform::SuperChip1 | Platform::SuperChip => 3583,
+            Platform::XoChip => 65024,
+            Platform::ModernChip8 | Platform::MegaChip8 => 3584,
+        };
+        Self {
+            tickrate: profile.tickrate,
+            shift_quirks: profile.quirks.shift,
+            load_store_quirks: profile.quirks.memory_leave_i_unchanged,
+            clip_quirks: !profile.quirks.wrap,
+            v_blank_quirks: profile.quirks.vblank,
+            jump_quirks: profile.quirks.jump,
+            logic_quirks: profile.quirks.logic,
+            max_size,
+            ..Self::default()
+        }
+    }
+
+    /// The platform the options target, going by the maximum program size
+    pub fn platform(&self) -> Platform {
+        match self.max_size {
+            3216 => Platform::Chip8,
+            3583 => Platform::SuperChip,
+            65024 => Platform::XoChip,
+            _ => Platform::ModernChip8,
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        Quirks {
+            shift: self.shift_quirks,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: self.load_store_quirks,
+            wrap: !self.clip_quirks,
+            jump: self.jump_quirks,
+            vblank: self.v_blank_quirks,
+            logic: self.logic_quirks,
+        }
+    }
+
+    /// The settings to run the cartridge's program with
+    pub fn profile(&self) -> Profile {
+        Profile {
+            quirks: self.quirks(),
+            tickrate: self.tickrate,
+            ..Profile::for_platform(self.platform())
+        }
+    }
+}
+
+/// A program along with its options
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cartridge {
+    /// The Octo source of the program
+    #[serde(rename = "program")]
+    pub source: String,
+    #[serde(default)]
+    pub options: Options,
+}
+
+impl Cartridge {
+    /// A cartridge holding `rom` as is, as a list of bytes starting at `main`
+    pub fn from_rom(rom: &[u8], options: Options) -> Self {
+        let mut source = String::from(": main\n");
+        for line in rom.chunks(16) {
+            let bytes: Vec<String> = line.iter().map(|b| format!("0x{:02X}", b)).collect();
+            source.push_str(&bytes.join(" "));
+            source.push('\n');
+        }
+        Self { source, options }
+    }
+
+    /// Read the cartridge hidden in a GIF image
+    pub fn from_gif(gif: &[u8]) -> Result<Self, CartridgeError> {
+        let mut reader = gif::Decoder::new(gif).read_info()?;
+        let mut nibbles = Vec::new();
+        while let Some(frame) = reader.read_next_frame()? {
+            nibbles.extend(frame.buffer.iter().map(|index| index & 0xF));
+        }
+        let mut bytes = nibbles.chunks_exact(2).map(|n| n[0] << 4 | n[1]);
+
+        let mut length = [0; 4];
+        for (byte, data) in length.iter_mut().zip(&mut bytes) {
+            *byte = data;
+        }
+        let length = u32::from_be_bytes(length) as usize;
+        let available = (nibbles.len() / 2).saturating_sub(4);
+        if length > available {
+            return Err(CartridgeError::Truncated(length, available));
+        }
+        let payload: Vec<u8> = bytes.take(length).collect();
+        Ok(serde_json::from_slice(&payload)?)
+    }
+
+    /// Assemble the program
+    pub fn program(&self) -> Result<Vec<u8>, CartridgeError> {
+        Ok(octo::assemble(&self.source, "cartridge.8o")?.bytes)
+    }
+}
+
+/// `#RRGGBB` as RGB, black if it isn't a valid color
+fn rgb(color: &str) -> [u8; 3] {
+    let hex = color.trim_start_matches('#');
+    match u32::from_str_radix(hex, 16) {
+        Ok(value) if hex.len() == 6 => {
+            let [_, r, g, b] = value.to_be_bytes();
+            [r, g, b]
+        }
+        _ => [0; 3],
+    }
+}
+
+/// Write `cartridge` as a GIF image, framed in the fill color on the background color of its
+/// palette
+pub fn write_gif<W: io::Write>(cartridge: &Cartridge, w: W) -> io::Result<()> {
+    let mut payload = serde_json::to_vec(cartridge)?;
+    let length = payload.len() as u32;
+    payload.splice(0..0, length.to_be_bytes().iter().copied());
+
+    let palette = &cartridge.options.palette;
+    let colors = [
+        rgb(&palette.background),
+        rgb(&palette.fill),
+        rgb(&palette.fill2),
+        rgb(&palette.blend),
+    ];
+    let mut global_palette = Vec::with_capacity(256 * 3);
+    for index in 0..256 {
+        global_palette.extend_from_slice(colors.get(index >> 4).unwrap_or(&[0; 3]));
+    }
+
+    let mut encoder = gif::Encoder::new(w, WIDTH, HEIGHT, &global_palette)?;
+    let size = usize::from(WIDTH) * usize::from(HEIGHT);
+    let nibbles: Vec<u8> = payload.iter().flat_map(|b| vec![b >> 4, b & 0xF]).collect();
+    for chunk in nibbles.chunks(size) {
+        let pixels: Vec<u8> = (0..size)
+            .map(|idx| {
+                let (x, y) = (idx % usize::from(WIDTH), idx / usize::from(WIDTH));
+                let border =
+                    x < 2 || y < 2 || x >= usize::from(WIDTH) - 2 || y >= usize::from(HEIGHT) - 2;
+                let base = if border { 0x10 } else { 0x00 };
+                base | chunk.get(idx).copied().unwrap_or(0)
+            })
+            .collect();
+        encoder.write_frame(&gif::Frame::from_indexed_pixels(
+            WIDTH, HEIGHT, &pixels, None,
+        ))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cartridge::{write_gif, Cartridge, CartridgeError, Options},
+        loader::Profile,
+        quirks::Platform,
+    };
+
+    #[test]
+    fn test_round_trip() {
+        let rom = std::fs::read("./games/tetris.ch8").unwrap();
+        let options = Options {
+            tickrate: 30,
+            ..Options::from_profile(&Profile::for_platform(Platform::SuperChip))
+        };
+        let cartridge = Cartridge::from_rom(&rom, options);
+
+        let mut gif = Vec::new();
+        write_gif(&cartridge, &mut gif).unwrap();
+        assert_eq!(b"GIF89a", &gif[..6]);
+
+        let loaded = Cartridge::from_gif(&gif).unwrap();
+        assert_eq!(cartridge, loaded);
+        assert_eq!(rom, loaded.program().unwrap());
+        let profile = loaded.options.profile();
+        assert_eq!(Platform::SuperChip, profile.platform);
+        assert_eq!(30, profile.tickrate);
+        assert_eq!(Platform::SuperChip.quirks(), profile.quirks);
+    }
+
+    #[test]
+    fn test_options() {
+        let cartridge: Cartridge = serde_json::from_str(
+            r##"{
+                "program": ": main loop again",
+                "options": { "tickrate": 200, "fillColor": "#FFFFFF", "clipQuirks": true, "maxSize": 65024 }
+            }"##,
+        )
+        .unwrap();
+        let options = &cartridge.options;
+        assert_eq!("#FFFFFF", options.palette.fill);
+        assert_eq!("#996600", options.palette.background);
+        assert_eq!(Platform::XoChip, options.platform());
+        assert!(!options.quirks().wrap);
+        assert_eq!(200, options.profile().tickrate);
+        assert_eq!(vec![0x12, 0x00], cartridge.program().unwrap());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut gif = Vec::new();
+        write_gif(
+            &Cartridge::from_rom(&[0x00, 0xE0], Options::default()),
+            &mut gif,
+        )
+        .unwrap();
+        assert!(matches!(
+            Cartridge::from_gif(&gif[..20]),
+            Err(CartridgeError::Decode(_))
+        ));
+        assert!(matches!(
+            Cartridge::from_gif(&gif[..gif.len() - 1]),
+            Err(CartridgeError::Decode(_))
+        ));
+    }
+}
@@ -0,0 +1,272 @@
+//! Code coverage for CHIP-8 programs.
+//!
+//! Like the profiler, coverage is fed every executed instruction through [`Coverage::record`], by
+//! the machine once [`Machine::enable_coverage`](crate::machine::Machine::enable_coverage) is
+//! called. On top of marking executed addresses it tracks, for every skip instruction, how many
+//! times the skip was taken and how many times it fell through. This is inferred from the address
+//! of the instruction executed right after the skip: anything but the next word means the skip
+//! was taken, whether it skipped a single word or XO-CHIP's 4-byte `F000 nnnn`.
+//!
+//! The result can be rendered as an annotated disassembly listing, or, given a [`SourceMap`], as
+//! an lcov tracefile keyed by the assembler source lines.
+use crate::{disassembler, instructions::Instruction, source_map::SourceMap};
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+/// How many times a skip instruction was taken or not
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Branch {
+    /// The next instruction was skipped
+    pub taken: u64,
+    /// Execution fell through to the next instruction
+    pub not_taken: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Coverage {
+    /// Hits per memory address
+    hits: Vec<u64>,
+    /// Branch statistics per skip instruction address
+    branches: BTreeMap<u16, Branch>,
+    /// The address of the last recorded instruction, if it was a skip
+    pending_skip: Option<u16>,
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Coverage {
+    /// The addressable range of the CHIP-8 memory
+    const ADDRESS_SPACE: usize = 0x1000;
+
+    pub fn new() -> Self {
+        Self {
+            hits: vec![0; Self::ADDRESS_SPACE],
+            branches: BTreeMap::new(),
+            pending_skip: None,
+        }
+    }
+
+    /// Record the execution of `instruction`, located at `pc`. Words that did not decode to an
+    /// instruction are recorded with `None`, they still count as executed and still resolve the
+    /// skip before them.
+    pub fn record(&mut self, pc: u16, instruction: Option<Instruction>) {
+        let pc = pc & 0xFFF;
+
+        if let Some(skip) = self.pending_skip.take() {
+            let branch = self.branches.entry(skip).or_default();
+            if pc == (skip + 2) & 0xFFF {
+                branch.not_taken += 1;
+            } else {
+                branch.taken += 1;
+            }
+        }
+
+        self.hits[pc as usize] += 1;
+        if instruction.is_some_and(|i| i.is_skip()) {
+            self.branches.entry(pc).or_default();
+            self.pending_skip = Some(pc);
+        }
+    }
+
+    pub fn is_executed(&self, addr: u16) -> bool {
+        self.hits(addr) > 0
+    }
+
+    /// How many times the instruction at `addr` was executed
+    pub fn hits(&self, addr: u16) -> u64 {
+        self.hits[(addr & 0xFFF) as usize]
+    }
+
+    /// Branch statistics for the skip instruction at `addr`, if it was ever executed
+    pub fn branch(&self, addr: u16) -> Option<&Branch> {
+        self.branches.get(&(addr & 0xFFF))
+    }
+
+    /// Write an annotated disassembly of `bytes`, located in memory at `origin`.
+    ///
+    /// Every line is prefixed with its hit count, or `#####` if it never ran, and skips are
+    /// suffixed with their taken/not taken counts.
+    pub fn write_listing<W: Write>(&self, bytes: &[u8], origin: u16, mut w: W) -> io::Result<()> {
+        for line in disassembler::disassemble(bytes, origin) {
+            match self.hits(line.address) {
+                0 => write!(w, "{:>10}  ", "#####")?,
+                hits => write!(w, "{:>10}  ", hits)?,
+            }
+            write!(w, "{}", line)?;
+            if let Some(branch) = self.branch(line.address) {
+                write!(
+                    w,
+                    "  [taken {}, not taken {}]",
+                    branch.taken, branch.not_taken
+                )?;
+            }
+            writeln!(w)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write an lcov tracefile, attributing every mapped address to its source line.
+    ///
+    /// Addresses that map to the same line are summed, and skips are reported as a pair of
+    /// branches, the first one being the taken skip.
+    pub fn write_lcov<W: Write>(&self, source_map: &SourceMap, mut w: W) -> io::Result<()> {
+        struct LineCoverage {
+            hits: u64,
+            branches: Vec<Branch>,
+        }
+
+        let mut files: BTreeMap<&str, BTreeMap<u32, LineCoverage>> = BTreeMap::new();
+        for (addr, location) in source_map.iter() {
+            let line = files
+                .entry(&location.file)
+                .or_default()
+                .entry(location.line)
+                .or_insert(LineCoverage {
+                    hits: 0,
+                    branches: Vec::new(),
+                });
+            line.hits += self.hits(addr);
+            if let Some(branch) = self.branch(addr) {
+                line.branches.push(*branch);
+            }
+        }
+
+        writeln!(w, "TN:")?;
+        for (file, lines) in files {
+            writeln!(w, "SF:{}", file)?;
+
+            let (mut found, mut hit) = (0, 0);
+            for (number, line) in &lines {
+                for (block, branch) in line.branches.iter().enumerate() {
+                    let executed = branch.taken + branch.not_taken > 0;
+                    for (idx, count) in [branch.taken, branch.not_taken].iter().enumerate() {
+                        write!(w, "BRDA:{},{},{},", number, block, idx)?;
+                        if executed {
+                            writeln!(w, "{}", count)?;
+                        } else {
+                            writeln!(w, "-")?;
+                        }
+                        found += 1;
+                        if *count > 0 {
+                            hit += 1;
+                        }
+                    }
+                }
+            }
+            writeln!(w, "BRF:{}", found)?;
+            writeln!(w, "BRH:{}", hit)?;
+
+            for (number, line) in &lines {
+                writeln!(w, "DA:{},{}", number, line.hits)?;
+            }
+            writeln!(w, "LF:{}", lines.len())?;
+            writeln!(w, "LH:{}", lines.values().filter(|l| l.hits > 0).count())?;
+            writeln!(w, "end_of_record")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        coverage::{Branch, Coverage},
+        instructions::Instruction::*,
+        source_map::{Location, SourceMap},
+    };
+
+    fn run() -> Coverage {
+        let mut coverage = Coverage::new();
+        // 0x200: SE V0, 0x01 ; 0x202: JP 0x200 ; 0x204: CLS
+        coverage.record(0x200, Some(SkipEqualImmediate(0x0, 0x1)));
+        coverage.record(0x202, Some(Jump(0x200)));
+        coverage.record(0x200, Some(SkipEqualImmediate(0x0, 0x1)));
+        coverage.record(0x204, Some(ClearScreen));
+        coverage
+    }
+
+    #[test]
+    fn test_branches() {
+        let coverage = run();
+        assert_eq!(2, coverage.hits(0x200));
+        assert!(coverage.is_executed(0x204));
+        assert!(!coverage.is_executed(0x206));
+        assert_eq!(
+            Some(&Branch {
+                taken: 1,
+                not_taken: 1
+            }),
+            coverage.branch(0x200)
+        );
+    }
+
+    #[test]
+    fn test_long_skip() {
+        let mut coverage = Coverage::new();
+        // 0x200: SE V0, 0x00 ; 0x202: F000 0x0300 ; 0x206: CLS
+        coverage.record(0x200, Some(SkipEqualImmediate(0x0, 0x0)));
+        coverage.record(0x206, Some(ClearScreen));
+        assert_eq!(1, coverage.branch(0x200).unwrap().taken);
+    }
+
+    #[test]
+    fn test_skip_undecoded() {
+        let mut coverage = Coverage::new();
+        // 0x200: SE V0, 0x01 ; 0x202: an unknown opcode, skipped over ; 0x204: CLS
+        coverage.record(0x200, Some(SkipEqualImmediate(0x0, 0x1)));
+        coverage.record(0x202, None);
+        coverage.record(0x204, Some(ClearScreen));
+        assert!(coverage.is_executed(0x202));
+        assert_eq!(
+            Some(&Branch {
+                taken: 0,
+                not_taken: 1
+            }),
+            coverage.branch(0x200)
+        );
+    }
+
+    #[test]
+    fn test_listing() {
+        let coverage = run();
+        let rom = [0x30, 0x01, 0x12, 0x00, 0x00, 0xE0, 0x00, 0xEE];
+        let mut listing = Vec::new();
+        coverage.write_listing(&rom, 0x200, &mut listing).unwrap();
+        assert_eq!(
+            "         2  0x200  3001  SE V0, 0x01  [taken 1, not taken 1]\n\
+             \x20        1  0x202  1200  JP 0x200\n\
+             \x20        1  0x204  00E0  CLS\n\
+             \x20    #####  0x206  00EE  RET\n",
+            String::from_utf8(listing).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_lcov() {
+        let coverage = run();
+        let mut source_map = SourceMap::new();
+        for (addr, line) in &[(0x200, 1), (0x202, 2), (0x204, 4), (0x206, 5)] {
+            let location = Location {
+                file: "loop.8o".to_string(),
+                line: *line,
+            };
+            source_map.insert(*addr, location);
+        }
+
+        let mut lcov = Vec::new();
+        coverage.write_lcov(&source_map, &mut lcov).unwrap();
+        assert_eq!(
+            "TN:\nSF:loop.8o\nBRDA:1,0,0,1\nBRDA:1,0,1,1\nBRF:2\nBRH:2\n\
+             DA:1,2\nDA:2,1\nDA:4,1\nDA:5,0\nLF:4\nLH:3\nend_of_record\n",
+            String::from_utf8(lcov).unwrap()
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! A database of known ROMs, keyed by the SHA-1 of their image.
+//!
+//! The database uses the `programs.json` format of the community chip-8-database: an array of
+//! programs, each with a title, authors and a set of ROMs keyed by their SHA-1. For each ROM it
+//! lists the platforms it runs on (preferred first), quirk overrides for some of those platforms,
+//! the speed it should run at and the keypad keys it uses.
+//!
+//! ROM collections and other tools often identify images by their CRC-32 instead. The
+//! chip-8-database only lists SHA-1 hashes, so ROMs may also carry an optional `crc32` field, a
+//! hex string, and the database indexes them on it too. The embedded database does so for every
+//! ROM; the SHA-1 is still looked up first.
+//!
+//! A small database covering the bundled games is embedded in the crate, and a full copy of the
+//! community database can be imported from a file with [`Database::from_path`].
+use crate::{
+    loader::Profile,
+    quirks::{Platform, QuirkOverrides},
+};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::Path,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum DatabaseError {
+    #[error("Failed to read database file")]
+    ReadFile(#[source] io::Error),
+    #[error("Failed to parse database")]
+    Parse(#[source] serde_json::Error),
+    #[error("Invalid CRC-32 `{0}`, expected 8 hex digits")]
+    InvalidCrc32(String),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProgramJson {
+    title: String,
+    description: Option<String>,
+    release: Option<String>,
+    #[serde(default)]
+    authors: Vec<String>,
+    roms: BTreeMap<String, RomJson>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RomJson {
+    file: Option<String>,
+    crc32: Option<String>,
+    #[serde(default)]
+    platforms: Vec<Platform>,
+    #[serde(default)]
+    quirky_platforms: BTreeMap<Platform, QuirkOverrides>,
+    tickrate: Option<u32>,
+    #[serde(default)]
+    keys: BTreeMap<String, u8>,
+}
+
+/// Everything known about a single ROM image
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub title: String,
+    pub description: Option<String>,
+    pub release: Option<String>,
+    pub authors: Vec<String>,
+    /// The usual file name of the ROM
+    pub file: Option<String>,
+    /// The platforms the ROM runs on, the preferred one first
+    pub platforms: Vec<Platform>,
+    /// Quirks that differ from the platform's defaults when running the ROM on that platform
+    pub quirky_platforms: BTreeMap<Platform, QuirkOverrides>,
+    /// The amount of instructions per frame, if it differs from the platform's default
+    pub tickrate: Option<u32>,
+    /// The keypad key for each action, e.g. `"left": 4`
+    pub keys: BTreeMap<String, u8>,
+}
+
+impl Entry {
+    /// The recommended settings to run the ROM with
+    pub fn profile(&self) -> Profile {
+        let platform = self.platforms.first().copied().unwrap_or_default();
+        let mut quirks = platform.quirks();
+        if let Some(overrides) = self.quirky_platforms.get(&platform) {
+            quirks = quirks.with_overrides(overrides);
+        }
+
+        Profile {
+            platform,
+            quirks,
+            tickrate: self.tickrate.unwrap_or_else(|| platform.tickrate()),
+            keys: self.keys.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Database {
+    /// Entries keyed by the lowercase hex SHA-1 of the ROM
+    entries: HashMap<String, Entry>,
+    /// The SHA-1 of the ROMs that have a known CRC-32
+    crc32: HashMap<u32, String>,
+}
+
+impl Database {
+    const EMBEDDED: &'static str = include_str!("database/programs.json");
+
+    /// The database embedded in the crate
+    pub fn embedded() -> Self {
+        Self::from_json(Self::EMBEDDED).expect("the embedded database is valid")
+    }
+
+    /// Parse a database in the chip-8-database `programs.json` format
+    pub fn from_json(json: &str) -> Result<Self, DatabaseError> {
+        let programs: Vec<ProgramJson> =
+            serde_json::from_str(json).map_err(DatabaseError::Parse)?;
+
+        let mut entries = HashMap::new();
+        let mut crc32 = HashMap::new();
+        for program in programs {
+            for (sha1, rom) in program.roms {
+                let sha1 = sha1.to_ascii_lowercase();
+                if let Some(crc) = &rom.crc32 {
+                    let value = u32::from_str_radix(crc, 16)
+                        .ok()
+                        .filter(|_| crc.len() == 8)
+                        .ok_or_else(|| DatabaseError::InvalidCrc32(crc.clone()))?;
+                    crc32.insert(value, sha1.clone());
+                }
+                let entry = Entry {
+                    title: program.title.clone(),
+                    description: program.description.clone(),
+                    release: program.release.clone(),
+                    authors: program.authors.clone(),
+                    file: rom.file,
+                    platforms: rom.platforms,
+                    quirky_platforms: rom.quirky_platforms,
+                    tickrate: rom.tickrate,
+                    keys: rom.keys,
+                };
+                entries.insert(sha1, entry);
+            }
+        }
+
+        Ok(Self { entries, crc32 })
+    }
+
+    /// Read a database in the chip-8-database `programs.json` format from a file
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, DatabaseError> {
+        let json = std::fs::read_to_string(path).map_err(DatabaseError::ReadFile)?;
+        Self::from_json(&json)
+    }
+
+    /// Add every entry in `other`, replacing the existing entries for the same ROMs
+    pub fn merge(&mut self, other: Database) {
+        self.entries.extend(other.entries);
+        self.crc32.extend(other.crc32);
+    }
+
+    /// Look up a ROM by its SHA-1, as a hex string
+    pub fn get(&self, sha1: &str) -> Option<&Entry> {
+        self.entries.get(&sha1.to_ascii_lowercase())
+    }
+
+    /// Look up a ROM by its CRC-32, for the ROMs the database knows the CRC-32 of
+    pub fn get_crc32(&self, crc32: u32) -> Option<&Entry> {
+        self.crc32
+            .get(&crc32)
+            .and_then(|sha1| self.entries.get(sha1))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        database::{Database, DatabaseError},
+        quirks::{Platform, Quirks},
+    };
+
+    const PROGRAMS: &str = r##"[
+      {
+        "title": "Quirky",
+        "authors": ["Someone"],
+        "roms": {
+          "0123456789ABCDEF0123456789ABCDEF01234567": {
+            "crc32": "DEADBEEF",
+            "platforms": ["superchip", "xochip"],
+            "quirkyPlatforms": { "superchip": { "jump": false } },
+            "tickrate": 50,
+            "keys": { "up": 5 },
+            "colors": { "pixels": ["#000000", "#ffffff"] }
+          }
+        }
+      }
+    ]"##;
+
+    #[test]
+    fn test_embedded() {
+        let db = Database::embedded();
+        let tetris = db.get("5f518084744bf3cb8733f6e5454dfd1634320563").unwrap();
+        assert_eq!("Tetris", tetris.title);
+        assert_eq!(Platform::Chip8, tetris.profile().platform);
+        assert_eq!(Some(tetris), db.get_crc32(0x0CE7_0772));
+    }
+
+    #[test]
+    fn test_crc32() {
+        let db = Database::from_json(PROGRAMS).unwrap();
+        assert_eq!("Quirky", db.get_crc32(0xDEAD_BEEF).unwrap().title);
+        assert_eq!(None, db.get_crc32(0));
+
+        let invalid = PROGRAMS.replace("DEADBEEF", "BEEF");
+        match Database::from_json(&invalid) {
+            Err(DatabaseError::InvalidCrc32(crc)) => assert_eq!("BEEF", crc),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_profile() {
+        let db = Database::from_json(PROGRAMS).unwrap();
+        let profile = db
+            .get("0123456789abcdef0123456789abcdef01234567")
+            .unwrap()
+            .profile();
+        assert_eq!(Platform::SuperChip, profile.platform);
+        assert_eq!(
+            Quirks {
+                jump: false,
+                ..Platform::SuperChip.quirks()
+            },
+            profile.quirks
+        );
+        assert_eq!(50, profile.tickrate);
+        assert_eq!(Some(&5), profile.keys.get("up"));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut db = Database::embedded();
+        let embedded = db.len();
+        db.merge(Database::from_json(PROGRAMS).unwrap());
+        assert_eq!(embedded + 1, db.len());
+    }
+}
@@ -0,0 +1,268 @@
+//! A line oriented debugger.
+//!
+//! The [`Debugger`] drives a [`Machine`] one instruction at a time and stops at breakpoints.
+//! Locations are resolved with a [`SymbolTable`], so breakpoints can be set on labels as well as
+//! on addresses. It understands the following commands:
+//!
+//! ```text
+//! break <location>     stop before executing the instruction at location   (b)
+//! delete <location>    remove the breakpoint at location                    (d)
+//! step [count]         execute count instructions, 1 by default             (s)
+//! continue             run until the next breakpoint or fault               (c)
+//! registers            show the registers                                   (r)
+//! where                show the next instruction                            (w)
+//! ```
+//!
+//! Timers tick after every `tickrate` instructions, or earlier when a draw waits for the vertical
+//! blank, so that stepping through a program behaves like running it frame by frame.
+use crate::{
+    disassembler::Line,
+    machine::{Executed, Machine, MachineError},
+    opcode::OpCode,
+    symbols::SymbolTable,
+};
+use std::{
+    collections::BTreeSet,
+    io::{self, Write},
+};
+use thiserror::Error;
+
+/// The amount of frames `continue` runs for before giving up on reaching a breakpoint
+const CONTINUE_FRAMES: u32 = 60 * 60 * 10;
+
+#[derive(Error, Debug)]
+pub enum DebuggerError {
+    #[error("Unknown command `{0}`")]
+    UnknownCommand(String),
+    #[error("Missing location, expected a label or an address")]
+    MissingLocation,
+    #[error("Unknown location `{0}`")]
+    UnknownLocation(String),
+    #[error("No breakpoint at {0:#05X}")]
+    NoBreakpoint(u16),
+    #[error("Invalid count `{0}`")]
+    InvalidCount(String),
+    #[error(transparent)]
+    Machine(#[from] MachineError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+pub struct Debugger {
+    machine: Machine,
+    symbols: SymbolTable,
+    breakpoints: BTreeSet<u16>,
+    /// The amount of instructions executed in the current frame
+    steps: u32,
+}
+
+impl Debugger {
+    pub fn new(machine: Machine, symbols: SymbolTable) -> Self {
+        Self {
+            machine,
+            symbols,
+            breakpoints: BTreeSet::new(),
+            steps: 0,
+        }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn breakpoints(&self) -> &BTreeSet<u16> {
+        &self.breakpoints
+    }
+
+    /// Stop before executing the instruction at `location`, a label or an address
+    pub fn break_at(&mut self, location: &str) -> Result<u16, DebuggerError> {
+        let addr = self.resolve(location)?;
+        self.breakpoints.insert(addr);
+        Ok(addr)
+    }
+
+    /// Remove the breakpoint at `location`
+    pub fn delete(&mut self, location: &str) -> Result<u16, DebuggerError> {
+        let addr = self.resolve(location)?;
+        if self.breakpoints.remove(&addr) {
+            Ok(addr)
+        } else {
+            Err(DebuggerError::NoBreakpoint(addr))
+        }
+    }
+
+    /// Execute a single instruction, ending the frame once it is over
+    pub fn step(&mut self) -> Result<Executed, MachineError> {
+        let executed = self.machine.step()?;
+        self.steps += 1;
+        if self.steps >= self.machine.tickrate() || self.machine.waiting_for_vblank() {
+            self.machine.end_frame();
+            self.steps = 0;
+        }
+        Ok(executed)
+    }
+
+    /// Run until the next breakpoint, for at most `frames` frames. Returns whether a breakpoint
+    /// was reached. The instruction at the current address is always executed, so that
+    /// continuing from a breakpoint does not stop at it again straight away.
+    pub fn resume(&mut self, frames: u32) -> Result<bool, MachineError> {
+        let budget = u64::from(frames) * u64::from(self.machine.tickrate());
+        for _ in 0..budget {
+            self.step()?;
+            if self.breakpoints.contains(&self.machine.pc()) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Run a single debugger command, writing its output to `w`
+    pub fn command<W: Write>(&mut self, line: &str, mut w: W) -> Result<(), DebuggerError> {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => return Ok(()),
+        };
+        let argument = words.next();
+
+        match command {
+            "break" | "b" => {
+                let addr = self.break_at(argument.ok_or(DebuggerError::MissingLocation)?)?;
+                writeln!(w, "Breakpoint at {}", self.describe(addr))?;
+            }
+            "delete" | "d" => {
+                let addr = self.delete(argument.ok_or(DebuggerError::MissingLocation)?)?;
+                writeln!(w, "Deleted breakpoint at {}", self.describe(addr))?;
+            }
+            "step" | "s" => {
+                let count = match argument {
+                    Some(count) => count
+                        .parse::<u32>()
+                        .map_err(|_| DebuggerError::InvalidCount(count.to_string()))?,
+                    None => 1,
+                };
+                for _ in 0..count {
+                    self.step()?;
+                }
+                self.write_next(&mut w)?;
+            }
+            "continue" | "c" => {
+                if self.resume(CONTINUE_FRAMES)? {
+                    writeln!(w, "Breakpoint reached")?;
+                }
+                self.write_next(&mut w)?;
+            }
+            "registers" | "r" => write!(w, "{}", self.machine.register())?,
+            "where" | "w" => self.write_next(&mut w)?,
+            _ => return Err(DebuggerError::UnknownCommand(command.to_string())),
+        }
+        Ok(())
+    }
+
+    fn resolve(&self, location: &str) -> Result<u16, DebuggerError> {
+        self.symbols
+            .resolve(location)
+            .ok_or_else(|| DebuggerError::UnknownLocation(location.to_string()))
+    }
+
+    /// An address along with its label, if it has one
+    fn describe(&self, addr: u16) -> String {
+        match self.symbols.label(addr) {
+            Some(label) => format!("{:#05X} ({})", addr, label),
+            None => format!("{:#05X}", addr),
+        }
+    }
+
+    /// Write the instruction about to be executed
+    fn write_next<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let pc = self.machine.pc();
+        if let Some(label) = self.symbols.label(pc) {
+            writeln!(w, "{}:", label)?;
+        }
+        let bytes = self.machine.memory().as_bytes();
+        let hi = bytes.get(usize::from(pc)).copied().unwrap_or(0);
+        let lo = bytes.get(usize::from(pc) + 1).copied().unwrap_or(0);
+        let line = Line::new(pc, OpCode::new(u16::from_be_bytes([hi, lo])));
+        writeln!(w, "{}", line.with_symbols(&self.symbols))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        debugger::{Debugger, DebuggerError},
+        loader::Profile,
+        machine::Machine,
+        memory::Memory,
+        symbols::SymbolTable,
+    };
+
+    fn debugger() -> Debugger {
+        let mut memory = Memory::new();
+        // 0x200: LD V0, 0; loop: ADD V0, 1; JP loop
+        memory
+            .load(&[0x60, 0x00, 0x70, 0x01, 0x12, 0x02], 0x200)
+            .unwrap();
+        let machine = Machine::new(memory, 0x200, &Profile::default());
+        let symbols = SymbolTable::parse("label 0x202 loop\n").unwrap();
+        Debugger::new(machine, symbols)
+    }
+
+    #[test]
+    fn test_break_on_label() {
+        let mut debugger = debugger();
+        let mut output = Vec::new();
+        debugger.command("break loop", &mut output).unwrap();
+        assert!(debugger.breakpoints().contains(&0x202));
+
+        debugger.command("continue", &mut output).unwrap();
+        assert_eq!(0x202, debugger.machine().pc());
+        debugger.command("c", &mut output).unwrap();
+        assert_eq!(0x202, debugger.machine().pc());
+        assert_eq!(1, debugger.machine().register().vs()[0]);
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("Breakpoint at 0x202 (loop)\nBreakpoint reached\nloop:\n"));
+        assert!(output.contains("0x202  7001  ADD V0, 0x01"), "{}", output);
+    }
+
+    #[test]
+    fn test_step() {
+        let mut debugger = debugger();
+        let mut output = Vec::new();
+        debugger.command("step 3", &mut output).unwrap();
+        assert_eq!(0x202, debugger.machine().pc());
+        assert_eq!(1, debugger.machine().register().vs()[0]);
+
+        output.clear();
+        debugger.command("where", &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("loop:\n0x202"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut debugger = debugger();
+        let mut output = Vec::new();
+        assert!(matches!(
+            debugger.command("break nowhere", &mut output),
+            Err(DebuggerError::UnknownLocation(_))
+        ));
+        assert!(matches!(
+            debugger.command("break", &mut output),
+            Err(DebuggerError::MissingLocation)
+        ));
+        assert!(matches!(
+            debugger.command("delete 0x204", &mut output),
+            Err(DebuggerError::NoBreakpoint(0x204))
+        ));
+        assert!(matches!(
+            debugger.command("step many", &mut output),
+            Err(DebuggerError::InvalidCount(_))
+        ));
+        assert!(matches!(
+            debugger.command("jump", &mut output),
+            Err(DebuggerError::UnknownCommand(_))
+        ));
+    }
+}
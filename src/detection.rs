@@ -0,0 +1,301 @@
+//! Heuristic platform detection for ROMs that are not in the database.
+//!
+//! The reachable code of the ROM is scanned for instructions that only exist on some platforms,
+//! e.g. `HIGH` and `SCD` only exist from SUPER-CHIP onwards, and `F000 nnnn` or plane selection
+//! only exist on XO-CHIP. The most capable platform with any evidence is assumed to be the one the
+//! ROM was written for.
+//!
+//! The scan also looks for instructions whose behaviour depends on a quirk, so that they can be
+//! reported, and in the clearest cases folded into the default profile for the ROM.
+use crate::{
+    analysis::{self, Analysis, Word},
+    instructions::Instruction,
+    loader::Profile,
+    quirks::Platform,
+};
+use std::{collections::BTreeMap, fmt};
+
+/// An instruction hinting at the platform a ROM was written for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Evidence {
+    pub address: u16,
+    pub platform: Platform,
+    pub description: &'static str,
+}
+
+/// An instruction whose behaviour depends on a quirk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuirkDependency {
+    pub address: u16,
+    /// The name of the quirk, as used by the chip-8-database
+    pub quirk: &'static str,
+    pub description: &'static str,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Detection {
+    /// The platform the ROM most likely targets
+    pub platform: Platform,
+    pub evidence: Vec<Evidence>,
+    pub dependencies: Vec<QuirkDependency>,
+    /// Whether every shift with distinct registers shifts into Vx from V0, which is how programs
+    /// written for in-place shifts were usually assembled. Only taken into account for CHIP-8,
+    /// where the shift quirk is ambiguous: plenty of "CHIP-8" programs were written for CHIP-48
+    /// or SUPER-CHIP, while XO-CHIP settled on shifting Vy.
+    in_place_shifts: bool,
+}
+
+pub(crate) fn platform_evidence(word: &Word) -> Option<(Platform, &'static str)> {
+    use Instruction::*;
+
+    let schip = Platform::SuperChip;
+    let xochip = Platform::XoChip;
+    let raw = u16::from(word.opcode);
+
+    let evidence = match word.instruction {
+        Some(ScrollDown(_)) => (schip, "scrolls the screen down"),
+        Some(ScrollRight) | Some(ScrollLeft) => (schip, "scrolls the screen sideways"),
+        Some(Exit) => (schip, "exits the interpreter"),
+        Some(LowRes) | Some(HighRes) => (schip, "switches the screen resolution"),
+        Some(Draw(_, _, 0)) => (schip, "draws 16x16 sprites"),
+        Some(LoadVIntoFlags(_)) | Some(LoadFlagsIntoV(_)) => {
+            (schip, "saves or loads the RPL user flags")
+        }
+        Some(_) => return None,
+        None => match (raw >> 12, raw & 0xFF, raw & 0xF) {
+            _ if raw == 0xF000 => (xochip, "loads I with a 16-bit address"),
+            _ if raw == 0xF002 => (xochip, "loads an audio pattern"),
+            (0x0, kk, _) if kk & 0xF0 == 0xD0 => (xochip, "scrolls the screen up"),
+            (0x5, _, 0x2) | (0x5, _, 0x3) => (xochip, "saves or loads a range of registers"),
+            (0xF, 0x01, _) => (xochip, "selects drawing planes"),
+            (0xF, 0x3A, _) => (xochip, "sets the audio pitch"),
+            (0xF, 0x30, _) => (schip, "points I at a large font digit"),
+            _ => return None,
+        },
+    };
+
+    Some(evidence)
+}
+
+/// Whether `instruction` reads or writes memory through I
+fn uses_i(instruction: Instruction) -> bool {
+    use Instruction::*;
+    matches!(
+        instruction,
+        Draw(..) | AddI(_) | LoadBCDIntoI(_) | LoadVIntoMem(_) | LoadMemIntoV(_)
+    )
+}
+
+/// Whether the instruction at `addr` is followed, without any branch or change to I in between, by
+/// an instruction using I
+fn reuses_i(analysis: &Analysis, addr: u16) -> bool {
+    let mut addr = addr;
+    loop {
+        let word = match analysis.code.get(&addr) {
+            Some(word) => word,
+            None => return false,
+        };
+        let next = addr.wrapping_add(word.size());
+        let successors = analysis.successors(addr, word);
+        if successors != [next] {
+            return false;
+        }
+
+        match analysis.code.get(&next).and_then(|w| w.instruction) {
+            Some(Instruction::LoadI(_)) | Some(Instruction::LoadSpriteIntoI(_)) | None => {
+                return false
+            }
+            Some(i) if uses_i(i) => return true,
+            Some(_) => addr = next,
+        }
+    }
+}
+
+impl Detection {
+    pub fn analyze(analysis: &Analysis) -> Self {
+        use Instruction::*;
+
+        let mut evidence = Vec::new();
+        let mut dependencies = Vec::new();
+        let mut shifts = (0, 0);
+
+        for (&address, word) in &analysis.code {
+            if let Some((platform, description)) = platform_evidence(word) {
+                evidence.push(Evidence {
+                    address,
+                    platform,
+                    description,
+                });
+            }
+
+            let dependency = match word.instruction {
+                Some(ShiftRight(x, y)) | Some(ShiftLeft(x, y)) if x != y => {
+                    shifts.0 += 1;
+                    if y == 0 {
+                        shifts.1 += 1;
+                    }
+                    Some(("shift", "shifts Vy into Vx, or Vx in place"))
+                }
+                Some(JumpImmediate(addr)) if addr & 0xF00 != 0 => {
+                    Some(("jump", "jumps relative to V0, or to Vx"))
+                }
+                Some(LoadVIntoMem(_)) | Some(LoadMemIntoV(_)) if reuses_i(analysis, address) => {
+                    Some((
+                        "memoryIncrementByX",
+                        "uses I after a register store or load changed it",
+                    ))
+                }
+                _ => None,
+            };
+            if let Some((quirk, description)) = dependency {
+                dependencies.push(QuirkDependency {
+                    address,
+                    quirk,
+                    description,
+                });
+            }
+        }
+
+        let has = |p| evidence.iter().any(|e: &Evidence| e.platform == p);
+        let platform = if has(Platform::XoChip) {
+            Platform::XoChip
+        } else if has(Platform::SuperChip) {
+            Platform::SuperChip
+        } else {
+            Platform::Chip8
+        };
+
+        Self {
+            platform,
+            evidence,
+            dependencies,
+            in_place_shifts: shifts.0 > 0 && shifts.0 == shifts.1,
+        }
+    }
+
+    /// Detect the platform of `bytes`, loaded in memory at `origin`
+    pub fn detect(bytes: &[u8], origin: u16) -> Self {
+        Self::analyze(&analysis::analyze(bytes, origin))
+    }
+
+    /// The default settings for the detected platform, adjusted for the quirks the ROM clearly
+    /// depends on
+    pub fn profile(&self) -> Profile {
+        let mut profile = Profile::for_platform(self.platform);
+        if self.in_place_shifts && self.platform == Platform::Chip8 {
+            profile.quirks.shift = true;
+        }
+        profile
+    }
+}
+
+impl fmt::Display for Detection {
+    /// Summarizes the evidence, showing the first address of each kind of evidence
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Detected: {}", self.platform)?;
+
+        let mut evidence: BTreeMap<(Platform, &str), Vec<u16>> = BTreeMap::new();
+        for e in &self.evidence {
+            evidence
+                .entry((e.platform, e.description))
+                .or_default()
+                .push(e.address);
+        }
+        for ((platform, description), addresses) in evidence {
+            writeln!(
+                f,
+                "  {:#05X} {} ({}, {} times)",
+                addresses[0],
+                description,
+                platform,
+                addresses.len()
+            )?;
+        }
+
+        let mut dependencies: BTreeMap<(&str, &str), Vec<u16>> = BTreeMap::new();
+        for d in &self.dependencies {
+            dependencies
+                .entry((d.quirk, d.description))
+                .or_default()
+                .push(d.address);
+        }
+        if !dependencies.is_empty() {
+            writeln!(f, "Quirk dependencies:")?;
+        }
+        for ((quirk, description), addresses) in dependencies {
+            writeln!(
+                f,
+                "  {:#05X} {}: {} ({} times)",
+                addresses[0],
+                quirk,
+                description,
+                addresses.len()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{detection::Detection, quirks::Platform};
+
+    #[test]
+    fn test_chip8() {
+        let rom = std::fs::read("./games/tetris.ch8").unwrap();
+        let detection = Detection::detect(&rom, 0x200);
+        assert_eq!(Platform::Chip8, detection.platform);
+        assert!(detection.evidence.is_empty());
+    }
+
+    #[test]
+    fn test_schip() {
+        #[rustfmt::skip]
+        let rom = [
+            0x00, 0xFF, // HIGH
+            0x80, 0x16, // SHR V0, V1
+            0xB3, 0x00, // JP V0, 0x300
+        ];
+        let detection = Detection::detect(&rom, 0x200);
+        assert_eq!(Platform::SuperChip, detection.platform);
+        assert_eq!(
+            vec!["shift", "jump"],
+            detection
+                .dependencies
+                .iter()
+                .map(|d| d.quirk)
+                .collect::<Vec<_>>()
+        );
+        assert!(!detection.profile().quirks.vblank);
+    }
+
+    #[test]
+    fn test_xochip() {
+        #[rustfmt::skip]
+        let rom = [
+            0xF0, 0x00, 0x12, 0x34, // I := long 0x1234
+            0xF2, 0x01,             // plane 2
+            0x81, 0x06,             // SHR V1, V0
+            0xF2, 0x55,             // LD [I], V2
+            0xD0, 0x15,             // DRW V0, V1, 5
+        ];
+        let detection = Detection::detect(&rom, 0x200);
+        assert_eq!(Platform::XoChip, detection.platform);
+        assert_eq!(2, detection.evidence.len());
+        assert!(!detection.profile().quirks.shift);
+        assert_eq!("memoryIncrementByX", detection.dependencies[1].quirk);
+    }
+
+    #[test]
+    fn test_in_place_shifts() {
+        #[rustfmt::skip]
+        let rom = [
+            0x81, 0x06, // SHR V1, V0
+            0x82, 0x0E, // SHL V2, V0
+        ];
+        let detection = Detection::detect(&rom, 0x200);
+        assert_eq!(Platform::Chip8, detection.platform);
+        assert!(detection.profile().quirks.shift);
+    }
+}
@@ -0,0 +1,199 @@
+//! A linear disassembler for CHIP-8 programs.
+//!
+//! CHIP-8 programs freely mix code and sprite data, so the disassembler does not try to tell them
+//! apart: every aligned 16-bit word is decoded, and words that are not valid instructions are shown
+//! as `DW` data. When a [`SymbolTable`] is available, [`write_listing`] uses it to name the targets
+//! of jumps, calls and `LD I` and to show data regions as `DB` bytes.
+use crate::{instructions::Instruction, opcode::OpCode, symbols::SymbolTable};
+use std::{
+    convert::TryFrom,
+    fmt,
+    io::{self, Write},
+};
+
+/// A single disassembled word
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Line {
+    /// The address of the word in memory
+    pub address: u16,
+    /// The raw word
+    pub opcode: OpCode,
+    /// The decoded instruction, if the word is a valid one
+    pub instruction: Option<Instruction>,
+}
+
+impl Line {
+    pub fn new(address: u16, opcode: OpCode) -> Self {
+        Self {
+            address,
+            opcode,
+            instruction: Instruction::try_from(opcode).ok(),
+        }
+    }
+
+    /// Display the line naming addresses with the labels in `symbols`
+    pub fn with_symbols<'a>(&'a self, symbols: &'a SymbolTable) -> SymbolicLine<'a> {
+        SymbolicLine {
+            line: self,
+            symbols,
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let word = u16::from(self.opcode);
+        write!(f, "{:#05X}  {:04X}  ", self.address, word)?;
+        match self.instruction {
+            Some(instruction) => write!(f, "{}", instruction),
+            None => write!(f, "DW {:#06X}", word),
+        }
+    }
+}
+
+pub struct SymbolicLine<'a> {
+    line: &'a Line,
+    symbols: &'a SymbolTable,
+}
+
+impl fmt::Display for SymbolicLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Instruction::*;
+
+        let (mnemonic, target) = match self.line.instruction {
+            Some(Jump(addr)) => ("JP", addr),
+            Some(Call(addr)) => ("CALL", addr),
+            Some(LoadI(addr)) => ("LD I,", addr),
+            Some(JumpImmediate(addr)) => ("JP V0,", addr),
+            _ => return write!(f, "{}", self.line),
+        };
+
+        match self.symbols.label(target) {
+            Some(label) => write!(
+                f,
+                "{:#05X}  {:04X}  {} {}",
+                self.line.address,
+                u16::from(self.line.opcode),
+                mnemonic,
+                label
+            ),
+            None => write!(f, "{}", self.line),
+        }
+    }
+}
+
+/// Disassemble `bytes`, which are located in memory starting at `origin`.
+///
+/// A trailing odd byte is shown as if it were followed by a zero byte.
+pub fn disassemble(bytes: &[u8], origin: u16) -> Vec<Line> {
+    bytes
+        .chunks(2)
+        .enumerate()
+        .map(|(idx, chunk)| {
+            let hi = chunk[0];
+            let lo = chunk.get(1).copied().unwrap_or(0);
+            let address = origin.wrapping_add(2 * idx as u16);
+            Line::new(address, OpCode::new(u16::from_be_bytes([hi, lo])))
+        })
+        .collect()
+}
+
+/// Write a disassembly listing of `bytes`, located in memory at `origin`, annotated with the
+/// labels, data regions and comments in `symbols`.
+pub fn write_listing<W: Write>(
+    bytes: &[u8],
+    origin: u16,
+    symbols: &SymbolTable,
+    mut w: W,
+) -> io::Result<()> {
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let address = origin.wrapping_add(offset as u16);
+        if let Some(label) = symbols.label(address) {
+            writeln!(w, "{}:", label)?;
+        }
+
+        let len = match symbols.data(address) {
+            Some(region) => {
+                // Stop at the end of the region or at the next label, whichever comes first
+                let region_end = usize::from(region.start) + usize::from(region.length);
+                let mut len = 1;
+                if offset + 1 < bytes.len()
+                    && usize::from(address) + 1 < region_end
+                    && symbols.label(address + 1).is_none()
+                {
+                    len = 2;
+                }
+
+                let data = &bytes[offset..offset + len];
+                write!(w, "{:#05X}  ", address)?;
+                for byte in data {
+                    write!(w, "{:02X}", byte)?;
+                }
+                let operands: Vec<String> = data.iter().map(|b| format!("{:#04X}", b)).collect();
+                write!(w, "{:1$}DB {2}", "", 6 - 2 * len, operands.join(", "))?;
+                len
+            }
+            None => {
+                let hi = bytes[offset];
+                let lo = bytes.get(offset + 1).copied().unwrap_or(0);
+                let line = Line::new(address, OpCode::new(u16::from_be_bytes([hi, lo])));
+                write!(w, "{}", line.with_symbols(symbols))?;
+                2
+            }
+        };
+
+        if let Some(comment) = symbols.comment(address) {
+            write!(w, "  ; {}", comment)?;
+        }
+        writeln!(w)?;
+        offset += len;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        disassembler::{disassemble, write_listing},
+        symbols::SymbolTable,
+    };
+
+    #[test]
+    fn test_disassemble() {
+        let lines: Vec<String> = disassemble(&[0x6A, 0x02, 0xF0, 0xF0, 0x00], 0x200)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            vec![
+                "0x200  6A02  LD VA, 0x02",
+                "0x202  F0F0  DW 0xF0F0",
+                "0x204  0000  DW 0x0000",
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn test_listing_with_symbols() {
+        let symbols = SymbolTable::parse(
+            "label 0x200 main\nlabel 0x206 sprite\ndata 0x206 3\ncomment 0x202 wait",
+        )
+        .unwrap();
+        let rom = [0xA2, 0x06, 0x12, 0x00, 0x12, 0x10, 0xF0, 0x90, 0xF0];
+        let mut listing = Vec::new();
+        write_listing(&rom, 0x200, &symbols, &mut listing).unwrap();
+        assert_eq!(
+            "main:\n\
+             0x200  A206  LD I, sprite\n\
+             0x202  1200  JP main  ; wait\n\
+             0x204  1210  JP 0x210\n\
+             sprite:\n\
+             0x206  F090  DB 0xF0, 0x90\n\
+             0x208  F0    DB 0xF0\n",
+            String::from_utf8(listing).unwrap()
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! The monochrome CHIP-8 display.
+//!
+//! CHIP-8 draws on a 64x32 screen, which SUPER-CHIP extends with a 128x64 high resolution mode.
+//! Sprites are drawn by XORing them onto the screen, and a pixel being turned off by a sprite is
+//! reported as a collision.
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Display {
+    hires: bool,
+    /// One byte per pixel, 1 when the pixel is lit, row by row
+    pixels: Vec<u8>,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display {
+    pub const WIDTH: usize = 64;
+    pub const HEIGHT: usize = 32;
+    pub const HIRES_WIDTH: usize = 128;
+    pub const HIRES_HEIGHT: usize = 64;
+
+    /// A blank low resolution display
+    pub fn new() -> Self {
+        Self {
+            hires: false,
+            pixels: vec![0; Self::WIDTH * Self::HEIGHT],
+        }
+    }
+
+    /// A display showing `pixels`, which must hold one byte per pixel of the resolution
+    pub(crate) fn from_pixels(hires: bool, pixels: Vec<u8>) -> Self {
+        let display = Self { hires, pixels };
+        debug_assert_eq!(display.width() * display.height(), display.pixels.len());
+        display
+    }
+
+    pub fn width(&self) -> usize {
+        if self.hires {
+            Self::HIRES_WIDTH
+        } else {
+            Self::WIDTH
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        if self.hires {
+            Self::HIRES_HEIGHT
+        } else {
+            Self::HEIGHT
+        }
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Switch between the low and high resolution modes, clearing the screen
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.pixels = vec![0; self.width() * self.height()];
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.iter_mut().for_each(|p| *p = 0);
+    }
+
+    /// Whether the pixel at (`x`, `y`) is lit
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        x < self.width() && y < self.height() && self.pixels[y * self.width() + x] != 0
+    }
+
+    /// The pixels, one byte per pixel and row by row, 1 when the pixel is lit
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// XOR a sprite onto the screen at (`x`, `y`), returning whether any pixel was turned off.
+    ///
+    /// Each row of the sprite is `width / 8` bytes, most significant bit leftmost. The starting
+    /// position always wraps around the screen, the rest of the sprite is clipped at the edges
+    /// unless `wrap` is set.
+    pub fn draw(&mut self, x: usize, y: usize, sprite: &[u8], width: usize, wrap: bool) -> bool {
+        let (w, h) = (self.width(), self.height());
+        let (x, y) = (x % w, y % h);
+        let bytes_per_row = width / 8;
+        let mut collision = false;
+
+        for (row, bytes) in sprite.chunks(bytes_per_row).enumerate() {
+            let py = y + row;
+            if py >= h && !wrap {
+                break;
+            }
+            for col in 0..width {
+                if bytes[col / 8] & (0x80 >> (col % 8)) == 0 {
+                    continue;
+                }
+                let px = x + col;
+                if px >= w && !wrap {
+                    break;
+                }
+                let pixel = &mut self.pixels[(py % h) * w + px % w];
+                collision |= *pixel != 0;
+                *pixel ^= 1;
+            }
+        }
+
+        collision
+    }
+
+    /// Scroll the screen down by `n` rows
+    pub fn scroll_down(&mut self, n: usize) {
+        let w = self.width();
+        let n = n.min(self.height());
+        self.pixels.rotate_right(n * w);
+        self.pixels[..n * w].iter_mut().for_each(|p| *p = 0);
+    }
+
+    /// Scroll the screen right by 4 pixels
+    pub fn scroll_right(&mut self) {
+        let w = self.width();
+        for row in self.pixels.chunks_mut(w) {
+            row.rotate_right(4);
+            row[..4].iter_mut().for_each(|p| *p = 0);
+        }
+    }
+
+    /// Scroll the screen left by 4 pixels
+    pub fn scroll_left(&mut self) {
+        let w = self.width();
+        for row in self.pixels.chunks_mut(w) {
+            row.rotate_left(4);
+            row[w - 4..].iter_mut().for_each(|p| *p = 0);
+        }
+    }
+}
+
+impl fmt::Display for Display {
+    /// Draws the screen with one character per pixel
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.pixels.chunks(self.width()) {
+            let line: String = row
+                .iter()
+                .map(|&p| if p != 0 { '█' } else { ' ' })
+                .collect();
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::display::Display;
+
+    #[test]
+    fn test_draw() {
+        let mut display = Display::new();
+        assert!(!display.draw(62, 0, &[0xF0], 8, false));
+        assert!(display.get(62, 0) && display.get(63, 0));
+        assert!(!display.get(0, 0));
+
+        assert!(display.draw(62, 0, &[0xF0], 8, true));
+        assert!(!display.get(62, 0) && display.get(0, 0) && display.get(1, 0));
+    }
+
+    #[test]
+    fn test_scroll() {
+        let mut display = Display::new();
+        display.set_hires(true);
+        display.draw(0, 0, &[0x80], 8, false);
+        display.scroll_down(2);
+        display.scroll_right();
+        assert!(display.get(4, 2));
+        display.scroll_left();
+        assert!(display.get(0, 2));
+        assert_eq!(1, display.pixels().iter().filter(|&&p| p != 0).count());
+    }
+}
@@ -0,0 +1,461 @@
+//! A C API for embedding the machine, declared in `include/chirp.h`.
+//!
+//! Machines are handed out as opaque [`ChirpMachine`] pointers, created with
+//! [`chirp_machine_new`] and destroyed with [`chirp_machine_free`]. Functions that can fail return
+//! a [`ChirpStatus`], and the message describing the last failure is available from
+//! [`chirp_machine_last_error`].
+//!
+//! The header is generated by cbindgen from this module, regenerate it after changing the API
+//! with `cbindgen --config cbindgen.toml --output include/chirp.h src/ffi.rs`.
+use crate::{
+    loader::{LoadError, Loader},
+    machine::{Fault, Machine, MachineError},
+    memory::MemoryError,
+    state::{State, StateError},
+};
+use std::{
+    ffi::CString,
+    fmt,
+    os::raw::{c_char, c_int},
+    ptr,
+};
+
+/// The result of a call. Every failure also sets the message returned by
+/// `chirp_machine_last_error`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChirpStatus {
+    Ok = 0,
+    /// A pointer argument was null
+    NullPointer = 1,
+    /// No ROM was loaded
+    NoRom = 2,
+    /// The ROM does not fit in memory
+    RomTooLarge = 3,
+    /// The ROM was loaded outside of the program area
+    InvalidAddress = 4,
+    /// The ROM could not be read
+    Io = 5,
+    /// The program called too many nested subroutines
+    StackOverflow = 6,
+    /// The program returned with an empty stack
+    StackUnderflow = 7,
+    /// The program accessed memory past its end
+    OutOfBoundsAccess = 8,
+    /// The program ran into an unknown opcode
+    UnknownOpCode = 9,
+    /// The program exited
+    Exit = 10,
+    /// The save state is invalid
+    InvalidState = 11,
+    /// The buffer is too small for the save state
+    BufferTooSmall = 12,
+    /// There is no such key
+    InvalidKey = 13,
+    /// The Octo cartridge is invalid
+    InvalidCartridge = 14,
+}
+
+impl From<&MemoryError> for ChirpStatus {
+    fn from(e: &MemoryError) -> Self {
+        match e {
+            MemoryError::LoadFile(_) | MemoryError::OpenFile(_) => ChirpStatus::Io,
+            MemoryError::OutOfBoundsAccess(_) => ChirpStatus::InvalidAddress,
+            MemoryError::RomTooLarge(..) | MemoryError::RomExceeds(_) => ChirpStatus::RomTooLarge,
+        }
+    }
+}
+
+impl From<&LoadError> for ChirpStatus {
+    fn from(e: &LoadError) -> Self {
+        match e {
+            LoadError::Memory(e) => ChirpStatus::from(e),
+            LoadError::Cartridge(_) => ChirpStatus::InvalidCartridge,
+        }
+    }
+}
+
+impl From<&MachineError> for ChirpStatus {
+    fn from(e: &MachineError) -> Self {
+        match e.fault {
+            Fault::StackOverflow => ChirpStatus::StackOverflow,
+            Fault::StackUnderflow => ChirpStatus::StackUnderflow,
+            Fault::OutOfBoundsAccess(_) => ChirpStatus::OutOfBoundsAccess,
+            Fault::UnknownOpCode => ChirpStatus::UnknownOpCode,
+            Fault::Exit => ChirpStatus::Exit,
+        }
+    }
+}
+
+impl From<&StateError> for ChirpStatus {
+    fn from(_: &StateError) -> Self {
+        ChirpStatus::InvalidState
+    }
+}
+
+/// A machine, along with the message describing the last failure
+pub struct ChirpMachine {
+    machine: Option<Machine>,
+    error: Option<CString>,
+}
+
+impl ChirpMachine {
+    /// Remember `e` as the last failure, and return its status
+    fn fail<E>(&mut self, e: &E) -> ChirpStatus
+    where
+        E: fmt::Display,
+        for<'a> ChirpStatus: From<&'a E>,
+    {
+        self.error = CString::new(e.to_string()).ok();
+        ChirpStatus::from(e)
+    }
+
+    fn fail_with(&mut self, status: ChirpStatus, message: &str) -> ChirpStatus {
+        self.error = CString::new(message).ok();
+        status
+    }
+
+    fn machine(&mut self) -> Result<&mut Machine, ChirpStatus> {
+        if self.machine.is_none() {
+            return Err(self.fail_with(ChirpStatus::NoRom, "No ROM was loaded"));
+        }
+        Ok(self.machine.as_mut().expect("checked above"))
+    }
+
+    fn run<F>(&mut self, f: F) -> ChirpStatus
+    where
+        F: FnOnce(&mut Machine) -> Result<(), MachineError>,
+    {
+        let result = match self.machine() {
+            Ok(machine) => f(machine),
+            Err(status) => return status,
+        };
+        match result {
+            Ok(()) => ChirpStatus::Ok,
+            Err(e) => self.fail(&e),
+        }
+    }
+}
+
+/// Create a machine, with no ROM loaded. Free it with `chirp_machine_free`.
+#[no_mangle]
+pub extern "C" fn chirp_machine_new() -> *mut ChirpMachine {
+    Box::into_raw(Box::new(ChirpMachine {
+        machine: None,
+        error: None,
+    }))
+}
+
+/// Free a machine created by `chirp_machine_new`, which may be null
+///
+/// # Safety
+///
+/// `m` must come from `chirp_machine_new`, and must not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn chirp_machine_free(m: *mut ChirpMachine) {
+    if !m.is_null() {
+        drop(Box::from_raw(m));
+    }
+}
+
+/// Load the `len` bytes at `rom` as a ROM, resetting the machine and using the ROM's recommended
+/// settings
+///
+/// # Safety
+///
+/// `m` must be a live machine, and `rom` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chirp_machine_load(
+    m: *mut ChirpMachine,
+    rom: *const u8,
+    len: usize,
+) -> ChirpStatus {
+    let m = match m.as_mut() {
+        Some(m) => m,
+        None => return ChirpStatus::NullPointer,
+    };
+    if rom.is_null() {
+        return m.fail_with(ChirpStatus::NullPointer, "The ROM is null");
+    }
+    match Loader::new().load(std::slice::from_raw_parts(rom, len)) {
+        Ok(rom) => {
+            m.machine = Some(Machine::from_rom(rom));
+            ChirpStatus::Ok
+        }
+        Err(e) => m.fail(&e),
+    }
+}
+
+/// Execute `cycles` instructions, stopping at the first fault
+///
+/// # Safety
+///
+/// `m` must be a live machine.
+#[no_mangle]
+pub unsafe extern "C" fn chirp_machine_run_cycles(
+    m: *mut ChirpMachine,
+    cycles: u32,
+) -> ChirpStatus {
+    match m.as_mut() {
+        Some(m) => m.run(|machine| {
+            for _ in 0..cycles {
+                machine.step()?;
+            }
+            Ok(())
+        }),
+        None => ChirpStatus::NullPointer,
+    }
+}
+
+/// Run a 60Hz frame
+///
+/// # Safety
+///
+/// `m` must be a live machine.
+#[no_mangle]
+pub unsafe extern "C" fn chirp_machine_run_frame(m: *mut ChirpMachine) -> ChirpStatus {
+    match m.as_mut() {
+        Some(m) => m.run(Machine::run_frame),
+        None => ChirpStatus::NullPointer,
+    }
+}
+
+/// The framebuffer, one byte per pixel and row by row, 1 when the pixel is lit. Its dimensions
+/// are written to `width` and `height`, which may be null. Returns null if no ROM was loaded.
+///
+/// The framebuffer is valid until the machine runs again.
+///
+/// # Safety
+///
+/// `m` must be a live machine, and `width` and `height` must be writable if not null.
+#[no_mangle]
+pub unsafe extern "C" fn chirp_machine_framebuffer(
+    m: *const ChirpMachine,
+    width: *mut u32,
+    height: *mut u32,
+) -> *const u8 {
+    let display = match m.as_ref().and_then(|m| m.machine.as_ref()) {
+        Some(machine) => machine.display(),
+        None => return ptr::null(),
+    };
+    if let Some(width) = width.as_mut() {
+        *width = display.width() as u32;
+    }
+    if let Some(height) = height.as_mut() {
+        *height = display.height() as u32;
+    }
+    display.pixels().as_ptr()
+}
+
+/// Press `key` (0 through 15) if `pressed` is non-zero, release it otherwise
+///
+/// # Safety
+///
+/// `m` must be a live machine.
+#[no_mangle]
+pub unsafe extern "C" fn chirp_machine_set_key(
+    m: *mut ChirpMachine,
+    key: u8,
+    pressed: c_int,
+) -> ChirpStatus {
+    let m = match m.as_mut() {
+        Some(m) => m,
+        None => return ChirpStatus::NullPointer,
+    };
+    if key > 0xF {
+        return m.fail_with(ChirpStatus::InvalidKey, "Keys go from 0 to 15");
+    }
+    match m.machine() {
+        Ok(machine) => {
+            machine.keypad_mut().set(key, pressed != 0);
+            ChirpStatus::Ok
+        }
+        Err(status) => status,
+    }
+}
+
+/// The size of a save state of the machine in bytes, 0 if no ROM was loaded
+///
+/// # Safety
+///
+/// `m` must be a live machine.
+#[no_mangle]
+pub unsafe extern "C" fn chirp_machine_state_size(m: *const ChirpMachine) -> usize {
+    match m.as_ref().and_then(|m| m.machine.as_ref()) {
+        Some(machine) => machine.save_state().to_bytes().len(),
+        None => 0,
+    }
+}
+
+/// Write a save state of the machine to the `len` bytes at `buf`, which must hold at least
+/// `chirp_machine_state_size` bytes
+///
+/// # Safety
+///
+/// `m` must be a live machine, and `buf` must point to `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chirp_machine_save_state(
+    m: *mut ChirpMachine,
+    buf: *mut u8,
+    len: usize,
+) -> ChirpStatus {
+    let m = match m.as_mut() {
+        Some(m) => m,
+        None => return ChirpStatus::NullPointer,
+    };
+    if buf.is_null() {
+        return m.fail_with(ChirpStatus::NullPointer, "The buffer is null");
+    }
+    let state = match m.machine() {
+        Ok(machine) => machine.save_state().to_bytes(),
+        Err(status) => return status,
+    };
+    if state.len() > len {
+        return m.fail_with(
+            ChirpStatus::BufferTooSmall,
+            "The buffer is smaller than the save state",
+        );
+    }
+    ptr::copy_nonoverlapping(state.as_ptr(), buf, state.len());
+    ChirpStatus::Ok
+}
+
+/// Restore the save state in the `len` bytes at `buf`, written by `chirp_machine_save_state`
+/// with the same ROM loaded
+///
+/// # Safety
+///
+/// `m` must be a live machine, and `buf` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chirp_machine_load_state(
+    m: *mut ChirpMachine,
+    buf: *const u8,
+    len: usize,
+) -> ChirpStatus {
+    let m = match m.as_mut() {
+        Some(m) => m,
+        None => return ChirpStatus::NullPointer,
+    };
+    if buf.is_null() {
+        return m.fail_with(ChirpStatus::NullPointer, "The buffer is null");
+    }
+    let state = match State::from_bytes(std::slice::from_raw_parts(buf, len)) {
+        Ok(state) => state,
+        Err(e) => return m.fail(&e),
+    };
+    match m.machine() {
+        Ok(machine) => {
+            machine.load_state(state);
+            ChirpStatus::Ok
+        }
+        Err(status) => status,
+    }
+}
+
+/// The message describing the last failure, or null if nothing failed yet. The message is valid
+/// until the next call failing on the machine.
+///
+/// # Safety
+///
+/// `m` must be a live machine.
+#[no_mangle]
+pub unsafe extern "C" fn chirp_machine_last_error(m: *const ChirpMachine) -> *const c_char {
+    match m.as_ref().and_then(|m| m.error.as_ref()) {
+        Some(error) => error.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ffi::*;
+    use std::ffi::CStr;
+
+    #[test]
+    fn test_machine() {
+        let rom = std::fs::read("./games/tetris.ch8").unwrap();
+        unsafe {
+            let m = chirp_machine_new();
+            assert_eq!(ChirpStatus::NoRom, chirp_machine_run_frame(m));
+            assert_eq!(
+                "No ROM was loaded",
+                CStr::from_ptr(chirp_machine_last_error(m))
+                    .to_str()
+                    .unwrap()
+            );
+
+            let too_large = vec![0; 0x1000];
+            assert_eq!(
+                ChirpStatus::RomTooLarge,
+                chirp_machine_load(m, too_large.as_ptr(), too_large.len())
+            );
+            assert_eq!(
+                ChirpStatus::Ok,
+                chirp_machine_load(m, rom.as_ptr(), rom.len())
+            );
+            assert_eq!(ChirpStatus::Ok, chirp_machine_set_key(m, 5, 1));
+            assert_eq!(ChirpStatus::InvalidKey, chirp_machine_set_key(m, 16, 1));
+            assert_eq!(ChirpStatus::Ok, chirp_machine_run_cycles(m, 100));
+
+            let mut state = vec![0; chirp_machine_state_size(m)];
+            assert_eq!(
+                ChirpStatus::BufferTooSmall,
+                chirp_machine_save_state(m, state.as_mut_ptr(), state.len() - 1)
+            );
+            assert_eq!(
+                ChirpStatus::Ok,
+                chirp_machine_save_state(m, state.as_mut_ptr(), state.len())
+            );
+            let (mut width, mut height) = (0, 0);
+            let saved = std::slice::from_raw_parts(
+                chirp_machine_framebuffer(m, &mut width, &mut height),
+                (width * height) as usize,
+            )
+            .to_vec();
+            assert_eq!((64, 32), (width, height));
+            assert!(saved.contains(&1));
+
+            for _ in 0..60 {
+                assert_eq!(ChirpStatus::Ok, chirp_machine_run_frame(m));
+            }
+            assert_eq!(
+                ChirpStatus::Ok,
+                chirp_machine_load_state(m, state.as_ptr(), state.len())
+            );
+            let restored = std::slice::from_raw_parts(
+                chirp_machine_framebuffer(m, ptr::null_mut(), ptr::null_mut()),
+                saved.len(),
+            );
+            assert_eq!(&saved[..], restored);
+            assert_eq!(
+                ChirpStatus::InvalidState,
+                chirp_machine_load_state(m, state.as_ptr(), 4)
+            );
+
+            chirp_machine_free(m);
+        }
+    }
+
+    #[test]
+    fn test_faults() {
+        #[rustfmt::skip]
+        let rom = [
+            0x00, 0xEE, // RET
+        ];
+        unsafe {
+            let m = chirp_machine_new();
+            chirp_machine_load(m, rom.as_ptr(), rom.len());
+            assert_eq!(ChirpStatus::StackUnderflow, chirp_machine_run_frame(m));
+            assert_eq!(
+                "Stack underflow at 0x200 (0x00EE)",
+                CStr::from_ptr(chirp_machine_last_error(m))
+                    .to_str()
+                    .unwrap()
+            );
+            chirp_machine_free(m);
+        }
+        assert_eq!(ChirpStatus::NullPointer, unsafe {
+            chirp_machine_run_frame(ptr::null_mut())
+        });
+    }
+}
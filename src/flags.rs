@@ -0,0 +1,114 @@
+//! Persistent storage for the SUPER-CHIP RPL user flags.
+//!
+//! On the HP48 calculators, `LD R, Vx` saved registers into the RPL user flags, which survived
+//! the interpreter exiting. Games use them to keep high scores, so the machine hands them to a
+//! [`FlagStore`] whenever they are saved, keyed by the SHA-1 of the ROM.
+//!
+//! [`FileFlagStore`] keeps the flags of each ROM in its own file, and [`MemoryFlagStore`] keeps
+//! them for the lifetime of the process, which is mostly useful for tests.
+use std::{
+    collections::HashMap,
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// The amount of flags on SUPER-CHIP
+pub const SCHIP_FLAGS: usize = 8;
+/// The amount of flags on XO-CHIP
+pub const XOCHIP_FLAGS: usize = 16;
+
+/// A place the flags of every ROM are persisted to
+pub trait FlagStore: fmt::Debug + Send + Sync {
+    /// The flags saved for `rom`, identified by its SHA-1, if any were saved
+    fn load(&self, rom: &str) -> io::Result<Option<Vec<u8>>>;
+    /// Save `flags` for `rom`, identified by its SHA-1
+    fn save(&self, rom: &str, flags: &[u8]) -> io::Result<()>;
+}
+
+/// Stores the flags of each ROM in `<dir>/<sha1>.flags`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileFlagStore {
+    dir: PathBuf,
+}
+
+impl FileFlagStore {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// The default directory for flag files, `$XDG_DATA_HOME/chirp/flags`, falling back to
+    /// `~/.local/share/chirp/flags`
+    pub fn default_dir() -> Option<PathBuf> {
+        let data = match std::env::var_os("XDG_DATA_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+        };
+        Some(data.join("chirp").join("flags"))
+    }
+
+    fn path(&self, rom: &str) -> PathBuf {
+        self.dir.join(format!("{}.flags", rom))
+    }
+}
+
+impl FlagStore for FileFlagStore {
+    fn load(&self, rom: &str) -> io::Result<Option<Vec<u8>>> {
+        match std::fs::read(self.path(rom)) {
+            Ok(flags) => Ok(Some(flags)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn save(&self, rom: &str, flags: &[u8]) -> io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.path(rom), flags)
+    }
+}
+
+/// Keeps the flags in memory
+#[derive(Debug, Default)]
+pub struct MemoryFlagStore {
+    flags: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemoryFlagStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl FlagStore for MemoryFlagStore {
+    fn load(&self, rom: &str) -> io::Result<Option<Vec<u8>>> {
+        let flags = self.flags.lock().expect("flag store lock poisoned");
+        Ok(flags.get(rom).cloned())
+    }
+
+    fn save(&self, rom: &str, flags: &[u8]) -> io::Result<()> {
+        let mut stored = self.flags.lock().expect("flag store lock poisoned");
+        stored.insert(rom.to_string(), flags.to_vec());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::flags::{FileFlagStore, FlagStore};
+
+    #[test]
+    fn test_file_store() {
+        let dir = std::env::temp_dir().join(format!("chirp-flags-{}", std::process::id()));
+        let store = FileFlagStore::new(&dir);
+        assert_eq!(None, store.load("abc").unwrap());
+
+        store.save("abc", &[1, 2, 3]).unwrap();
+        assert_eq!(
+            Some(vec![1, 2, 3]),
+            FileFlagStore::new(&dir).load("abc").unwrap()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
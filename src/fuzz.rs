@@ -0,0 +1,149 @@
+//! The checks behind the fuzz targets.
+//!
+//! Each function takes arbitrary bytes and panics when the code under test misbehaves on them.
+//! The cargo-fuzz targets in `fuzz/` hand them the inputs libFuzzer generates, and
+//! `tests/fuzz.rs` replays the corpus in `fuzz/corpus` through them, along with mutations of it,
+//! so that the seeds keep being checked by `cargo test` on stable Rust.
+use crate::{
+    assembler, disassembler,
+    instructions::Instruction,
+    loader::Profile,
+    machine::{Engine, FaultPolicy, Machine},
+    memory::Memory,
+    opcode::OpCode,
+    quirks::Platform,
+    state::State,
+};
+use std::convert::TryFrom;
+
+/// The amount of frames the execution checks run for
+const FRAMES: u32 = 30;
+
+/// Every word decodes either to nothing or to an instruction encoding back to the same word
+pub fn decode(data: &[u8]) {
+    for word in data.chunks_exact(2) {
+        let opcode = OpCode::new(u16::from_be_bytes([word[0], word[1]]));
+        if let Ok(instruction) = Instruction::try_from(opcode) {
+            assert_eq!(
+                opcode,
+                OpCode::from(instruction),
+                "{:?} decodes to {:?}",
+                opcode,
+                instruction
+            );
+        }
+    }
+}
+
+/// The disassembly of any program assembles back to the program, and anything the assembler
+/// accepts survives the same round trip
+pub fn disassemble(data: &[u8]) {
+    round_trip(data);
+    if let Ok(source) = std::str::from_utf8(data) {
+        if let Ok(assembly) = assembler::assemble(source, "fuzz.asm") {
+            round_trip(&assembly.bytes);
+        }
+    }
+}
+
+fn round_trip(bytes: &[u8]) {
+    let lines = disassembler::disassemble(bytes, 0x200);
+    let source: Vec<String> = lines
+        .iter()
+        .map(|line| match line.instruction {
+            Some(instruction) => instruction.to_string(),
+            None => format!("DW {:#06X}", u16::from(line.opcode)),
+        })
+        .collect();
+    let assembly = assembler::assemble(&source.join("\n"), "round-trip.asm")
+        .unwrap_or_else(|e| panic!("{}", e));
+
+    // The disassembler pads a trailing odd byte with a zero, so there is a word for every line
+    for ((line, text), word) in lines.iter().zip(&source).zip(assembly.bytes.chunks(2)) {
+        assert_eq!(
+            u16::from(line.opcode),
+            u16::from_be_bytes([word[0], word[1]]),
+            "{:#05X}: `{}` assembles to a different word",
+            line.address,
+            text
+        );
+    }
+    assert_eq!(lines.len() * 2, assembly.bytes.len());
+}
+
+/// Parsing never panics, a parsed state encodes to bytes parsing to the same state, and a machine
+/// can run from it
+pub fn state(data: &[u8]) {
+    let state = match State::from_bytes(data) {
+        Ok(state) => state,
+        Err(_) => return,
+    };
+    assert_eq!(Ok(&state), State::from_bytes(&state.to_bytes()).as_ref());
+
+    for &policy in &[FaultPolicy::Halt, FaultPolicy::Wrap, FaultPolicy::Ignore] {
+        let mut machine = Machine::new(Memory::new(), 0x200, &Profile::default()).policy(policy);
+        machine.load_state(state.clone());
+        run(machine);
+    }
+}
+
+/// Run a random program, picking the platform and fault policy from the first byte, checking
+/// that the machine never panics and that its state stays consistent
+pub fn execute(data: &[u8]) {
+    let (config, rom) = match data.split_first() {
+        Some((config, rom)) => (*config, rom),
+        None => return,
+    };
+    let platform = Platform::ALL[usize::from(config & 0xF) % Platform::ALL.len()];
+    let policy =
+        [FaultPolicy::Halt, FaultPolicy::Wrap, FaultPolicy::Ignore][usize::from(config >> 4) % 3];
+
+    let mut memory = Memory::new();
+    if memory.load(rom, 0x200).is_err() {
+        return;
+    }
+    let profile = Profile::for_platform(platform);
+    let mut machine = Machine::new(memory, 0x200, &profile).policy(policy);
+    // Hold down keys taken from the program, so that key waits don't stall every run
+    machine.keypad_mut().set_bits(u16::from_be_bytes([
+        rom.first().copied().unwrap_or(0),
+        rom.last().copied().unwrap_or(0),
+    ]));
+    run(machine);
+}
+
+/// Run `machine` for a few frames, checking invariants after every instruction and that the
+/// cached engine agrees with the interpreter
+fn run(machine: Machine) {
+    let mut cached = machine.clone().engine(Engine::Cached);
+    let mut machine = machine;
+    for _ in 0..FRAMES {
+        let interpreted = machine.run_frame_with(|machine| {
+            let executed = machine.step()?;
+            check(machine);
+            Ok(executed)
+        });
+        assert_eq!(interpreted, cached.run_frame());
+        assert_eq!(machine.save_state(), cached.save_state());
+        if interpreted.is_err() {
+            break;
+        }
+    }
+}
+
+fn check(machine: &Machine) {
+    let register = machine.register();
+    assert!(
+        usize::from(machine.pc()) < Memory::MEMORY_SIZE,
+        "the PC {:#X} is outside of memory",
+        machine.pc()
+    );
+    assert!(
+        register.sp <= register.stack.len(),
+        "stack pointer {} past a stack of {}",
+        register.sp,
+        register.stack.len()
+    );
+    let display = machine.display();
+    assert_eq!(display.width() * display.height(), display.pixels().len());
+}
@@ -0,0 +1,472 @@
+//! A reinforcement learning environment in the style of OpenAI Gym.
+//!
+//! An [`Env`] runs a ROM one step at a time: each step holds down the keys of the chosen action
+//! for a few frames and returns the display as the observation, along with a reward and whether
+//! the episode is over. What counts as a reward and when an episode ends depends on the game, so
+//! it is described by a [`Script`] reading the game's memory, typically stored next to the ROM as
+//! JSON:
+//!
+//! ```json
+//! {
+//!     "score": { "address": 768, "length": 3, "format": "bcd" },
+//!     "done": [{ "address": 770, "equals": 0 }]
+//! }
+//! ```
+//!
+//! The reward of a step is how much the score went up, and the episode ends once any of the
+//! `done` conditions holds or the machine halts. Environments are cheap to clone, so that many
+//! rollouts can run in parallel from the same starting point.
+use crate::{loader::Rom, machine::Machine, memory::Memory, state::State};
+use serde::Deserialize;
+use std::{convert::TryFrom, io, path::Path};
+
+#[derive(Debug, thiserror::Error)]
+pub enum EnvError {
+    #[error("Invalid action {0}, there are {1} actions")]
+    InvalidAction(usize, usize),
+    #[error("Failed to read script file")]
+    ReadScript(#[source] io::Error),
+    #[error("Failed to parse script")]
+    ParseScript(#[source] serde_json::Error),
+}
+
+/// How a value is stored in memory
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// An unsigned integer, most significant byte first
+    #[default]
+    Binary,
+    /// One decimal digit per byte, most significant first, as stored by `LD B, Vx`
+    Bcd,
+}
+
+/// A value in the memory of the game
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "ValueJson")]
+pub struct Value {
+    pub address: u16,
+    /// The amount of bytes the value takes up, at most 8 in binary and 19 digits in BCD so that
+    /// the value fits in 64 bits
+    pub length: u16,
+    pub format: Format,
+}
+
+#[derive(Deserialize)]
+struct ValueJson {
+    address: u16,
+    #[serde(default = "ValueJson::default_length")]
+    length: u16,
+    #[serde(default)]
+    format: Format,
+}
+
+impl ValueJson {
+    fn default_length() -> u16 {
+        1
+    }
+}
+
+impl TryFrom<ValueJson> for Value {
+    type Error = String;
+    fn try_from(json: ValueJson) -> Result<Self, Self::Error> {
+        let max = match json.format {
+            Format::Binary => 8,
+            Format::Bcd => 19,
+        };
+        if json.length > max {
+            return Err(format!(
+                "a {:?} value is at most {} bytes long, not {}",
+                json.format, max, json.length
+            ));
+        }
+        Ok(Self {
+            address: json.address,
+            length: json.length,
+            format: json.format,
+        })
+    }
+}
+
+impl Value {
+    /// Read the value from `memory`, bytes past the end of memory read as zero. Values too large
+    /// for 64 bits saturate.
+    pub fn read(&self, memory: &Memory) -> u64 {
+        (0..self.length).fold(0, |value: u64, offset| {
+            let byte = memory
+                .peek(usize::from(self.address) + usize::from(offset))
+                .unwrap_or(0);
+            match self.format {
+                Format::Binary => value
+                    .checked_shl(8)
+                    .map_or(u64::MAX, |v| v | u64::from(byte)),
+                Format::Bcd => value.saturating_mul(10).saturating_add(u64::from(byte)),
+            }
+        })
+    }
+}
+
+/// Ends the episode when a value in memory is equal to `equals`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub struct Condition {
+    #[serde(flatten)]
+    pub value: Value,
+    pub equals: u64,
+}
+
+/// Where a game keeps its score and what ends an episode
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct Script {
+    /// The score, without which every reward is zero
+    #[serde(default)]
+    pub score: Option<Value>,
+    /// The episode ends when any of these holds
+    #[serde(default)]
+    pub done: Vec<Condition>,
+}
+
+impl Script {
+    pub fn from_json(json: &str) -> Result<Self, EnvError> {
+        serde_json::from_str(json).map_err(EnvError::ParseScript)
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, EnvError> {
+        let json = std::fs::read_to_string(path).map_err(EnvError::ReadScript)?;
+        Self::from_json(&json)
+    }
+
+    fn score(&self, memory: &Memory) -> u64 {
+        self.score.map_or(0, |score| score.read(memory))
+    }
+
+    fn is_done(&self, memory: &Memory) -> bool {
+        self.done
+            .iter()
+            .any(|condition| condition.value.read(memory) == condition.equals)
+    }
+}
+
+/// The outcome of a step
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    /// The display after the step, one byte per pixel and row by row, 1 when the pixel is lit
+    pub observation: Vec<u8>,
+    /// How much the score went up during the step
+    pub reward: f64,
+    /// Whether the episode is over, after which further steps do nothing
+    pub done: bool,
+}
+
+/// A game being played by an agent
+#[derive(Clone, Debug)]
+pub struct Env {
+    machine: Machine,
+    /// The machine as it was built, which every episode starts from
+    start: State,
+    script: Script,
+    /// The keys held down by each action, one bit per key
+    actions: Vec<u16>,
+    frameskip: u32,
+    sticky: f64,
+    /// The keys held down during the last frame
+    held: u16,
+    score: u64,
+    done: bool,
+    /// The state of the xorshift random number generator deciding on sticky actions
+    rng: u64,
+}
+
+impl Env {
+    /// An environment playing on `machine`, which every episode starts from. The actions are to
+    /// press nothing or a single key, one for each key in the profile if the ROM has one and one
+    /// for each of the 16 keys otherwise.
+    pub fn new(machine: Machine, script: Script) -> Self {
+        let mut env = Self {
+            start: machine.save_state(),
+            machine,
+            script,
+            actions: std::iter::once(0)
+                .chain((0..0x10).map(|key| 1 << key))
+                .collect(),
+            frameskip: 1,
+            sticky: 0.0,
+            held: 0,
+            score: 0,
+            done: false,
+            rng: 0,
+        };
+        env.reset(0);
+        env
+    }
+
+    /// An environment playing `rom` with its recommended settings
+    pub fn from_rom(rom: Rom, script: Script) -> Self {
+        let mut keys: Vec<u8> = rom.profile.keys.values().copied().collect();
+        keys.sort_unstable();
+        keys.dedup();
+        let env = Self::new(Machine::from_rom(rom), script);
+        if keys.is_empty() {
+            env
+        } else {
+            env.actions(keys.into_iter().map(|key| 1 << key).collect())
+        }
+    }
+
+    /// Use `actions` as the keys held down by each action, one bit per key. Pressing nothing is
+    /// always action 0, followed by these.
+    pub fn actions(mut self, actions: Vec<u16>) -> Self {
+        self.actions = std::iter::once(0).chain(actions).collect();
+        self
+    }
+
+    /// Repeat each action for `frames` frames, summing up the rewards
+    pub fn frameskip(mut self, frames: u32) -> Self {
+        self.frameskip = frames.max(1);
+        self
+    }
+
+    /// Keep holding the keys of the previous frame instead of those of the chosen action with
+    /// probability `probability` on each frame, so that agents can't rely on exact timing
+    pub fn sticky_actions(mut self, probability: f64) -> Self {
+        self.sticky = probability;
+        self
+    }
+
+    /// The amount of actions, which are numbered from 0
+    pub fn action_count(&self) -> usize {
+        self.actions.len()
+    }
+
+    /// The keys held down by each action, one bit per key
+    pub fn action_keys(&self) -> &[u16] {
+        &self.actions
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    /// Start a new episode, with the random number generators of the game and of the sticky
+    /// actions seeded by `seed`, returning the first observation
+    pub fn reset(&mut self, seed: u64) -> Vec<u8> {
+        self.machine.load_state(self.start.clone());
+        self.machine.seed(seed);
+        // Don't let the sticky actions follow the same sequence as the game
+        self.rng = !seed ^ 0x9E37_79B9_7F4A_7C15;
+        self.held = 0;
+        self.score = self.script.score(self.machine.memory());
+        self.done = false;
+        self.observation()
+    }
+
+    /// Hold down the keys of `action` for the next few frames
+    pub fn step(&mut self, action: usize) -> Result<Step, EnvError> {
+        let keys = *self
+            .actions
+            .get(action)
+            .ok_or(EnvError::InvalidAction(action, self.actions.len()))?;
+
+        let mut reward = 0.0;
+        for _ in 0..self.frameskip {
+            if self.done {
+                break;
+            }
+            if self.sticky <= 0.0 || self.random() >= self.sticky {
+                self.held = keys;
+            }
+            self.machine.keypad_mut().set_bits(self.held);
+            let halted = self.machine.run_frame().is_err();
+
+            let memory = self.machine.memory();
+            let score = self.script.score(memory);
+            reward += score as f64 - self.score as f64;
+            self.score = score;
+            self.done = halted || self.script.is_done(memory);
+        }
+
+        Ok(Step {
+            observation: self.observation(),
+            reward,
+            done: self.done,
+        })
+    }
+
+    fn observation(&self) -> Vec<u8> {
+        self.machine.display().pixels().to_vec()
+    }
+
+    /// A random number in `[0, 1)`
+    fn random(&mut self) -> f64 {
+        let mut x = self.rng;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng = x;
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        gym::{Condition, Env, Format, Script, Value},
+        loader::Loader,
+        memory::Memory,
+    };
+    use std::convert::TryFrom;
+
+    /// Counts up to 10 in BCD at 0x300 while key 5 is held down, then exits
+    #[rustfmt::skip]
+    const COUNTER: [u8; 16] = [
+        0x65, 0x05, // LD V5, 0x05
+        0xA3, 0x00, // LD I, 0x300
+        0xE5, 0xA1, // SKNP V5
+        0x70, 0x01, // ADD V0, 0x01
+        0xF0, 0x33, // LD B, V0
+        0x30, 0x0A, // SE V0, 0x0A
+        0x12, 0x02, // JP 0x202
+        0x00, 0xFD, // EXIT
+    ];
+
+    fn env(script: Script) -> Env {
+        let rom = Loader::new().load(&COUNTER).unwrap();
+        Env::from_rom(rom, script).actions(vec![1 << 5])
+    }
+
+    fn script() -> Script {
+        Script::from_json(r#"{ "score": { "address": 768, "length": 3, "format": "bcd" } }"#)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_script() {
+        let script = Script::from_json(
+            r#"{
+                "score": { "address": 768, "length": 3, "format": "bcd" },
+                "done": [{ "address": 770, "equals": 5 }]
+            }"#,
+        )
+        .unwrap();
+        let value = Value {
+            address: 0x300,
+            length: 3,
+            format: Format::Bcd,
+        };
+        assert_eq!(Some(value), script.score);
+        let condition = Condition {
+            value: Value {
+                address: 0x302,
+                length: 1,
+                format: Format::Binary,
+            },
+            equals: 5,
+        };
+        assert_eq!(vec![condition], script.done);
+        assert!(Script::from_json(r#"{ "score": { "format": "bcd" } }"#).is_err());
+        assert!(Script::from_json(
+            r#"{ "score": { "address": 768, "length": 20, "format": "bcd" } }"#
+        )
+        .is_err());
+        assert!(
+            Script::from_json(r#"{ "done": [{ "address": 768, "length": 9, "equals": 0 }] }"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_large_values() {
+        let memory = Memory::try_from(&[0xFF; 24][..]).unwrap();
+        let bcd = Value {
+            address: 0x200,
+            length: 24,
+            format: Format::Bcd,
+        };
+        assert_eq!(u64::MAX, bcd.read(&memory));
+        let binary = Value {
+            address: 0x200,
+            length: 9,
+            format: Format::Binary,
+        };
+        assert_eq!(u64::MAX, binary.read(&memory));
+    }
+
+    #[test]
+    fn test_step() {
+        let mut env = env(script());
+        assert_eq!(2, env.action_count());
+        assert!(env.step(2).is_err());
+
+        let step = env.step(0).unwrap();
+        assert_eq!(64 * 32, step.observation.len());
+        assert_eq!((0.0, false), (step.reward, step.done));
+
+        let mut total = 0.0;
+        let mut steps = 0;
+        loop {
+            let step = env.step(1).unwrap();
+            assert!(step.reward >= 0.0);
+            total += step.reward;
+            steps += 1;
+            if step.done {
+                break;
+            }
+        }
+        assert_eq!(10.0, total);
+
+        // Frameskip repeats the action, so fewer steps earn the same reward
+        let mut env = env.frameskip(4);
+        env.reset(0);
+        let mut skipped = 0;
+        while !env.step(1).unwrap().done {
+            skipped += 1;
+        }
+        assert!(skipped < steps);
+    }
+
+    #[test]
+    fn test_done() {
+        let mut script = script();
+        script.done.push(Condition {
+            value: Value {
+                address: 0x302,
+                length: 1,
+                format: Format::Binary,
+            },
+            equals: 5,
+        });
+        let mut env = env(script);
+        while !env.step(1).unwrap().done {}
+        assert!(env.machine().fault().is_none());
+        assert_eq!(5, env.machine().memory().peek(0x302).unwrap());
+    }
+
+    #[test]
+    fn test_reset() {
+        let rom = Loader::new().load_path("./games/tetris.ch8").unwrap();
+        let mut env = Env::from_rom(rom, Script::default()).sticky_actions(0.25);
+        let first = env.reset(7);
+
+        let mut clone = env.clone();
+        let actions: Vec<usize> = (0..200).map(|i| i % env.action_count()).collect();
+        let run = |env: &mut Env| -> Vec<Vec<u8>> {
+            actions
+                .iter()
+                .map(|&action| env.step(action).unwrap().observation)
+                .collect()
+        };
+        let played = run(&mut env);
+        assert_eq!(played, run(&mut clone));
+
+        assert_eq!(first, env.reset(7));
+        assert_eq!(played, run(&mut env));
+    }
+
+    #[test]
+    fn test_sticky_actions() {
+        // Keys are released when an episode starts, and always sticking keeps them released
+        let mut env = env(script()).sticky_actions(1.0);
+        for _ in 0..100 {
+            assert_eq!(0.0, env.step(1).unwrap().reward);
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! The interface between the machine and whatever frontend presents it.
+//!
+//! The core knows nothing about windows, terminals or speakers. A frontend implements the four
+//! traits below and hands them to [`Machine::run_frame_on`](crate::machine::Machine::run_frame_on),
+//! which calls them at the same points of every 60Hz frame:
+//!
+//! 1. [`Clock::wait_frame`], to pace the emulation
+//! 2. [`InputSource::poll`], to update the keypad before any instruction runs
+//! 3. the frame's instructions and the timer tick
+//! 4. [`VideoSink::present`] with the display as the frame left it
+//! 5. [`AudioSink::tone`] with whether the buzzer sounds until the next frame
+//!
+//! The null implementations ignore everything, and the recording ones keep what they were given
+//! so that tests can inspect it.
+use crate::{display::Display, keypad::Keypad};
+use std::time::{Duration, Instant};
+
+/// Presents the display
+pub trait VideoSink {
+    /// Show `display`, called once per frame after the instructions ran
+    fn present(&mut self, display: &Display);
+}
+
+/// Plays the buzzer
+pub trait AudioSink {
+    /// Whether the buzzer sounds until the next frame, called once per frame
+    fn tone(&mut self, playing: bool);
+}
+
+/// Reads the keys held down
+pub trait InputSource {
+    /// Update `keypad` with the keys held down, called once per frame before the instructions run
+    fn poll(&mut self, keypad: &mut Keypad);
+}
+
+/// Paces the emulation
+pub trait Clock {
+    /// Wait for the next frame to start, called once per frame before anything else
+    fn wait_frame(&mut self);
+}
+
+/// The frontend a machine runs on
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Host<V, A, I, C> {
+    pub video: V,
+    pub audio: A,
+    pub input: I,
+    pub clock: C,
+}
+
+impl<V, A, I, C> Host<V, A, I, C>
+where
+    V: VideoSink,
+    A: AudioSink,
+    I: InputSource,
+    C: Clock,
+{
+    pub fn new(video: V, audio: A, input: I, clock: C) -> Self {
+        Self {
+            video,
+            audio,
+            input,
+            clock,
+        }
+    }
+}
+
+/// A host that ignores everything, running as fast as possible
+pub type NullHost = Host<NullVideo, NullAudio, NullInput, NullClock>;
+
+/// Ignores every frame
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NullVideo;
+
+impl VideoSink for NullVideo {
+    fn present(&mut self, _display: &Display) {}
+}
+
+/// Ignores the buzzer
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NullAudio;
+
+impl AudioSink for NullAudio {
+    fn tone(&mut self, _playing: bool) {}
+}
+
+/// Never presses any key, leaving the keypad as it is
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NullInput;
+
+impl InputSource for NullInput {
+    fn poll(&mut self, _keypad: &mut Keypad) {}
+}
+
+/// Never waits
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NullClock;
+
+impl Clock for NullClock {
+    fn wait_frame(&mut self) {}
+}
+
+/// Keeps every frame presented
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RecordingVideo {
+    pub frames: Vec<Display>,
+}
+
+impl VideoSink for RecordingVideo {
+    fn present(&mut self, display: &Display) {
+        self.frames.push(display.clone());
+    }
+}
+
+/// Keeps whether the buzzer sounded on each frame
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RecordingAudio {
+    pub tones: Vec<bool>,
+}
+
+impl AudioSink for RecordingAudio {
+    fn tone(&mut self, playing: bool) {
+        self.tones.push(playing);
+    }
+}
+
+/// Replays recorded keypad states, one per frame, and releases every key once they run out
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RecordedInput {
+    /// One bit per key held down, for each frame
+    states: Vec<u16>,
+    frame: usize,
+}
+
+impl RecordedInput {
+    pub fn new(states: Vec<u16>) -> Self {
+        Self { states, frame: 0 }
+    }
+}
+
+impl InputSource for RecordedInput {
+    fn poll(&mut self, keypad: &mut Keypad) {
+        keypad.set_bits(self.states.get(self.frame).copied().unwrap_or(0));
+        self.frame += 1;
+    }
+}
+
+/// Counts the frames without waiting
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RecordingClock {
+    pub frames: u64,
+}
+
+impl Clock for RecordingClock {
+    fn wait_frame(&mut self) {
+        self.frames += 1;
+    }
+}
+
+/// Runs frames at 60Hz in real time
+#[derive(Clone, Copy, Debug)]
+pub struct RealTimeClock {
+    next: Option<Instant>,
+}
+
+impl RealTimeClock {
+    /// The length of a frame
+    pub const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+    pub fn new() -> Self {
+        Self { next: None }
+    }
+}
+
+impl Default for RealTimeClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for RealTimeClock {
+    fn wait_frame(&mut self) {
+        let now = Instant::now();
+        let next = match self.next {
+            Some(next) if next > now => {
+                std::thread::sleep(next - now);
+                next
+            }
+            // Don't try to catch up after falling behind
+            _ => now,
+        };
+        self.next = Some(next + Self::FRAME);
+    }
+}
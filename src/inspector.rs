@@ -0,0 +1,283 @@
+//! Tools for inspecting and poking at a running program's memory.
+//!
+//! * [`write_dump`] prints a range of memory, annotated with the labels and comments of a
+//!   [`SymbolTable`].
+//! * [`diff`] lists the bytes that differ between two snapshots of memory.
+//! * [`Scanner`] narrows down the location of a variable, such as the score or the amount of lives
+//!   left, by repeatedly filtering the candidate addresses on how their value changed.
+//! * [`Cheats`] freezes addresses to a value. The machine applies them at the end of every frame,
+//!   see [`Machine::freeze`](crate::machine::Machine::freeze).
+use crate::{
+    memory::{Memory, MemoryError},
+    symbols::SymbolTable,
+};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{self, Write},
+    ops::Range,
+};
+
+/// The amount of bytes shown per line by `write_dump`
+const DUMP_WIDTH: usize = 16;
+
+/// Write a hex dump of `range`, with one line per 16 bytes. Each line is followed by the labels
+/// and comments from `symbols` for the addresses it covers.
+pub fn write_dump<W: Write>(
+    memory: &Memory,
+    range: Range<u16>,
+    symbols: &SymbolTable,
+    mut w: W,
+) -> io::Result<()> {
+    let bytes = memory.as_bytes();
+    let start = usize::from(range.start).min(bytes.len());
+    let end = usize::from(range.end).min(bytes.len());
+
+    let mut addr = start;
+    while addr < end {
+        let row = &bytes[addr..end.min(addr + DUMP_WIDTH)];
+        write!(w, "{:#05X} ", addr)?;
+        for byte in row {
+            write!(w, " {:02X}", byte)?;
+        }
+        write!(w, "{:1$}  |", "", 3 * (DUMP_WIDTH - row.len()))?;
+        for byte in row {
+            let c = if byte.is_ascii_graphic() || *byte == b' ' {
+                *byte as char
+            } else {
+                '.'
+            };
+            write!(w, "{}", c)?;
+        }
+        write!(w, "|")?;
+
+        let notes: Vec<String> = (addr..addr + row.len())
+            .flat_map(|a| {
+                let a = a as u16;
+                let label = symbols.label(a).map(|l| format!("{:#05X} {}", a, l));
+                let comment = symbols.comment(a).map(|c| format!("{:#05X} {}", a, c));
+                label.into_iter().chain(comment)
+            })
+            .collect();
+        if !notes.is_empty() {
+            write!(w, "  ; {}", notes.join(", "))?;
+        }
+        writeln!(w)?;
+
+        addr += row.len();
+    }
+
+    Ok(())
+}
+
+/// A byte that differs between two snapshots of memory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Change {
+    pub address: u16,
+    pub old: u8,
+    pub new: u8,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:#05X}: {:#04X} -> {:#04X}",
+            self.address, self.old, self.new
+        )
+    }
+}
+
+/// Every byte that differs between `old` and `new`, in ascending address order
+pub fn diff(old: &Memory, new: &Memory) -> Vec<Change> {
+    old.as_bytes()
+        .iter()
+        .zip(new.as_bytes())
+        .enumerate()
+        .filter(|(_, (old, new))| old != new)
+        .map(|(address, (old, new))| Change {
+            address: address as u16,
+            old: *old,
+            new: *new,
+        })
+        .collect()
+}
+
+/// How a candidate's value must relate to its value in the previous scan to be kept
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    /// The value is exactly the given one
+    Equal(u8),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl Filter {
+    fn matches(self, previous: u8, current: u8) -> bool {
+        match self {
+            Filter::Equal(value) => current == value,
+            Filter::Changed => current != previous,
+            Filter::Unchanged => current == previous,
+            Filter::Increased => current > previous,
+            Filter::Decreased => current < previous,
+        }
+    }
+}
+
+/// An iterative value scanner, as found in cheat tools.
+///
+/// The scanner starts with every program address as a candidate, and each call to
+/// [`Scanner::scan`] drops the candidates whose value does not match the filter.
+#[derive(Clone, Debug)]
+pub struct Scanner {
+    /// The candidates and their value as of the last scan
+    candidates: BTreeMap<u16, u8>,
+}
+
+impl Scanner {
+    /// Start a new scan, taking `memory` as the baseline for the first filter
+    pub fn new(memory: &Memory) -> Self {
+        let candidates = memory
+            .as_bytes()
+            .iter()
+            .enumerate()
+            .skip(Memory::MEMORY_START)
+            .map(|(addr, value)| (addr as u16, *value))
+            .collect();
+        Self { candidates }
+    }
+
+    /// Keep only the candidates whose value in `memory` matches `filter`
+    pub fn scan(&mut self, memory: &Memory, filter: Filter) {
+        let bytes = memory.as_bytes();
+        self.candidates.retain(|addr, previous| {
+            let current = bytes[usize::from(*addr)];
+            let keep = filter.matches(*previous, current);
+            *previous = current;
+            keep
+        });
+    }
+
+    /// The remaining candidates, with their value as of the last scan
+    pub fn candidates(&self) -> impl Iterator<Item = (u16, u8)> + '_ {
+        self.candidates.iter().map(|(addr, value)| (*addr, *value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+}
+
+/// A set of addresses frozen to a value
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cheats {
+    frozen: BTreeMap<u16, u8>,
+}
+
+impl Cheats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Freeze `addr` to `value`, replacing any previous value it was frozen to
+    pub fn freeze(&mut self, addr: u16, value: u8) {
+        self.frozen.insert(addr, value);
+    }
+
+    pub fn unfreeze(&mut self, addr: u16) {
+        self.frozen.remove(&addr);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u16, u8)> + '_ {
+        self.frozen.iter().map(|(addr, value)| (*addr, *value))
+    }
+
+    /// Write every frozen value into `memory`, meant to be called once per frame
+    pub fn apply(&self, memory: &mut Memory) -> Result<(), MemoryError> {
+        for (addr, value) in self.iter() {
+            *memory.get_mut(usize::from(addr))? = value;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        inspector::{diff, write_dump, Change, Cheats, Filter, Scanner},
+        memory::{Memory, MemoryError},
+        symbols::SymbolTable,
+    };
+
+    #[test]
+    fn test_dump() {
+        let mut memory = Memory::new();
+        *memory.get_mut(0x300).unwrap() = b'H';
+        *memory.get_mut(0x301).unwrap() = b'i';
+        let symbols = SymbolTable::parse("label 0x301 greeting\ncomment 0x311 unused").unwrap();
+
+        let mut dump = Vec::new();
+        write_dump(&memory, 0x300..0x312, &symbols, &mut dump).unwrap();
+        assert_eq!(
+            "0x300  48 69 00 00 00 00 00 00 00 00 00 00 00 00 00 00  |Hi..............|  \
+             ; 0x301 greeting\n\
+             0x310  00 00                                            |..|  ; 0x311 unused\n",
+            String::from_utf8(dump).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = Memory::new();
+        let mut new = old.clone();
+        *new.get_mut(0x2A0).unwrap() = 0x10;
+        assert_eq!(
+            vec![Change {
+                address: 0x2A0,
+                old: 0x00,
+                new: 0x10
+            }],
+            diff(&old, &new)
+        );
+        assert_eq!("0x2A0: 0x00 -> 0x10", diff(&old, &new)[0].to_string());
+    }
+
+    #[test]
+    fn test_scanner() {
+        let mut memory = Memory::new();
+        *memory.get_mut(0x300).unwrap() = 3;
+        *memory.get_mut(0x400).unwrap() = 3;
+        let mut scanner = Scanner::new(&memory);
+
+        scanner.scan(&memory, Filter::Equal(3));
+        assert_eq!(2, scanner.len());
+
+        *memory.get_mut(0x300).unwrap() = 2;
+        scanner.scan(&memory, Filter::Decreased);
+        assert_eq!(vec![(0x300, 2)], scanner.candidates().collect::<Vec<_>>());
+
+        scanner.scan(&memory, Filter::Changed);
+        assert!(scanner.is_empty());
+    }
+
+    #[test]
+    fn test_cheats() {
+        let mut memory = Memory::new();
+        let mut cheats = Cheats::new();
+        cheats.freeze(0x300, 9);
+        cheats.apply(&mut memory).unwrap();
+        assert_eq!(9, *memory.get(0x300).unwrap());
+
+        cheats.freeze(0x100, 9);
+        match cheats.apply(&mut memory) {
+            Err(MemoryError::OutOfBoundsAccess(0x100)) => (),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+}
@@ -1,61 +1,64 @@
-///! The original implementation of the CHIP-8 language includes 36 different instructions,
-///! including math, graphics, and flow control functions. Super Chip-48 added an additional 10
-///! instructions, for a total of 46.
-///!
-///! All instructions are 2 bytes long and are stored most-significant-byte first. In memory, the
-///! first byte of each instruction should be located at an even addresses. If a program includes
-///! sprite data, it should be padded so any instructions following it will be properly situated in
-///! RAM.
-///!
-///! | Symbol   | Width (bits) |
-///! |:--------:|:------------:|
-///! | `x`, `y` | 4            |
-///! | `k`...   | 4 * `k`      |
-///!
-///! | OpCode   | ASM                  | Op                                                                        |
-///! | -------- | -------------------- | ------------------------------------------------------------------------- |
-///! | `0kkk`   | `SYS addr`           | Jump to a machine code routine at `kkk` [DEPRECATED]                      |
-///! | `00Ck`   | `SCD`                | Scroll down `k` lines                                                     |
-///! | `00FB`   | `SCR`                | Scroll right by 4 pixel                                                   |
-///! | `00FC`   | `SCL`                | Scroll left by 4 pixel                                                    |
-///! | `00FD`   | `EXIT`               | Quit the emulator                                                         |
-///! | `00FE`   | `LOW`                | Set CHIP-8 graphics mode                                                  |
-///! | `00FF`   | `HIGH`               | Set SCHIP-8 graphics mode                                                 |
-///! | `00E0`   | `CLS`                | Clear the display                                                         |
-///! | `00EE`   | `RET`                | Return from a subroutine                                                  |
-///! | `1kkk`   | `JP addr`            | Jump to location `kkk`                                                    |
-///! | `2kkk`   | `CALL addr`          | Call subroutine at `kkk`                                                  |
-///! | `3xkk`   | `SE Vx, byte`        | Skip next instruction if `Vx = kk`                                        |
-///! | `4xkk`   | `SNE Vx, byte`       | Skip next instruction if `Vx != kk`                                       |
-///! | `5xy0`   | `SE Vx, Vy`          | Skip next instruction if `Vx = Vy`                                        |
-///! | `6xkk`   | `LD Vx, byte`        | Set Vx = kk                                                               |
-///! | `7xkk`   | `ADD Vx, byte`       | Set Vx = Vx + kk                                                          |
-///! | `8xy0`   | `LD Vx Vy`           | Set Vx = Vy                                                               |
-///! | `8xy1`   | `OR Vx, Vy`          | Set Vx = Vx OR Vy                                                         |
-///! | `8xy2`   | `AND Vx, Vy`         | Set Vx = Vx AND Vy                                                        |
-///! | `8xy3`   | `XOR Vx, Vy`         | Set Vx = Vx XOR Vy                                                        |
-///! | `8xy4`   | `ADD Vx, Vy`         | Set Vx = Vx + Vy, VF = carry                                              |
-///! | `8xy5`   | `SUB Vx, Vy`         | Set Vx = Vx - Vy, VF = not borrow                                         |
-///! | `8xy6`   | `SHR Vx{, Vy}`       | Set Vx = Vx SHR 1, VF = carry                                             |
-///! | `8xy7`   | `SUBN Vx{, Vy}`      | Set Vx = Vy - Vx, set VF = not borrow                                     |
-///! | `8xyE`   | `SHL Vx{, Vy}`       | Set Vx = Vx SHL 1, VF = carry                                             |
-///! | `9xy0`   | `SNE Vx, Vy`         | Skip next instruction if Vx != Vy                                         |
-///! | `Akkk`   | `LD I, addr`         | Set I = kkk                                                               |
-///! | `Bkkk`   | `JP V0, addr`        | Jump to location kkk + V0                                                 |
-///! | `Cxkk`   | `RND Vx, byte`       | Set Vx = random byte AND kk                                               |
-///! | `Dxyk`   | `DRW Vx, Vy, nibble` | Display n-byte sprite starting at M[I] from (Vx, Vy), set VF = collision  |
-///! | `Ex9E`   | `SKP Vx`             | Skip next instruction if key with the value of Vx is pressed.             |
-///! | `ExA1`   | `SNKP Vx`            | Skip next instruction if key with the value of Vx is not pressed          |
-///! | `Fx07`   | `LD Vx, DT`          | Set Vx = delay timer value                                                |
-///! | `Fx0A`   | `LD Vx, K`           | Wait for a key press, store the value of the key in Vx                    |
-///! | `Fx15`   | `LD DT, Vx`          | Set delay timer = Vx                                                      |
-///! | `Fx18`   | `LD ST, Vx`          | Set sound timer = Vx                                                      |
-///! | `Fx1E`   | `ADD I, Vx`          | Set I = I + Vx                                                            |
-///! | `Fx29`   | `LD F, Vx`           | Set I = location of sprite for digit Vx                                   |
-///! | `Fx33`   | `LD B, Vx`           | Store BCD representation of Vx in memory locations I, I+1, and I+2        |
-///! | `Fx55`   | `LD [I], Vx`         | Store registers V0 through Vx in memory starting at location I            |
-///! | `Fx65`   | `LD Vx, [I]`         | Read registers V0 through Vx from memory starting at location I           |
+//! The original implementation of the CHIP-8 language includes 36 different instructions,
+//! including math, graphics, and flow control functions. Super Chip-48 added an additional 10
+//! instructions, for a total of 46.
+//!
+//! All instructions are 2 bytes long and are stored most-significant-byte first. In memory, the
+//! first byte of each instruction should be located at an even addresses. If a program includes
+//! sprite data, it should be padded so any instructions following it will be properly situated in
+//! RAM.
+//!
+//! | Symbol   | Width (bits) |
+//! |:--------:|:------------:|
+//! | `x`, `y` | 4            |
+//! | `k`...   | 4 * `k`      |
+//!
+//! | OpCode   | ASM                  | Op                                                                        |
+//! | -------- | -------------------- | ------------------------------------------------------------------------- |
+//! | `0kkk`   | `SYS addr`           | Jump to a machine code routine at `kkk` [DEPRECATED]                      |
+//! | `00Ck`   | `SCD`                | Scroll down `k` lines                                                     |
+//! | `00FB`   | `SCR`                | Scroll right by 4 pixel                                                   |
+//! | `00FC`   | `SCL`                | Scroll left by 4 pixel                                                    |
+//! | `00FD`   | `EXIT`               | Quit the emulator                                                         |
+//! | `00FE`   | `LOW`                | Set CHIP-8 graphics mode                                                  |
+//! | `00FF`   | `HIGH`               | Set SCHIP-8 graphics mode                                                 |
+//! | `00E0`   | `CLS`                | Clear the display                                                         |
+//! | `00EE`   | `RET`                | Return from a subroutine                                                  |
+//! | `1kkk`   | `JP addr`            | Jump to location `kkk`                                                    |
+//! | `2kkk`   | `CALL addr`          | Call subroutine at `kkk`                                                  |
+//! | `3xkk`   | `SE Vx, byte`        | Skip next instruction if `Vx = kk`                                        |
+//! | `4xkk`   | `SNE Vx, byte`       | Skip next instruction if `Vx != kk`                                       |
+//! | `5xy0`   | `SE Vx, Vy`          | Skip next instruction if `Vx = Vy`                                        |
+//! | `6xkk`   | `LD Vx, byte`        | Set Vx = kk                                                               |
+//! | `7xkk`   | `ADD Vx, byte`       | Set Vx = Vx + kk                                                          |
+//! | `8xy0`   | `LD Vx Vy`           | Set Vx = Vy                                                               |
+//! | `8xy1`   | `OR Vx, Vy`          | Set Vx = Vx OR Vy                                                         |
+//! | `8xy2`   | `AND Vx, Vy`         | Set Vx = Vx AND Vy                                                        |
+//! | `8xy3`   | `XOR Vx, Vy`         | Set Vx = Vx XOR Vy                                                        |
+//! | `8xy4`   | `ADD Vx, Vy`         | Set Vx = Vx + Vy, VF = carry                                              |
+//! | `8xy5`   | `SUB Vx, Vy`         | Set Vx = Vx - Vy, VF = not borrow                                         |
+//! | `8xy6`   | `SHR Vx{, Vy}`       | Set Vx = Vx SHR 1, VF = carry                                             |
+//! | `8xy7`   | `SUBN Vx{, Vy}`      | Set Vx = Vy - Vx, set VF = not borrow                                     |
+//! | `8xyE`   | `SHL Vx{, Vy}`       | Set Vx = Vx SHL 1, VF = carry                                             |
+//! | `9xy0`   | `SNE Vx, Vy`         | Skip next instruction if Vx != Vy                                         |
+//! | `Akkk`   | `LD I, addr`         | Set I = kkk                                                               |
+//! | `Bkkk`   | `JP V0, addr`        | Jump to location kkk + V0                                                 |
+//! | `Cxkk`   | `RND Vx, byte`       | Set Vx = random byte AND kk                                               |
+//! | `Dxyk`   | `DRW Vx, Vy, nibble` | Display n-byte sprite starting at M[I] from (Vx, Vy), set VF = collision  |
+//! | `Ex9E`   | `SKP Vx`             | Skip next instruction if key with the value of Vx is pressed.             |
+//! | `ExA1`   | `SNKP Vx`            | Skip next instruction if key with the value of Vx is not pressed          |
+//! | `Fx07`   | `LD Vx, DT`          | Set Vx = delay timer value                                                |
+//! | `Fx0A`   | `LD Vx, K`           | Wait for a key press, store the value of the key in Vx                    |
+//! | `Fx15`   | `LD DT, Vx`          | Set delay timer = Vx                                                      |
+//! | `Fx18`   | `LD ST, Vx`          | Set sound timer = Vx                                                      |
+//! | `Fx1E`   | `ADD I, Vx`          | Set I = I + Vx                                                            |
+//! | `Fx29`   | `LD F, Vx`           | Set I = location of sprite for digit Vx                                   |
+//! | `Fx33`   | `LD B, Vx`           | Store BCD representation of Vx in memory locations I, I+1, and I+2        |
+//! | `Fx55`   | `LD [I], Vx`         | Store registers V0 through Vx in memory starting at location I            |
+//! | `Fx65`   | `LD Vx, [I]`         | Read registers V0 through Vx from memory starting at location I           |
+//! | `Fx75`   | `LD R, Vx`           | Store registers V0 through Vx in the RPL user flags                       |
+//! | `Fx85`   | `LD Vx, R`           | Read registers V0 through Vx from the RPL user flags                      |
 use crate::opcode::OpCode;
+use std::{convert::TryFrom, fmt, sync::OnceLock};
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, PartialOrd, Ord)]
 pub enum Instruction {
@@ -219,60 +222,341 @@ pub enum Instruction {
     /// | -------- | -------------------- | ------------------------------------------------------------------------- |
     /// | `Fx65`   | `LD Vx, [I]`         | Read registers V0 through Vx from memory starting at location I           |
     LoadMemIntoV(u8),
+    /// | OpCode   | ASM                  | Op                                                                        |
+    /// | -------- | -------------------- | ------------------------------------------------------------------------- |
+    /// | `Fx75`   | `LD R, Vx`           | Store registers V0 through Vx in the RPL user flags                       |
+    LoadVIntoFlags(u8),
+    /// | OpCode   | ASM                  | Op                                                                        |
+    /// | -------- | -------------------- | ------------------------------------------------------------------------- |
+    /// | `Fx85`   | `LD Vx, R`           | Read registers V0 through Vx from the RPL user flags                      |
+    LoadFlagsIntoV(u8),
 }
 
-impl From<OpCode> for Instruction {
-    fn from(opcode: OpCode) -> Self {
-        use bitvec::prelude::*;
+impl Instruction {
+    /// Whether the instruction conditionally skips the next one
+    pub fn is_skip(&self) -> bool {
         use Instruction::*;
+        matches!(
+            self,
+            SkipEqualImmediate(..)
+                | SkipNotEqualImmediate(..)
+                | SkipEqual(..)
+                | SkipNotEqual(..)
+                | SkipOnKey(..)
+                | SkipNotOnKey(..)
+        )
+    }
 
-        match opcode[12..16].load::<u8>() {
-            0x0 => match opcode[4..12].load::<u8>() {
-                0x0C => ScrollDown(opcode[0..4].load::<u8>()),
-                0x0F => match opcode[0..4].load::<u8>() {
+    /// The name of the instruction variant, without its operands
+    pub fn name(&self) -> &'static str {
+        match self {
+            Instruction::ScrollDown(_) => "ScrollDown",
+            Instruction::ScrollRight => "ScrollRight",
+            Instruction::ScrollLeft => "ScrollLeft",
+            Instruction::Exit => "Exit",
+            Instruction::LowRes => "LowRes",
+            Instruction::HighRes => "HighRes",
+            Instruction::ClearScreen => "ClearScreen",
+            Instruction::Return => "Return",
+            Instruction::Jump(_) => "Jump",
+            Instruction::Call(_) => "Call",
+            Instruction::SkipEqualImmediate(_, _) => "SkipEqualImmediate",
+            Instruction::SkipNotEqualImmediate(_, _) => "SkipNotEqualImmediate",
+            Instruction::SkipEqual(_, _) => "SkipEqual",
+            Instruction::LoadImmediate(_, _) => "LoadImmediate",
+            Instruction::AddImmediate(_, _) => "AddImmediate",
+            Instruction::Load(_, _) => "Load",
+            Instruction::Or(_, _) => "Or",
+            Instruction::And(_, _) => "And",
+            Instruction::Xor(_, _) => "Xor",
+            Instruction::Add(_, _) => "Add",
+            Instruction::Sub(_, _) => "Sub",
+            Instruction::ShiftRight(_, _) => "ShiftRight",
+            Instruction::SubNumeric(_, _) => "SubNumeric",
+            Instruction::ShiftLeft(_, _) => "ShiftLeft",
+            Instruction::SkipNotEqual(_, _) => "SkipNotEqual",
+            Instruction::LoadI(_) => "LoadI",
+            Instruction::JumpImmediate(_) => "JumpImmediate",
+            Instruction::Random(_, _) => "Random",
+            Instruction::Draw(_, _, _) => "Draw",
+            Instruction::SkipOnKey(_) => "SkipOnKey",
+            Instruction::SkipNotOnKey(_) => "SkipNotOnKey",
+            Instruction::LoadDTIntoV(_) => "LoadDTIntoV",
+            Instruction::LoadKey(_) => "LoadKey",
+            Instruction::LoadVIntoDT(_) => "LoadVIntoDT",
+            Instruction::LoadVIntoST(_) => "LoadVIntoST",
+            Instruction::AddI(_) => "AddI",
+            Instruction::LoadSpriteIntoI(_) => "LoadSpriteIntoI",
+            Instruction::LoadBCDIntoI(_) => "LoadBCDIntoI",
+            Instruction::LoadVIntoMem(_) => "LoadVIntoMem",
+            Instruction::LoadMemIntoV(_) => "LoadMemIntoV",
+            Instruction::LoadVIntoFlags(_) => "LoadVIntoFlags",
+            Instruction::LoadFlagsIntoV(_) => "LoadFlagsIntoV",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum InstructionError {
+    #[error("Unknown opcode {0:?}")]
+    UnknownOpCode(OpCode),
+}
+
+impl TryFrom<OpCode> for Instruction {
+    type Error = InstructionError;
+    fn try_from(opcode: OpCode) -> Result<Self, Self::Error> {
+        use Instruction::*;
+
+        let unknown = Err(InstructionError::UnknownOpCode(opcode));
+
+        let instruction = match opcode.o() {
+            0x0 => match opcode.nnn() >> 4 {
+                0x0C => ScrollDown(opcode.n()),
+                0x0F => match opcode.n() {
                     0xB => ScrollRight,
                     0xC => ScrollLeft,
                     0xD => Exit,
                     0xE => LowRes,
                     0xF => HighRes,
-                    _ => todo!(),
+                    _ => return unknown,
                 },
-                0x0E => match opcode[0..4].load::<u8>() {
+                0x0E => match opcode.n() {
                     0x0 => ClearScreen,
                     0xE => Return,
-                    _ => todo!(),
+                    _ => return unknown,
                 },
-                _ => todo!(),
+                _ => return unknown,
+            },
+            0x1 => Jump(opcode.nnn()),
+            0x2 => Call(opcode.nnn()),
+            0x3 => SkipEqualImmediate(opcode.x(), opcode.kk()),
+            0x4 => SkipNotEqualImmediate(opcode.x(), opcode.kk()),
+            0x5 => match opcode.n() {
+                0x0 => SkipEqual(opcode.x(), opcode.y()),
+                _ => return unknown,
             },
-            0x1 => Jump(opcode[0..12].load::<u16>()),
-            0x2 => Call(opcode[0..12].load::<u16>()),
-            0x3 => SkipEqualImmediate(opcode[8..12].load::<u8>(), opcode[0..8].load::<u8>()),
-            0x4 => SkipNotEqualImmediate(opcode[8..12].load::<u8>(), opcode[0..8].load::<u8>()),
-            0x5 => match opcode[0..4].load::<u8>() {
-                0x0 => SkipEqual(opcode[4..8].load::<u8>(), opcode[8..12].load::<u8>()),
-                _ => todo!(),
+            0x6 => LoadImmediate(opcode.x(), opcode.kk()),
+            0x7 => AddImmediate(opcode.x(), opcode.kk()),
+            0x8 => match opcode.n() {
+                0x0 => Load(opcode.x(), opcode.y()),
+                0x1 => Or(opcode.x(), opcode.y()),
+                0x2 => And(opcode.x(), opcode.y()),
+                0x3 => Xor(opcode.x(), opcode.y()),
+                0x4 => Add(opcode.x(), opcode.y()),
+                0x5 => Sub(opcode.x(), opcode.y()),
+                0x6 => ShiftRight(opcode.x(), opcode.y()),
+                0x7 => SubNumeric(opcode.x(), opcode.y()),
+                0xE => ShiftLeft(opcode.x(), opcode.y()),
+                _ => return unknown,
             },
-            0x6 => LoadImmediate(opcode[8..12].load::<u8>(), opcode[0..8].load::<u8>()),
-            0x7 => AddImmediate(opcode[8..12].load::<u8>(), opcode[0..8].load::<u8>()),
-            0x8 => match opcode[0..4].load::<u8>() {
-                0x0 => Load(opcode[8..12].load::<u8>(), opcode[4..8].load::<u8>()),
-                0x1 => Or(opcode[8..12].load::<u8>(), opcode[4..8].load::<u8>()),
-                _ => todo!(),
+            0x9 => match opcode.n() {
+                0x0 => SkipNotEqual(opcode.x(), opcode.y()),
+                _ => return unknown,
             },
-            _ => todo!(),
+            0xA => LoadI(opcode.nnn()),
+            0xB => JumpImmediate(opcode.nnn()),
+            0xC => Random(opcode.x(), opcode.kk()),
+            0xD => Draw(opcode.x(), opcode.y(), opcode.n()),
+            0xE => match opcode.kk() {
+                0x9E => SkipOnKey(opcode.x()),
+                0xA1 => SkipNotOnKey(opcode.x()),
+                _ => return unknown,
+            },
+            0xF => match opcode.kk() {
+                0x07 => LoadDTIntoV(opcode.x()),
+                0x0A => LoadKey(opcode.x()),
+                0x15 => LoadVIntoDT(opcode.x()),
+                0x18 => LoadVIntoST(opcode.x()),
+                0x1E => AddI(opcode.x()),
+                0x29 => LoadSpriteIntoI(opcode.x()),
+                0x33 => LoadBCDIntoI(opcode.x()),
+                0x55 => LoadVIntoMem(opcode.x()),
+                0x65 => LoadMemIntoV(opcode.x()),
+                0x75 => LoadVIntoFlags(opcode.x()),
+                0x85 => LoadFlagsIntoV(opcode.x()),
+                _ => return unknown,
+            },
+            _ => return unknown,
+        };
+
+        Ok(instruction)
+    }
+}
+
+/// Every opcode decoded ahead of time.
+///
+/// Decoding through the table is a single lookup, at the cost of building it once and keeping
+/// 64K instructions in memory, which pays off for long running machines. Machines share a single
+/// table, see [`DecodeTable::shared`] and [`Engine::Table`](crate::machine::Engine::Table).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodeTable {
+    instructions: Vec<Option<Instruction>>,
+}
+
+impl Default for DecodeTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DecodeTable {
+    pub fn new() -> Self {
+        let instructions = (0..=u16::MAX)
+            .map(|opcode| Instruction::try_from(OpCode::new(opcode)).ok())
+            .collect();
+        Self { instructions }
+    }
+
+    /// The table shared by every machine, built the first time it is needed
+    pub fn shared() -> &'static Self {
+        static TABLE: OnceLock<DecodeTable> = OnceLock::new();
+        TABLE.get_or_init(Self::new)
+    }
+
+    /// The instruction encoded by `opcode`, `None` for unknown opcodes
+    #[inline]
+    pub fn decode(&self, opcode: OpCode) -> Option<Instruction> {
+        self.instructions[usize::from(u16::from(opcode))]
+    }
+}
+
+impl fmt::Display for Instruction {
+    /// Formats the instruction using the assembly syntax from the module level table
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Instruction::*;
+        match *self {
+            ScrollDown(k) => write!(f, "SCD {:#X}", k),
+            ScrollRight => write!(f, "SCR"),
+            ScrollLeft => write!(f, "SCL"),
+            Exit => write!(f, "EXIT"),
+            LowRes => write!(f, "LOW"),
+            HighRes => write!(f, "HIGH"),
+            ClearScreen => write!(f, "CLS"),
+            Return => write!(f, "RET"),
+            Jump(addr) => write!(f, "JP {:#05X}", addr),
+            Call(addr) => write!(f, "CALL {:#05X}", addr),
+            SkipEqualImmediate(x, kk) => write!(f, "SE V{:X}, {:#04X}", x, kk),
+            SkipNotEqualImmediate(x, kk) => write!(f, "SNE V{:X}, {:#04X}", x, kk),
+            SkipEqual(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            LoadImmediate(x, kk) => write!(f, "LD V{:X}, {:#04X}", x, kk),
+            AddImmediate(x, kk) => write!(f, "ADD V{:X}, {:#04X}", x, kk),
+            Load(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Or(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            And(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Xor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Add(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Sub(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            ShiftRight(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            SubNumeric(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            ShiftLeft(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            SkipNotEqual(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            LoadI(addr) => write!(f, "LD I, {:#05X}", addr),
+            JumpImmediate(addr) => write!(f, "JP V0, {:#05X}", addr),
+            Random(x, kk) => write!(f, "RND V{:X}, {:#04X}", x, kk),
+            Draw(x, y, k) => write!(f, "DRW V{:X}, V{:X}, {:#X}", x, y, k),
+            SkipOnKey(x) => write!(f, "SKP V{:X}", x),
+            SkipNotOnKey(x) => write!(f, "SNKP V{:X}", x),
+            LoadDTIntoV(x) => write!(f, "LD V{:X}, DT", x),
+            LoadKey(x) => write!(f, "LD V{:X}, K", x),
+            LoadVIntoDT(x) => write!(f, "LD DT, V{:X}", x),
+            LoadVIntoST(x) => write!(f, "LD ST, V{:X}", x),
+            AddI(x) => write!(f, "ADD I, V{:X}", x),
+            LoadSpriteIntoI(x) => write!(f, "LD F, V{:X}", x),
+            LoadBCDIntoI(x) => write!(f, "LD B, V{:X}", x),
+            LoadVIntoMem(x) => write!(f, "LD [I], V{:X}", x),
+            LoadMemIntoV(x) => write!(f, "LD V{:X}, [I]", x),
+            LoadVIntoFlags(x) => write!(f, "LD R, V{:X}", x),
+            LoadFlagsIntoV(x) => write!(f, "LD V{:X}, R", x),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{instructions::Instruction, opcode::OpCode};
+    use crate::{
+        instructions::{DecodeTable, Instruction, InstructionError},
+        opcode::OpCode,
+    };
+    use std::convert::TryFrom;
 
     #[test]
     fn test_scroll_down() {
         let op = OpCode::new(0x00CA);
-        let int: Instruction = op.into();
+        let int = Instruction::try_from(op).unwrap();
         let expected = Instruction::ScrollDown(0xA);
         assert_eq!(expected, int);
     }
+
+    #[test]
+    fn test_skip_equal() {
+        // Vx comes first, a swapped decoding only shows with distinct registers
+        let op = OpCode::new(0x5AB0);
+        assert_eq!(
+            Instruction::SkipEqual(0xA, 0xB),
+            Instruction::try_from(op).unwrap()
+        );
+        assert_eq!(
+            Some(Instruction::SkipEqual(0xA, 0xB)),
+            DecodeTable::new().decode(op)
+        );
+    }
+
+    #[test]
+    fn test_unknown() {
+        for &op in &[0x0000, 0x00E1, 0x5AB1, 0x800F, 0x9AB1, 0xEA00, 0xFA00] {
+            let op = OpCode::new(op);
+            assert_eq!(
+                Err(InstructionError::UnknownOpCode(op)),
+                Instruction::try_from(op)
+            );
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        use Instruction::*;
+        assert_eq!("CLS", ClearScreen.to_string());
+        assert_eq!("JP 0x2A4", Jump(0x2A4).to_string());
+        assert_eq!("LD VA, 0x02", LoadImmediate(0xA, 0x02).to_string());
+        assert_eq!("DRW V0, V1, 0x5", Draw(0x0, 0x1, 0x5).to_string());
+        assert_eq!("LD [I], VF", LoadVIntoMem(0xF).to_string());
+        assert_eq!("LD R, V7", LoadVIntoFlags(0x7).to_string());
+    }
+
+    #[test]
+    fn test_name() {
+        use Instruction::*;
+        assert_eq!("ClearScreen", ClearScreen.name());
+        assert_eq!("SkipEqualImmediate", SkipEqualImmediate(0x1, 0x2).name());
+    }
+
+    #[test]
+    fn test_decode_table() {
+        let table = DecodeTable::new();
+        for opcode in 0..=u16::MAX {
+            let opcode = OpCode::new(opcode);
+            assert_eq!(Instruction::try_from(opcode).ok(), table.decode(opcode));
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut valid = 0;
+        for opcode in (0..=u16::MAX).map(OpCode::new) {
+            if let Ok(instruction) = Instruction::try_from(opcode) {
+                assert_eq!(
+                    opcode,
+                    OpCode::from(instruction),
+                    "{} decoded from {:?} as {:?}",
+                    instruction.name(),
+                    opcode,
+                    instruction
+                );
+                valid += 1;
+            }
+        }
+        // 23 without operands or with a nibble, 10 * 4096 with an address or register and byte,
+        // 11 * 256 with two registers, 13 * 16 with a register
+        assert_eq!(23 + 10 * 4096 + 11 * 256 + 13 * 16, valid);
+    }
 }
@@ -0,0 +1,64 @@
+//! The 16-key hexadecimal keypad.
+//!
+//! The original COSMAC VIP keypad was laid out as follows, most emulators map it onto the left
+//! side of a QWERTY keyboard:
+//!
+//! | | | | |
+//! |-|-|-|-|
+//! |1|2|3|C|
+//! |4|5|6|D|
+//! |7|8|9|E|
+//! |A|0|B|F|
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Keypad {
+    /// One bit per key, set while the key is held down
+    pressed: u16,
+}
+
+impl Keypad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set whether `key` (0 through F) is held down
+    pub fn set(&mut self, key: u8, pressed: bool) {
+        let bit = 1 << (key & 0xF);
+        if pressed {
+            self.pressed |= bit;
+        } else {
+            self.pressed &= !bit;
+        }
+    }
+
+    pub fn press(&mut self, key: u8) {
+        self.set(key, true)
+    }
+
+    pub fn release(&mut self, key: u8) {
+        self.set(key, false)
+    }
+
+    pub fn is_pressed(&self, key: u8) -> bool {
+        self.pressed & (1 << (key & 0xF)) != 0
+    }
+
+    /// One bit per key held down, key 0 being the lowest bit
+    pub fn bits(&self) -> u16 {
+        self.pressed
+    }
+
+    /// Hold down the keys whose bits are set in `bits`, releasing the others
+    pub fn set_bits(&mut self, bits: u16) {
+        self.pressed = bits;
+    }
+
+    /// The lowest key held down, if any
+    pub fn first_pressed(&self) -> Option<u8> {
+        if self.pressed == 0 {
+            None
+        } else {
+            Some(self.pressed.trailing_zeros() as u8)
+        }
+    }
+}
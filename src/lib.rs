@@ -1,5 +1,36 @@
 #![allow(unused, dead_code)]
+pub mod analysis;
+pub mod assembler;
+pub mod batch;
+pub mod cache;
+pub mod cartridge;
+pub mod coverage;
+pub mod database;
+pub mod debugger;
+pub mod detection;
+pub mod disassembler;
+pub mod display;
+pub mod ffi;
+pub mod flags;
+pub mod fuzz;
+pub mod gym;
+pub mod host;
+pub mod inspector;
 pub mod instructions;
+pub mod keypad;
+pub mod libretro;
+pub mod lint;
+pub mod loader;
+pub mod machine;
 pub mod memory;
+pub mod octo;
 pub mod opcode;
+pub mod profiler;
+pub mod quirks;
+pub mod recompiler;
 pub mod register;
+pub mod screenshot;
+pub mod source_map;
+pub mod state;
+pub mod symbols;
+pub mod wasm;
@@ -0,0 +1,785 @@
+//! A libretro core, letting RetroArch and other libretro frontends run CHIP-8 games.
+//!
+//! The shared library built from the crate exports the libretro API, so it can be loaded as a
+//! core as is. The core maps the retropad onto the hex keypad, using the keys the database
+//! recommends for the directions and the A and B buttons when the ROM is a known one, and plays a
+//! square wave while the sound timer runs.
+//!
+//! Two core options are available: `chirp_platform` picks the platform whose quirks the machine
+//! follows, and `chirp_speed` the amount of instructions executed per frame. Both default to
+//! `auto`, the ROM's recommended settings, and changing them carries the running game over.
+//!
+//! Only the parts of the libretro API the core uses are declared here, see `libretro.h` for the
+//! rest.
+use crate::{
+    display::Display,
+    loader::{Loader, Rom},
+    machine::Machine,
+    quirks::Platform,
+    state::State,
+};
+use std::{
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_uint, c_void},
+    ptr,
+    sync::Mutex,
+};
+
+const RETRO_API_VERSION: c_uint = 1;
+
+const RETRO_ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS: c_uint = 11;
+const RETRO_ENVIRONMENT_GET_VARIABLE: c_uint = 15;
+const RETRO_ENVIRONMENT_SET_VARIABLES: c_uint = 16;
+const RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE: c_uint = 17;
+
+const RETRO_PIXEL_FORMAT_XRGB8888: c_uint = 1;
+const RETRO_DEVICE_JOYPAD: c_uint = 1;
+const RETRO_REGION_NTSC: c_uint = 0;
+
+const RETRO_DEVICE_ID_JOYPAD_B: c_uint = 0;
+const RETRO_DEVICE_ID_JOYPAD_UP: c_uint = 4;
+const RETRO_DEVICE_ID_JOYPAD_DOWN: c_uint = 5;
+const RETRO_DEVICE_ID_JOYPAD_LEFT: c_uint = 6;
+const RETRO_DEVICE_ID_JOYPAD_RIGHT: c_uint = 7;
+const RETRO_DEVICE_ID_JOYPAD_A: c_uint = 8;
+
+/// The names of the retropad buttons, indexed by their id
+const BUTTONS: [&str; 16] = [
+    "B", "Y", "Select", "Start", "Up", "Down", "Left", "Right", "A", "X", "L", "R", "L2", "R2",
+    "L3", "R3",
+];
+
+/// The key pressed by each retropad button, indexed by its id. The directions and A are laid out
+/// like on the keypad, and every key is on some button.
+const DEFAULT_KEYMAP: [u8; 16] = [
+    0x0, 0x3, 0x7, 0x9, 0x2, 0x8, 0x4, 0x6, 0x5, 0x1, 0xA, 0xB, 0xC, 0xD, 0xE, 0xF,
+];
+
+/// The database actions and the button they are mapped to
+const ACTIONS: [(&str, c_uint); 6] = [
+    ("up", RETRO_DEVICE_ID_JOYPAD_UP),
+    ("down", RETRO_DEVICE_ID_JOYPAD_DOWN),
+    ("left", RETRO_DEVICE_ID_JOYPAD_LEFT),
+    ("right", RETRO_DEVICE_ID_JOYPAD_RIGHT),
+    ("a", RETRO_DEVICE_ID_JOYPAD_A),
+    ("b", RETRO_DEVICE_ID_JOYPAD_B),
+];
+
+const SAMPLE_RATE: f64 = 44100.0;
+const FPS: f64 = 60.0;
+/// The pitch of the buzzer
+const TONE: f64 = 440.0;
+const VOLUME: i16 = 0x1000;
+
+const PLATFORM_OPTION: &[u8] = b"chirp_platform\0";
+const SPEED_OPTION: &[u8] = b"chirp_speed\0";
+/// The core options, in the `Description; default|other values` format
+const OPTIONS: [(&[u8], &[u8]); 2] = [
+    (
+        PLATFORM_OPTION,
+        concat!(
+            "Platform; auto|originalChip8|hybridVIP|modernChip8|chip8x|chip48|superchip1|",
+            "superchip|megachip8|xochip\0"
+        )
+        .as_bytes(),
+    ),
+    (
+        SPEED_OPTION,
+        b"Instructions per frame; auto|7|10|15|20|30|50|100|200|500|1000\0",
+    ),
+];
+
+#[repr(C)]
+pub struct RetroSystemInfo {
+    pub library_name: *const c_char,
+    pub library_version: *const c_char,
+    pub valid_extensions: *const c_char,
+    pub need_fullpath: bool,
+    pub block_extract: bool,
+}
+
+#[repr(C)]
+pub struct RetroGameGeometry {
+    pub base_width: c_uint,
+    pub base_height: c_uint,
+    pub max_width: c_uint,
+    pub max_height: c_uint,
+    pub aspect_ratio: f32,
+}
+
+#[repr(C)]
+pub struct RetroSystemTiming {
+    pub fps: f64,
+    pub sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct RetroSystemAvInfo {
+    pub geometry: RetroGameGeometry,
+    pub timing: RetroSystemTiming,
+}
+
+#[repr(C)]
+pub struct RetroGameInfo {
+    pub path: *const c_char,
+    pub data: *const c_void,
+    pub size: usize,
+    pub meta: *const c_char,
+}
+
+#[repr(C)]
+pub struct RetroVariable {
+    pub key: *const c_char,
+    pub value: *const c_char,
+}
+
+#[repr(C)]
+pub struct RetroInputDescriptor {
+    pub port: c_uint,
+    pub device: c_uint,
+    pub index: c_uint,
+    pub id: c_uint,
+    pub description: *const c_char,
+}
+
+pub type RetroEnvironment = unsafe extern "C" fn(cmd: c_uint, data: *mut c_void) -> bool;
+pub type RetroVideoRefresh =
+    unsafe extern "C" fn(data: *const c_void, width: c_uint, height: c_uint, pitch: usize);
+pub type RetroAudioSample = unsafe extern "C" fn(left: i16, right: i16);
+pub type RetroAudioSampleBatch = unsafe extern "C" fn(data: *const i16, frames: usize) -> usize;
+pub type RetroInputPoll = unsafe extern "C" fn();
+pub type RetroInputState =
+    unsafe extern "C" fn(port: c_uint, device: c_uint, index: c_uint, id: c_uint) -> i16;
+
+/// The settings picked with the core options, `None` meaning the ROM's recommended ones
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Options {
+    platform: Option<Platform>,
+    tickrate: Option<u32>,
+}
+
+/// A game being played
+struct Game {
+    rom: Rom,
+    machine: Machine,
+    /// The key pressed by each retropad button
+    keymap: [u8; 16],
+    /// The descriptions of the buttons, which must outlive the input descriptors
+    descriptions: Vec<CString>,
+}
+
+impl Game {
+    fn new(rom: Rom, options: Options) -> Self {
+        let mut keymap = DEFAULT_KEYMAP;
+        for (action, button) in ACTIONS.iter() {
+            if let Some(key) = rom.profile.keys.get(*action) {
+                keymap[*button as usize] = key & 0xF;
+            }
+        }
+        let descriptions = keymap
+            .iter()
+            .zip(BUTTONS.iter())
+            .map(|(key, button)| {
+                CString::new(format!("Key {:X} ({})", key, button)).expect("no nul bytes")
+            })
+            .collect();
+        let machine = machine(&rom, options);
+        Self {
+            rom,
+            machine,
+            keymap,
+            descriptions,
+        }
+    }
+
+    fn input_descriptors(&self) -> Vec<RetroInputDescriptor> {
+        let mut descriptors: Vec<_> = self
+            .descriptions
+            .iter()
+            .enumerate()
+            .map(|(id, description)| RetroInputDescriptor {
+                port: 0,
+                device: RETRO_DEVICE_JOYPAD,
+                index: 0,
+                id: id as c_uint,
+                description: description.as_ptr(),
+            })
+            .collect();
+        // The list ends with a zeroed descriptor
+        descriptors.push(RetroInputDescriptor {
+            port: 0,
+            device: 0,
+            index: 0,
+            id: 0,
+            description: ptr::null(),
+        });
+        descriptors
+    }
+}
+
+/// A machine running `rom` with `options` applied over its recommended settings
+fn machine(rom: &Rom, options: Options) -> Machine {
+    let mut rom = rom.clone();
+    if let Some(platform) = options.platform {
+        rom.profile.platform = platform;
+        rom.profile.quirks = platform.quirks();
+        rom.profile.tickrate = platform.tickrate();
+    }
+    if let Some(tickrate) = options.tickrate {
+        rom.profile.tickrate = tickrate;
+    }
+    Machine::from_rom(rom)
+}
+
+/// Everything the frontend handed to the core
+struct Core {
+    environment: Option<RetroEnvironment>,
+    video_refresh: Option<RetroVideoRefresh>,
+    audio_sample_batch: Option<RetroAudioSampleBatch>,
+    input_poll: Option<RetroInputPoll>,
+    input_state: Option<RetroInputState>,
+    options: Options,
+    game: Option<Game>,
+    /// The framebuffer handed to the frontend, in XRGB8888
+    framebuffer: Vec<u32>,
+    /// The audio samples of a frame, interleaved stereo
+    samples: Vec<i16>,
+    /// The position in the period of the square wave, from 0 to 1
+    phase: f64,
+}
+
+static CORE: Mutex<Core> = Mutex::new(Core {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+    options: Options {
+        platform: None,
+        tickrate: None,
+    },
+    game: None,
+    framebuffer: Vec::new(),
+    samples: Vec::new(),
+    phase: 0.0,
+});
+
+fn core() -> std::sync::MutexGuard<'static, Core> {
+    CORE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Core {
+    unsafe fn environment(&self, cmd: c_uint, data: *mut c_void) -> bool {
+        match self.environment {
+            Some(environment) => environment(cmd, data),
+            None => false,
+        }
+    }
+
+    /// The value of the core option `key`, if the frontend knows it
+    unsafe fn variable(&self, key: &'static [u8]) -> Option<String> {
+        let mut variable = RetroVariable {
+            key: key.as_ptr() as *const c_char,
+            value: ptr::null(),
+        };
+        let data = &mut variable as *mut RetroVariable as *mut c_void;
+        if !self.environment(RETRO_ENVIRONMENT_GET_VARIABLE, data) || variable.value.is_null() {
+            return None;
+        }
+        Some(
+            CStr::from_ptr(variable.value)
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+
+    unsafe fn read_options(&mut self) {
+        let platform = self.variable(PLATFORM_OPTION);
+        let speed = self.variable(SPEED_OPTION);
+        self.options = Options {
+            platform: platform.and_then(|p| p.parse().ok()),
+            tickrate: speed.and_then(|s| s.parse().ok()),
+        };
+    }
+
+    /// Pick up changes to the core options, carrying the running game over to the new settings
+    unsafe fn update_options(&mut self) {
+        let mut updated = false;
+        let data = &mut updated as *mut bool as *mut c_void;
+        if !self.environment(RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE, data) || !updated {
+            return;
+        }
+        self.read_options();
+        let options = self.options;
+        if let Some(game) = &mut self.game {
+            let state = game.machine.save_state();
+            game.machine = machine(&game.rom, options);
+            game.machine.load_state(state);
+        }
+    }
+
+    unsafe fn poll_input(&mut self) {
+        if let Some(input_poll) = self.input_poll {
+            input_poll();
+        }
+        let (input_state, game) = match (self.input_state, &mut self.game) {
+            (Some(input_state), Some(game)) => (input_state, game),
+            _ => return,
+        };
+        let mut pressed = 0u16;
+        for (id, key) in game.keymap.iter().enumerate() {
+            if input_state(0, RETRO_DEVICE_JOYPAD, 0, id as c_uint) != 0 {
+                pressed |= 1 << key;
+            }
+        }
+        game.machine.keypad_mut().set_bits(pressed);
+    }
+
+    unsafe fn present(&mut self) {
+        let display = match &self.game {
+            Some(game) => game.machine.display(),
+            None => return,
+        };
+        self.framebuffer.clear();
+        self.framebuffer.extend(display.pixels().iter().map(|&lit| {
+            if lit != 0 {
+                0x00FF_FFFF
+            } else {
+                0
+            }
+        }));
+        if let Some(video_refresh) = self.video_refresh {
+            let width = display.width();
+            video_refresh(
+                self.framebuffer.as_ptr() as *const c_void,
+                width as c_uint,
+                display.height() as c_uint,
+                width * std::mem::size_of::<u32>(),
+            );
+        }
+    }
+
+    /// Play a frame of sound: a square wave while the sound timer runs, silence otherwise
+    unsafe fn play(&mut self) {
+        let playing = match &self.game {
+            Some(game) => game.machine.register().st() > 0,
+            None => false,
+        };
+        let frames = (SAMPLE_RATE / FPS) as usize;
+        self.samples.clear();
+        for _ in 0..frames {
+            let sample = match (playing, self.phase < 0.5) {
+                (false, _) => 0,
+                (true, true) => VOLUME,
+                (true, false) => -VOLUME,
+            };
+            self.samples.extend_from_slice(&[sample, sample]);
+            self.phase = (self.phase + TONE / SAMPLE_RATE) % 1.0;
+        }
+        if let Some(audio_sample_batch) = self.audio_sample_batch {
+            audio_sample_batch(self.samples.as_ptr(), frames);
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    core().game = None;
+}
+
+/// # Safety
+///
+/// `info` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut RetroSystemInfo) {
+    *info = RetroSystemInfo {
+        library_name: b"chirp\0".as_ptr() as *const c_char,
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+        valid_extensions: b"ch8|c8|sc8|xo8\0".as_ptr() as *const c_char,
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+/// # Safety
+///
+/// `info` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut RetroSystemAvInfo) {
+    *info = RetroSystemAvInfo {
+        geometry: RetroGameGeometry {
+            base_width: Display::WIDTH as c_uint,
+            base_height: Display::HEIGHT as c_uint,
+            max_width: Display::HIRES_WIDTH as c_uint,
+            max_height: Display::HIRES_HEIGHT as c_uint,
+            aspect_ratio: 2.0,
+        },
+        timing: RetroSystemTiming {
+            fps: FPS,
+            sample_rate: SAMPLE_RATE,
+        },
+    };
+}
+
+/// # Safety
+///
+/// `environment` must be safe to call with the commands of the libretro API.
+#[no_mangle]
+pub unsafe extern "C" fn retro_set_environment(environment: RetroEnvironment) {
+    let mut core = core();
+    core.environment = Some(environment);
+    let mut variables: Vec<_> = OPTIONS
+        .iter()
+        .map(|(key, value)| RetroVariable {
+            key: key.as_ptr() as *const c_char,
+            value: value.as_ptr() as *const c_char,
+        })
+        .collect();
+    variables.push(RetroVariable {
+        key: ptr::null(),
+        value: ptr::null(),
+    });
+    core.environment(
+        RETRO_ENVIRONMENT_SET_VARIABLES,
+        variables.as_mut_ptr() as *mut c_void,
+    );
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(video_refresh: RetroVideoRefresh) {
+    core().video_refresh = Some(video_refresh);
+}
+
+/// The core plays sound in batches, see `retro_set_audio_sample_batch`
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_audio_sample: RetroAudioSample) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(audio_sample_batch: RetroAudioSampleBatch) {
+    core().audio_sample_batch = Some(audio_sample_batch);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(input_poll: RetroInputPoll) {
+    core().input_poll = Some(input_poll);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(input_state: RetroInputState) {
+    core().input_state = Some(input_state);
+}
+
+/// Only the retropad is supported
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+/// Restart the game, keeping the user flags
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    let mut core = core();
+    let options = core.options;
+    if let Some(game) = &mut core.game {
+        let mut fresh = machine(&game.rom, options).save_state();
+        fresh.flags = game.machine.flags().to_vec();
+        game.machine.load_state(fresh);
+    }
+}
+
+/// Run a frame: poll the input, execute the frame's instructions, then present the screen and
+/// play the frame's sound. A halted game keeps showing its last frame.
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    let mut core = core();
+    unsafe {
+        core.update_options();
+        core.poll_input();
+        if let Some(game) = &mut core.game {
+            if let Err(e) = game.machine.run_frame() {
+                log::debug!("The game halted: {}", e);
+            }
+        }
+        core.present();
+        core.play();
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    match &core().game {
+        Some(game) => game.machine.save_state().to_bytes().len(),
+        None => 0,
+    }
+}
+
+/// # Safety
+///
+/// `data` must point to `size` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let state = match &core().game {
+        Some(game) => game.machine.save_state().to_bytes(),
+        None => return false,
+    };
+    if state.len() > size {
+        return false;
+    }
+    ptr::copy_nonoverlapping(state.as_ptr(), data as *mut u8, state.len());
+    true
+}
+
+/// # Safety
+///
+/// `data` must point to `size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    let bytes = std::slice::from_raw_parts(data as *const u8, size);
+    match (State::from_bytes(bytes), &mut core().game) {
+        (Ok(state), Some(game)) => {
+            game.machine.load_state(state);
+            true
+        }
+        (Err(e), _) => {
+            log::warn!("Failed to load the save state: {}", e);
+            false
+        }
+        (Ok(_), None) => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+/// # Safety
+///
+/// `game` must be null, or point to a game whose data holds `size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const RetroGameInfo) -> bool {
+    let game = match game.as_ref() {
+        Some(game) if !game.data.is_null() => game,
+        _ => return false,
+    };
+    let bytes = std::slice::from_raw_parts(game.data as *const u8, game.size);
+    let rom = match Loader::new().load(bytes) {
+        Ok(rom) => rom,
+        Err(e) => {
+            log::warn!("Failed to load the game: {}", e);
+            return false;
+        }
+    };
+
+    let mut core = core();
+    let mut format = RETRO_PIXEL_FORMAT_XRGB8888;
+    if !core.environment(
+        RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+        &mut format as *mut c_uint as *mut c_void,
+    ) {
+        log::warn!("The frontend does not support XRGB8888");
+        return false;
+    }
+    core.read_options();
+    let game = Game::new(rom, core.options);
+    let mut descriptors = game.input_descriptors();
+    core.environment(
+        RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS,
+        descriptors.as_mut_ptr() as *mut c_void,
+    );
+    core.game = Some(game);
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const RetroGameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    core().game = None;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+/// The memory of the machine is not exposed to the frontend
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(_id: c_uint) -> *mut c_void {
+    ptr::null_mut()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(_id: c_uint) -> usize {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::libretro::*;
+    use std::cell::RefCell;
+
+    /// What the core handed to the frontend
+    #[derive(Default)]
+    struct Frontend {
+        options: Vec<String>,
+        descriptors: usize,
+        frames: Vec<(c_uint, c_uint, Vec<u32>)>,
+        samples: Vec<usize>,
+        /// The value of the speed option, and whether it changed
+        speed: Option<(CString, bool)>,
+        /// The buttons held down
+        buttons: u16,
+    }
+
+    thread_local! {
+        static FRONTEND: RefCell<Frontend> = RefCell::new(Frontend::default());
+    }
+
+    unsafe extern "C" fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+        FRONTEND.with(|f| {
+            let mut f = f.borrow_mut();
+            match cmd {
+                RETRO_ENVIRONMENT_SET_PIXEL_FORMAT => {
+                    *(data as *const c_uint) == RETRO_PIXEL_FORMAT_XRGB8888
+                }
+                RETRO_ENVIRONMENT_SET_VARIABLES => {
+                    let mut variable = data as *const RetroVariable;
+                    while !(*variable).key.is_null() {
+                        let key = CStr::from_ptr((*variable).key);
+                        f.options.push(key.to_string_lossy().into_owned());
+                        variable = variable.add(1);
+                    }
+                    true
+                }
+                RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS => {
+                    let mut descriptor = data as *const RetroInputDescriptor;
+                    while !(*descriptor).description.is_null() {
+                        f.descriptors += 1;
+                        descriptor = descriptor.add(1);
+                    }
+                    true
+                }
+                RETRO_ENVIRONMENT_GET_VARIABLE => {
+                    let variable = &mut *(data as *mut RetroVariable);
+                    match &f.speed {
+                        Some((speed, _))
+                            if CStr::from_ptr(variable.key).to_bytes() == b"chirp_speed" =>
+                        {
+                            variable.value = speed.as_ptr();
+                            true
+                        }
+                        _ => false,
+                    }
+                }
+                RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE => {
+                    let updated = f.speed.as_mut().is_some_and(|(_, u)| std::mem::take(u));
+                    *(data as *mut bool) = updated;
+                    true
+                }
+                _ => false,
+            }
+        })
+    }
+
+    unsafe extern "C" fn video_refresh(
+        data: *const c_void,
+        width: c_uint,
+        height: c_uint,
+        pitch: usize,
+    ) {
+        assert_eq!(width as usize * 4, pitch);
+        let pixels = std::slice::from_raw_parts(data as *const u32, (width * height) as usize);
+        FRONTEND.with(|f| f.borrow_mut().frames.push((width, height, pixels.to_vec())));
+    }
+
+    unsafe extern "C" fn audio_sample_batch(_data: *const i16, frames: usize) -> usize {
+        FRONTEND.with(|f| f.borrow_mut().samples.push(frames));
+        frames
+    }
+
+    unsafe extern "C" fn input_poll() {}
+
+    unsafe extern "C" fn input_state(port: c_uint, device: c_uint, _: c_uint, id: c_uint) -> i16 {
+        let buttons = FRONTEND.with(|f| f.borrow().buttons);
+        (port == 0 && device == RETRO_DEVICE_JOYPAD && buttons & 1 << id != 0) as i16
+    }
+
+    fn run(frames: usize) -> Vec<u32> {
+        for _ in 0..frames {
+            retro_run();
+        }
+        FRONTEND.with(|f| f.borrow().frames.last().unwrap().2.clone())
+    }
+
+    #[test]
+    fn test_core() {
+        unsafe {
+            retro_set_environment(environment);
+            retro_set_video_refresh(video_refresh);
+            retro_set_audio_sample_batch(audio_sample_batch);
+            retro_set_input_poll(input_poll);
+            retro_set_input_state(input_state);
+            retro_init();
+        }
+        FRONTEND.with(|f| assert_eq!(vec!["chirp_platform", "chirp_speed"], f.borrow().options));
+
+        let rom = std::fs::read("./games/tetris.ch8").unwrap();
+        let info = RetroGameInfo {
+            path: ptr::null(),
+            data: rom.as_ptr() as *const c_void,
+            size: rom.len(),
+            meta: ptr::null(),
+        };
+        assert!(unsafe { retro_load_game(&info) });
+        FRONTEND.with(|f| assert_eq!(16, f.borrow().descriptors));
+        // Tetris rotates with A, mapped to key 4 by the database
+        assert_eq!(4, core().game.as_ref().unwrap().keymap[8]);
+
+        FRONTEND.with(|f| f.borrow_mut().buttons = 1 << RETRO_DEVICE_ID_JOYPAD_A);
+        let pixels = run(60);
+        FRONTEND.with(|f| {
+            let f = f.borrow();
+            assert_eq!((64, 32), (f.frames[0].0, f.frames[0].1));
+            assert_eq!(vec![735; 60], f.samples);
+        });
+        assert!(pixels.contains(&0x00FF_FFFF));
+
+        // Loading a save state brings the screen back
+        let mut state = vec![0; retro_serialize_size()];
+        assert!(unsafe { retro_serialize(state.as_mut_ptr() as *mut c_void, state.len()) });
+        let saved = run(1);
+        run(60);
+        assert!(unsafe { retro_unserialize(state.as_ptr() as *const c_void, state.len()) });
+        assert_eq!(saved, run(1));
+        assert!(!unsafe { retro_unserialize(state.as_ptr() as *const c_void, 4) });
+
+        // Changing the speed carries the game over
+        FRONTEND.with(|f| f.borrow_mut().speed = Some((CString::new("1000").unwrap(), true)));
+        let pc = core().game.as_ref().unwrap().machine.pc();
+        retro_run();
+        let core = core();
+        let machine = &core.game.as_ref().unwrap().machine;
+        assert_ne!(pc, machine.pc());
+        assert_eq!(Some(1000), core.options.tickrate);
+        drop(core);
+
+        retro_unload_game();
+        retro_deinit();
+    }
+}
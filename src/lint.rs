@@ -0,0 +1,496 @@
+//! A static linter for common CHIP-8 programming mistakes.
+//!
+//! The linter runs over the reachable code found by [`analysis`](crate::analysis) and reports:
+//!
+//! | Lint                  | Severity | Meaning                                                 |
+//! | --------------------- | -------- | ------------------------------------------------------- |
+//! | `out-of-range-target` | error    | A jump or call leaves the program                       |
+//! | `odd-target`          | warning  | A jump or call targets an odd address                   |
+//! | `falls-into-data`     | error    | Execution runs into data or past the end of the program |
+//! | `interpreter-write`   | error    | A store writes into the interpreter area, below 0x200   |
+//! | `flag-register`       | warning  | VF is used as a general purpose register                |
+//! | `stack-overflow`      | error    | A static call chain is deeper than the 16 level stack   |
+//! | `recursion`           | warning  | A subroutine may call itself, so its depth is unbounded |
+//! | `quirk-sensitive`     | note     | The instruction behaves differently across platforms    |
+//!
+//! Diagnostics are shown in a compiler-like format by their `Display` implementation, and can be
+//! serialized as JSON with [`to_json`].
+use crate::{
+    analysis::{self, Analysis},
+    detection::{self, Detection},
+    instructions::Instruction,
+    memory::Memory,
+    register::Register,
+};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => f.write_str("error"),
+            Severity::Warning => f.write_str("warning"),
+            Severity::Note => f.write_str("note"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Lint {
+    OutOfRangeTarget,
+    OddTarget,
+    FallsIntoData,
+    InterpreterWrite,
+    FlagRegister,
+    StackOverflow,
+    Recursion,
+    QuirkSensitive,
+}
+
+impl Lint {
+    pub fn severity(self) -> Severity {
+        match self {
+            Lint::OutOfRangeTarget
+            | Lint::FallsIntoData
+            | Lint::InterpreterWrite
+            | Lint::StackOverflow => Severity::Error,
+            Lint::OddTarget | Lint::FlagRegister | Lint::Recursion => Severity::Warning,
+            Lint::QuirkSensitive => Severity::Note,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::OutOfRangeTarget => "out-of-range-target",
+            Lint::OddTarget => "odd-target",
+            Lint::FallsIntoData => "falls-into-data",
+            Lint::InterpreterWrite => "interpreter-write",
+            Lint::FlagRegister => "flag-register",
+            Lint::StackOverflow => "stack-overflow",
+            Lint::Recursion => "recursion",
+            Lint::QuirkSensitive => "quirk-sensitive",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Diagnostic {
+    pub address: u16,
+    pub severity: Severity,
+    pub lint: Lint,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(address: u16, lint: Lint, message: String) -> Self {
+        Self {
+            address,
+            severity: lint.severity(),
+            lint,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:#05X}: {}[{}]: {}",
+            self.address,
+            self.severity,
+            self.lint.name(),
+            self.message
+        )
+    }
+}
+
+/// Serialize `diagnostics` as a JSON array
+pub fn to_json(diagnostics: &[Diagnostic]) -> String {
+    serde_json::to_string_pretty(diagnostics).expect("diagnostics are always serializable")
+}
+
+/// Lint `bytes`, loaded in memory at `origin`. Diagnostics are sorted by address.
+pub fn lint(bytes: &[u8], origin: u16) -> Vec<Diagnostic> {
+    let analysis = analysis::analyze(bytes, origin);
+    let end = u32::from(origin) + bytes.len() as u32;
+
+    let mut diagnostics = Vec::new();
+    check_targets(&analysis, end, &mut diagnostics);
+    check_data(&analysis, end, &mut diagnostics);
+    check_interpreter_writes(&analysis, &mut diagnostics);
+    check_flag_register(&analysis, &mut diagnostics);
+    check_stack(&analysis, &mut diagnostics);
+
+    for dependency in Detection::analyze(&analysis).dependencies {
+        let message = format!("{} ({} quirk)", dependency.description, dependency.quirk);
+        diagnostics.push(Diagnostic::new(
+            dependency.address,
+            Lint::QuirkSensitive,
+            message,
+        ));
+    }
+
+    diagnostics.sort();
+    diagnostics
+}
+
+fn check_targets(analysis: &Analysis, end: u32, diagnostics: &mut Vec<Diagnostic>) {
+    let targets = analysis
+        .jumps
+        .iter()
+        .map(|t| ("jumps", t))
+        .chain(analysis.subroutines.iter().map(|t| ("calls", t)));
+
+    for (verb, (&target, sources)) in targets {
+        for &source in sources {
+            if target < analysis.origin || u32::from(target) + 1 >= end {
+                let message = format!("{} to {:#05X}, outside of the program", verb, target);
+                diagnostics.push(Diagnostic::new(source, Lint::OutOfRangeTarget, message));
+            } else if target % 2 != 0 {
+                let message = format!("{} to odd address {:#05X}", verb, target);
+                diagnostics.push(Diagnostic::new(source, Lint::OddTarget, message));
+            }
+        }
+    }
+}
+
+fn check_data(analysis: &Analysis, end: u32, diagnostics: &mut Vec<Diagnostic>) {
+    use Instruction::*;
+
+    // Sprites are the most common kind of data, and are pointed at with `LD I, addr`
+    let sprites: BTreeSet<u16> = analysis
+        .code
+        .values()
+        .filter_map(|word| match word.instruction {
+            Some(LoadI(addr)) => Some(addr),
+            _ => None,
+        })
+        .collect();
+
+    for (&addr, word) in &analysis.code {
+        // Only report the boundary between code and data, not what follows
+        if word.instruction.is_none() && detection::platform_evidence(word).is_none() {
+            continue;
+        }
+        if matches!(word.instruction, Some(Jump(_))) {
+            continue;
+        }
+
+        for successor in analysis.successors(addr, word) {
+            if matches!(word.instruction, Some(Call(target)) if target == successor) {
+                continue;
+            }
+
+            let message = if u32::from(successor) + 1 >= end {
+                format!(
+                    "execution runs past the end of the program at {:#05X}",
+                    successor
+                )
+            } else if sprites.contains(&successor) {
+                format!("execution runs into sprite data at {:#05X}", successor)
+            } else {
+                match analysis.code.get(&successor) {
+                    Some(w)
+                        if w.instruction.is_none() && detection::platform_evidence(w).is_none() =>
+                    {
+                        format!(
+                            "execution runs into data at {:#05X} ({:?} is not an instruction)",
+                            successor, w.opcode
+                        )
+                    }
+                    _ => continue,
+                }
+            };
+            diagnostics.push(Diagnostic::new(addr, Lint::FallsIntoData, message));
+        }
+    }
+}
+
+fn check_interpreter_writes(analysis: &Analysis, diagnostics: &mut Vec<Diagnostic>) {
+    use Instruction::*;
+
+    for (&start, word) in &analysis.code {
+        let i = match word.instruction {
+            Some(LoadI(addr)) => addr,
+            Some(LoadSpriteIntoI(_)) => 0,
+            _ => continue,
+        };
+        if usize::from(i) >= Memory::MEMORY_START {
+            continue;
+        }
+
+        // Follow the straight line code after I was set, until I changes or control branches
+        let mut addr = start;
+        while let Some(word) = analysis.code.get(&addr) {
+            if addr != start {
+                match word.instruction {
+                    Some(LoadVIntoMem(_)) | Some(LoadBCDIntoI(_)) => {
+                        let message = format!(
+                            "stores into the interpreter area at {:#05X}, I was set at {:#05X}",
+                            i, start
+                        );
+                        diagnostics.push(Diagnostic::new(addr, Lint::InterpreterWrite, message));
+                    }
+                    Some(LoadI(_)) | Some(LoadSpriteIntoI(_)) | Some(AddI(_)) => break,
+                    _ => (),
+                }
+            }
+
+            let next = addr.wrapping_add(word.size());
+            if analysis.successors(addr, word) != [next] {
+                break;
+            }
+            addr = next;
+        }
+    }
+}
+
+fn check_flag_register(analysis: &Analysis, diagnostics: &mut Vec<Diagnostic>) {
+    use Instruction::*;
+
+    for (&addr, word) in &analysis.code {
+        let clobbered = match word.instruction {
+            Some(Or(0xF, _)) | Some(And(0xF, _)) | Some(Xor(0xF, _)) => true,
+            Some(Add(0xF, _)) | Some(Sub(0xF, _)) | Some(SubNumeric(0xF, _)) => true,
+            Some(ShiftRight(0xF, _)) | Some(ShiftLeft(0xF, _)) => true,
+            Some(LoadImmediate(0xF, _)) | Some(AddImmediate(0xF, _)) | Some(Load(0xF, _)) => false,
+            Some(Random(0xF, _)) | Some(LoadDTIntoV(0xF)) | Some(LoadKey(0xF)) => false,
+            // Only the loads ending at VF write it, `Fx65` and `Fx85` for a lower x leave it alone
+            Some(LoadMemIntoV(0xF)) | Some(LoadFlagsIntoV(0xF)) => false,
+            _ => continue,
+        };
+
+        let message = if clobbered {
+            "the result stored in VF is immediately overwritten by the flag".to_string()
+        } else {
+            "VF is used as a general purpose register, but is clobbered by arithmetic and draws"
+                .to_string()
+        };
+        diagnostics.push(Diagnostic::new(addr, Lint::FlagRegister, message));
+    }
+}
+
+/// The subroutines called by the code reachable from `entry` without returning
+fn callees(analysis: &Analysis, entry: u16) -> BTreeMap<u16, u16> {
+    let mut callees = BTreeMap::new();
+    let mut visited = BTreeSet::new();
+    let mut pending = vec![entry];
+
+    while let Some(addr) = pending.pop() {
+        if !visited.insert(addr) {
+            continue;
+        }
+        let word = match analysis.code.get(&addr) {
+            Some(word) => word,
+            None => continue,
+        };
+        match word.instruction {
+            Some(Instruction::Call(target)) => {
+                callees.entry(target).or_insert(addr);
+                pending.push(addr.wrapping_add(2));
+            }
+            Some(Instruction::Return) => (),
+            _ => pending.extend(analysis.successors(addr, word)),
+        }
+    }
+
+    callees
+}
+
+struct StackCheck<'a> {
+    analysis: &'a Analysis,
+    /// The deepest chain of calls below each subroutine, including itself
+    depths: BTreeMap<u16, Vec<u16>>,
+    /// The subroutines currently being checked
+    active: Vec<u16>,
+    recursive: BTreeSet<u16>,
+}
+
+impl StackCheck<'_> {
+    /// The deepest chain of calls made from `entry`, not including `entry` itself
+    fn deepest(&mut self, entry: u16) -> Vec<u16> {
+        let mut deepest = Vec::new();
+        for callee in callees(self.analysis, entry).into_keys() {
+            if self.active.contains(&callee) {
+                self.recursive.insert(callee);
+                continue;
+            }
+            let chain = match self.depths.get(&callee) {
+                Some(chain) => chain.clone(),
+                None => {
+                    self.active.push(callee);
+                    let mut chain = vec![callee];
+                    chain.extend(self.deepest(callee));
+                    self.active.pop();
+                    self.depths.insert(callee, chain.clone());
+                    chain
+                }
+            };
+            if chain.len() > deepest.len() {
+                deepest = chain;
+            }
+        }
+        deepest
+    }
+}
+
+fn check_stack(analysis: &Analysis, diagnostics: &mut Vec<Diagnostic>) {
+    let mut check = StackCheck {
+        analysis,
+        depths: BTreeMap::new(),
+        active: vec![analysis.origin],
+        recursive: BTreeSet::new(),
+    };
+    let chain = check.deepest(analysis.origin);
+
+    if chain.len() > Register::STACK_DEPTH {
+        let path: Vec<String> = chain.iter().map(|addr| format!("{:#05X}", addr)).collect();
+        let message = format!(
+            "calls nest {} deep, but the stack only holds {}: {}",
+            chain.len(),
+            Register::STACK_DEPTH,
+            path.join(" -> ")
+        );
+        let first_call = callees(analysis, analysis.origin)[&chain[0]];
+        diagnostics.push(Diagnostic::new(first_call, Lint::StackOverflow, message));
+    }
+
+    for entry in check.recursive {
+        let message = "subroutine may call itself, its stack usage is unbounded".to_string();
+        diagnostics.push(Diagnostic::new(entry, Lint::Recursion, message));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lint::{lint, to_json, Lint};
+
+    fn lints(rom: &[u8]) -> Vec<(u16, Lint)> {
+        lint(rom, 0x200)
+            .into_iter()
+            .map(|d| (d.address, d.lint))
+            .collect()
+    }
+
+    #[test]
+    fn test_targets() {
+        #[rustfmt::skip]
+        let rom = [
+            0x22, 0x03, // 0x200: CALL 0x203
+            0x12, 0x04, // 0x202: JP 0x204
+            0x13, 0x00, // 0x204: JP 0x300
+        ];
+        assert_eq!(
+            vec![(0x200, Lint::OddTarget), (0x204, Lint::OutOfRangeTarget)],
+            lints(&rom)
+        );
+    }
+
+    #[test]
+    fn test_falls_into_data() {
+        #[rustfmt::skip]
+        let rom = [
+            0xA2, 0x04, // 0x200: LD I, 0x204
+            0xD0, 0x13, // 0x202: DRW V0, V1, 3
+            0xF0, 0x90, // 0x204: sprite
+            0xF0, 0x90,
+        ];
+        assert_eq!(vec![(0x202, Lint::FallsIntoData)], lints(&rom));
+    }
+
+    #[test]
+    fn test_interpreter_write() {
+        #[rustfmt::skip]
+        let rom = [
+            0xA0, 0x50, // 0x200: LD I, 0x050
+            0x60, 0x01, // 0x202: LD V0, 0x01
+            0xF0, 0x55, // 0x204: LD [I], V0
+            0x12, 0x06, // 0x206: JP 0x206
+        ];
+        assert_eq!(vec![(0x204, Lint::InterpreterWrite)], lints(&rom));
+    }
+
+    #[test]
+    fn test_flag_register() {
+        #[rustfmt::skip]
+        let rom = [
+            0x7F, 0x01, // 0x200: ADD VF, 0x01
+            0x8F, 0x04, // 0x202: ADD VF, V0
+            0x6F, 0x02, // 0x204: LD VF, 0x02
+            0x8F, 0x00, // 0x206: LD VF, V0
+            0xFF, 0x07, // 0x208: LD VF, DT
+            0xFE, 0x65, // 0x20A: LD VE, [I]
+            0x12, 0x0C, // 0x20C: JP 0x20C
+        ];
+        assert_eq!(
+            vec![
+                (0x200, Lint::FlagRegister),
+                (0x202, Lint::FlagRegister),
+                (0x204, Lint::FlagRegister),
+                (0x206, Lint::FlagRegister),
+                (0x208, Lint::FlagRegister),
+            ],
+            lints(&rom)
+        );
+    }
+
+    #[test]
+    fn test_stack() {
+        // 0x200 calls 0x204, which calls 0x208, ... 17 levels deep
+        let mut rom = vec![0x22, 0x04, 0x12, 0x02];
+        for level in 1..=17u16 {
+            let addr = 0x200 + 4 * level;
+            let call = if level == 17 {
+                0x00E0
+            } else {
+                0x2000 | (addr + 4)
+            };
+            rom.extend_from_slice(&call.to_be_bytes());
+            rom.extend_from_slice(&[0x00, 0xEE]);
+        }
+        assert_eq!(vec![(0x200, Lint::StackOverflow)], lints(&rom));
+
+        // 0x200 calls 0x204, which calls itself
+        let rom = [0x22, 0x04, 0x12, 0x02, 0x22, 0x04, 0x00, 0xEE];
+        assert_eq!(vec![(0x204, Lint::Recursion)], lints(&rom));
+    }
+
+    #[test]
+    fn test_json() {
+        let rom = [0x13, 0x00];
+        assert_eq!(
+            r#"[
+  {
+    "address": 512,
+    "severity": "error",
+    "lint": "out-of-range-target",
+    "message": "jumps to 0x300, outside of the program"
+  }
+]"#,
+            to_json(&lint(&rom, 0x200))
+        );
+    }
+
+    #[test]
+    fn test_games() {
+        let rom = std::fs::read("./games/tetris.ch8").unwrap();
+        let errors: Vec<_> = lint(&rom, 0x200)
+            .into_iter()
+            .filter(|d| d.lint != Lint::QuirkSensitive)
+            .collect();
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+}
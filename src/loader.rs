@@ -0,0 +1,304 @@
+//! Loading ROMs along with the settings they need to run.
+//!
+//! The loader copies the ROM into memory and looks its SHA-1 up in a [`Database`], so that known
+//! ROMs automatically run on the right platform, with the right quirks and at the right speed.
+//! Unknown ROMs get the profile suggested by [`Detection`].
+//!
+//! Octo cartridges are loaded too: the loader assembles the program they carry and runs it with
+//! the options of the cartridge rather than the ones from the database.
+use crate::{
+    cartridge::{Cartridge, CartridgeError, Options},
+    database::{Database, Entry},
+    detection::Detection,
+    memory::{LoadInfo, Memory, MemoryError},
+    quirks::{Platform, Quirks},
+};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+};
+
+/// The first bytes of GIF images, and so of Octo cartridges
+const GIF_MAGIC: &[u8] = b"GIF8";
+
+#[derive(Debug, thiserror::Error)]
+pub enum LoadError {
+    #[error(transparent)]
+    Memory(#[from] MemoryError),
+    #[error("Failed to load Octo cartridge")]
+    Cartridge(#[from] CartridgeError),
+}
+
+/// The settings to run a ROM with
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Profile {
+    pub platform: Platform,
+    pub quirks: Quirks,
+    /// The amount of instructions executed per 60Hz frame
+    pub tickrate: u32,
+    /// The keypad key for each action, e.g. `"left": 4`
+    pub keys: BTreeMap<String, u8>,
+}
+
+impl Profile {
+    /// The default settings for `platform`
+    pub fn for_platform(platform: Platform) -> Self {
+        Self {
+            platform,
+            quirks: platform.quirks(),
+            tickrate: platform.tickrate(),
+            keys: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::for_platform(Platform::default())
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Platform: {}", self.platform)?;
+        writeln!(f, "Quirks:   {}", self.quirks)?;
+        writeln!(f, "Speed:    {} instructions per frame", self.tickrate)?;
+        if !self.keys.is_empty() {
+            let keys: Vec<String> = self
+                .keys
+                .iter()
+                .map(|(action, key)| format!("{} = {:X}", action, key))
+                .collect();
+            writeln!(f, "Keys:     {}", keys.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A ROM loaded into memory
+#[derive(Clone, Debug)]
+pub struct Rom {
+    pub memory: Memory,
+    pub info: LoadInfo,
+    /// The database entry for the ROM, if it is a known one
+    pub entry: Option<Entry>,
+    /// The platform and quirks the ROM appears to use
+    pub detection: Detection,
+    /// The settings the ROM should be run with
+    pub profile: Profile,
+    /// The options of the Octo cartridge the ROM was loaded from
+    pub cartridge: Option<Options>,
+}
+
+impl fmt::Display for Rom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(entry) = &self.entry {
+            writeln!(f, "Title:    {}", entry.title)?;
+            if !entry.authors.is_empty() {
+                writeln!(f, "Authors:  {}", entry.authors.join(", "))?;
+            }
+            if let Some(release) = &entry.release {
+                writeln!(f, "Release:  {}", release)?;
+            }
+            if let Some(description) = &entry.description {
+                writeln!(f, "About:    {}", description)?;
+            }
+        } else {
+            writeln!(f, "Title:    unknown ROM")?;
+        }
+        writeln!(f, "Size:     {} bytes", self.info.size)?;
+        writeln!(f, "Address:  {:#05X}", self.info.address)?;
+        writeln!(f, "SHA-1:    {}", self.info.sha1)?;
+        writeln!(f, "CRC-32:   {:08x}", self.info.crc32)?;
+        write!(f, "{}", self.profile)?;
+        if let Some(options) = &self.cartridge {
+            writeln!(
+                f,
+                "Colors:   {} on {}",
+                options.palette.fill, options.palette.background
+            )?;
+        }
+        write!(f, "{}", self.detection)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Loader {
+    database: Database,
+    address: u16,
+}
+
+impl Default for Loader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Loader {
+    /// A loader using the embedded database, loading ROMs at 0x200
+    pub fn new() -> Self {
+        Self {
+            database: Database::embedded(),
+            address: Memory::MEMORY_START as u16,
+        }
+    }
+
+    /// Use `database` to look ROMs up
+    pub fn database(mut self, database: Database) -> Self {
+        self.database = database;
+        self
+    }
+
+    /// Load ROMs at `address`
+    pub fn address(mut self, address: u16) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Load `rom`, or the program of the Octo cartridge `rom`, into a fresh memory
+    pub fn load(&self, rom: &[u8]) -> Result<Rom, LoadError> {
+        if rom.starts_with(GIF_MAGIC) {
+            return self.load_cartridge(rom);
+        }
+        let mut memory = Memory::new();
+        let info = memory.load(rom, self.address)?;
+        Ok(self.identify(memory, info))
+    }
+
+    /// Load the ROM at `path` into a fresh memory. Not available on the web, see `Loader::load`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_path<P: AsRef<Path>>(&self, path: P) -> Result<Rom, LoadError> {
+        let f = std::fs::File::open(path).map_err(MemoryError::OpenFile)?;
+        let metadata = f.metadata().map_err(MemoryError::LoadFile)?;
+        let mut reader = BufReader::new(f);
+        if reader
+            .fill_buf()
+            .map_err(MemoryError::LoadFile)?
+            .starts_with(GIF_MAGIC)
+        {
+            let mut gif = Vec::new();
+            reader
+                .read_to_end(&mut gif)
+                .map_err(MemoryError::LoadFile)?;
+            return self.load_cartridge(&gif);
+        }
+        let mut memory = Memory::new();
+        let info = memory
+            .load_reader(reader, self.address)
+            .map_err(|e| e.with_metadata(&metadata))?;
+        Ok(self.identify(memory, info))
+    }
+
+    /// Load the program of the Octo cartridge `gif` into a fresh memory, to run with the options
+    /// of the cartridge
+    pub fn load_cartridge(&self, gif: &[u8]) -> Result<Rom, LoadError> {
+        let cartridge = Cartridge::from_gif(gif)?;
+        let program = cartridge.program()?;
+        let mut memory = Memory::new();
+        let info = memory.load(&program, self.address)?;
+        let mut rom = self.identify(memory, info);
+        rom.profile = cartridge.options.profile();
+        rom.cartridge = Some(cartridge.options);
+        Ok(rom)
+    }
+
+    fn identify(&self, memory: Memory, info: LoadInfo) -> Rom {
+        let entry = self
+            .database
+            .get(&info.sha1)
+            .or_else(|| self.database.get_crc32(info.crc32))
+            .cloned();
+        let start = usize::from(info.address);
+        let detection =
+            Detection::detect(&memory.as_bytes()[start..start + info.size], info.address);
+        let profile = match &entry {
+            Some(entry) => entry.profile(),
+            None => detection.profile(),
+        };
+
+        Rom {
+            memory,
+            info,
+            entry,
+            detection,
+            profile,
+            cartridge: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cartridge::{self, Cartridge, CartridgeError, Options},
+        database::Database,
+        loader::{LoadError, Loader, Profile},
+        memory::MemoryError,
+    };
+
+    #[test]
+    fn test_known_rom() {
+        let rom = Loader::new().load_path("./games/tetris.ch8").unwrap();
+        assert_eq!("Tetris", rom.entry.as_ref().unwrap().title);
+        assert_eq!(Some(&4), rom.profile.keys.get("a"));
+    }
+
+    #[test]
+    fn test_unknown_rom() {
+        let rom = Loader::new()
+            .database(Database::default())
+            .load_path("./games/tetris.ch8")
+            .unwrap();
+        assert!(rom.entry.is_none());
+        assert_eq!(Profile::default(), rom.profile);
+        assert!(rom.to_string().starts_with("Title:    unknown ROM\n"));
+    }
+
+    #[test]
+    fn test_too_large() {
+        match Loader::new().address(0xF00).load_path("./games/tetris.ch8") {
+            Err(LoadError::Memory(MemoryError::RomTooLarge(494, 0x100))) => (),
+            r => panic!("unexpected result {:?}", r.map(|rom| rom.info)),
+        }
+    }
+
+    #[test]
+    fn test_cartridge() {
+        let options = Options {
+            tickrate: 500,
+            ..Options::default()
+        };
+        let mut gif = Vec::new();
+        cartridge::write_gif(
+            &Cartridge::from_rom(&[0x00, 0xE0], options.clone()),
+            &mut gif,
+        )
+        .unwrap();
+
+        let rom = Loader::new().load(&gif).unwrap();
+        assert_eq!(2, rom.info.size);
+        assert_eq!(&[0x00, 0xE0], &rom.memory.as_bytes()[0x200..0x202]);
+        assert_eq!(options.profile(), rom.profile);
+        assert_eq!(Some(options), rom.cartridge);
+        assert!(rom.to_string().contains("Colors:   #FFCC00 on #996600\n"));
+    }
+
+    #[test]
+    fn test_unsupported_cartridge() {
+        let cartridge = Cartridge {
+            source: ": main plane 1".to_string(),
+            options: Options::default(),
+        };
+        let mut gif = Vec::new();
+        cartridge::write_gif(&cartridge, &mut gif).unwrap();
+
+        match Loader::new().load(&gif) {
+            Err(LoadError::Cartridge(e @ CartridgeError::Assemble(_))) => {
+                assert!(e.to_string().contains("XO-CHIP"))
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+}
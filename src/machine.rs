@@ -0,0 +1,1199 @@
+//! The CHIP-8 virtual machine, tying the registers, memory, display and keypad together.
+//!
+//! [`Machine::step`] fetches, decodes and executes a single instruction, and
+//! [`Machine::run_frame`] runs a 60Hz frame worth of instructions before ticking the timers.
+//!
+//! Programs misbehave: they overflow the stack, point I past the end of memory or run into data.
+//! Rather than panicking, the machine reports a [`MachineError`] carrying the address and opcode
+//! of the faulting instruction, and the [`FaultPolicy`] decides whether the machine halts, wraps
+//! around, or skips the instruction and carries on.
+use crate::{
+    cache::BlockCache,
+    coverage::Coverage,
+    display::Display,
+    flags::{self, FlagStore},
+    host::{AudioSink, Clock, Host, InputSource, VideoSink},
+    inspector::Cheats,
+    instructions::{DecodeTable, Instruction},
+    keypad::Keypad,
+    loader::{Profile, Rom},
+    memory::{Memory, MemoryError},
+    opcode::OpCode,
+    profiler::Profiler,
+    quirks::{Platform, Quirks},
+    register::Register,
+    state::State,
+};
+use std::{convert::TryFrom, fmt, num::NonZeroUsize, str::FromStr, sync::Arc};
+
+/// The built-in hexadecimal font, 5 bytes per digit
+pub const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+/// The address the font is stored at, in the interpreter area
+pub const FONT_ADDRESS: u16 = 0x050;
+
+/// What went wrong when executing an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum Fault {
+    #[error("Stack overflow")]
+    StackOverflow,
+    #[error("Stack underflow")]
+    StackUnderflow,
+    #[error("Out of bounds memory access at {0:#05X}")]
+    OutOfBoundsAccess(usize),
+    #[error("Unknown opcode")]
+    UnknownOpCode,
+    #[error("Program exited")]
+    Exit,
+}
+
+/// A fault, along with the instruction that caused it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("{fault} at {pc:#05X} ({opcode:?})")]
+pub struct MachineError {
+    /// The address of the faulting instruction
+    pub pc: u16,
+    pub opcode: OpCode,
+    pub fault: Fault,
+}
+
+/// How the machine reacts to a fault. `Exit` always halts the machine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum FaultPolicy {
+    /// Stop executing, every further step reports the same error
+    #[default]
+    Halt,
+    /// Wrap the stack pointer and memory addresses around, skip unknown opcodes
+    Wrap,
+    /// Skip the faulting instruction, or the word that could not be fetched
+    Ignore,
+}
+
+impl FromStr for FaultPolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "halt" => Ok(FaultPolicy::Halt),
+            "wrap" => Ok(FaultPolicy::Wrap),
+            "ignore" => Ok(FaultPolicy::Ignore),
+            _ => Err(format!("unknown fault policy '{}'", s)),
+        }
+    }
+}
+
+/// How the machine executes instructions
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Engine {
+    /// Fetch and decode every instruction as it is executed
+    #[default]
+    Interpreter,
+    /// Decode each basic block once and replay it, see [`BlockCache`]
+    Cached,
+    /// Fetch every instruction as it is executed, decoding it with a lookup in a table of every
+    /// opcode, see [`DecodeTable`]
+    Table,
+}
+
+impl FromStr for Engine {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "interpreter" => Ok(Engine::Interpreter),
+            "cached" => Ok(Engine::Cached),
+            "table" => Ok(Engine::Table),
+            _ => Err(format!("unknown engine '{}'", s)),
+        }
+    }
+}
+
+/// An instruction the machine executed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Executed {
+    /// The address of the instruction
+    pub pc: u16,
+    pub opcode: OpCode,
+    /// The decoded instruction, `None` for an unknown opcode skipped by the policy
+    pub instruction: Option<Instruction>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Machine {
+    register: Register,
+    memory: Memory,
+    display: Display,
+    keypad: Keypad,
+    quirks: Quirks,
+    tickrate: u32,
+    policy: FaultPolicy,
+    /// The RPL user flags
+    flags: Vec<u8>,
+    /// Where the flags are persisted, along with the SHA-1 of the ROM they belong to
+    flag_store: Option<(Arc<dyn FlagStore>, String)>,
+    /// Set when the flags changed since they were last persisted
+    flags_dirty: bool,
+    /// The decoded blocks, when using the cached engine
+    cache: Option<Box<BlockCache>>,
+    /// The table decoding opcodes, when using the table engine
+    table: Option<&'static DecodeTable>,
+    /// Fed every executed instruction, when profiling
+    profiler: Option<Box<Profiler>>,
+    /// Fed every executed instruction, when tracking coverage
+    coverage: Option<Box<Coverage>>,
+    /// The values written back into memory at the end of every frame
+    cheats: Cheats,
+    /// The state of the xorshift random number generator
+    rng: u64,
+    /// The fault that halted the machine
+    fault: Option<MachineError>,
+    /// Set when a draw is waiting for the vertical blank interrupt
+    vblank_wait: bool,
+}
+
+impl Machine {
+    /// A machine with `memory`, starting execution at `pc` with the settings in `profile`
+    pub fn new(memory: Memory, pc: u16, profile: &Profile) -> Self {
+        let flag_count = match profile.platform {
+            Platform::XoChip => flags::XOCHIP_FLAGS,
+            _ => flags::SCHIP_FLAGS,
+        };
+        let mut machine = Self {
+            register: Register::new(),
+            memory,
+            display: Display::new(),
+            keypad: Keypad::new(),
+            quirks: profile.quirks,
+            tickrate: profile.tickrate,
+            policy: FaultPolicy::default(),
+            flags: vec![0; flag_count],
+            flag_store: None,
+            flags_dirty: false,
+            cache: None,
+            table: None,
+            profiler: None,
+            coverage: None,
+            cheats: Cheats::new(),
+            rng: 0,
+            fault: None,
+            vblank_wait: false,
+        };
+        for (offset, byte) in FONT.iter().enumerate() {
+            machine
+                .memory
+                .poke(usize::from(FONT_ADDRESS) + offset, *byte)
+                .expect("the font fits in the interpreter area");
+        }
+        machine.register.pc = pc;
+        machine.seed(0);
+        machine
+    }
+
+    /// A machine running `rom` with its recommended settings
+    pub fn from_rom(rom: Rom) -> Self {
+        Self::new(rom.memory, rom.info.address, &rom.profile)
+    }
+
+    /// Use `policy` to handle faults
+    pub fn policy(mut self, policy: FaultPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Persist the RPL user flags of the ROM with SHA-1 `rom` in `store`, restoring the flags
+    /// saved by a previous run. Flags changed by the program are saved at the end of the frame,
+    /// or when the machine is dropped.
+    pub fn flag_store(mut self, store: Arc<dyn FlagStore>, rom: &str) -> Self {
+        match store.load(rom) {
+            Ok(Some(saved)) => {
+                let n = saved.len().min(self.flags.len());
+                self.flags[..n].copy_from_slice(&saved[..n]);
+            }
+            Ok(None) => (),
+            Err(e) => log::warn!("Failed to load the user flags of {}: {}", rom, e),
+        }
+        self.flag_store = Some((store, rom.to_string()));
+        self
+    }
+
+    /// The RPL user flags, 8 of them on SUPER-CHIP and 16 on XO-CHIP
+    pub fn flags(&self) -> &[u8] {
+        &self.flags
+    }
+
+    /// Mutable access to the RPL user flags, which are saved to the flag store at the end of the
+    /// frame
+    pub fn flags_mut(&mut self) -> &mut [u8] {
+        self.flags_dirty = true;
+        &mut self.flags
+    }
+
+    /// Seed the random number generator used by `RND`
+    pub fn seed(&mut self, seed: u64) {
+        // xorshift gets stuck on zero, so mix the seed with an arbitrary odd constant
+        self.rng = seed ^ 0x9E37_79B9_7F4A_7C15;
+    }
+
+    /// The next byte of the random number generator used by `RND`
+    pub fn random(&mut self) -> u8 {
+        let mut x = self.rng;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng = x;
+        (x >> 32) as u8
+    }
+
+    /// Execute instructions with `engine`
+    pub fn engine(mut self, engine: Engine) -> Self {
+        self.cache = match engine {
+            Engine::Cached => Some(Box::new(BlockCache::new())),
+            Engine::Interpreter | Engine::Table => None,
+        };
+        self.table = match engine {
+            Engine::Table => Some(DecodeTable::shared()),
+            Engine::Interpreter | Engine::Cached => None,
+        };
+        self
+    }
+
+    /// Profile every instruction the machine executes from now on, see [`Machine::profiler`]
+    pub fn enable_profiler(mut self) -> Self {
+        self.profiler = Some(Box::new(Profiler::new()));
+        self
+    }
+
+    /// The profile of the instructions executed so far, if profiling is enabled
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_deref()
+    }
+
+    /// Track the code coverage of the program from now on, see [`Machine::coverage`]
+    pub fn enable_coverage(mut self) -> Self {
+        self.coverage = Some(Box::new(Coverage::new()));
+        self
+    }
+
+    /// The coverage of the instructions executed so far, if tracking it is enabled
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_deref()
+    }
+
+    /// Use a stack holding `depth` return addresses, rather than the usual 16. The return
+    /// addresses already on the stack are kept, the innermost ones are dropped when they don't fit.
+    pub fn stack_depth(mut self, depth: NonZeroUsize) -> Self {
+        let kept = self.register.sp.min(depth.get());
+        let mut stack = Register::with_stack_depth(depth).stack;
+        stack[..kept].copy_from_slice(&self.register.stack[..kept]);
+        self.register.stack = stack;
+        self.register.sp = kept;
+        self
+    }
+
+    pub fn register(&self) -> &Register {
+        &self.register
+    }
+
+    pub fn register_mut(&mut self) -> &mut Register {
+        &mut self.register
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// Mutable access to the memory, which throws away every cached block
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        if let Some(cache) = &mut self.cache {
+            cache.clear();
+        }
+        &mut self.memory
+    }
+
+    /// Freeze `addr` to `value`: the value is written back at the end of every frame, whatever
+    /// the program stored there in the meantime
+    pub fn freeze(&mut self, addr: u16, value: u8) -> Result<(), MemoryError> {
+        self.memory.get(usize::from(addr))?;
+        self.cheats.freeze(addr, value);
+        Ok(())
+    }
+
+    pub fn unfreeze(&mut self, addr: u16) {
+        self.cheats.unfreeze(addr);
+    }
+
+    pub fn cheats(&self) -> &Cheats {
+        &self.cheats
+    }
+
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    pub fn display_mut(&mut self) -> &mut Display {
+        &mut self.display
+    }
+
+    pub fn keypad(&self) -> &Keypad {
+        &self.keypad
+    }
+
+    pub fn keypad_mut(&mut self) -> &mut Keypad {
+        &mut self.keypad
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.register.pc
+    }
+
+    /// The amount of instructions executed per 60Hz frame
+    pub fn tickrate(&self) -> u32 {
+        self.tickrate
+    }
+
+    /// Whether a draw is waiting for the vertical blank, ending the current frame early
+    pub fn waiting_for_vblank(&self) -> bool {
+        self.vblank_wait
+    }
+
+    /// The fault that halted the machine, if any
+    pub fn fault(&self) -> Option<&MachineError> {
+        self.fault.as_ref()
+    }
+
+    /// A snapshot of the machine, see the [`state`](crate::state) module
+    pub fn save_state(&self) -> State {
+        State {
+            register: self.register.clone(),
+            memory: self.memory.clone(),
+            display: self.display.clone(),
+            keypad: self.keypad,
+            flags: self.flags.clone(),
+            rng: self.rng,
+            vblank_wait: self.vblank_wait,
+            fault: self.fault,
+        }
+    }
+
+    /// Restore a snapshot taken by `save_state`, keeping the settings of the machine. The user
+    /// flags are not saved to the flag store until the program saves them again.
+    pub fn load_state(&mut self, state: State) {
+        self.register = state.register;
+        self.memory = state.memory;
+        self.display = state.display;
+        self.keypad = state.keypad;
+        self.flags = state.flags;
+        self.rng = state.rng;
+        self.vblank_wait = state.vblank_wait;
+        self.fault = state.fault;
+        if let Some(cache) = &mut self.cache {
+            cache.clear();
+        }
+    }
+
+    /// Decrement the delay and sound timers, which run at 60Hz
+    pub fn tick_timers(&mut self) {
+        self.register.dt = self.register.dt.saturating_sub(1);
+        self.register.st = self.register.st.saturating_sub(1);
+    }
+
+    /// End the current 60Hz frame, for callers stepping through frames one instruction at a time:
+    /// tick the timers, stop waiting for the vertical blank and apply the cheats
+    pub fn end_frame(&mut self) {
+        self.vblank_wait = false;
+        self.tick_timers();
+        self.save_flags();
+        self.cheats
+            .apply(&mut self.memory)
+            .expect("frozen addresses are checked by freeze");
+        if let Some(cache) = &mut self.cache {
+            for (addr, _) in self.cheats.iter() {
+                cache.invalidate(usize::from(addr));
+            }
+        }
+    }
+
+    /// Run a 60Hz frame: up to `tickrate` instructions, stopping early when a draw waits for the
+    /// vertical blank, then tick the timers
+    pub fn run_frame(&mut self) -> Result<(), MachineError> {
+        self.run_frame_with(Machine::step)
+    }
+
+    /// Run a 60Hz frame like `run_frame`, executing each instruction with `step`
+    pub(crate) fn run_frame_with<F>(&mut self, mut step: F) -> Result<(), MachineError>
+    where
+        F: FnMut(&mut Machine) -> Result<Executed, MachineError>,
+    {
+        for _ in 0..self.tickrate {
+            step(self)?;
+            if self.vblank_wait {
+                break;
+            }
+        }
+        self.end_frame();
+        Ok(())
+    }
+
+    /// Run a 60Hz frame on `host`, see the [`host`](crate::host) module for when each part of
+    /// the host is called. The frame is presented even when the machine faults.
+    pub fn run_frame_on<V, A, I, C>(
+        &mut self,
+        host: &mut Host<V, A, I, C>,
+    ) -> Result<(), MachineError>
+    where
+        V: VideoSink,
+        A: AudioSink,
+        I: InputSource,
+        C: Clock,
+    {
+        host.clock.wait_frame();
+        host.input.poll(&mut self.keypad);
+        let result = self.run_frame();
+        host.video.present(&self.display);
+        host.audio.tone(self.register.st > 0);
+        result
+    }
+
+    /// Execute a single instruction
+    pub fn step(&mut self) -> Result<Executed, MachineError> {
+        if let Some(fault) = self.fault {
+            return Err(fault);
+        }
+
+        let pc = self.register.pc;
+        let cached = match &mut self.cache {
+            Some(cache) => cache.get(&self.memory, pc),
+            None => None,
+        };
+        let (opcode, instruction) = match cached {
+            Some(op) => (op.opcode, op.instruction),
+            // Instructions straddling the end of memory are left to the interpreter
+            None => match self.fetch(pc) {
+                Ok(opcode) => match self.table {
+                    Some(table) => (opcode, table.decode(opcode)),
+                    None => (opcode, Instruction::try_from(opcode).ok()),
+                },
+                Err(_) if self.policy == FaultPolicy::Ignore => {
+                    log::debug!("Skipping word that cannot be fetched at {:#05X}", pc);
+                    self.register.pc = pc.wrapping_add(2) & 0xFFF;
+                    if let Some(coverage) = &mut self.coverage {
+                        coverage.record(pc, None);
+                    }
+                    return Ok(Executed {
+                        pc,
+                        opcode: OpCode::default(),
+                        instruction: None,
+                    });
+                }
+                Err(fault) => {
+                    let error = MachineError {
+                        pc,
+                        opcode: OpCode::default(),
+                        fault,
+                    };
+                    self.fault = Some(error);
+                    return Err(error);
+                }
+            },
+        };
+
+        self.execute_at(pc, opcode, instruction)
+    }
+
+    fn execute_at(
+        &mut self,
+        pc: u16,
+        opcode: OpCode,
+        instruction: Option<Instruction>,
+    ) -> Result<Executed, MachineError> {
+        self.register.pc = pc.wrapping_add(2);
+        let result = match instruction {
+            Some(instruction) => self.execute(instruction),
+            None => Err(Fault::UnknownOpCode),
+        };
+
+        match result {
+            Ok(()) => (),
+            Err(fault) if fault == Fault::Exit || self.policy == FaultPolicy::Halt => {
+                self.register.pc = pc;
+                let error = MachineError { pc, opcode, fault };
+                self.fault = Some(error);
+                return Err(error);
+            }
+            // Wrapping is handled where the fault can occur, anything left is skipped
+            Err(_) => log::debug!("Skipping faulting instruction at {:#05X}", pc),
+        }
+        // Like addresses, the PC is 12 bits wide: running off the end of memory, or jumping past
+        // it with `Bnnn`, wraps around
+        self.register.pc &= 0xFFF;
+
+        if let (Some(profiler), Some(instruction)) = (&mut self.profiler, instruction) {
+            profiler.record(pc, instruction);
+        }
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record(pc, instruction);
+        }
+
+        Ok(Executed {
+            pc,
+            opcode,
+            instruction,
+        })
+    }
+
+    /// Persist the flags if they changed since they were last saved
+    fn save_flags(&mut self) {
+        if !self.flags_dirty {
+            return;
+        }
+        self.flags_dirty = false;
+        if let Some((store, rom)) = &self.flag_store {
+            if let Err(e) = store.save(rom, &self.flags) {
+                log::warn!("Failed to save the user flags of {}: {}", rom, e);
+            }
+        }
+    }
+
+    /// The amount of flags `Fx75` and `Fx85` copy, warning when the program asks for more flags
+    /// than the platform has
+    fn flag_count(&self, x: u8) -> usize {
+        let n = usize::from(x) + 1;
+        if n > self.flags.len() {
+            log::warn!(
+                "Accessing V0 to V{:X} in the user flags, only {} of them exist",
+                x,
+                self.flags.len()
+            );
+        }
+        n.min(self.flags.len())
+    }
+
+    fn fetch(&self, pc: u16) -> Result<OpCode, Fault> {
+        let hi = self.read(usize::from(pc))?;
+        let lo = self.read(usize::from(pc) + 1)?;
+        Ok(OpCode::new(u16::from_be_bytes([hi, lo])))
+    }
+
+    /// Check `address` is in memory, wrapping it around when the policy says so
+    fn address(&self, address: usize) -> Result<usize, Fault> {
+        if address < Memory::MEMORY_SIZE {
+            Ok(address)
+        } else if self.policy == FaultPolicy::Wrap {
+            Ok(address % Memory::MEMORY_SIZE)
+        } else {
+            Err(Fault::OutOfBoundsAccess(address))
+        }
+    }
+
+    fn read(&self, address: usize) -> Result<u8, Fault> {
+        let address = self.address(address)?;
+        Ok(self.memory.as_bytes()[address])
+    }
+
+    /// Check the `len` bytes from I are in memory before any of them is touched, so that a
+    /// skipped instruction leaves no partial writes behind
+    fn check_i(&self, len: usize) -> Result<(), Fault> {
+        let start = usize::from(self.register.i);
+        for address in start..start + len {
+            self.address(address)?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, address: usize, value: u8) -> Result<(), Fault> {
+        let address = self.address(address)?;
+        if let Some(cache) = &mut self.cache {
+            cache.invalidate(address);
+        }
+        self.memory
+            .poke(address, value)
+            .map_err(|_| Fault::OutOfBoundsAccess(address))
+    }
+
+    fn push(&mut self, address: u16) -> Result<(), Fault> {
+        let depth = self.register.stack.len();
+        let mut sp = self.register.sp;
+        if sp >= depth {
+            if self.policy != FaultPolicy::Wrap {
+                return Err(Fault::StackOverflow);
+            }
+            sp = 0;
+        }
+        self.register.stack[sp] = address;
+        self.register.sp = sp + 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<u16, Fault> {
+        let depth = self.register.stack.len();
+        let sp = match self.register.sp {
+            0 if self.policy == FaultPolicy::Wrap => depth - 1,
+            0 => return Err(Fault::StackUnderflow),
+            sp => sp - 1,
+        };
+        self.register.sp = sp;
+        Ok(self.register.stack[sp])
+    }
+
+    fn skip_if(&mut self, condition: bool) {
+        if condition {
+            self.register.pc = self.register.pc.wrapping_add(2);
+        }
+    }
+
+    /// Execute `instruction`, with the PC already pointing at the next one
+    fn execute(&mut self, instruction: Instruction) -> Result<(), Fault> {
+        use Instruction::*;
+
+        let quirks = self.quirks;
+        let r = &mut self.register;
+        match instruction {
+            ScrollDown(n) => self.display.scroll_down(usize::from(n)),
+            ScrollRight => self.display.scroll_right(),
+            ScrollLeft => self.display.scroll_left(),
+            Exit => return Err(Fault::Exit),
+            LowRes => self.display.set_hires(false),
+            HighRes => self.display.set_hires(true),
+            ClearScreen => self.display.clear(),
+            Return => self.register.pc = self.pop()?,
+            Jump(addr) => r.pc = addr,
+            Call(addr) => {
+                self.push(self.register.pc)?;
+                self.register.pc = addr;
+            }
+            SkipEqualImmediate(x, kk) => {
+                let v = r.v[usize::from(x)];
+                self.skip_if(v == kk)
+            }
+            SkipNotEqualImmediate(x, kk) => {
+                let v = r.v[usize::from(x)];
+                self.skip_if(v != kk)
+            }
+            SkipEqual(x, y) => {
+                let equal = r.v[usize::from(x)] == r.v[usize::from(y)];
+                self.skip_if(equal)
+            }
+            SkipNotEqual(x, y) => {
+                let equal = r.v[usize::from(x)] == r.v[usize::from(y)];
+                self.skip_if(!equal)
+            }
+            LoadImmediate(x, kk) => r.v[usize::from(x)] = kk,
+            AddImmediate(x, kk) => {
+                r.v[usize::from(x)] = r.v[usize::from(x)].wrapping_add(kk);
+            }
+            Load(x, y) => r.v[usize::from(x)] = r.v[usize::from(y)],
+            Or(x, y) | And(x, y) | Xor(x, y) => {
+                let (vx, vy) = (r.v[usize::from(x)], r.v[usize::from(y)]);
+                r.v[usize::from(x)] = match instruction {
+                    Or(..) => vx | vy,
+                    And(..) => vx & vy,
+                    _ => vx ^ vy,
+                };
+                if quirks.logic {
+                    r.v[0xF] = 0;
+                }
+            }
+            Add(x, y) => {
+                let (sum, carry) = r.v[usize::from(x)].overflowing_add(r.v[usize::from(y)]);
+                r.v[usize::from(x)] = sum;
+                r.v[0xF] = carry as u8;
+            }
+            Sub(x, y) => {
+                let (vx, vy) = (r.v[usize::from(x)], r.v[usize::from(y)]);
+                r.v[usize::from(x)] = vx.wrapping_sub(vy);
+                r.v[0xF] = (vx >= vy) as u8;
+            }
+            SubNumeric(x, y) => {
+                let (vx, vy) = (r.v[usize::from(x)], r.v[usize::from(y)]);
+                r.v[usize::from(x)] = vy.wrapping_sub(vx);
+                r.v[0xF] = (vy >= vx) as u8;
+            }
+            ShiftRight(x, y) => {
+                let v = r.v[usize::from(if quirks.shift { x } else { y })];
+                r.v[usize::from(x)] = v >> 1;
+                r.v[0xF] = v & 1;
+            }
+            ShiftLeft(x, y) => {
+                let v = r.v[usize::from(if quirks.shift { x } else { y })];
+                r.v[usize::from(x)] = v << 1;
+                r.v[0xF] = v >> 7;
+            }
+            LoadI(addr) => r.i = addr,
+            JumpImmediate(addr) => {
+                let x = if quirks.jump { (addr >> 8) & 0xF } else { 0 };
+                r.pc = addr.wrapping_add(u16::from(r.v[usize::from(x)]));
+            }
+            Random(x, kk) => {
+                let random = self.random();
+                self.register.v[usize::from(x)] = random & kk;
+            }
+            Draw(x, y, n) => self.draw(x, y, n)?,
+            SkipOnKey(x) => {
+                let pressed = self.keypad.is_pressed(r.v[usize::from(x)]);
+                self.skip_if(pressed)
+            }
+            SkipNotOnKey(x) => {
+                let pressed = self.keypad.is_pressed(r.v[usize::from(x)]);
+                self.skip_if(!pressed)
+            }
+            LoadDTIntoV(x) => r.v[usize::from(x)] = r.dt,
+            LoadKey(x) => match self.keypad.first_pressed() {
+                Some(key) => r.v[usize::from(x)] = key,
+                // Wait by executing the instruction again
+                None => r.pc = r.pc.wrapping_sub(2),
+            },
+            LoadVIntoDT(x) => r.dt = r.v[usize::from(x)],
+            LoadVIntoST(x) => r.st = r.v[usize::from(x)],
+            AddI(x) => r.i = r.i.wrapping_add(u16::from(r.v[usize::from(x)])),
+            LoadSpriteIntoI(x) => {
+                r.i = FONT_ADDRESS + u16::from(r.v[usize::from(x)] & 0xF) * 5;
+            }
+            LoadBCDIntoI(x) => {
+                self.check_i(3)?;
+                let v = self.register.v[usize::from(x)];
+                let i = usize::from(self.register.i);
+                for (offset, digit) in [v / 100, v / 10 % 10, v % 10].iter().enumerate() {
+                    self.write(i + offset, *digit)?;
+                }
+            }
+            LoadVIntoMem(x) | LoadMemIntoV(x) => {
+                let len = usize::from(x) + 1;
+                self.check_i(len)?;
+                let i = usize::from(self.register.i);
+                for offset in 0..len {
+                    if let LoadVIntoMem(_) = instruction {
+                        self.write(i + offset, self.register.v[offset])?;
+                    } else {
+                        self.register.v[offset] = self.read(i + offset)?;
+                    }
+                }
+                if !quirks.memory_leave_i_unchanged {
+                    let increment = if quirks.memory_increment_by_x {
+                        x
+                    } else {
+                        x + 1
+                    };
+                    self.register.i = self.register.i.wrapping_add(u16::from(increment));
+                }
+            }
+            LoadVIntoFlags(x) => {
+                let n = self.flag_count(x);
+                self.flags[..n].copy_from_slice(&self.register.v[..n]);
+                self.flags_dirty = true;
+            }
+            LoadFlagsIntoV(x) => {
+                let n = self.flag_count(x);
+                self.register.v[..n].copy_from_slice(&self.flags[..n]);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn draw(&mut self, x: u8, y: u8, n: u8) -> Result<(), Fault> {
+        // A zero height draws a 16x16 sprite
+        let (width, len) = if n == 0 {
+            (16, 32)
+        } else {
+            (8, usize::from(n))
+        };
+        self.check_i(len)?;
+
+        let i = usize::from(self.register.i);
+        let sprite = (i..i + len)
+            .map(|address| self.read(address))
+            .collect::<Result<Vec<u8>, Fault>>()?;
+        let vx = usize::from(self.register.v[usize::from(x)]);
+        let vy = usize::from(self.register.v[usize::from(y)]);
+        let collision = self.display.draw(vx, vy, &sprite, width, self.quirks.wrap);
+
+        self.register.v[0xF] = collision as u8;
+        self.vblank_wait = self.quirks.vblank;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Machine {
+    /// The screen, followed by the fault that halted the machine, if any
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.display)?;
+        if let Some(fault) = &self.fault {
+            writeln!(f, "Halted: {}", fault)?;
+            if let Ok(instruction) = Instruction::try_from(fault.opcode) {
+                writeln!(f, "        {}", instruction)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Machine {
+    fn drop(&mut self) {
+        self.save_flags();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        display::Display,
+        flags::{FlagStore, MemoryFlagStore},
+        host::{Host, RecordedInput, RecordingAudio, RecordingClock, RecordingVideo},
+        loader::Profile,
+        machine::{Engine, Fault, FaultPolicy, Machine, MachineError},
+        memory::{Memory, MemoryError},
+        opcode::OpCode,
+    };
+    use std::{convert::TryFrom, num::NonZeroUsize, sync::Arc};
+
+    fn machine(rom: &[u8]) -> Machine {
+        let memory = Memory::try_from(rom).unwrap();
+        Machine::new(memory, 0x200, &Profile::default())
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0xFF, // LD V0, 0xFF
+            0x61, 0x02, // LD V1, 0x02
+            0x80, 0x14, // ADD V0, V1
+        ];
+        let mut m = machine(&rom);
+        for _ in 0..3 {
+            m.step().unwrap();
+        }
+        assert_eq!(0x01, m.register.v[0]);
+        assert_eq!(1, m.register.v[0xF]);
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        // CALL 0x200, forever
+        let mut m = machine(&[0x22, 0x00]);
+        let depth = m.register.stack.len();
+        for _ in 0..depth {
+            m.step().unwrap();
+        }
+        let error = MachineError {
+            pc: 0x200,
+            opcode: OpCode::new(0x2200),
+            fault: Fault::StackOverflow,
+        };
+        assert_eq!(Err(error), m.step());
+        assert_eq!(Err(error), m.step());
+        assert_eq!(Some(&error), m.fault());
+        assert_eq!(
+            "Stack overflow at 0x200 (0x2200)",
+            m.fault().unwrap().to_string()
+        );
+
+        let mut m = machine(&[0x22, 0x00]).policy(FaultPolicy::Wrap);
+        for _ in 0..=depth {
+            m.step().unwrap();
+        }
+        assert_eq!(1, m.register.sp);
+    }
+
+    #[test]
+    fn test_stack_depth() {
+        let mut m = machine(&[]);
+        m.register.set_stack(&[0x202, 0x304, 0x406]).unwrap();
+        let m = m.stack_depth(NonZeroUsize::new(24).unwrap());
+        assert_eq!(24, m.register().stack_depth());
+        assert_eq!(&[0x202, 0x304, 0x406], m.register().stack());
+
+        let m = m.stack_depth(NonZeroUsize::new(2).unwrap());
+        assert_eq!(&[0x202, 0x304], m.register().stack());
+    }
+
+    #[test]
+    fn test_stack_underflow() {
+        let mut m = machine(&[0x00, 0xEE]);
+        assert_eq!(Fault::StackUnderflow, m.step().unwrap_err().fault);
+
+        let mut m = machine(&[0x00, 0xEE, 0x00, 0xE0]).policy(FaultPolicy::Ignore);
+        m.step().unwrap();
+        assert_eq!(0x202, m.pc());
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        #[rustfmt::skip]
+        let rom = [
+            0xAF, 0xFE, // LD I, 0xFFE
+            0xF2, 0x55, // LD [I], V2
+        ];
+        let mut m = machine(&rom);
+        m.step().unwrap();
+        assert_eq!(
+            Fault::OutOfBoundsAccess(0x1000),
+            m.step().unwrap_err().fault
+        );
+        assert_eq!(0x202, m.pc());
+
+        let mut m = machine(&rom).policy(FaultPolicy::Ignore);
+        m.step().unwrap();
+        m.step().unwrap();
+        assert_eq!(0, m.memory().as_bytes()[0xFFE]);
+        assert_eq!(0xFFE, m.register.i);
+
+        let mut m = machine(&rom).policy(FaultPolicy::Wrap);
+        m.register.v[2] = 0xAA;
+        m.step().unwrap();
+        m.step().unwrap();
+        assert_eq!(0xAA, m.memory().as_bytes()[0x000]);
+    }
+
+    #[test]
+    fn test_fetch_out_of_bounds() {
+        let mut m = machine(&[]);
+        m.register.pc = 0xFFF;
+        assert_eq!(
+            Fault::OutOfBoundsAccess(0x1000),
+            m.step().unwrap_err().fault
+        );
+
+        let mut m = machine(&[]).policy(FaultPolicy::Ignore);
+        m.register.pc = 0xFFF;
+        assert_eq!(None, m.step().unwrap().instruction);
+        assert_eq!(0x001, m.pc());
+        assert!(m.fault().is_none());
+
+        // JP V0, 0xFFF jumps past the end of memory and wraps around
+        let mut m = machine(&[0x60, 0x02, 0xBF, 0xFF]);
+        m.step().unwrap();
+        m.step().unwrap();
+        assert_eq!(0x001, m.pc());
+    }
+
+    #[test]
+    fn test_unknown_and_exit() {
+        let mut m = machine(&[0xFF, 0xFF, 0x00, 0xFD]);
+        assert_eq!(Fault::UnknownOpCode, m.step().unwrap_err().fault);
+
+        let mut m = machine(&[0xFF, 0xFF, 0x00, 0xFD]).policy(FaultPolicy::Ignore);
+        assert_eq!(None, m.step().unwrap().instruction);
+        assert_eq!(Fault::Exit, m.step().unwrap_err().fault);
+        assert!(m
+            .to_string()
+            .ends_with("Halted: Program exited at 0x202 (0x00FD)\n        EXIT\n"));
+    }
+
+    #[test]
+    fn test_draw_font() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x08, // LD V0, 0x08
+            0xF0, 0x29, // LD F, V0
+            0xD1, 0x15, // DRW V1, V1, 5
+        ];
+        let mut m = machine(&rom);
+        m.run_frame().unwrap();
+        assert_eq!(0x202 + 4, m.pc());
+        assert!(m.display().get(0, 0) && !m.display().get(1, 1));
+        assert_eq!(
+            vec!["████", "█  █", "████", "█  █", "████"],
+            m.display().to_string().lines().take(5).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_flags() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x2A, // LD V0, 0x2A
+            0xF1, 0x75, // LD R, V1
+            0x60, 0x00, // LD V0, 0x00
+            0xF0, 0x85, // LD V0, R
+        ];
+        let store = Arc::new(MemoryFlagStore::new());
+        let mut m = machine(&rom).flag_store(store.clone(), "abc");
+        for _ in 0..2 {
+            m.step().unwrap();
+        }
+        // The flags are saved once the frame is over
+        assert_eq!(None, store.load("abc").unwrap());
+        m.end_frame();
+        assert_eq!(
+            Some(vec![0x2A, 0, 0, 0, 0, 0, 0, 0]),
+            store.load("abc").unwrap()
+        );
+
+        // Or when the machine is dropped mid-frame
+        let other = Arc::new(MemoryFlagStore::new());
+        let mut m = machine(&rom).flag_store(other.clone(), "abc");
+        m.step().unwrap();
+        m.step().unwrap();
+        drop(m);
+        assert_eq!(store.load("abc").unwrap(), other.load("abc").unwrap());
+
+        // A new run starts with the flags saved by the previous one
+        let mut m = machine(&rom[4..]).flag_store(store, "abc");
+        m.step().unwrap();
+        m.step().unwrap();
+        assert_eq!(0x2A, m.register.v[0]);
+    }
+
+    #[test]
+    fn test_host() {
+        #[rustfmt::skip]
+        let rom = [
+            0xF0, 0x0A, // 0x200: LD V0, K
+            0xF0, 0x18, // 0x202: LD ST, V0
+            0xF0, 0x29, // 0x204: LD F, V0
+            0xD0, 0x15, // 0x206: DRW V0, V1, 0x5
+            0x12, 0x08, // 0x208: JP 0x208
+        ];
+        let mut m = machine(&rom);
+        let mut host = Host::new(
+            RecordingVideo::default(),
+            RecordingAudio::default(),
+            RecordedInput::new(vec![0, 1 << 3]),
+            RecordingClock::default(),
+        );
+        for _ in 0..4 {
+            m.run_frame_on(&mut host).unwrap();
+        }
+
+        assert_eq!(4, host.clock.frames);
+        assert_eq!(vec![false, true, true, false], host.audio.tones);
+        assert_eq!(Display::new(), host.video.frames[0]);
+        assert!(host.video.frames[1].get(3, 0));
+        assert_eq!(m.display, host.video.frames[3]);
+        // The recorded input ran out, releasing the key
+        assert_eq!(None, m.keypad.first_pressed());
+    }
+
+    #[test]
+    fn test_profiler() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x00, // 0x200: LD V0, 0x00
+            0x70, 0x01, // 0x202: ADD V0, 0x01
+            0x12, 0x02, // 0x204: JP 0x202
+        ];
+        let mut m = machine(&rom);
+        m.step().unwrap();
+        assert!(m.profiler().is_none());
+
+        let mut m = machine(&rom).enable_profiler();
+        for _ in 0..7 {
+            m.step().unwrap();
+        }
+        let profiler = m.profiler().unwrap();
+        assert_eq!(7, profiler.total());
+        assert_eq!(3, profiler.address_hits(0x202));
+        assert_eq!(3, profiler.instruction_hits("Jump"));
+        assert_eq!(0x202, profiler.hot_loops()[0].0.start);
+    }
+
+    #[test]
+    fn test_coverage() {
+        #[rustfmt::skip]
+        let rom = [
+            0x70, 0x01, // 0x200: ADD V0, 0x01
+            0x30, 0x03, // 0x202: SE V0, 0x03
+            0x12, 0x00, // 0x204: JP 0x200
+            0x12, 0x06, // 0x206: JP 0x206
+        ];
+        let mut m = machine(&rom).enable_coverage();
+        for _ in 0..10 {
+            m.step().unwrap();
+        }
+        let coverage = m.coverage().unwrap();
+        assert_eq!(3, coverage.hits(0x200));
+        assert_eq!(2, coverage.hits(0x204));
+        assert_eq!(2, coverage.branch(0x202).unwrap().not_taken);
+        assert_eq!(1, coverage.branch(0x202).unwrap().taken);
+    }
+
+    #[test]
+    fn test_cheats() {
+        #[rustfmt::skip]
+        let rom = [
+            0xA3, 0x00, // LD I, 0x300
+            0x60, 0x05, // LD V0, 0x05
+            0xF0, 0x55, // LD [I], V0
+            0x12, 0x06, // JP 0x206
+        ];
+        let mut m = machine(&rom);
+        m.freeze(0x300, 0x63).unwrap();
+        m.run_frame().unwrap();
+        assert_eq!(0x63, m.memory.peek(0x300).unwrap());
+        m.unfreeze(0x300);
+        m.register.pc = 0x200;
+        m.run_frame().unwrap();
+        assert_eq!(0x05, m.memory.peek(0x300).unwrap());
+
+        assert!(matches!(
+            m.freeze(0x100, 1),
+            Err(MemoryError::OutOfBoundsAccess(0x100))
+        ));
+    }
+
+    #[test]
+    fn test_engines() {
+        let rom = std::fs::read("./games/tetris.ch8").unwrap();
+        let mut interpreter = machine(&rom);
+        let mut others = [
+            machine(&rom).engine(Engine::Cached),
+            machine(&rom).engine(Engine::Table),
+        ];
+        for frame in 0..600 {
+            if frame % 50 == 0 {
+                interpreter.keypad_mut().set(5, frame % 100 == 0);
+                for other in &mut others {
+                    other.keypad_mut().set(5, frame % 100 == 0);
+                }
+            }
+            let result = interpreter.run_frame();
+            for other in &mut others {
+                assert_eq!(result, other.run_frame());
+            }
+        }
+        for other in &others {
+            assert_eq!(interpreter.register, other.register);
+            assert_eq!(interpreter.memory, other.memory);
+            assert_eq!(interpreter.display, other.display);
+        }
+    }
+
+    #[test]
+    fn test_self_modifying_code() {
+        #[rustfmt::skip]
+        let rom = [
+            0xA2, 0x0A, // 0x200: LD I, 0x20A
+            0x60, 0x61, // 0x202: LD V0, 0x61
+            0x61, 0x07, // 0x204: LD V1, 0x07
+            0xF1, 0x55, // 0x206: LD [I], V1
+            0x12, 0x0A, // 0x208: JP 0x20A
+            0x61, 0x00, // 0x20A: LD V1, 0x00, overwritten with LD V1, 0x07
+            0x12, 0x0C, // 0x20C: JP 0x20C
+        ];
+        let mut m = machine(&rom).engine(Engine::Cached);
+        // Decode the block at 0x20A before it is overwritten
+        m.register.pc = 0x20A;
+        m.step().unwrap();
+        m.register.pc = 0x200;
+        for _ in 0..6 {
+            m.step().unwrap();
+        }
+        assert_eq!(0x07, m.register.v[1]);
+    }
+}
@@ -10,6 +10,24 @@ pub enum MemoryError {
     OpenFile(#[source] io::Error),
     #[error("Out of bounds memory access at position {0}")]
     OutOfBoundsAccess(usize),
+    #[error("ROM is {0} bytes, but only {1} bytes are available")]
+    RomTooLarge(usize, usize),
+    #[error("ROM is more than the {0} bytes that are available")]
+    RomExceeds(usize),
+}
+
+impl MemoryError {
+    /// Report the actual size of a ROM read from a regular file, instead of only the space it
+    /// exceeds
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn with_metadata(self, metadata: &std::fs::Metadata) -> Self {
+        match self {
+            MemoryError::RomExceeds(available) if metadata.is_file() => {
+                MemoryError::RomTooLarge(metadata.len() as usize, available)
+            }
+            e => e,
+        }
+    }
 }
 
 /// The CHIP-8 language is capable of accessing up to 4Kb (4,096 bytes) of RAM, from location 0x000
@@ -23,11 +41,14 @@ pub enum MemoryError {
 /// The entire memory is accessible and byte addressable. As the instructions are 16bits long,
 /// their addresses are usually even (if some 8-bit data are inserted into the code, the
 /// instructions may become odd-addressed).
-#[derive(Debug)]
+///
+/// Programs are copied in with [`Memory::load`], or read with [`Memory::load_reader`].
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Memory {
     memory: Vec<u8>,
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl fmt::Display for Memory {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use hexyl::{BorderStyle, Printer};
@@ -42,45 +63,41 @@ impl fmt::Display for Memory {
     }
 }
 
-impl io::Write for Memory {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        // Make sure we write at most MEMORY_SIZE - MEMORY_START bytes
-        let buf_len = buf.len();
-        let writeable_size = Self::MEMORY_SIZE - Self::MEMORY_START;
-        let data_length = if buf_len < writeable_size {
-            buf_len
-        } else {
-            writeable_size
-        };
-
-        self.memory[Self::MEMORY_START..data_length].copy_from_slice(buf);
-        Ok(data_length)
-    }
-
-    fn flush(&mut self) -> io::Result<()> {
-        // NOOP, since this is just a Vec
+/// hexyl does not build for the web, so fall back to a plain hex dump
+#[cfg(target_arch = "wasm32")]
+impl fmt::Display for Memory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (n, line) in self.memory.chunks(16).enumerate() {
+            write!(f, "{:03X}:", n * 16)?;
+            for byte in line {
+                write!(f, " {:02X}", byte)?;
+            }
+            writeln!(f)?;
+        }
         Ok(())
     }
 }
 
-impl From<&[u8]> for Memory {
-    fn from(buf: &[u8]) -> Self {
+impl TryFrom<&[u8]> for Memory {
+    type Error = MemoryError;
+    fn try_from(buf: &[u8]) -> Result<Self, Self::Error> {
         let mut memory = Self::default();
-        memory.write(buf);
-        memory
+        memory.load(buf, Self::MEMORY_START as u16)?;
+        Ok(memory)
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl TryFrom<std::fs::File> for Memory {
     type Error = MemoryError;
-    fn try_from(mut f: std::fs::File) -> Result<Self, Self::Error> {
+    fn try_from(f: std::fs::File) -> Result<Self, Self::Error> {
         let mut memory = Self::default();
-        f.read(&mut memory.memory[Self::MEMORY_START..])
-            .map_err(MemoryError::LoadFile)?;
+        memory.load_file(f, Self::MEMORY_START as u16)?;
         Ok(memory)
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl TryFrom<&std::path::Path> for Memory {
     type Error = MemoryError;
     fn try_from(p: &std::path::Path) -> Result<Self, Self::Error> {
@@ -89,6 +106,9 @@ impl TryFrom<&std::path::Path> for Memory {
     }
 }
 
+/// Load the ROM at the path `s`. Not available on the web, where there is no filesystem: load the
+/// bytes of the ROM instead.
+#[cfg(not(target_arch = "wasm32"))]
 impl TryFrom<&str> for Memory {
     type Error = MemoryError;
     fn try_from(s: &str) -> Result<Self, Self::Error> {
@@ -97,6 +117,35 @@ impl TryFrom<&str> for Memory {
     }
 }
 
+/// Information about a ROM loaded into memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadInfo {
+    /// The address the ROM was loaded at
+    pub address: u16,
+    /// The size of the ROM in bytes
+    pub size: usize,
+    /// The SHA-1 of the ROM, as a lowercase hex string
+    pub sha1: String,
+    /// The CRC-32 of the ROM, as used by zip files and PNG images
+    pub crc32: u32,
+}
+
+/// The CRC-32 of `bytes`, computed a bit at a time: ROMs are small and hashed once, so a lookup
+/// table isn't worth it
+fn crc32(bytes: &[u8]) -> u32 {
+    const POLYNOMIAL: u32 = 0xEDB8_8320;
+
+    let mut crc = !0;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (POLYNOMIAL & mask);
+        }
+    }
+    !crc
+}
+
 impl Default for Memory {
     fn default() -> Self {
         Self::new()
@@ -104,8 +153,12 @@ impl Default for Memory {
 }
 
 impl Memory {
-    const MEMORY_SIZE: usize = 0x1000;
-    const MEMORY_START: usize = 0x200; // The first 512 bytes were reserved for the CHIP-8 interpreter
+    pub const MEMORY_SIZE: usize = 0x1000;
+    pub const MEMORY_START: usize = 0x200; // The first 512 bytes were reserved for the CHIP-8 interpreter
+    /// Programs for the ETI 660 start at 0x600
+    pub const ETI_660_START: usize = 0x600;
+    /// Programs for the hi-res CHIP-8 variant start at 0x2C0, after a 0x200 bootstrap
+    pub const HIRES_START: usize = 0x2C0;
 
     pub fn new() -> Self {
         Self {
@@ -113,6 +166,61 @@ impl Memory {
         }
     }
 
+    /// Copy `rom` into memory starting at `address`.
+    ///
+    /// Fails if `address` is inside the interpreter area, or if the ROM does not fit between
+    /// `address` and the end of memory.
+    pub fn load(&mut self, rom: &[u8], address: u16) -> Result<LoadInfo, MemoryError> {
+        let start = Self::check_idx(usize::from(address))?;
+        let available = Self::MEMORY_SIZE - start;
+        if rom.len() > available {
+            return Err(MemoryError::RomTooLarge(rom.len(), available));
+        }
+
+        self.memory[start..start + rom.len()].copy_from_slice(rom);
+        Ok(LoadInfo {
+            address,
+            size: rom.len(),
+            sha1: sha1::Sha1::from(rom).hexdigest(),
+            crc32: crc32(rom),
+        })
+    }
+
+    /// Read the whole of `reader` and load it into memory starting at `address`, see
+    /// `Memory::load`. Reading stops one byte past the available space, so a ROM that is too large
+    /// is reported as `MemoryError::RomExceeds`, without its actual size.
+    pub fn load_reader<R: Read>(
+        &mut self,
+        mut reader: R,
+        address: u16,
+    ) -> Result<LoadInfo, MemoryError> {
+        let start = Self::check_idx(usize::from(address))?;
+        let available = Self::MEMORY_SIZE - start;
+
+        // Read one byte past the available space, which is enough to tell the ROM is too large
+        // without reading an endless reader like a pipe or `/dev/zero`
+        let mut rom = Vec::with_capacity(available + 1);
+        reader
+            .by_ref()
+            .take(available as u64 + 1)
+            .read_to_end(&mut rom)
+            .map_err(MemoryError::LoadFile)?;
+        if rom.len() > available {
+            return Err(MemoryError::RomExceeds(available));
+        }
+
+        self.load(&rom, address)
+    }
+
+    /// Read the file `f` into memory starting at `address`, see `Memory::load_reader`. The size of
+    /// a regular file is known, so it is reported when the ROM is too large.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_file(&mut self, f: std::fs::File, address: u16) -> Result<LoadInfo, MemoryError> {
+        let metadata = f.metadata().map_err(MemoryError::LoadFile)?;
+        self.load_reader(f, address)
+            .map_err(|e| e.with_metadata(&metadata))
+    }
+
     #[inline]
     fn check_idx(idx: usize) -> Result<usize, MemoryError> {
         if !(Self::MEMORY_START..Self::MEMORY_SIZE).contains(&idx) {
@@ -134,6 +242,29 @@ impl Memory {
         Ok(unsafe { self.memory.get_unchecked_mut(idx) })
     }
 
+    /// Read the byte at `idx`, which may be in the interpreter area
+    pub fn peek(&self, idx: usize) -> Result<u8, MemoryError> {
+        self.memory
+            .get(idx)
+            .copied()
+            .ok_or(MemoryError::OutOfBoundsAccess(idx))
+    }
+
+    /// Write the byte at `idx`, which may be in the interpreter area
+    pub fn poke(&mut self, idx: usize, value: u8) -> Result<(), MemoryError> {
+        let byte = self
+            .memory
+            .get_mut(idx)
+            .ok_or(MemoryError::OutOfBoundsAccess(idx))?;
+        *byte = value;
+        Ok(())
+    }
+
+    /// The whole memory image, including the interpreter area
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.memory[..]
+    }
+
     pub fn dump(&self) {
         println!("{}", self)
     }
@@ -142,4 +273,96 @@ impl Memory {
 #[cfg(test)]
 mod tests {
     use crate::memory::*;
+
+    /// A reader returning a single byte per call to `read`
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match (self.0.split_first(), buf.first_mut()) {
+                (Some((byte, rest)), Some(out)) => {
+                    *out = *byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_load_file() {
+        let memory = Memory::try_from("./games/tetris.ch8").unwrap();
+        let rom = std::fs::read("./games/tetris.ch8").unwrap();
+        assert_eq!(&rom[..], &memory.as_bytes()[0x200..0x200 + rom.len()]);
+    }
+
+    #[test]
+    fn test_load_info() {
+        let mut memory = Memory::new();
+        let info = memory.load(b"abc", Memory::ETI_660_START as u16).unwrap();
+        assert_eq!(
+            LoadInfo {
+                address: 0x600,
+                size: 3,
+                sha1: "a9993e364706816aba3e25717850c26c9cd0d89d".to_string(),
+                crc32: 0x3524_41C2,
+            },
+            info
+        );
+        assert_eq!(b'a', *memory.get(0x600).unwrap());
+    }
+
+    #[test]
+    fn test_load_short_reads() {
+        let rom: Vec<u8> = (0..=255).collect();
+        let mut memory = Memory::new();
+        let info = memory
+            .load_reader(Trickle(&rom), Memory::HIRES_START as u16)
+            .unwrap();
+        assert_eq!(256, info.size);
+        assert_eq!(&rom[..], &memory.as_bytes()[0x2C0..0x3C0]);
+    }
+
+    #[test]
+    fn test_load_too_large() {
+        let rom = vec![0xFF; 0xE01];
+        match Memory::try_from(&rom[..]) {
+            Err(MemoryError::RomTooLarge(0xE01, 0xE00)) => (),
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        let mut memory = Memory::new();
+        match memory.load_reader(&rom[..], Memory::ETI_660_START as u16) {
+            Err(MemoryError::RomExceeds(0xA00)) => (),
+            r => panic!("unexpected result {:?}", r),
+        }
+        assert_eq!(Memory::new(), memory);
+
+        // Endless readers are not read to their end
+        match memory.load_reader(io::repeat(0xFF), Memory::MEMORY_START as u16) {
+            Err(MemoryError::RomExceeds(0xE00)) => (),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_load_into_interpreter_area() {
+        match Memory::new().load(&[0x00, 0xE0], 0x100) {
+            Err(MemoryError::OutOfBoundsAccess(0x100)) => (),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_peek_poke() {
+        let mut memory = Memory::new();
+        memory.poke(0x050, 0xF0).unwrap();
+        assert_eq!(0xF0, memory.peek(0x050).unwrap());
+        assert!(memory.get(0x050).is_err());
+        match memory.poke(0x1000, 0) {
+            Err(MemoryError::OutOfBoundsAccess(0x1000)) => (),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
 }
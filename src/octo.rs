@@ -0,0 +1,1107 @@
+//! An assembler for Octo, the high level assembly language most modern CHIP-8 programs are
+//! written in.
+//!
+//! The assembler follows the Octo compiler, so that it emits the same bytes for the same source.
+//! Tokens are separated by whitespace and `#` starts a comment. The supported statements are:
+//!
+//! | Statement                         | Effect                                                  |
+//! | --------------------------------- | ------------------------------------------------------- |
+//! | `: name`                          | Define a label at the current address                   |
+//! | `:next name`                      | Define a label at the operand of the next instruction   |
+//! | `:alias name vx`                  | Name a register                                         |
+//! | `:const name value`               | Name a value                                            |
+//! | `:calc name { expr }`             | Name the value of an expression, see below              |
+//! | `:macro name args... { body }`    | Define a macro, `name args...` then expands to the body |
+//! | `:org addr`                       | Continue assembling at `addr`                           |
+//! | `:byte value`, `value`            | Emit a byte                                             |
+//! | `:unpack nibble label`            | `v0 := nibble << 4 \| label >> 8 ; v1 := label`         |
+//! | `:call addr`, `label`             | Call a subroutine                                       |
+//! | `if cond then`                    | Execute the next statement only when `cond` holds       |
+//! | `if cond begin ... else ... end`  | Structured conditional, `else` is optional              |
+//! | `loop ... while cond ... again`   | Loop, leaving it when a `while` condition doesn't hold  |
+//!
+//! `:breakpoint name` and `:monitor what length` only matter to Octo's debugger and are
+//! ignored. XO-CHIP instructions and `:stringmode` are not supported.
+//!
+//! Along with these go Octo's names for the instructions, like `v0 := random 0xFF` or
+//! `sprite v0 v1 5`. The conditions are `vx == value`, `vx != value`, `vx key`, `vx -key` and the
+//! comparisons `<`, `>`, `<=` and `>=`, which clobber `vf`. Values are numbers, constants, labels
+//! or `{ expr }`.
+//!
+//! Expressions are made of numbers, constants, labels, `HERE`, `PI` and `E`, unary operators like
+//! `-`, `~` or `floor` and binary operators like `+`, `<<` or `min`. Just like in Octo, binary
+//! operators have no precedence and are evaluated right to left, so `2 * 3 + 1` is 8.
+//!
+//! A program starts at the `main` label. Unless `main` is the first thing in the program, the
+//! assembler puts a jump to it at 0x200.
+use crate::{
+    assembler::Assembly,
+    instructions::Instruction,
+    opcode::OpCode,
+    source_map::{Location, SourceMap},
+    symbols::{DataRegion, SymbolTable},
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::TryFrom,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum OctoError {
+    #[error("{0}: unexpected end of source")]
+    UnexpectedEnd(Location),
+    #[error("{0}: unexpected `{1}`")]
+    Unexpected(Location, String),
+    #[error("{0}: expected a register, found `{1}`")]
+    ExpectedRegister(Location, String),
+    #[error("{0}: expected a value, found `{1}`")]
+    ExpectedValue(Location, String),
+    #[error("{0}: value {1} is out of range")]
+    OutOfRange(Location, i64),
+    #[error("{0}: `{1}` is already defined")]
+    Redefined(Location, String),
+    #[error("{0}: undefined label `{1}`")]
+    UndefinedLabel(Location, String),
+    #[error("{0}: `{1}` without a matching `{2}`")]
+    Unbalanced(Location, String, &'static str),
+    #[error("The program has no `main` label")]
+    MissingMain,
+}
+
+/// Where programs are loaded
+const ORIGIN: u16 = 0x200;
+
+#[derive(Clone, Debug)]
+struct Token {
+    text: String,
+    line: u32,
+}
+
+#[derive(Clone, Debug)]
+struct Macro {
+    args: Vec<String>,
+    body: Vec<Token>,
+}
+
+/// A label used before its definition, patched once the program is assembled
+enum Fixup {
+    /// The address field of the instruction at `at`
+    Address { at: u16 },
+    /// The two instructions emitted by `:unpack` at `at`
+    Unpack { at: u16, nibble: u8 },
+}
+
+/// An open control flow block
+enum Block {
+    /// `if ... begin`, with the jump over its body
+    If { jump: u16, location: Location },
+    /// `else`, with the jump over its body
+    Else { jump: u16, location: Location },
+    /// `loop`, with the jumps out of it emitted by `while`
+    Loop {
+        start: u16,
+        whiles: Vec<u16>,
+        location: Location,
+    },
+}
+
+struct Compiler<'a> {
+    file: &'a str,
+    tokens: VecDeque<Token>,
+    /// The line of the last token taken, to report errors at the end of the source
+    line: u32,
+    here: u16,
+    bytes: Vec<u8>,
+    /// Whether the program needs a jump to `main` at the origin
+    jump_to_main: bool,
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<(String, Location, Fixup)>,
+    blocks: Vec<Block>,
+    /// The data bytes emitted since the last instruction or label, as a start address and length
+    data: Option<(u16, u16)>,
+    symbols: SymbolTable,
+    source_map: SourceMap,
+}
+
+/// Assemble the Octo `source`, read from `file`, into a program loaded at 0x200
+pub fn assemble(source: &str, file: &str) -> Result<Assembly, OctoError> {
+    let tokens = source
+        .lines()
+        .enumerate()
+        .flat_map(|(idx, line)| {
+            let code = line.split('#').next().unwrap_or_default();
+            code.split_whitespace().map(move |text| Token {
+                text: text.to_string(),
+                line: idx as u32 + 1,
+            })
+        })
+        .collect();
+
+    let mut compiler = Compiler {
+        file,
+        tokens,
+        line: 1,
+        // Leave room for the jump to `main`
+        here: ORIGIN + 2,
+        bytes: vec![0; 2],
+        jump_to_main: true,
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        macros: HashMap::new(),
+        fixups: Vec::new(),
+        blocks: Vec::new(),
+        data: None,
+        symbols: SymbolTable::new(),
+        source_map: SourceMap::new(),
+    };
+    while !compiler.tokens.is_empty() {
+        compiler.statement()?;
+    }
+    compiler.finish()
+}
+
+impl Compiler<'_> {
+    fn location(&self, line: u32) -> Location {
+        Location {
+            file: self.file.to_string(),
+            line,
+        }
+    }
+
+    fn next(&mut self) -> Result<Token, OctoError> {
+        match self.tokens.pop_front() {
+            Some(token) => {
+                self.line = token.line;
+                Ok(token)
+            }
+            None => Err(OctoError::UnexpectedEnd(self.location(self.line))),
+        }
+    }
+
+    fn peek(&self, offset: usize) -> Option<&str> {
+        self.tokens.get(offset).map(|token| token.text.as_str())
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), OctoError> {
+        let token = self.next()?;
+        if token.text == text {
+            Ok(())
+        } else {
+            Err(OctoError::Unexpected(self.location(token.line), token.text))
+        }
+    }
+
+    fn name(&mut self) -> Result<(String, Location), OctoError> {
+        let token = self.next()?;
+        let location = self.location(token.line);
+        if is_name(&token.text) && register(&token.text).is_none() {
+            Ok((token.text, location))
+        } else {
+            Err(OctoError::Unexpected(location, token.text))
+        }
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        self.labels.contains_key(name)
+            || self.constants.contains_key(name)
+            || self.aliases.contains_key(name)
+            || self.macros.contains_key(name)
+    }
+
+    fn define(&self, name: &str, location: &Location) -> Result<(), OctoError> {
+        if self.is_defined(name) {
+            Err(OctoError::Redefined(location.clone(), name.to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn register(&mut self) -> Result<u8, OctoError> {
+        let token = self.next()?;
+        self.register_of(&token)
+    }
+
+    fn register_of(&self, token: &Token) -> Result<u8, OctoError> {
+        register(&token.text)
+            .or_else(|| self.aliases.get(&token.text).copied())
+            .ok_or_else(|| {
+                OctoError::ExpectedRegister(self.location(token.line), token.text.clone())
+            })
+    }
+
+    fn is_register(&self, offset: usize) -> bool {
+        self.peek(offset)
+            .is_some_and(|t| register(t).is_some() || self.aliases.contains_key(t))
+    }
+
+    /// The value of a number, constant, label or `{ expr }`, if it is known
+    fn try_value(&mut self, token: &Token) -> Result<Option<f64>, OctoError> {
+        if token.text == "{" {
+            let value = self.expression()?;
+            self.expect("}")?;
+            return Ok(Some(value));
+        }
+        Ok(number(&token.text)
+            .or_else(|| self.constants.get(&token.text).copied())
+            .or_else(|| self.labels.get(&token.text).map(|&a| f64::from(a))))
+    }
+
+    fn value(&mut self) -> Result<i64, OctoError> {
+        let token = self.next()?;
+        match self.try_value(&token)? {
+            Some(value) => Ok(value as i64),
+            None => Err(OctoError::ExpectedValue(
+                self.location(token.line),
+                token.text,
+            )),
+        }
+    }
+
+    fn ranged(&mut self, min: i64, max: i64) -> Result<i64, OctoError> {
+        let value = self.value()?;
+        if (min..=max).contains(&value) {
+            Ok(value)
+        } else {
+            Err(OctoError::OutOfRange(self.location(self.line), value))
+        }
+    }
+
+    /// A byte, negative values being two's complement
+    fn byte(&mut self) -> Result<u8, OctoError> {
+        self.ranged(-128, 255).map(|v| v as u8)
+    }
+
+    fn nibble(&mut self) -> Result<u8, OctoError> {
+        self.ranged(0, 0xF).map(|v| v as u8)
+    }
+
+    /// A 12-bit address for the instruction about to be emitted, which may be a label defined
+    /// later on
+    fn address(&mut self, fixup: impl FnOnce(u16) -> Fixup) -> Result<u16, OctoError> {
+        let token = self.next()?;
+        let location = self.location(token.line);
+        match self.try_value(&token)? {
+            Some(value) if (0.0..=f64::from(0xFFF)).contains(&value) => Ok(value as u16),
+            Some(value) => Err(OctoError::OutOfRange(location, value as i64)),
+            None if is_name(&token.text) => {
+                self.fixups.push((token.text, location, fixup(self.here)));
+                Ok(0)
+            }
+            None => Err(OctoError::ExpectedValue(location, token.text)),
+        }
+    }
+
+    fn write(&mut self, byte: u8) -> Result<(), OctoError> {
+        let offset = usize::from(self.here.wrapping_sub(ORIGIN));
+        if self.here < ORIGIN {
+            return Err(OctoError::OutOfRange(
+                self.location(self.line),
+                i64::from(self.here),
+            ));
+        }
+        if self.bytes.len() <= offset {
+            self.bytes.resize(offset + 1, 0);
+        }
+        self.bytes[offset] = byte;
+        self.here = self.here.checked_add(1).ok_or_else(|| {
+            OctoError::OutOfRange(self.location(self.line), i64::from(self.here) + 1)
+        })?;
+        Ok(())
+    }
+
+    fn emit(&mut self, instruction: Instruction, line: u32) -> Result<(), OctoError> {
+        self.end_data();
+        self.source_map.insert(self.here, self.location(line));
+        let word = u16::from(OpCode::from(instruction));
+        for byte in word.to_be_bytes().iter() {
+            self.write(*byte)?;
+        }
+        Ok(())
+    }
+
+    fn emit_data(&mut self, byte: u8) -> Result<(), OctoError> {
+        self.data = match self.data {
+            Some((start, length)) if start + length == self.here => Some((start, length + 1)),
+            _ => {
+                self.end_data();
+                Some((self.here, 1))
+            }
+        };
+        self.write(byte)
+    }
+
+    fn end_data(&mut self) {
+        if let Some((start, length)) = self.data.take() {
+            self.symbols.insert_data(DataRegion {
+                start,
+                length,
+                name: None,
+            });
+        }
+    }
+
+    /// Overwrite the instruction at `at`
+    fn patch(&mut self, at: u16, instruction: Instruction) {
+        let word = u16::from(OpCode::from(instruction));
+        let offset = usize::from(at - ORIGIN);
+        self.bytes[offset..offset + 2].copy_from_slice(&word.to_be_bytes());
+    }
+
+    fn statement(&mut self) -> Result<(), OctoError> {
+        use Instruction::*;
+
+        let token = self.next()?;
+        let line = token.line;
+        let location = self.location(line);
+        match token.text.as_str() {
+            ":" => {
+                let (name, location) = self.name()?;
+                self.define(&name, &location)?;
+                if name == "main" && self.here == ORIGIN + 2 {
+                    // The program starts right away, no need to jump to it
+                    self.jump_to_main = false;
+                    self.bytes.clear();
+                    self.here = ORIGIN;
+                }
+                self.end_data();
+                self.symbols.insert_label(self.here, &name);
+                self.labels.insert(name, self.here);
+            }
+            ":next" => {
+                let (name, location) = self.name()?;
+                self.define(&name, &location)?;
+                self.end_data();
+                // The operand is the second byte of the instruction
+                let address = self.here.wrapping_add(1);
+                self.symbols.insert_label(address, &name);
+                self.labels.insert(name, address);
+            }
+            ":breakpoint" => {
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                // The length may be a format string, which can span several tokens
+                let mut format = self.next()?.text;
+                if format.starts_with('"') {
+                    while format.len() < 2 || !format.ends_with('"') {
+                        format = self.next()?.text;
+                    }
+                }
+            }
+            ":alias" => {
+                let (name, location) = self.name()?;
+                let register = self.register()?;
+                if self.labels.contains_key(&name) || self.constants.contains_key(&name) {
+                    return Err(OctoError::Redefined(location, name));
+                }
+                self.aliases.insert(name, register);
+            }
+            ":const" | ":calc" => {
+                let (name, location) = self.name()?;
+                self.define(&name, &location)?;
+                let value = if token.text == ":calc" {
+                    self.expect("{")?;
+                    let value = self.expression()?;
+                    self.expect("}")?;
+                    value
+                } else {
+                    self.value()? as f64
+                };
+                self.constants.insert(name, value);
+            }
+            ":macro" => {
+                let (name, location) = self.name()?;
+                self.define(&name, &location)?;
+                let mut args = Vec::new();
+                loop {
+                    let token = self.next()?;
+                    if token.text == "{" {
+                        break;
+                    }
+                    args.push(token.text);
+                }
+                let mut body = Vec::new();
+                let mut depth = 0;
+                loop {
+                    let token = self.next()?;
+                    match token.text.as_str() {
+                        "{" => depth += 1,
+                        "}" if depth == 0 => break,
+                        "}" => depth -= 1,
+                        _ => (),
+                    }
+                    body.push(token);
+                }
+                self.macros.insert(name, Macro { args, body });
+            }
+            ":org" => {
+                let address = self.ranged(i64::from(ORIGIN), 0xFFFF)?;
+                self.end_data();
+                self.here = address as u16;
+            }
+            ":byte" => {
+                let byte = self.byte()?;
+                self.emit_data(byte)?;
+            }
+            ":unpack" => {
+                let nibble = self.nibble()?;
+                let address = self.address(|at| Fixup::Unpack { at, nibble })?;
+                let (hi, lo) = unpack(nibble, address);
+                self.emit(LoadImmediate(0, hi), line)?;
+                self.emit(LoadImmediate(1, lo), line)?;
+            }
+            ":call" => {
+                let address = self.address(|at| Fixup::Address { at })?;
+                self.emit(Call(address), line)?;
+            }
+            ";" | "return" => self.emit(Return, line)?,
+            "clear" => self.emit(ClearScreen, line)?,
+            "exit" => self.emit(Exit, line)?,
+            "lores" => self.emit(LowRes, line)?,
+            "hires" => self.emit(HighRes, line)?,
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit(ScrollDown(n), line)?;
+            }
+            "scroll-right" => self.emit(ScrollRight, line)?,
+            "scroll-left" => self.emit(ScrollLeft, line)?,
+            "jump" => {
+                let address = self.address(|at| Fixup::Address { at })?;
+                self.emit(Jump(address), line)?;
+            }
+            "jump0" => {
+                let address = self.address(|at| Fixup::Address { at })?;
+                self.emit(JumpImmediate(address), line)?;
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.emit(Draw(x, y, n), line)?;
+            }
+            "bcd" | "save" | "load" | "saveflags" | "loadflags" => {
+                let x = self.register()?;
+                let instruction = match token.text.as_str() {
+                    "bcd" => LoadBCDIntoI(x),
+                    "save" => LoadVIntoMem(x),
+                    "load" => LoadMemIntoV(x),
+                    "saveflags" => LoadVIntoFlags(x),
+                    _ => LoadFlagsIntoV(x),
+                };
+                self.emit(instruction, line)?;
+            }
+            "i" => {
+                let op = self.next()?;
+                match op.text.as_str() {
+                    ":=" if self.peek(0) == Some("hex") => {
+                        self.next()?;
+                        let x = self.register()?;
+                        self.emit(LoadSpriteIntoI(x), line)?;
+                    }
+                    ":=" => {
+                        let address = self.address(|at| Fixup::Address { at })?;
+                        self.emit(LoadI(address), line)?;
+                    }
+                    "+=" => {
+                        let x = self.register()?;
+                        self.emit(AddI(x), line)?;
+                    }
+                    _ => return Err(OctoError::Unexpected(self.location(op.line), op.text)),
+                }
+            }
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                let instruction = if token.text == "delay" {
+                    LoadVIntoDT(x)
+                } else {
+                    LoadVIntoST(x)
+                };
+                self.emit(instruction, line)?;
+            }
+            "if" => {
+                // The condition is `vx key`, `vx -key` or `vx op value`
+                let end = match self.peek(1) {
+                    Some("key") | Some("-key") => 2,
+                    _ => 3,
+                };
+                match self.peek(end) {
+                    Some("then") => {
+                        self.condition(false, line)?;
+                        self.next()?;
+                    }
+                    Some("begin") => {
+                        self.condition(true, line)?;
+                        self.next()?;
+                        self.blocks.push(Block::If {
+                            jump: self.here,
+                            location,
+                        });
+                        self.emit(Jump(0), line)?;
+                    }
+                    Some(_) => {
+                        let token = self.tokens[end].clone();
+                        return Err(OctoError::Unexpected(self.location(token.line), token.text));
+                    }
+                    None => return Err(OctoError::UnexpectedEnd(location)),
+                }
+            }
+            "else" => match self.blocks.pop() {
+                Some(Block::If { jump, .. }) => {
+                    self.blocks.push(Block::Else {
+                        jump: self.here,
+                        location,
+                    });
+                    self.emit(Jump(0), line)?;
+                    self.patch(jump, Jump(self.here));
+                }
+                _ => return Err(OctoError::Unbalanced(location, token.text, "if ... begin")),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump, .. }) | Some(Block::Else { jump, .. }) => {
+                    self.patch(jump, Jump(self.here));
+                }
+                _ => return Err(OctoError::Unbalanced(location, token.text, "begin")),
+            },
+            "loop" => self.blocks.push(Block::Loop {
+                start: self.here,
+                whiles: Vec::new(),
+                location,
+            }),
+            "while" => {
+                self.condition(true, line)?;
+                let at = self.here;
+                match self.blocks.iter_mut().rev().find_map(|block| match block {
+                    Block::Loop { whiles, .. } => Some(whiles),
+                    _ => None,
+                }) {
+                    Some(whiles) => whiles.push(at),
+                    None => return Err(OctoError::Unbalanced(location, token.text, "loop")),
+                }
+                self.emit(Jump(0), line)?;
+            }
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop { start, whiles, .. }) => {
+                    self.emit(Jump(start), line)?;
+                    for at in whiles {
+                        self.patch(at, Jump(self.here));
+                    }
+                }
+                _ => return Err(OctoError::Unbalanced(location, token.text, "loop")),
+            },
+            text if self.macros.contains_key(text) => {
+                let Macro { args, body } = self.macros[text].clone();
+                let mut values = HashMap::new();
+                for arg in args {
+                    values.insert(arg, self.next()?.text);
+                }
+                for token in body.into_iter().rev() {
+                    let text = values.get(&token.text).cloned().unwrap_or(token.text);
+                    self.tokens.push_front(Token { text, line });
+                }
+            }
+            _ if register(&token.text).is_some() || self.aliases.contains_key(&token.text) => {
+                let x = self.register_of(&token)?;
+                self.assignment(x, line)?;
+            }
+            "{" => {
+                let value = self.expression()?;
+                self.expect("}")?;
+                let value = value as i64;
+                if !(-128..=255).contains(&value) {
+                    return Err(OctoError::OutOfRange(location, value));
+                }
+                self.emit_data(value as u8)?;
+            }
+            text => match number(text).or_else(|| self.constants.get(text).copied()) {
+                Some(value) => {
+                    let value = value as i64;
+                    if !(-128..=255).contains(&value) {
+                        return Err(OctoError::OutOfRange(location, value));
+                    }
+                    self.emit_data(value as u8)?;
+                }
+                // Anything else calls a subroutine
+                None if is_name(text) => {
+                    let address = match self.labels.get(text) {
+                        Some(&address) => address,
+                        None => {
+                            let at = self.here;
+                            self.fixups
+                                .push((token.text.clone(), location, Fixup::Address { at }));
+                            0
+                        }
+                    };
+                    self.emit(Call(address), line)?;
+                }
+                None => return Err(OctoError::Unexpected(location, token.text)),
+            },
+        }
+        Ok(())
+    }
+
+    /// `vx op ...`
+    fn assignment(&mut self, x: u8, line: u32) -> Result<(), OctoError> {
+        use Instruction::*;
+
+        let op = self.next()?;
+        let rhs_is_register = self.is_register(0);
+        let instruction = match op.text.as_str() {
+            ":=" if rhs_is_register => Load(x, self.register()?),
+            ":=" => match self.peek(0) {
+                Some("random") => {
+                    self.next()?;
+                    Random(x, self.byte()?)
+                }
+                Some("key") => {
+                    self.next()?;
+                    LoadKey(x)
+                }
+                Some("delay") => {
+                    self.next()?;
+                    LoadDTIntoV(x)
+                }
+                _ => LoadImmediate(x, self.byte()?),
+            },
+            "+=" if rhs_is_register => Add(x, self.register()?),
+            "+=" => AddImmediate(x, self.byte()?),
+            "-=" if rhs_is_register => Sub(x, self.register()?),
+            "-=" => AddImmediate(x, self.byte()?.wrapping_neg()),
+            "=-" => SubNumeric(x, self.register()?),
+            "|=" => Or(x, self.register()?),
+            "&=" => And(x, self.register()?),
+            "^=" => Xor(x, self.register()?),
+            ">>=" => ShiftRight(x, self.register()?),
+            "<<=" => ShiftLeft(x, self.register()?),
+            _ => return Err(OctoError::Unexpected(self.location(op.line), op.text)),
+        };
+        self.emit(instruction, line)
+    }
+
+    /// Emit the instructions skipping the next one unless the condition holds, or when it holds
+    /// if `negated`
+    fn condition(&mut self, negated: bool, line: u32) -> Result<(), OctoError> {
+        use Instruction::*;
+
+        let x = self.register()?;
+        let op = self.next()?;
+        let mut op_text = op.text.as_str();
+        if negated {
+            op_text = match op_text {
+                "==" => "!=",
+                "!=" => "==",
+                "key" => "-key",
+                "-key" => "key",
+                "<" => ">=",
+                ">" => "<=",
+                ">=" => "<",
+                "<=" => ">",
+                other => other,
+            };
+        }
+        match op_text {
+            "key" => return self.emit(SkipNotOnKey(x), line),
+            "-key" => return self.emit(SkipOnKey(x), line),
+            _ => (),
+        }
+
+        let rhs_is_register = self.is_register(0);
+        let instruction = match op_text {
+            "==" if rhs_is_register => SkipNotEqual(x, self.register()?),
+            "==" => SkipNotEqualImmediate(x, self.byte()?),
+            "!=" if rhs_is_register => SkipEqual(x, self.register()?),
+            "!=" => SkipEqualImmediate(x, self.byte()?),
+            "<" | ">" | "<=" | ">=" => {
+                // Compare through vf: load the right hand side into it and subtract, leaving
+                // whether there was no borrow in vf
+                if rhs_is_register {
+                    let y = self.register()?;
+                    self.emit(Load(0xF, y), line)?;
+                } else {
+                    let kk = self.byte()?;
+                    self.emit(LoadImmediate(0xF, kk), line)?;
+                }
+                let subtract = match op_text {
+                    ">" | "<=" => Sub(0xF, x),
+                    _ => SubNumeric(0xF, x),
+                };
+                self.emit(subtract, line)?;
+                match op_text {
+                    ">" | "<" => SkipEqualImmediate(0xF, 1),
+                    _ => SkipNotEqualImmediate(0xF, 1),
+                }
+            }
+            _ => return Err(OctoError::Unexpected(self.location(op.line), op.text)),
+        };
+        self.emit(instruction, line)
+    }
+
+    /// An expression, up to a closing `}` or `)` which is left in place. Binary operators are
+    /// right associative and have no precedence.
+    fn expression(&mut self) -> Result<f64, OctoError> {
+        let lhs = self.term()?;
+        match self.peek(0) {
+            Some("}") | Some(")") | None => return Ok(lhs),
+            _ => (),
+        }
+        let op = self.next()?;
+        let rhs = self.expression()?;
+        let (a, b) = (lhs as i64, rhs as i64);
+        let value = match op.text.as_str() {
+            "+" => lhs + rhs,
+            "-" => lhs - rhs,
+            "*" => lhs * rhs,
+            "/" => lhs / rhs,
+            "%" => lhs % rhs,
+            "pow" => lhs.powf(rhs),
+            "min" => lhs.min(rhs),
+            "max" => lhs.max(rhs),
+            "&" => (a & b) as f64,
+            "|" => (a | b) as f64,
+            "^" => (a ^ b) as f64,
+            "<<" => a.checked_shl(b as u32).unwrap_or(0) as f64,
+            ">>" => a.checked_shr(b as u32).unwrap_or(0) as f64,
+            "<" => f64::from(u8::from(lhs < rhs)),
+            ">" => f64::from(u8::from(lhs > rhs)),
+            "<=" => f64::from(u8::from(lhs <= rhs)),
+            ">=" => f64::from(u8::from(lhs >= rhs)),
+            "==" => f64::from(u8::from(lhs == rhs)),
+            "!=" => f64::from(u8::from(lhs != rhs)),
+            _ => return Err(OctoError::Unexpected(self.location(op.line), op.text)),
+        };
+        Ok(value)
+    }
+
+    fn term(&mut self) -> Result<f64, OctoError> {
+        let token = self.next()?;
+        let location = self.location(token.line);
+        let value = match token.text.as_str() {
+            "(" => {
+                let value = self.expression()?;
+                self.expect(")")?;
+                value
+            }
+            "-" => -self.term()?,
+            "~" => !(self.term()? as i64) as f64,
+            "!" => f64::from(u8::from(self.term()? == 0.0)),
+            "abs" => self.term()?.abs(),
+            "sqrt" => self.term()?.sqrt(),
+            "sin" => self.term()?.sin(),
+            "cos" => self.term()?.cos(),
+            "tan" => self.term()?.tan(),
+            "exp" => self.term()?.exp(),
+            "log" => self.term()?.ln(),
+            "sign" => self.term()?.signum(),
+            "ceil" => self.term()?.ceil(),
+            "floor" => self.term()?.floor(),
+            "@" => {
+                let address = self.term()? as i64;
+                let offset = address - i64::from(ORIGIN);
+                match usize::try_from(offset).ok().and_then(|o| self.bytes.get(o)) {
+                    Some(&byte) => f64::from(byte),
+                    None => return Err(OctoError::OutOfRange(location, address)),
+                }
+            }
+            "HERE" => f64::from(self.here),
+            "PI" => std::f64::consts::PI,
+            "E" => std::f64::consts::E,
+            text => match number(text)
+                .or_else(|| self.constants.get(text).copied())
+                .or_else(|| self.labels.get(text).map(|&a| f64::from(a)))
+            {
+                Some(value) => value,
+                None => return Err(OctoError::ExpectedValue(location, token.text)),
+            },
+        };
+        Ok(value)
+    }
+
+    /// Check everything was closed, resolve the labels used before their definition and put in
+    /// the jump to `main`
+    fn finish(mut self) -> Result<Assembly, OctoError> {
+        use Instruction::*;
+
+        self.end_data();
+        if let Some(block) = self.blocks.pop() {
+            let (location, token, expected) = match block {
+                Block::If { location, .. } => (location, "if ... begin", "end"),
+                Block::Else { location, .. } => (location, "else", "end"),
+                Block::Loop { location, .. } => (location, "loop", "again"),
+            };
+            return Err(OctoError::Unbalanced(location, token.to_string(), expected));
+        }
+
+        for (name, location, fixup) in std::mem::take(&mut self.fixups) {
+            let address = match self.labels.get(&name) {
+                Some(&address) => address,
+                None => return Err(OctoError::UndefinedLabel(location, name)),
+            };
+            match fixup {
+                Fixup::Address { at } => {
+                    let offset = usize::from(at - ORIGIN);
+                    let word = u16::from_be_bytes([self.bytes[offset], self.bytes[offset + 1]]);
+                    if address > 0xFFF {
+                        return Err(OctoError::OutOfRange(location, i64::from(address)));
+                    }
+                    let word = word & 0xF000 | address;
+                    self.bytes[offset..offset + 2].copy_from_slice(&word.to_be_bytes());
+                }
+                Fixup::Unpack { at, nibble } => {
+                    let (hi, lo) = unpack(nibble, address);
+                    self.patch(at, LoadImmediate(0, hi));
+                    self.patch(at + 2, LoadImmediate(1, lo));
+                }
+            }
+        }
+
+        let main = *self.labels.get("main").ok_or(OctoError::MissingMain)?;
+        if self.jump_to_main {
+            self.patch(ORIGIN, Jump(main));
+        }
+
+        Ok(Assembly {
+            origin: ORIGIN,
+            bytes: self.bytes,
+            symbols: self.symbols,
+            source_map: self.source_map,
+        })
+    }
+}
+
+/// The bytes loaded into v0 and v1 by `:unpack`
+fn unpack(nibble: u8, address: u16) -> (u8, u8) {
+    (nibble << 4 | (address >> 8) as u8 & 0xF, address as u8)
+}
+
+fn register(s: &str) -> Option<u8> {
+    let s = s.strip_prefix('v').or_else(|| s.strip_prefix('V'))?;
+    if s.len() == 1 {
+        u8::from_str_radix(s, 16).ok()
+    } else {
+        None
+    }
+}
+
+fn number(s: &str) -> Option<f64> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, s),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()? as f64
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2).ok()? as f64
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn is_name(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        octo::{assemble, OctoError},
+        source_map::Location,
+    };
+
+    fn bytes(source: &str) -> Vec<u8> {
+        assemble(source, "test.8o").unwrap().bytes
+    }
+
+    #[test]
+    fn test_instructions() {
+        let source = "
+            : main
+                clear
+                v0 := 5  v1 := v0  v2 := random 0x0F  v3 := key  v4 := delay
+                v0 += 1  v0 += v1  v0 -= 1  v0 -= v1  v0 =- v1
+                v0 |= v1  v0 &= v1  v0 ^= v1  v0 >>= v1  v0 <<= v1
+                i := 0x300  i := hex v5  i += v6
+                delay := v7  buzzer := v8
+                sprite v0 v1 5  bcd v9  save va  load vb  saveflags vc  loadflags vd
+                hires lores scroll-down 4 scroll-right scroll-left
+                jump0 0x400 ; return exit
+        ";
+        #[rustfmt::skip]
+        assert_eq!(
+            vec![
+                0x00, 0xE0,
+                0x60, 0x05, 0x81, 0x00, 0xC2, 0x0F, 0xF3, 0x0A, 0xF4, 0x07,
+                0x70, 0x01, 0x80, 0x14, 0x70, 0xFF, 0x80, 0x15, 0x80, 0x17,
+                0x80, 0x11, 0x80, 0x12, 0x80, 0x13, 0x80, 0x16, 0x80, 0x1E,
+                0xA3, 0x00, 0xF5, 0x29, 0xF6, 0x1E,
+                0xF7, 0x15, 0xF8, 0x18,
+                0xD0, 0x15, 0xF9, 0x33, 0xFA, 0x55, 0xFB, 0x65, 0xFC, 0x75, 0xFD, 0x85,
+                0x00, 0xFF, 0x00, 0xFE, 0x00, 0xC4, 0x00, 0xFB, 0x00, 0xFC,
+                0xB4, 0x00, 0x00, 0xEE, 0x00, 0xEE, 0x00, 0xFD,
+            ],
+            bytes(source)
+        );
+    }
+
+    #[test]
+    fn test_labels() {
+        // `main` isn't first, so the program starts with a jump to it
+        let source = "
+            : sub
+                v0 := 1
+                return
+            : main
+                sub
+                :unpack 0xA sub
+                i := data
+                sprite v0 v1 5
+                loop again
+            : data
+                0xF0 0x90 0xF0 0x90 0xF0
+        ";
+        let assembly = assemble(source, "test.8o").unwrap();
+        #[rustfmt::skip]
+        assert_eq!(
+            vec![
+                0x12, 0x06,
+                0x60, 0x01, 0x00, 0xEE,
+                0x22, 0x02, 0x60, 0xA2, 0x61, 0x02, 0xA2, 0x12, 0xD0, 0x15, 0x12, 0x10,
+                0xF0, 0x90, 0xF0, 0x90, 0xF0,
+            ],
+            assembly.bytes
+        );
+        assert_eq!(Some(0x206), assembly.symbols.address("main"));
+        assert_eq!(Some(0x212), assembly.symbols.address("data"));
+        assert_eq!(5, assembly.symbols.data(0x212).unwrap().length);
+        assert_eq!(
+            Some(&Location {
+                file: "test.8o".to_string(),
+                line: 9
+            }),
+            assembly.source_map.get(0x20E)
+        );
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let source = "
+            : main
+                v0 := 0
+                loop
+                    v0 += 1
+                    if v0 == 10 then v1 := 2
+                    while v0 != 20
+                again
+                if v0 == v1 begin
+                    v2 := 1
+                else
+                    v2 := 2
+                end
+                if v3 key begin v4 := 1 end
+                if v1 > 5 then v2 := 1
+                if v1 <= v2 then v2 := 1
+                exit
+        ";
+        #[rustfmt::skip]
+        assert_eq!(
+            vec![
+                0x60, 0x00,
+                // loop
+                0x70, 0x01, 0x40, 0x0A, 0x61, 0x02, 0x40, 0x14, 0x12, 0x0E, 0x12, 0x02,
+                // if ... else ... end
+                0x50, 0x10, 0x12, 0x16, 0x62, 0x01, 0x12, 0x18, 0x62, 0x02,
+                // if ... key begin ... end
+                0xE3, 0x9E, 0x12, 0x1E, 0x64, 0x01,
+                // comparisons through vf
+                0x6F, 0x05, 0x8F, 0x15, 0x3F, 0x01, 0x62, 0x01,
+                0x8F, 0x20, 0x8F, 0x15, 0x4F, 0x01, 0x62, 0x01,
+                0x00, 0xFD,
+            ],
+            bytes(source)
+        );
+    }
+
+    #[test]
+    fn test_definitions() {
+        let source = "
+            :alias x v3
+            :const SPEED 4
+            :calc DOUBLE { SPEED * 2 + 1 }
+            :macro move reg amount { reg += amount }
+            : main
+                move x SPEED
+                x := DOUBLE
+                x := { ( SPEED * 2 ) + 1 }
+                i := hex x
+                :org 0x210
+                0xFF
+        ";
+        let mut expected = vec![0x73, 0x04, 0x63, 0x0C, 0x63, 0x09, 0xF3, 0x29];
+        expected.resize(0x10, 0);
+        expected.push(0xFF);
+        assert_eq!(expected, bytes(source));
+    }
+
+    #[test]
+    fn test_debugger_directives() {
+        let source = r#"
+            : main
+                :breakpoint start
+                :monitor v0 4
+                :monitor score "%i points"
+                :next target
+                v0 := 0
+                i := target
+            : score
+        "#;
+        let assembly = assemble(source, "test.8o").unwrap();
+        assert_eq!(vec![0x60, 0x00, 0xA2, 0x01], assembly.bytes);
+        assert_eq!(Some(0x201), assembly.symbols.address("target"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let location = |line| Location {
+            file: "test.8o".to_string(),
+            line,
+        };
+        assert_eq!(Err(OctoError::MissingMain), assemble("clear", "test.8o"));
+        assert_eq!(
+            Err(OctoError::UndefinedLabel(location(1), "nowhere".into())),
+            assemble(": main jump nowhere", "test.8o")
+        );
+        assert_eq!(
+            Err(OctoError::Redefined(location(2), "main".into())),
+            assemble(": main\n: main", "test.8o")
+        );
+        assert_eq!(
+            Err(OctoError::OutOfRange(location(1), 256)),
+            assemble(": main v0 := 256", "test.8o")
+        );
+        assert_eq!(
+            Err(OctoError::ExpectedRegister(location(1), "i".into())),
+            assemble(": main sprite i v0 1", "test.8o")
+        );
+        assert_eq!(
+            Err(OctoError::Unbalanced(location(2), "loop".into(), "again")),
+            assemble(": main\nloop\nv0 += 1", "test.8o")
+        );
+        assert_eq!(
+            Err(OctoError::Unbalanced(location(1), "end".into(), "begin")),
+            assemble(": main end", "test.8o")
+        );
+        assert_eq!(
+            Err(OctoError::UnexpectedEnd(location(1))),
+            assemble(": main v0 :=", "test.8o")
+        );
+    }
+}
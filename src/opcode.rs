@@ -14,6 +14,14 @@ impl fmt::Debug for OpCode {
     }
 }
 
+impl From<OpCode> for u16 {
+    fn from(opcode: OpCode) -> Self {
+        opcode.0
+    }
+}
+
+/// Bit level access to the opcode, bit 0 being the least significant. The shift and mask
+/// accessors, like `OpCode::x`, are much faster and should be preferred.
 impl Index<Range<usize>> for OpCode {
     type Output = BitSlice<Lsb0, u16>;
     #[inline]
@@ -29,78 +37,85 @@ impl IndexMut<Range<usize>> for OpCode {
     }
 }
 
-macro_rules! opcode {
-    ($a:expr) => {{
-        let mut opcode = OpCode::default();
-        opcode[0..16].store($a as u16);
-        opcode
-    }};
-}
-
 impl OpCode {
     pub fn new(opcode: u16) -> Self {
         OpCode(opcode)
     }
 
+    /// The highest nibble, which selects the kind of instruction
+    #[inline]
+    pub fn o(self) -> u8 {
+        (self.0 >> 12) as u8
+    }
+
+    /// The second nibble, usually the first register operand
+    #[inline]
+    pub fn x(self) -> u8 {
+        (self.0 >> 8 & 0xF) as u8
+    }
+
+    /// The third nibble, usually the second register operand
+    #[inline]
+    pub fn y(self) -> u8 {
+        (self.0 >> 4 & 0xF) as u8
+    }
+
+    /// The lowest nibble
+    #[inline]
+    pub fn n(self) -> u8 {
+        (self.0 & 0xF) as u8
+    }
+
+    /// The lowest byte
+    #[inline]
+    pub fn kk(self) -> u8 {
+        (self.0 & 0xFF) as u8
+    }
+
+    /// The lowest 12 bits, usually an address
+    #[inline]
+    pub fn nnn(self) -> u16 {
+        self.0 & 0xFFF
+    }
+
     #[inline]
     pub fn oooo(oooo: u16) -> Self {
-        let mut opcode = Self::default();
-        opcode[0..16].store(oooo);
-        opcode
+        OpCode(oooo)
     }
 
     #[inline]
     pub fn oook(ooo: u16, k: u8) -> Self {
-        let mut opcode = Self::default();
-        opcode[4..16].store(ooo);
-        opcode[0..4].store(k);
-        opcode
+        OpCode((ooo & 0xFFF) << 4 | u16::from(k & 0xF))
     }
 
     #[inline]
     pub fn okkk(o: u8, kkk: u16) -> Self {
-        let mut opcode = Self::default();
-        opcode[12..16].store(o);
-        opcode[0..12].store(kkk);
-        opcode
+        OpCode(u16::from(o & 0xF) << 12 | kkk & 0xFFF)
     }
 
     #[inline]
     pub fn oxoo(o: u8, x: u8, oo: u8) -> Self {
-        let mut opcode = Self::default();
-        opcode[12..16].store(o);
-        opcode[8..12].store(x);
-        opcode[0..8].store(oo);
-        opcode
+        Self::oxkk(o, x, oo)
     }
 
     #[inline]
     pub fn oxkk(o: u8, x: u8, kk: u8) -> Self {
-        let mut opcode = Self::default();
-        opcode[12..16].store(o);
-        opcode[8..12].store(x);
-        opcode[0..8].store(kk);
-        opcode
+        OpCode(u16::from(o & 0xF) << 12 | u16::from(x & 0xF) << 8 | u16::from(kk))
     }
 
     #[inline]
     pub fn oxyo(om: u8, x: u8, y: u8, ol: u8) -> Self {
-        let mut opcode = Self::default();
-        opcode[12..16].store(om);
-        opcode[8..12].store(x);
-        opcode[4..8].store(y);
-        opcode[0..4].store(ol);
-        opcode
+        Self::oxyk(om, x, y, ol)
     }
 
     #[inline]
     pub fn oxyk(o: u8, x: u8, y: u8, k: u8) -> Self {
-        let mut opcode = Self::default();
-        opcode[12..16].store(o);
-        opcode[8..12].store(x);
-        opcode[4..8].store(y);
-        opcode[0..4].store(k);
-        opcode
+        OpCode(
+            u16::from(o & 0xF) << 12
+                | u16::from(x & 0xF) << 8
+                | u16::from(y & 0xF) << 4
+                | u16::from(k & 0xF),
+        )
     }
 }
 
@@ -109,7 +124,7 @@ impl From<Instruction> for OpCode {
         use Instruction::*;
         match i {
             ScrollDown(k) => OpCode::oook(0x00C, k),
-            ScrollRight => opcode!(0x00FB),
+            ScrollRight => OpCode::oooo(0x00FB),
             ScrollLeft => OpCode::oooo(0x00FC),
             Exit => OpCode::oooo(0x00FD),
             LowRes => OpCode::oooo(0x00FE),
@@ -148,13 +163,21 @@ impl From<Instruction> for OpCode {
             LoadBCDIntoI(vx) => OpCode::oxoo(0xF, vx, 0x33),
             LoadVIntoMem(vx) => OpCode::oxoo(0xF, vx, 0x55),
             LoadMemIntoV(vx) => OpCode::oxoo(0xF, vx, 0x65),
+            LoadVIntoFlags(vx) => OpCode::oxoo(0xF, vx, 0x75),
+            LoadFlagsIntoV(vx) => OpCode::oxoo(0xF, vx, 0x85),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{instructions::Instruction::*, opcode::OpCode};
+    use crate::{
+        instructions::{Instruction, Instruction::*},
+        opcode::OpCode,
+    };
+    use bitvec::prelude::*;
+    use proptest::prelude::*;
+    use std::convert::TryFrom;
 
     macro_rules! test_int {
         ($int:expr, $val:expr) => {
@@ -165,6 +188,27 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_fields() {
+        let op = OpCode::new(0xDABC);
+        assert_eq!(0xD, op.o());
+        assert_eq!(0xA, op.x());
+        assert_eq!(0xB, op.y());
+        assert_eq!(0xC, op.n());
+        assert_eq!(0xBC, op.kk());
+        assert_eq!(0xABC, op.nnn());
+
+        // The accessors agree with the bit level indexing
+        for op in (0..=u16::MAX).step_by(7).map(OpCode::new) {
+            assert_eq!(op[12..16].load::<u8>(), op.o());
+            assert_eq!(op[8..12].load::<u8>(), op.x());
+            assert_eq!(op[4..8].load::<u8>(), op.y());
+            assert_eq!(op[0..4].load::<u8>(), op.n());
+            assert_eq!(op[0..8].load::<u8>(), op.kk());
+            assert_eq!(op[0..12].load::<u16>(), op.nnn());
+        }
+    }
+
     #[test]
     fn test_scroll_down() {
         test_int!(ScrollDown(0xA), 0x00CA);
@@ -364,4 +408,81 @@ mod tests {
     fn test_load_mem_into_v() {
         test_int!(LoadMemIntoV(0x0A), 0xFA65);
     }
+
+    #[test]
+    fn test_load_v_into_flags() {
+        test_int!(LoadVIntoFlags(0x07), 0xF775);
+    }
+
+    #[test]
+    fn test_load_flags_into_v() {
+        test_int!(LoadFlagsIntoV(0x07), 0xF785);
+    }
+
+    /// Any instruction, with every operand in the range its field can hold
+    fn instruction() -> impl Strategy<Value = Instruction> {
+        let x = || 0..0x10u8;
+        let addr = || 0..0x1000u16;
+        let kk = any::<u8>;
+        prop_oneof![
+            x().prop_map(ScrollDown),
+            Just(ScrollRight),
+            Just(ScrollLeft),
+            Just(Exit),
+            Just(LowRes),
+            Just(HighRes),
+            Just(ClearScreen),
+            Just(Return),
+            addr().prop_map(Jump),
+            addr().prop_map(Call),
+            (x(), kk()).prop_map(|(x, kk)| SkipEqualImmediate(x, kk)),
+            (x(), kk()).prop_map(|(x, kk)| SkipNotEqualImmediate(x, kk)),
+            (x(), x()).prop_map(|(x, y)| SkipEqual(x, y)),
+            (x(), kk()).prop_map(|(x, kk)| LoadImmediate(x, kk)),
+            (x(), kk()).prop_map(|(x, kk)| AddImmediate(x, kk)),
+            (x(), x()).prop_map(|(x, y)| Load(x, y)),
+            (x(), x()).prop_map(|(x, y)| Or(x, y)),
+            (x(), x()).prop_map(|(x, y)| And(x, y)),
+            (x(), x()).prop_map(|(x, y)| Xor(x, y)),
+            (x(), x()).prop_map(|(x, y)| Add(x, y)),
+            (x(), x()).prop_map(|(x, y)| Sub(x, y)),
+            (x(), x()).prop_map(|(x, y)| ShiftRight(x, y)),
+            (x(), x()).prop_map(|(x, y)| SubNumeric(x, y)),
+            (x(), x()).prop_map(|(x, y)| ShiftLeft(x, y)),
+            (x(), x()).prop_map(|(x, y)| SkipNotEqual(x, y)),
+            addr().prop_map(LoadI),
+            addr().prop_map(JumpImmediate),
+            (x(), kk()).prop_map(|(x, kk)| Random(x, kk)),
+            (x(), x(), x()).prop_map(|(x, y, n)| Draw(x, y, n)),
+            x().prop_map(SkipOnKey),
+            x().prop_map(SkipNotOnKey),
+            x().prop_map(LoadDTIntoV),
+            x().prop_map(LoadKey),
+            x().prop_map(LoadVIntoDT),
+            x().prop_map(LoadVIntoST),
+            x().prop_map(AddI),
+            x().prop_map(LoadSpriteIntoI),
+            x().prop_map(LoadBCDIntoI),
+            x().prop_map(LoadVIntoMem),
+            x().prop_map(LoadMemIntoV),
+            x().prop_map(LoadVIntoFlags),
+            x().prop_map(LoadFlagsIntoV),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn test_round_trip(instruction in instruction()) {
+            let opcode = OpCode::from(instruction);
+            prop_assert_eq!(
+                Ok(instruction),
+                Instruction::try_from(opcode),
+                "{} encoded as {:?}",
+                instruction.name(),
+                opcode
+            );
+            let debug = format!("{:?}", instruction);
+            prop_assert!(debug.starts_with(instruction.name()), "{} named {}", debug, instruction.name());
+        }
+    }
 }
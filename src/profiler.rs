@@ -0,0 +1,400 @@
+//! An execution profiler for CHIP-8 programs.
+//!
+//! The profiler is fed every executed instruction through [`Profiler::record`] and keeps:
+//!
+//! * a hit count for every address in memory;
+//! * a hit count for every `Instruction` variant;
+//! * per-subroutine call counts, and self/inclusive instruction counts, derived by pairing `Call`
+//!   with `Return` on a shadow stack;
+//! * the iteration count of every loop, where a loop is any backwards transfer of control that is
+//!   not a `Call` or `Return`.
+//!
+//! Recording an instruction is a handful of array and counter updates and a lookup of its variant,
+//! other map lookups only happen when the call stack changes, so it can be left on during normal
+//! runs: see [`Machine::enable_profiler`](crate::machine::Machine::enable_profiler) and
+//! `chirp run --profile`.
+use crate::instructions::Instruction;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    io::{self, Write},
+    mem::{self, Discriminant},
+};
+
+/// The amount of entries shown in each section of the report
+const REPORT_ENTRIES: usize = 10;
+
+/// Statistics gathered for a single subroutine
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Subroutine {
+    /// How many times the subroutine was called
+    pub calls: u64,
+    /// Instructions executed by the subroutine itself
+    pub self_hits: u64,
+    /// Instructions executed by the subroutine and everything it called
+    pub inclusive_hits: u64,
+}
+
+/// A loop, detected as a backwards transfer of control from `end` to `start`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Loop {
+    /// The first address of the loop body, i.e. the target of the backwards branch
+    pub start: u16,
+    /// The address of the instruction that branched backwards
+    pub end: u16,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Frame {
+    /// The address the subroutine was called at
+    entry: u16,
+    /// The value of `Profiler::total` when the frame was entered
+    entered_at: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Profiler {
+    /// Hits per memory address
+    address_hits: Vec<u64>,
+    /// Hits per `Instruction` variant, along with an instruction of that variant to name it
+    instruction_hits: HashMap<Discriminant<Instruction>, (Instruction, u64)>,
+    /// Statistics per subroutine, keyed by entry address
+    subroutines: BTreeMap<u16, Subroutine>,
+    /// Iterations per loop
+    loops: HashMap<Loop, u64>,
+    /// The shadow call stack
+    frames: Vec<Frame>,
+    /// Every call stack seen so far, interned by its caller's stack and entry address to an index
+    /// into `stack_hits`. Index 0 is the empty stack.
+    stacks: HashMap<(usize, u16), usize>,
+    /// The caller's stack and the entry address of every interned call stack
+    stack_links: Vec<(usize, u16)>,
+    /// Hits per interned call stack
+    stack_hits: Vec<u64>,
+    /// The interned index of the current call stack
+    current_stack: usize,
+    /// The last recorded instruction, used to detect backwards branches
+    last: Option<(u16, Instruction)>,
+    /// The total amount of recorded instructions
+    total: u64,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    /// The addressable range of the CHIP-8 memory
+    const ADDRESS_SPACE: usize = 0x1000;
+
+    pub fn new() -> Self {
+        Self {
+            address_hits: vec![0; Self::ADDRESS_SPACE],
+            instruction_hits: HashMap::new(),
+            subroutines: BTreeMap::new(),
+            loops: HashMap::new(),
+            frames: Vec::new(),
+            stacks: HashMap::new(),
+            stack_links: vec![(0, 0)],
+            stack_hits: vec![0],
+            current_stack: 0,
+            last: None,
+            total: 0,
+        }
+    }
+
+    /// Record the execution of `instruction`, located at `pc`
+    pub fn record(&mut self, pc: u16, instruction: Instruction) {
+        let pc = pc & 0xFFF;
+
+        if let Some((last_pc, last)) = self.last {
+            let is_subroutine_edge = matches!(last, Instruction::Call(_) | Instruction::Return);
+            if pc <= last_pc && !is_subroutine_edge {
+                let l = Loop {
+                    start: pc,
+                    end: last_pc,
+                };
+                *self.loops.entry(l).or_insert(0) += 1;
+            }
+        }
+
+        self.total += 1;
+        self.address_hits[pc as usize] += 1;
+        self.instruction_hits
+            .entry(mem::discriminant(&instruction))
+            .or_insert((instruction, 0))
+            .1 += 1;
+        self.stack_hits[self.current_stack] += 1;
+        if let Some(frame) = self.frames.last() {
+            if let Some(sub) = self.subroutines.get_mut(&frame.entry) {
+                sub.self_hits += 1;
+            }
+        }
+
+        match instruction {
+            Instruction::Call(addr) => self.enter(addr & 0xFFF),
+            Instruction::Return => self.leave(),
+            _ => (),
+        }
+
+        self.last = Some((pc, instruction));
+    }
+
+    fn enter(&mut self, entry: u16) {
+        self.subroutines.entry(entry).or_default().calls += 1;
+        self.frames.push(Frame {
+            entry,
+            entered_at: self.total,
+        });
+
+        let next = self.stack_hits.len();
+        let idx = *self
+            .stacks
+            .entry((self.current_stack, entry))
+            .or_insert(next);
+        if idx == next {
+            self.stack_links.push((self.current_stack, entry));
+            self.stack_hits.push(0);
+        }
+        self.current_stack = idx;
+    }
+
+    fn leave(&mut self) {
+        // A return without a matching call means we started profiling mid-subroutine, there is
+        // nothing to attribute it to.
+        if let Some(frame) = self.frames.pop() {
+            if let Some(sub) = self.subroutines.get_mut(&frame.entry) {
+                sub.inclusive_hits += self.total - frame.entered_at;
+            }
+            self.current_stack = self.stack_links[self.current_stack].0;
+        }
+    }
+
+    /// The entry addresses of the interned call stack `idx`, outermost first
+    fn stack(&self, mut idx: usize) -> Vec<u16> {
+        let mut entries = Vec::new();
+        while idx != 0 {
+            let (caller, entry) = self.stack_links[idx];
+            entries.push(entry);
+            idx = caller;
+        }
+        entries.reverse();
+        entries
+    }
+
+    /// The total amount of recorded instructions
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// How many times the instruction at `addr` was executed
+    pub fn address_hits(&self, addr: u16) -> u64 {
+        self.address_hits[(addr & 0xFFF) as usize]
+    }
+
+    /// How many times an instruction variant, as named by `Instruction::name`, was executed
+    pub fn instruction_hits(&self, name: &str) -> u64 {
+        self.instruction_hits
+            .values()
+            .find(|(instruction, _)| instruction.name() == name)
+            .map_or(0, |(_, hits)| *hits)
+    }
+
+    /// Statistics for the subroutine whose entry point is `entry`
+    pub fn subroutine(&self, entry: u16) -> Option<&Subroutine> {
+        self.subroutines.get(&entry)
+    }
+
+    /// All detected loops with their iteration counts, hottest first
+    pub fn hot_loops(&self) -> Vec<(Loop, u64)> {
+        let mut loops: Vec<(Loop, u64)> = self.loops.iter().map(|(l, n)| (*l, *n)).collect();
+        loops.sort_by(|(la, a), (lb, b)| b.cmp(a).then(la.cmp(lb)));
+        loops
+    }
+
+    /// The addresses that were executed at least once, hottest first
+    pub fn hot_addresses(&self) -> Vec<(u16, u64)> {
+        let mut addrs: Vec<(u16, u64)> = self
+            .address_hits
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| **n > 0)
+            .map(|(addr, n)| (addr as u16, *n))
+            .collect();
+        addrs.sort_by(|(aa, a), (ab, b)| b.cmp(a).then(aa.cmp(ab)));
+        addrs
+    }
+
+    /// Write the recorded call stacks in the folded format understood by `flamegraph.pl` and
+    /// `inferno`, one `main;0x2A4;0x300 <hits>` line per stack.
+    pub fn write_folded<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let mut stacks: Vec<(Vec<u16>, u64)> = self
+            .stack_hits
+            .iter()
+            .enumerate()
+            .filter(|(_, hits)| **hits > 0)
+            .map(|(idx, hits)| (self.stack(idx), *hits))
+            .collect();
+        stacks.sort();
+
+        for (stack, hits) in stacks {
+            write!(w, "main")?;
+            for entry in stack {
+                write!(w, ";{:#05X}", entry)?;
+            }
+            writeln!(w, " {}", hits)?;
+        }
+
+        Ok(())
+    }
+
+    fn percent(&self, hits: u64) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            hits as f64 * 100.0 / self.total as f64
+        }
+    }
+}
+
+impl fmt::Display for Profiler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Executed {} instructions", self.total)?;
+
+        writeln!(f, "\nHottest addresses:")?;
+        for (addr, hits) in self.hot_addresses().into_iter().take(REPORT_ENTRIES) {
+            writeln!(
+                f,
+                "  {:#05X} {:>12} {:>6.2}%",
+                addr,
+                hits,
+                self.percent(hits)
+            )?;
+        }
+
+        writeln!(f, "\nInstructions:")?;
+        let mut instructions: Vec<(&str, u64)> = self
+            .instruction_hits
+            .values()
+            .map(|(instruction, hits)| (instruction.name(), *hits))
+            .collect();
+        instructions.sort_by(|(na, a), (nb, b)| b.cmp(a).then(na.cmp(nb)));
+        for (name, hits) in instructions {
+            writeln!(
+                f,
+                "  {:<24} {:>12} {:>6.2}%",
+                name,
+                hits,
+                self.percent(hits)
+            )?;
+        }
+
+        writeln!(f, "\nSubroutines:")?;
+        let mut subroutines: Vec<(&u16, &Subroutine)> = self.subroutines.iter().collect();
+        subroutines.sort_by(|(ea, a), (eb, b)| {
+            b.inclusive_hits
+                .cmp(&a.inclusive_hits)
+                .then(b.self_hits.cmp(&a.self_hits))
+                .then(ea.cmp(eb))
+        });
+        for (entry, sub) in subroutines.into_iter().take(REPORT_ENTRIES) {
+            writeln!(
+                f,
+                "  {:#05X} calls {:>8} self {:>12} inclusive {:>12}",
+                entry, sub.calls, sub.self_hits, sub.inclusive_hits
+            )?;
+        }
+
+        writeln!(f, "\nHot loops:")?;
+        for (l, iterations) in self.hot_loops().into_iter().take(REPORT_ENTRIES) {
+            let body: u64 = (l.start..=l.end)
+                .map(|addr| self.address_hits[addr as usize])
+                .sum();
+            writeln!(
+                f,
+                "  {:#05X}..={:#05X} iterations {:>10} body {:>12} {:>6.2}%",
+                l.start,
+                l.end,
+                iterations,
+                body,
+                self.percent(body)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        instructions::Instruction::*,
+        profiler::{Loop, Profiler},
+    };
+
+    #[test]
+    fn test_address_and_instruction_hits() {
+        let mut profiler = Profiler::new();
+        profiler.record(0x200, LoadImmediate(0x0, 0x1));
+        profiler.record(0x202, AddImmediate(0x0, 0x1));
+        profiler.record(0x202, AddImmediate(0x0, 0x1));
+
+        assert_eq!(3, profiler.total());
+        assert_eq!(1, profiler.address_hits(0x200));
+        assert_eq!(2, profiler.address_hits(0x202));
+        assert_eq!(2, profiler.instruction_hits("AddImmediate"));
+        assert_eq!(0, profiler.instruction_hits("Draw"));
+    }
+
+    #[test]
+    fn test_subroutines() {
+        let mut profiler = Profiler::new();
+        profiler.record(0x200, Call(0x300));
+        profiler.record(0x300, Call(0x400));
+        profiler.record(0x400, ClearScreen);
+        profiler.record(0x402, Return);
+        profiler.record(0x302, Return);
+        profiler.record(0x202, Jump(0x202));
+
+        let outer = profiler.subroutine(0x300).unwrap();
+        assert_eq!(1, outer.calls);
+        assert_eq!(2, outer.self_hits);
+        assert_eq!(4, outer.inclusive_hits);
+
+        let inner = profiler.subroutine(0x400).unwrap();
+        assert_eq!(2, inner.self_hits);
+        assert_eq!(2, inner.inclusive_hits);
+
+        let mut folded = Vec::new();
+        profiler.write_folded(&mut folded).unwrap();
+        assert_eq!(
+            "main 2\nmain;0x300 2\nmain;0x300;0x400 2\n",
+            String::from_utf8(folded).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_hot_loops() {
+        let mut profiler = Profiler::new();
+        for _ in 0..3 {
+            profiler.record(0x200, AddImmediate(0x0, 0x1));
+            profiler.record(0x202, SkipEqualImmediate(0x0, 0x3));
+            profiler.record(0x204, Jump(0x200));
+        }
+        profiler.record(0x200, Call(0x200));
+
+        let loops = profiler.hot_loops();
+        assert_eq!(
+            vec![(
+                Loop {
+                    start: 0x200,
+                    end: 0x204
+                },
+                3
+            )],
+            loops
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! Platforms and the behavioural quirks that set them apart.
+//!
+//! Over the years, CHIP-8 interpreters disagreed on the exact semantics of a handful of
+//! instructions, and programs written for one interpreter often rely on its particular behaviour.
+//! The quirks and platforms are the ones used by the community chip-8-database, so that its
+//! metadata can be used as is.
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// The interpreters a program may have been written for
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum Platform {
+    /// The original COSMAC VIP interpreter
+    #[default]
+    #[serde(rename = "originalChip8")]
+    Chip8,
+    /// The COSMAC VIP interpreter with programs using machine code routines
+    #[serde(rename = "hybridVIP")]
+    HybridVip,
+    /// The behaviour most modern interpreters settled on
+    #[serde(rename = "modernChip8")]
+    ModernChip8,
+    #[serde(rename = "chip8x")]
+    Chip8X,
+    /// CHIP-48 for the HP48 calculators
+    #[serde(rename = "chip48")]
+    Chip48,
+    /// SUPER-CHIP 1.0
+    #[serde(rename = "superchip1")]
+    SuperChip1,
+    /// SUPER-CHIP 1.1
+    #[serde(rename = "superchip")]
+    SuperChip,
+    #[serde(rename = "megachip8")]
+    MegaChip8,
+    #[serde(rename = "xochip")]
+    XoChip,
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::HybridVip => "CHIP-8 (hybrid VIP)",
+            Platform::ModernChip8 => "CHIP-8 (modern)",
+            Platform::Chip8X => "CHIP-8X",
+            Platform::Chip48 => "CHIP-48",
+            Platform::SuperChip1 => "SUPER-CHIP 1.0",
+            Platform::SuperChip => "SUPER-CHIP 1.1",
+            Platform::MegaChip8 => "MEGA-CHIP8",
+            Platform::XoChip => "XO-CHIP",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Parses the names used by the chip-8-database, e.g. `superchip`
+impl FromStr for Platform {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(serde_json::Value::String(s.to_string()))
+            .map_err(|_| format!("unknown platform '{}'", s))
+    }
+}
+
+impl Platform {
+    /// Every platform, oldest first
+    pub const ALL: [Platform; 9] = [
+        Platform::Chip8,
+        Platform::HybridVip,
+        Platform::ModernChip8,
+        Platform::Chip8X,
+        Platform::Chip48,
+        Platform::SuperChip1,
+        Platform::SuperChip,
+        Platform::MegaChip8,
+        Platform::XoChip,
+    ];
+
+    /// The quirks of the platform's reference interpreter
+    pub fn quirks(self) -> Quirks {
+        let vip = Quirks {
+            vblank: true,
+            logic: true,
+            ..Quirks::default()
+        };
+        let schip = Quirks {
+            shift: true,
+            jump: true,
+            ..Quirks::default()
+        };
+
+        match self {
+            Platform::Chip8 | Platform::HybridVip | Platform::Chip8X => vip,
+            Platform::ModernChip8 => Quirks::default(),
+            Platform::Chip48 | Platform::SuperChip1 => Quirks {
+                memory_increment_by_x: true,
+                ..schip
+            },
+            Platform::SuperChip | Platform::MegaChip8 => Quirks {
+                memory_leave_i_unchanged: true,
+                ..schip
+            },
+            Platform::XoChip => Quirks {
+                wrap: true,
+                ..Quirks::default()
+            },
+        }
+    }
+
+    /// The amount of instructions executed per 60Hz frame by default
+    pub fn tickrate(self) -> u32 {
+        match self {
+            Platform::Chip8 | Platform::HybridVip | Platform::ModernChip8 | Platform::Chip8X => 15,
+            Platform::Chip48 | Platform::SuperChip1 | Platform::SuperChip => 30,
+            Platform::MegaChip8 => 1000,
+            Platform::XoChip => 100,
+        }
+    }
+}
+
+/// The behaviours that differ between interpreters, all disabled means the behaviour of modern
+/// interpreters.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Quirks {
+    /// `8xy6`/`8xyE` shift Vx in place, ignoring Vy
+    pub shift: bool,
+    /// `Fx55`/`Fx65` increment I by x rather than by x + 1
+    pub memory_increment_by_x: bool,
+    /// `Fx55`/`Fx65` leave I unchanged
+    pub memory_leave_i_unchanged: bool,
+    /// Sprites wrap around the edges of the screen instead of being clipped
+    pub wrap: bool,
+    /// `Bxkk` jumps to `xkk + Vx` rather than `kkk + V0`
+    pub jump: bool,
+    /// `Dxyk` waits for the vertical blank interrupt before drawing
+    pub vblank: bool,
+    /// `8xy1`/`8xy2`/`8xy3` reset VF to zero
+    pub logic: bool,
+}
+
+/// A partial set of quirks, overriding only the ones that are set
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuirkOverrides {
+    pub shift: Option<bool>,
+    pub memory_increment_by_x: Option<bool>,
+    pub memory_leave_i_unchanged: Option<bool>,
+    pub wrap: Option<bool>,
+    pub jump: Option<bool>,
+    pub vblank: Option<bool>,
+    pub logic: Option<bool>,
+}
+
+impl Quirks {
+    /// Apply every quirk set in `overrides`
+    pub fn with_overrides(self, overrides: &QuirkOverrides) -> Self {
+        Self {
+            shift: overrides.shift.unwrap_or(self.shift),
+            memory_increment_by_x: overrides
+                .memory_increment_by_x
+                .unwrap_or(self.memory_increment_by_x),
+            memory_leave_i_unchanged: overrides
+                .memory_leave_i_unchanged
+                .unwrap_or(self.memory_leave_i_unchanged),
+            wrap: overrides.wrap.unwrap_or(self.wrap),
+            jump: overrides.jump.unwrap_or(self.jump),
+            vblank: overrides.vblank.unwrap_or(self.vblank),
+            logic: overrides.logic.unwrap_or(self.logic),
+        }
+    }
+}
+
+impl fmt::Display for Quirks {
+    /// Lists the enabled quirks, or `none`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let quirks = [
+            ("shift", self.shift),
+            ("memoryIncrementByX", self.memory_increment_by_x),
+            ("memoryLeaveIUnchanged", self.memory_leave_i_unchanged),
+            ("wrap", self.wrap),
+            ("jump", self.jump),
+            ("vblank", self.vblank),
+            ("logic", self.logic),
+        ];
+        let enabled: Vec<&str> = quirks
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| *name)
+            .collect();
+
+        if enabled.is_empty() {
+            f.write_str("none")
+        } else {
+            f.write_str(&enabled.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::quirks::{Platform, QuirkOverrides, Quirks};
+
+    #[test]
+    fn test_overrides() {
+        let overrides = QuirkOverrides {
+            shift: Some(true),
+            vblank: Some(false),
+            ..QuirkOverrides::default()
+        };
+        assert_eq!(
+            Quirks {
+                shift: true,
+                logic: true,
+                ..Quirks::default()
+            },
+            Platform::Chip8.quirks().with_overrides(&overrides)
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!("none", Quirks::default().to_string());
+        assert_eq!("vblank, logic", Platform::Chip8.quirks().to_string());
+    }
+
+    #[test]
+    fn test_parse_platform() {
+        assert_eq!(Ok(Platform::SuperChip), "superchip".parse());
+        assert_eq!(Ok(Platform::Chip8), "originalChip8".parse());
+        assert!("chip9".parse::<Platform>().is_err());
+    }
+}
@@ -0,0 +1,645 @@
+//! Recompilation of ROMs to Rust source.
+//!
+//! [`write_module`] splits the reachable code found by [`analysis`](crate::analysis) into basic
+//! blocks, and translates every block to a Rust function running its instructions directly on the
+//! [`Machine`](crate::machine::Machine): register and memory operations are inlined, draws go
+//! straight to its `Display` and key checks to its `Keypad`, and the quirks of the ROM are compiled
+//! in. A block hands control over by setting the PC, and `run_frame` is a state machine
+//! dispatching on the PC to the block holding it. Blocks can be entered at any of their
+//! instructions, so that a frame can end anywhere.
+//!
+//! Code that cannot be known ahead of time is left to the interpreter, one instruction at a time:
+//! the targets of `JP V0, addr`, which depend on V0 at runtime, and code the program overwrote,
+//! which is detected by comparing a block to the ROM before running it. Blocks end after every
+//! write to memory for that reason. A block also stops before an instruction that would fault,
+//! for the interpreter to report the fault according to the policy of the machine. This keeps the
+//! module behaving exactly like the interpreter, frame by frame. Only the instructions left to the
+//! interpreter are profiled.
+use crate::{
+    analysis::{self, Analysis},
+    instructions::Instruction,
+    loader::Rom,
+    machine::FONT_ADDRESS,
+    quirks::Quirks,
+};
+use std::{
+    collections::BTreeSet,
+    io::{self, Write},
+    mem,
+};
+
+/// The imports of the generated module
+const PRELUDE: &str = "\
+#![allow(dead_code)]
+
+use chirp::{
+    loader::Loader,
+    machine::{Machine, MachineError},
+    memory::Memory,
+};
+";
+
+/// The functions the blocks are built on, which are the same for every ROM
+const RUNTIME: &str = "\
+/// The instructions left to run in the current frame
+struct Frame {
+    budget: u32,
+    /// Set when a draw waits for the vertical blank, which ends the frame
+    vblank: bool,
+}
+
+impl Frame {
+    /// Count an executed instruction, returns whether the frame is over, with the PC at `next`
+    fn next(&mut self, m: &mut Machine, next: u16) -> bool {
+        self.budget -= 1;
+        let over = self.budget == 0 || self.vblank;
+        if over {
+            m.register_mut().set_pc(next);
+        }
+        over
+    }
+
+    /// Count an executed instruction and carry on at `target`, returns true to leave the block
+    fn jump(&mut self, m: &mut Machine, target: u16) -> bool {
+        self.budget -= 1;
+        m.register_mut().set_pc(target & 0xFFF);
+        true
+    }
+}
+
+/// Leave the instruction at `pc` to the interpreter
+fn interpret(m: &mut Machine, pc: u16) -> bool {
+    m.register_mut().set_pc(pc);
+    false
+}
+
+/// Whether the code from `pc` to `end` is still the code that was recompiled
+fn unmodified(m: &Machine, pc: u16, end: u16) -> bool {
+    let (pc, end, origin) = (usize::from(pc), usize::from(end), usize::from(ORIGIN));
+    m.memory().as_bytes()[pc..end] == ROM[pc - origin..end - origin]
+}
+
+/// Whether the `len` bytes from `address` are in memory
+fn in_memory(address: u16, len: usize) -> bool {
+    usize::from(address) + len <= Memory::MEMORY_SIZE
+}
+
+/// Write `values` from `address`, which is checked to be in memory
+fn write(m: &mut Machine, address: u16, values: &[u8]) {
+    let memory = m.memory_mut();
+    for (offset, value) in values.iter().enumerate() {
+        memory
+            .poke(usize::from(address) + offset, *value)
+            .expect(\"the address is in memory\");
+    }
+}
+
+/// Run a 60Hz frame, see `Machine::run_frame`
+pub fn run_frame(m: &mut Machine) -> Result<(), MachineError> {
+    if let Some(fault) = m.fault() {
+        return Err(*fault);
+    }
+    let mut frame = Frame {
+        budget: m.tickrate(),
+        vblank: false,
+    };
+    while frame.budget > 0 && !frame.vblank {
+        if !run_block(m, &mut frame) {
+            m.step()?;
+            frame.budget -= 1;
+            frame.vblank = m.waiting_for_vblank();
+        }
+    }
+    m.end_frame();
+    Ok(())
+}
+";
+
+/// An instruction translated to Rust
+struct Translated {
+    addr: u16,
+    instruction: Instruction,
+    /// The statements running the instruction
+    statements: Vec<String>,
+    /// The value the block returns after the statements, for instructions ending the block
+    leave: Option<String>,
+}
+
+/// Translate `instruction`, located at `addr`. Returns `None` for the instructions that are
+/// always left to the interpreter.
+fn translate(addr: u16, instruction: Instruction, quirks: &Quirks) -> Option<Translated> {
+    use Instruction::*;
+
+    let next = addr.wrapping_add(2) & 0xFFF;
+    // Leave instructions accessing memory past its end to the interpreter, which faults
+    let in_memory = |len: usize| {
+        format!(
+            "if !in_memory(i, {}) {{\n    return interpret(m, {:#05X});\n}}",
+            len, addr
+        )
+    };
+    let skip = |condition: String| -> (Vec<String>, Option<String>) {
+        (
+            vec![format!("let skip = {};", condition)],
+            Some(format!(
+                "frame.jump(m, if skip {{ {:#05X} }} else {{ {:#05X} }})",
+                addr.wrapping_add(4),
+                addr.wrapping_add(2)
+            )),
+        )
+    };
+    let registers = |statements: &[String]| -> Vec<String> {
+        let mut all = vec!["let v = m.register_mut().vs_mut();".to_string()];
+        all.extend_from_slice(statements);
+        all
+    };
+    // `Fx55` and `Fx65` move I past the registers they copy
+    let increment = |x: u8| -> Option<u8> {
+        let increment = if quirks.memory_increment_by_x {
+            x
+        } else {
+            x + 1
+        };
+        if quirks.memory_leave_i_unchanged || increment == 0 {
+            None
+        } else {
+            Some(increment)
+        }
+    };
+
+    let (statements, leave) = match instruction {
+        Exit | JumpImmediate(_) => return None,
+        ScrollDown(n) => (vec![format!("m.display_mut().scroll_down({});", n)], None),
+        ScrollRight => (vec!["m.display_mut().scroll_right();".to_string()], None),
+        ScrollLeft => (vec!["m.display_mut().scroll_left();".to_string()], None),
+        LowRes => (vec!["m.display_mut().set_hires(false);".to_string()], None),
+        HighRes => (vec!["m.display_mut().set_hires(true);".to_string()], None),
+        ClearScreen => (vec!["m.display_mut().clear();".to_string()], None),
+        Return => (
+            vec![format!(
+                "let next = match m.register_mut().pop() {{\n    Some(next) => next,\n    \
+                 None => return interpret(m, {:#05X}),\n}};",
+                addr
+            )],
+            Some("frame.jump(m, next)".to_string()),
+        ),
+        Jump(target) => (vec![], Some(format!("frame.jump(m, {:#05X})", target))),
+        Call(target) => (
+            vec![format!(
+                "if m.register_mut().push({:#05X}).is_err() {{\n    \
+                 return interpret(m, {:#05X});\n}}",
+                addr.wrapping_add(2),
+                addr
+            )],
+            Some(format!("frame.jump(m, {:#05X})", target)),
+        ),
+        SkipEqualImmediate(x, kk) => skip(format!("m.register().vs()[{:#X}] == {:#04X}", x, kk)),
+        SkipNotEqualImmediate(x, kk) => skip(format!("m.register().vs()[{:#X}] != {:#04X}", x, kk)),
+        SkipEqual(x, y) => skip(format!(
+            "m.register().vs()[{:#X}] == m.register().vs()[{:#X}]",
+            x, y
+        )),
+        SkipNotEqual(x, y) => skip(format!(
+            "m.register().vs()[{:#X}] != m.register().vs()[{:#X}]",
+            x, y
+        )),
+        SkipOnKey(x) => skip(format!(
+            "m.keypad().is_pressed(m.register().vs()[{:#X}])",
+            x
+        )),
+        SkipNotOnKey(x) => skip(format!(
+            "!m.keypad().is_pressed(m.register().vs()[{:#X}])",
+            x
+        )),
+        LoadImmediate(x, kk) => (registers(&[format!("v[{:#X}] = {:#04X};", x, kk)]), None),
+        AddImmediate(x, kk) => (
+            registers(&[format!(
+                "v[{0:#X}] = v[{0:#X}].wrapping_add({1:#04X});",
+                x, kk
+            )]),
+            None,
+        ),
+        Load(x, y) => (registers(&[format!("v[{:#X}] = v[{:#X}];", x, y)]), None),
+        Or(x, y) | And(x, y) | Xor(x, y) => {
+            let operator = match instruction {
+                Or(..) => "|",
+                And(..) => "&",
+                _ => "^",
+            };
+            let mut statements = vec![format!("v[{:#X}] {}= v[{:#X}];", x, operator, y)];
+            if quirks.logic {
+                statements.push("v[0xF] = 0;".to_string());
+            }
+            (registers(&statements), None)
+        }
+        Add(x, y) => (
+            registers(&[
+                format!(
+                    "let (sum, carry) = v[{:#X}].overflowing_add(v[{:#X}]);",
+                    x, y
+                ),
+                format!("v[{:#X}] = sum;", x),
+                "v[0xF] = carry as u8;".to_string(),
+            ]),
+            None,
+        ),
+        Sub(x, y) | SubNumeric(x, y) => {
+            let (minuend, subtrahend) = match instruction {
+                Sub(..) => ("vx", "vy"),
+                _ => ("vy", "vx"),
+            };
+            (
+                registers(&[
+                    format!("let (vx, vy) = (v[{:#X}], v[{:#X}]);", x, y),
+                    format!("v[{:#X}] = {}.wrapping_sub({});", x, minuend, subtrahend),
+                    format!("v[0xF] = ({} >= {}) as u8;", minuend, subtrahend),
+                ]),
+                None,
+            )
+        }
+        ShiftRight(x, y) | ShiftLeft(x, y) => {
+            let source = if quirks.shift { x } else { y };
+            let (shift, flag) = match instruction {
+                ShiftRight(..) => (">>", "shifted & 1"),
+                _ => ("<<", "shifted >> 7"),
+            };
+            (
+                registers(&[
+                    format!("let shifted = v[{:#X}];", source),
+                    format!("v[{:#X}] = shifted {} 1;", x, shift),
+                    format!("v[0xF] = {};", flag),
+                ]),
+                None,
+            )
+        }
+        LoadI(addr) => (
+            vec![format!("m.register_mut().set_i({:#05X});", addr)],
+            None,
+        ),
+        Random(x, kk) => (
+            vec![
+                "let random = m.random();".to_string(),
+                format!(
+                    "m.register_mut().vs_mut()[{:#X}] = random & {:#04X};",
+                    x, kk
+                ),
+            ],
+            None,
+        ),
+        Draw(x, y, n) => {
+            // A zero height draws a 16x16 sprite
+            let (width, len) = if n == 0 {
+                (16, 32)
+            } else {
+                (8, usize::from(n))
+            };
+            let mut statements = vec![
+                "let i = m.register().i();".to_string(),
+                in_memory(len),
+                format!("let mut sprite = [0; {}];", len),
+                format!(
+                    "sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..{}]);",
+                    len
+                ),
+                "let v = m.register().vs();".to_string(),
+                format!(
+                    "let (x, y) = (usize::from(v[{:#X}]), usize::from(v[{:#X}]));",
+                    x, y
+                ),
+                format!(
+                    "let collision = m.display_mut().draw(x, y, &sprite, {}, {});",
+                    width, quirks.wrap
+                ),
+                "m.register_mut().vs_mut()[0xF] = collision as u8;".to_string(),
+            ];
+            if quirks.vblank {
+                statements.push("frame.vblank = true;".to_string());
+            }
+            (statements, None)
+        }
+        LoadDTIntoV(x) => (
+            vec![
+                "let r = m.register_mut();".to_string(),
+                "let dt = r.dt();".to_string(),
+                format!("r.vs_mut()[{:#X}] = dt;", x),
+            ],
+            None,
+        ),
+        LoadKey(x) => (
+            vec![
+                format!(
+                    "let key = match m.keypad().first_pressed() {{\n    Some(key) => key,\n    \
+                     // Wait by executing the instruction again\n    \
+                     None => return frame.jump(m, {:#05X}),\n}};",
+                    addr
+                ),
+                format!("m.register_mut().vs_mut()[{:#X}] = key;", x),
+            ],
+            None,
+        ),
+        LoadVIntoDT(x) | LoadVIntoST(x) | AddI(x) | LoadSpriteIntoI(x) => {
+            let set = match instruction {
+                LoadVIntoDT(_) => "r.set_dt(vx);".to_string(),
+                LoadVIntoST(_) => "r.set_st(vx);".to_string(),
+                AddI(_) => "r.set_i(r.i().wrapping_add(u16::from(vx)));".to_string(),
+                _ => format!("r.set_i({:#05X} + u16::from(vx & 0xF) * 5);", FONT_ADDRESS),
+            };
+            (
+                vec![
+                    "let r = m.register_mut();".to_string(),
+                    format!("let vx = r.vs()[{:#X}];", x),
+                    set,
+                ],
+                None,
+            )
+        }
+        LoadBCDIntoI(x) => (
+            vec![
+                format!(
+                    "let (vx, i) = (m.register().vs()[{:#X}], m.register().i());",
+                    x
+                ),
+                in_memory(3),
+                "write(m, i, &[vx / 100, vx / 10 % 10, vx % 10]);".to_string(),
+            ],
+            Some(format!("frame.jump(m, {:#05X})", next)),
+        ),
+        LoadVIntoMem(x) => {
+            let len = usize::from(x) + 1;
+            let mut statements = vec![
+                "let i = m.register().i();".to_string(),
+                in_memory(len),
+                "let v = *m.register().vs();".to_string(),
+                format!("write(m, i, &v[..{}]);", len),
+            ];
+            if let Some(increment) = increment(x) {
+                statements.push(format!(
+                    "m.register_mut().set_i(i.wrapping_add({}));",
+                    increment
+                ));
+            }
+            (statements, Some(format!("frame.jump(m, {:#05X})", next)))
+        }
+        LoadMemIntoV(x) => {
+            let len = usize::from(x) + 1;
+            let mut statements = vec![
+                "let i = m.register().i();".to_string(),
+                in_memory(len),
+                format!("let mut values = [0; {}];", len),
+                format!(
+                    "values.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..{}]);",
+                    len
+                ),
+                "let r = m.register_mut();".to_string(),
+                format!("r.vs_mut()[..{}].copy_from_slice(&values);", len),
+            ];
+            if let Some(increment) = increment(x) {
+                statements.push(format!("r.set_i(i.wrapping_add({}));", increment));
+            }
+            (statements, None)
+        }
+        LoadVIntoFlags(x) => (
+            vec![
+                "let v = *m.register().vs();".to_string(),
+                "let flags = m.flags_mut();".to_string(),
+                format!("let n = flags.len().min({});", usize::from(x) + 1),
+                "flags[..n].copy_from_slice(&v[..n]);".to_string(),
+            ],
+            None,
+        ),
+        LoadFlagsIntoV(x) => (
+            vec![
+                "let mut v = *m.register().vs();".to_string(),
+                format!("let n = m.flags().len().min({});", usize::from(x) + 1),
+                "v[..n].copy_from_slice(&m.flags()[..n]);".to_string(),
+                "*m.register_mut().vs_mut() = v;".to_string(),
+            ],
+            None,
+        ),
+    };
+
+    Some(Translated {
+        addr,
+        instruction,
+        statements,
+        leave,
+    })
+}
+
+/// Split the reachable code into basic blocks of translated instructions. A block starts wherever
+/// control is transferred to, and ends with the instructions transferring control, writing to
+/// memory or left to the interpreter.
+fn blocks(analysis: &Analysis, quirks: &Quirks) -> Vec<Vec<Translated>> {
+    let mut leaders = BTreeSet::new();
+    leaders.insert(analysis.origin);
+    for (&addr, word) in &analysis.code {
+        let fall_through = addr.wrapping_add(2);
+        leaders.extend(
+            analysis
+                .successors(addr, word)
+                .into_iter()
+                .filter(|target| *target != fall_through),
+        );
+    }
+
+    let mut blocks = Vec::new();
+    let mut block: Vec<Translated> = Vec::new();
+    for (&addr, word) in &analysis.code {
+        let extends = block.last().is_some_and(|last| {
+            last.leave.is_none() && last.addr.wrapping_add(2) == addr && !leaders.contains(&addr)
+        });
+        if !extends && !block.is_empty() {
+            blocks.push(mem::take(&mut block));
+        }
+        if let Some(translated) = word
+            .instruction
+            .and_then(|instruction| translate(addr, instruction, quirks))
+        {
+            block.push(translated);
+        }
+    }
+    if !block.is_empty() {
+        blocks.push(block);
+    }
+    blocks
+}
+
+/// Write `statements`, indented by `indent` spaces
+fn write_statements<W: Write>(w: &mut W, statements: &[String], indent: usize) -> io::Result<()> {
+    for line in statements.iter().flat_map(|statement| statement.lines()) {
+        writeln!(w, "{:indent$}{}", "", line, indent = indent)?;
+    }
+    Ok(())
+}
+
+fn write_block<W: Write>(w: &mut W, block: &[Translated]) -> io::Result<()> {
+    let (first, last) = (&block[0], &block[block.len() - 1]);
+    writeln!(w, "/// {:#05X} to {:#05X}", first.addr, last.addr)?;
+    writeln!(
+        w,
+        "fn block_{:03x}(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {{",
+        first.addr
+    )?;
+    writeln!(
+        w,
+        "    if !unmodified(m, pc, {:#05X}) {{",
+        last.addr.wrapping_add(2)
+    )?;
+    writeln!(w, "        return false;")?;
+    writeln!(w, "    }}")?;
+
+    for translated in &block[..block.len() - 1] {
+        writeln!(w, "    if pc <= {:#05X} {{", translated.addr)?;
+        writeln!(w, "        // {}", translated.instruction)?;
+        write_statements(w, &translated.statements, 8)?;
+        writeln!(
+            w,
+            "        if frame.next(m, {:#05X}) {{",
+            translated.addr.wrapping_add(2) & 0xFFF
+        )?;
+        writeln!(w, "            return true;")?;
+        writeln!(w, "        }}")?;
+        writeln!(w, "    }}")?;
+    }
+
+    // The last instruction leaves the block, by falling through to the next one if need be
+    writeln!(w, "    // {}", last.instruction)?;
+    write_statements(w, &last.statements, 4)?;
+    match &last.leave {
+        Some(leave) => writeln!(w, "    {}", leave)?,
+        None => writeln!(
+            w,
+            "    frame.jump(m, {:#05X})",
+            last.addr.wrapping_add(2) & 0xFFF
+        )?,
+    }
+    writeln!(w, "}}")?;
+    writeln!(w)
+}
+
+/// Recompile the program of `rom` and write the source of the Rust module to `w`. `name` is the
+/// name of the ROM, only used in the documentation of the module.
+pub fn write_module<W: Write>(rom: &Rom, name: &str, mut w: W) -> io::Result<()> {
+    let origin = rom.info.address;
+    let start = usize::from(origin);
+    let bytes = &rom.memory.as_bytes()[start..start + rom.info.size];
+    let quirks = &rom.profile.quirks;
+    let analysis = analysis::analyze(bytes, origin);
+    let blocks = blocks(&analysis, quirks);
+
+    writeln!(w, "//! `{}`, recompiled by `chirp recompile`.", name)?;
+    writeln!(w, "//!")?;
+    writeln!(w, "//! SHA-1:  {}", rom.info.sha1)?;
+    writeln!(
+        w,
+        "//! Quirks: {}, the machine must run with the same quirks",
+        quirks
+    )?;
+    writeln!(w, "{}", PRELUDE)?;
+
+    writeln!(w, "/// The ROM image")?;
+    writeln!(w, "#[rustfmt::skip]")?;
+    writeln!(w, "pub const ROM: &[u8] = &[")?;
+    for chunk in bytes.chunks(12) {
+        let line: Vec<String> = chunk.iter().map(|b| format!("{:#04X}", b)).collect();
+        writeln!(w, "    {},", line.join(", "))?;
+    }
+    writeln!(w, "];")?;
+    writeln!(w)?;
+    writeln!(w, "/// The address the ROM is loaded at")?;
+    writeln!(w, "const ORIGIN: u16 = {:#05X};", origin)?;
+    writeln!(w)?;
+
+    writeln!(
+        w,
+        "/// A machine with the ROM loaded, using its recommended settings"
+    )?;
+    writeln!(w, "pub fn machine() -> Machine {{")?;
+    writeln!(w, "    let rom = Loader::new()")?;
+    writeln!(w, "        .address(ORIGIN)")?;
+    writeln!(w, "        .load(ROM)")?;
+    writeln!(w, "        .expect(\"the ROM fits in memory\");")?;
+    writeln!(w, "    Machine::from_rom(rom)")?;
+    writeln!(w, "}}")?;
+    writeln!(w)?;
+
+    writeln!(
+        w,
+        "/// Run the block holding the PC, returns false when the instruction at the PC is left to"
+    )?;
+    writeln!(w, "/// the interpreter")?;
+    writeln!(
+        w,
+        "fn run_block(m: &mut Machine, frame: &mut Frame) -> bool {{"
+    )?;
+    writeln!(w, "    let pc = m.pc();")?;
+    writeln!(w, "    match pc {{")?;
+    for block in &blocks {
+        let (first, last) = (block[0].addr, block[block.len() - 1].addr);
+        if first == last {
+            writeln!(
+                w,
+                "        {:#05X} => block_{:03x}(m, pc, frame),",
+                first, first
+            )?;
+        } else {
+            writeln!(
+                w,
+                "        {:#05X}..={:#05X} if pc & 1 == {} => block_{:03x}(m, pc, frame),",
+                first,
+                last,
+                first & 1,
+                first
+            )?;
+        }
+    }
+    writeln!(w, "        _ => false,")?;
+    writeln!(w, "    }}")?;
+    writeln!(w, "}}")?;
+    writeln!(w)?;
+
+    for block in &blocks {
+        write_block(&mut w, block)?;
+    }
+
+    write!(w, "{}", RUNTIME)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{loader::Loader, recompiler::write_module};
+
+    #[test]
+    fn test_recompile() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x01, // 0x200: LD V0, 0x01
+            0x30, 0x01, // 0x202: SE V0, 0x01
+            0x70, 0x01, // 0x204: ADD V0, 0x01
+            0xB2, 0x0A, // 0x206: JP V0, 0x20A
+            0x00, 0xE0, // 0x208: CLS
+            0x00, 0xE0, // 0x20A: CLS, only reachable through the indirect jump
+        ];
+        let rom = Loader::new().load(&rom).unwrap();
+        let mut source = Vec::new();
+        write_module(&rom, "test.ch8", &mut source).unwrap();
+        let source = String::from_utf8(source).unwrap();
+        assert!(source.starts_with("//! `test.ch8`, recompiled by `chirp recompile`.\n"));
+        assert!(source.contains("    0x60, 0x01, 0x30, 0x01, 0x70, 0x01, 0xB2, 0x0A, 0x00, 0xE0,"));
+
+        // The skip ends the first block, and its targets start new ones
+        assert!(
+            source.contains("        0x200..=0x202 if pc & 1 == 0 => block_200(m, pc, frame),\n")
+        );
+        assert!(source.contains("        0x204 => block_204(m, pc, frame),\n"));
+        assert!(source.contains(
+            "    let skip = m.register().vs()[0x0] == 0x01;\n    \
+             frame.jump(m, if skip { 0x206 } else { 0x204 })\n"
+        ));
+        assert!(source.contains("    v[0x0] = v[0x0].wrapping_add(0x01);\n"));
+
+        // The indirect jump and what only it reaches are left to the interpreter
+        assert!(!source.contains("0x206 =>"));
+        assert!(!source.contains("0x20A =>"));
+    }
+}
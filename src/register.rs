@@ -1,15 +1,96 @@
+use std::{convert::TryFrom, fmt, num::NonZeroUsize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum RegisterError {
+    #[error("There is no register V{0:X}, registers go from V0 to VF")]
+    InvalidRegister(u8),
+    #[error("{0} return addresses do not fit in a stack of depth {1}")]
+    StackTooSmall(usize, usize),
+}
+
+/// A general purpose register, V0 through VF
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum V {
+    V0,
+    V1,
+    V2,
+    V3,
+    V4,
+    V5,
+    V6,
+    V7,
+    V8,
+    V9,
+    VA,
+    VB,
+    VC,
+    VD,
+    VE,
+    /// The flag register, set by arithmetic and drawing instructions
+    VF,
+}
+
+impl V {
+    /// Every register, in order
+    pub const ALL: [V; 16] = [
+        V::V0,
+        V::V1,
+        V::V2,
+        V::V3,
+        V::V4,
+        V::V5,
+        V::V6,
+        V::V7,
+        V::V8,
+        V::V9,
+        V::VA,
+        V::VB,
+        V::VC,
+        V::VD,
+        V::VE,
+        V::VF,
+    ];
+
+    /// The index of the register, 0 for V0 through 15 for VF
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl TryFrom<u8> for V {
+    type Error = RegisterError;
+    fn try_from(x: u8) -> Result<Self, Self::Error> {
+        V::ALL
+            .get(usize::from(x))
+            .copied()
+            .ok_or(RegisterError::InvalidRegister(x))
+    }
+}
+
+impl From<V> for u8 {
+    fn from(v: V) -> Self {
+        v as u8
+    }
+}
+
+impl fmt::Display for V {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "V{:X}", *self as u8)
+    }
+}
+
 /// The register bank for a CHIP-8 CPU
 // XXX: I wish there was a name for the set of registers in a CPU
-#[derive(Default)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Register {
     /// The general purpose registers
     ///
     /// CHIP-8 has 16 general purpose 8-bit registers, usually referred to as Vx, where x is a
     /// hexadecimal digit (0 through F).
     ///
-    /// The VF (`v[0xE]`) register should not be used by any program, as it is used as carry
+    /// The VF (`v[0xF]`) register should not be used by any program, as it is used as carry
     /// (when using arithmetic instructions) and collision detector (when drawing sprites).
-    pub(crate) v: [u8; 0xF],
+    pub(crate) v: [u8; 0x10],
     /// The address register
     ///
     /// As the memory is 4Kb, the interpreter uses only its 12 lower bits. The remaining 4 could be
@@ -30,10 +111,194 @@ pub struct Register {
     /// The program counter (PC) is used to store the currently executing address
     pub(crate) pc: u16,
     /// The stack pointer (SP) is used to point to the topmost level of the stack.
-    pub(crate) sp: u8,
+    pub(crate) sp: usize,
     /// The stack is used to store the address that the interpreter shoud return to when finished
-    /// with a subroutine. CHIP-8 allows for up to 16 levels of nested subroutines.
+    /// with a subroutine. CHIP-8 allows for up to 16 levels of nested subroutines, some SUPER-CHIP
+    /// programs expect more.
     ///
     /// NB: This may not apply to the original CHIP-8 as no documentation was found on the stack.
-    stack: [u16; 0xF],
+    pub(crate) stack: Vec<u16>,
+}
+
+impl Default for Register {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Register {
+    /// The usual depth of the stack
+    pub const STACK_DEPTH: usize = 16;
+
+    /// A zeroed register bank with a 16 level stack
+    pub fn new() -> Self {
+        let depth = NonZeroUsize::new(Self::STACK_DEPTH).expect("the usual stack is not empty");
+        Self::with_stack_depth(depth)
+    }
+
+    /// A zeroed register bank with a `depth` level stack
+    pub fn with_stack_depth(depth: NonZeroUsize) -> Self {
+        Self {
+            v: [0; 0x10],
+            i: 0,
+            dt: 0,
+            st: 0,
+            pc: 0,
+            sp: 0,
+            stack: vec![0; depth.get()],
+        }
+    }
+
+    pub fn v(&self, v: V) -> u8 {
+        self.v[v.index()]
+    }
+
+    pub fn set_v(&mut self, v: V, value: u8) {
+        self.v[v.index()] = value;
+    }
+
+    /// Every general purpose register, V0 first
+    pub fn vs(&self) -> &[u8; 0x10] {
+        &self.v
+    }
+
+    /// Mutable access to every general purpose register, V0 first
+    pub fn vs_mut(&mut self) -> &mut [u8; 0x10] {
+        &mut self.v
+    }
+
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    pub fn set_i(&mut self, i: u16) {
+        self.i = i;
+    }
+
+    pub fn dt(&self) -> u8 {
+        self.dt
+    }
+
+    pub fn set_dt(&mut self, dt: u8) {
+        self.dt = dt;
+    }
+
+    pub fn st(&self) -> u8 {
+        self.st
+    }
+
+    pub fn set_st(&mut self, st: u8) {
+        self.st = st;
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    /// The amount of return addresses on the stack
+    pub fn sp(&self) -> usize {
+        self.sp
+    }
+
+    /// The maximum amount of nested subroutine calls
+    pub fn stack_depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// The return addresses on the stack, the innermost last
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sp]
+    }
+
+    /// Push the return address `address`. Fails if the stack is full.
+    pub fn push(&mut self, address: u16) -> Result<(), RegisterError> {
+        if self.sp >= self.stack.len() {
+            return Err(RegisterError::StackTooSmall(self.sp + 1, self.stack.len()));
+        }
+        self.stack[self.sp] = address;
+        self.sp += 1;
+        Ok(())
+    }
+
+    /// Pop the innermost return address, if there is one
+    pub fn pop(&mut self) -> Option<u16> {
+        self.sp = self.sp.checked_sub(1)?;
+        Some(self.stack[self.sp])
+    }
+
+    /// Replace the contents of the stack with `addresses`, the innermost last. Fails if there
+    /// are more addresses than the stack holds.
+    pub fn set_stack(&mut self, addresses: &[u16]) -> Result<(), RegisterError> {
+        if addresses.len() > self.stack.len() {
+            return Err(RegisterError::StackTooSmall(
+                addresses.len(),
+                self.stack.len(),
+            ));
+        }
+        self.stack[..addresses.len()].copy_from_slice(addresses);
+        self.sp = addresses.len();
+        Ok(())
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (n, v) in self.v.iter().enumerate() {
+            let sep = if n % 8 == 7 { "\n" } else { " " };
+            write!(f, "V{:X}={:02X}{}", n, v, sep)?;
+        }
+        writeln!(
+            f,
+            "I={:03X} DT={:02X} ST={:02X} PC={:03X} SP={}",
+            self.i, self.dt, self.st, self.pc, self.sp
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::register::{Register, RegisterError, V};
+    use std::{convert::TryFrom, num::NonZeroUsize};
+
+    #[test]
+    fn test_registers() {
+        let mut register = Register::new();
+        register.set_v(V::VF, 1);
+        register.set_v(V::try_from(0xA).unwrap(), 0x42);
+        assert_eq!(1, register.vs()[0xF]);
+        assert_eq!(0x42, register.v(V::VA));
+        assert_eq!(Err(RegisterError::InvalidRegister(0x10)), V::try_from(0x10));
+        assert_eq!("VA", V::VA.to_string());
+    }
+
+    #[test]
+    fn test_stack() {
+        let mut register = Register::with_stack_depth(NonZeroUsize::new(32).unwrap());
+        assert_eq!(32, register.stack_depth());
+        assert_eq!(Register::STACK_DEPTH, Register::new().stack_depth());
+
+        register.set_stack(&[0x202, 0x304]).unwrap();
+        assert_eq!(&[0x202, 0x304], register.stack());
+        assert_eq!(
+            Err(RegisterError::StackTooSmall(33, 32)),
+            register.set_stack(&[0; 33])
+        );
+
+        register.push(0x406).unwrap();
+        assert_eq!(Some(0x406), register.pop());
+        assert_eq!(Some(0x304), register.pop());
+
+        let mut register = Register::with_stack_depth(NonZeroUsize::new(1).unwrap());
+        assert_eq!(None, register.pop());
+        register.push(0x202).unwrap();
+        assert_eq!(
+            Err(RegisterError::StackTooSmall(2, 1)),
+            register.push(0x204)
+        );
+        assert_eq!(&[0x202], register.stack());
+    }
 }
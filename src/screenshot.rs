@@ -0,0 +1,203 @@
+//! Screenshots of the display as Netpbm images.
+//!
+//! Screenshots are written as plain PBM (`P1`) images, with a `0` or `1` per pixel and a line of
+//! text per row, so that the ones checked into the repository can be read and diffed as text.
+//! Rows of the high resolution display are split over two lines, as lines are limited to 70
+//! characters. Comparing two screenshots produces a PPM image highlighting the pixels that differ.
+use crate::display::Display;
+use std::io;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ScreenshotError {
+    #[error("Not a plain PBM image")]
+    BadMagic,
+    #[error("Invalid PBM header")]
+    InvalidHeader,
+    #[error("Invalid pixel {0:?}")]
+    InvalidPixel(char),
+    #[error("Expected {0} pixels, found {1}")]
+    WrongSize(usize, usize),
+}
+
+/// A monochrome image
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Screenshot {
+    pub width: usize,
+    pub height: usize,
+    /// One byte per pixel, 1 when the pixel is lit, row by row
+    pub pixels: Vec<u8>,
+}
+
+impl Screenshot {
+    /// A screenshot of what `display` shows
+    pub fn of(display: &Display) -> Self {
+        Self {
+            width: display.width(),
+            height: display.height(),
+            pixels: display.pixels().to_vec(),
+        }
+    }
+
+    /// Parse a plain PBM image
+    pub fn from_pbm(pbm: &str) -> Result<Self, ScreenshotError> {
+        // Comments run from a `#` to the end of the line
+        let mut text = String::with_capacity(pbm.len());
+        for line in pbm.lines() {
+            text.push_str(line.split('#').next().unwrap_or_default());
+            text.push('\n');
+        }
+
+        let mut tokens = text.split_whitespace();
+        if tokens.next() != Some("P1") {
+            return Err(ScreenshotError::BadMagic);
+        }
+        let mut dimension = || -> Result<usize, ScreenshotError> {
+            tokens
+                .next()
+                .and_then(|token| token.parse().ok())
+                .ok_or(ScreenshotError::InvalidHeader)
+        };
+        let width = dimension()?;
+        let height = dimension()?;
+
+        let pixels = tokens
+            .flat_map(str::chars)
+            .map(|c| match c {
+                '0' => Ok(0),
+                '1' => Ok(1),
+                c => Err(ScreenshotError::InvalidPixel(c)),
+            })
+            .collect::<Result<Vec<u8>, _>>()?;
+        if pixels.len() != width * height {
+            return Err(ScreenshotError::WrongSize(width * height, pixels.len()));
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// The SHA-1 of the dimensions and pixels
+    pub fn sha1(&self) -> String {
+        let mut bytes = Vec::with_capacity(self.pixels.len() + 4);
+        bytes.extend_from_slice(&(self.width as u16).to_be_bytes());
+        bytes.extend_from_slice(&(self.height as u16).to_be_bytes());
+        bytes.extend_from_slice(&self.pixels);
+        sha1::Sha1::from(bytes).hexdigest()
+    }
+
+    fn get(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.pixels[y * self.width + x] != 0
+    }
+}
+
+/// Write `screenshot` as a plain PBM image
+pub fn write_pbm<W: io::Write>(screenshot: &Screenshot, mut w: W) -> io::Result<()> {
+    writeln!(w, "P1")?;
+    writeln!(w, "{} {}", screenshot.width, screenshot.height)?;
+    for row in screenshot.pixels.chunks(screenshot.width.max(1)) {
+        for line in row.chunks(64) {
+            let line: String = line
+                .iter()
+                .map(|&p| if p != 0 { '1' } else { '0' })
+                .collect();
+            writeln!(w, "{}", line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write a binary PPM image showing where `actual` differs from `expected`: lit pixels missing
+/// from `actual` in red, pixels that should not be lit in green, and the pixels both agree on in
+/// grey
+pub fn write_diff<W: io::Write>(
+    expected: &Screenshot,
+    actual: &Screenshot,
+    mut w: W,
+) -> io::Result<()> {
+    let width = expected.width.max(actual.width);
+    let height = expected.height.max(actual.height);
+    write!(w, "P6\n{} {}\n255\n", width, height)?;
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let rgb = match (expected.get(x, y), actual.get(x, y)) {
+                (true, true) => [0x80, 0x80, 0x80],
+                (false, false) => [0x00, 0x00, 0x00],
+                (true, false) => [0xFF, 0x00, 0x00],
+                (false, true) => [0x00, 0xFF, 0x00],
+            };
+            pixels.extend_from_slice(&rgb);
+        }
+    }
+    w.write_all(&pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        display::Display,
+        screenshot::{write_diff, write_pbm, Screenshot, ScreenshotError},
+    };
+
+    #[test]
+    fn test_pbm() {
+        let mut display = Display::new();
+        display.draw(1, 2, &[0b1010_0000], 8, false);
+        let screenshot = Screenshot::of(&display);
+        let mut pbm = Vec::new();
+        write_pbm(&screenshot, &mut pbm).unwrap();
+        let pbm = String::from_utf8(pbm).unwrap();
+
+        let lines: Vec<&str> = pbm.lines().collect();
+        assert_eq!(&["P1", "64 32"], &lines[..2]);
+        assert_eq!(&format!("0101{}", "0".repeat(60)), lines[4]);
+        assert_eq!(Ok(screenshot), Screenshot::from_pbm(&pbm));
+
+        display.set_hires(true);
+        let screenshot = Screenshot::of(&display);
+        let mut pbm = Vec::new();
+        write_pbm(&screenshot, &mut pbm).unwrap();
+        let pbm = String::from_utf8(pbm).unwrap();
+        assert!(pbm.lines().all(|line| line.len() <= 70));
+        assert_eq!(Ok(screenshot), Screenshot::from_pbm(&pbm));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(Err(ScreenshotError::BadMagic), Screenshot::from_pbm("P4"));
+        assert_eq!(
+            Err(ScreenshotError::InvalidHeader),
+            Screenshot::from_pbm("P1\n# width\nwide 2")
+        );
+        assert_eq!(
+            Err(ScreenshotError::InvalidPixel('2')),
+            Screenshot::from_pbm("P1 2 1\n12")
+        );
+        assert_eq!(
+            Err(ScreenshotError::WrongSize(4, 3)),
+            Screenshot::from_pbm("P1 2 2\n10 1")
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let expected = Screenshot::from_pbm("P1 2 2\n11\n00").unwrap();
+        let actual = Screenshot::from_pbm("P1 2 2\n10\n01").unwrap();
+        assert_ne!(expected.sha1(), actual.sha1());
+
+        let mut ppm = Vec::new();
+        write_diff(&expected, &actual, &mut ppm).unwrap();
+        let header = b"P6\n2 2\n255\n";
+        assert_eq!(&header[..], &ppm[..header.len()]);
+        #[rustfmt::skip]
+        assert_eq!(
+            &[
+                0x80, 0x80, 0x80,   0xFF, 0x00, 0x00,
+                0x00, 0x00, 0x00,   0x00, 0xFF, 0x00,
+            ][..],
+            &ppm[header.len()..]
+        );
+    }
+}
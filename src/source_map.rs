@@ -0,0 +1,116 @@
+//! Mapping from memory addresses back to the assembler source they were generated from.
+//!
+//! Source maps are stored as plain text, with one `<addr> <file>:<line>` entry per line.
+use crate::symbols::parse_number;
+use std::{collections::BTreeMap, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SourceMapError {
+    #[error("Invalid source map entry on line {0}")]
+    Parse(usize),
+}
+
+/// A position in an assembler source file
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Location {
+    pub file: String,
+    /// The line number, starting at 1
+    pub line: u32,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    locations: BTreeMap<u16, Location>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that the word at `address` was generated by the source at `location`
+    pub fn insert(&mut self, address: u16, location: Location) {
+        self.locations.insert(address, location);
+    }
+
+    pub fn get(&self, address: u16) -> Option<&Location> {
+        self.locations.get(&address)
+    }
+
+    /// All mapped addresses, in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &Location)> {
+        self.locations.iter().map(|(addr, loc)| (*addr, loc))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    /// Parse a source map in the format written by its `Display` implementation
+    pub fn parse(s: &str) -> Result<Self, SourceMapError> {
+        let mut map = Self::new();
+
+        for (idx, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let entry = line
+                .split_once(char::is_whitespace)
+                .and_then(|(addr, loc)| {
+                    let (file, line) = loc.trim().rsplit_once(':')?;
+                    let location = Location {
+                        file: file.to_string(),
+                        line: line.parse().ok()?,
+                    };
+                    Some((parse_number(addr)?, location))
+                });
+            let (addr, location) = entry.ok_or(SourceMapError::Parse(idx + 1))?;
+            map.insert(addr, location);
+        }
+
+        Ok(map)
+    }
+}
+
+impl fmt::Display for SourceMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (addr, location) in self.iter() {
+            writeln!(f, "{:#05X} {}", addr, location)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source_map::{Location, SourceMap, SourceMapError};
+
+    #[test]
+    fn test_round_trip() {
+        let map = SourceMap::parse("0x200 games/pong.8o:3\n0x202 games/pong.8o:4\n").unwrap();
+        assert_eq!(
+            Some(&Location {
+                file: "games/pong.8o".to_string(),
+                line: 4
+            }),
+            map.get(0x202)
+        );
+        assert_eq!(map, SourceMap::parse(&map.to_string()).unwrap());
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(
+            Err(SourceMapError::Parse(2)),
+            SourceMap::parse("0x200 pong.8o:3\n0x202 pong.8o\n")
+        );
+    }
+}
@@ -0,0 +1,309 @@
+//! Save states.
+//!
+//! A [`State`] is a snapshot of everything a program can change: the registers, memory, display,
+//! keypad, user flags, random number generator and the fault that halted the machine, if any. The
+//! settings the machine was built with, like its quirks and fault policy, are not part of it, so
+//! a state should be loaded into a machine set up for the same ROM.
+//!
+//! States are encoded in a versioned binary format, with every integer big endian:
+//!
+//! | Field        | Size                                |
+//! | ------------ | ----------------------------------- |
+//! | Magic        | 4, `CH8S`                           |
+//! | Version      | 1                                   |
+//! | V0-VF        | 16                                  |
+//! | I, PC        | 2 each                              |
+//! | DT, ST       | 1 each                              |
+//! | Stack depth  | 2                                   |
+//! | SP           | 2                                   |
+//! | Stack        | 2 per level                         |
+//! | Memory       | 4096                                |
+//! | Hires        | 1                                   |
+//! | Pixels       | 128 * 64, low resolution is padded  |
+//! | Keypad       | 2                                   |
+//! | Flag count   | 1                                   |
+//! | Flags        | 1 per flag                          |
+//! | RNG          | 8                                   |
+//! | VBlank wait  | 1                                   |
+//! | Fault        | 9, see `write_fault`                |
+use crate::{
+    display::Display,
+    keypad::Keypad,
+    machine::{Fault, MachineError},
+    memory::Memory,
+    opcode::OpCode,
+    register::Register,
+};
+use std::convert::TryFrom;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum StateError {
+    #[error("Not a save state")]
+    BadMagic,
+    #[error(
+        "Save state version {0} is not supported, expected version {}",
+        State::VERSION
+    )]
+    UnsupportedVersion(u8),
+    #[error("Save state is truncated")]
+    Truncated,
+    #[error("Save state is corrupted: {0}")]
+    Corrupted(&'static str),
+}
+
+/// A snapshot of a machine
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct State {
+    pub register: Register,
+    pub memory: Memory,
+    pub display: Display,
+    pub keypad: Keypad,
+    pub flags: Vec<u8>,
+    pub rng: u64,
+    pub vblank_wait: bool,
+    pub fault: Option<MachineError>,
+}
+
+impl State {
+    pub const MAGIC: &'static [u8; 4] = b"CH8S";
+    pub const VERSION: u8 = 1;
+
+    /// Encode the state
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let r = &self.register;
+        let mut out = Vec::with_capacity(0x3100);
+        out.extend_from_slice(Self::MAGIC);
+        out.push(Self::VERSION);
+
+        out.extend_from_slice(&r.v);
+        out.extend_from_slice(&r.i.to_be_bytes());
+        out.extend_from_slice(&r.pc.to_be_bytes());
+        out.push(r.dt);
+        out.push(r.st);
+        out.extend_from_slice(&(r.stack.len() as u16).to_be_bytes());
+        out.extend_from_slice(&(r.sp as u16).to_be_bytes());
+        for address in &r.stack {
+            out.extend_from_slice(&address.to_be_bytes());
+        }
+
+        out.extend_from_slice(self.memory.as_bytes());
+
+        out.push(self.display.is_hires() as u8);
+        let pixels = self.display.pixels();
+        out.extend_from_slice(pixels);
+        out.resize(
+            out.len() + Display::HIRES_WIDTH * Display::HIRES_HEIGHT - pixels.len(),
+            0,
+        );
+
+        out.extend_from_slice(&self.keypad.bits().to_be_bytes());
+        out.push(self.flags.len() as u8);
+        out.extend_from_slice(&self.flags);
+        out.extend_from_slice(&self.rng.to_be_bytes());
+        out.push(self.vblank_wait as u8);
+        write_fault(self.fault, &mut out);
+        out
+    }
+
+    /// Decode a state encoded by `State::to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, StateError> {
+        let mut r = Reader(bytes);
+        if r.take(4)? != Self::MAGIC {
+            return Err(StateError::BadMagic);
+        }
+        match r.u8()? {
+            Self::VERSION => (),
+            version => return Err(StateError::UnsupportedVersion(version)),
+        }
+
+        let mut v = [0; 0x10];
+        v.copy_from_slice(r.take(0x10)?);
+        let i = r.u16()?;
+        let pc = r.u16()?;
+        let dt = r.u8()?;
+        let st = r.u8()?;
+        let depth = usize::from(r.u16()?);
+        let sp = usize::from(r.u16()?);
+        if depth == 0 {
+            return Err(StateError::Corrupted("empty stack"));
+        }
+        if sp > depth {
+            return Err(StateError::Corrupted(
+                "stack pointer past the end of the stack",
+            ));
+        }
+        let stack = (0..depth).map(|_| r.u16()).collect::<Result<_, _>>()?;
+        let register = Register {
+            v,
+            i,
+            dt,
+            st,
+            pc,
+            sp,
+            stack,
+        };
+
+        let mut memory = Memory::new();
+        for (address, byte) in r.take(Memory::MEMORY_SIZE)?.iter().enumerate() {
+            memory
+                .poke(address, *byte)
+                .expect("the image is the size of memory");
+        }
+
+        let hires = match r.u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(StateError::Corrupted("invalid resolution")),
+        };
+        let pixels = r.take(Display::HIRES_WIDTH * Display::HIRES_HEIGHT)?;
+        let len = if hires {
+            pixels.len()
+        } else {
+            Display::WIDTH * Display::HEIGHT
+        };
+        let display = Display::from_pixels(hires, pixels[..len].to_vec());
+
+        let mut keypad = Keypad::new();
+        keypad.set_bits(r.u16()?);
+        let count = usize::from(r.u8()?);
+        let flags = r.take(count)?.to_vec();
+        let rng = u64::from_be_bytes(<[u8; 8]>::try_from(r.take(8)?).expect("took 8 bytes"));
+        let vblank_wait = r.u8()? != 0;
+        let fault = read_fault(&mut r)?;
+
+        if !r.0.is_empty() {
+            return Err(StateError::Corrupted("trailing bytes"));
+        }
+        Ok(Self {
+            register,
+            memory,
+            display,
+            keypad,
+            flags,
+            rng,
+            vblank_wait,
+            fault,
+        })
+    }
+}
+
+/// Write the fault that halted the machine, if any, as a kind (0 for none), the PC and opcode
+/// of the faulting instruction, and the address of an out of bounds access
+fn write_fault(fault: Option<MachineError>, out: &mut Vec<u8>) {
+    let (kind, pc, opcode, address) = match fault {
+        None => (0, 0, 0, 0),
+        Some(MachineError { pc, opcode, fault }) => {
+            let (kind, address) = match fault {
+                Fault::StackOverflow => (1, 0),
+                Fault::StackUnderflow => (2, 0),
+                Fault::OutOfBoundsAccess(address) => (3, address as u32),
+                Fault::UnknownOpCode => (4, 0),
+                Fault::Exit => (5, 0),
+            };
+            (kind, pc, u16::from(opcode), address)
+        }
+    };
+    out.push(kind);
+    out.extend_from_slice(&pc.to_be_bytes());
+    out.extend_from_slice(&opcode.to_be_bytes());
+    out.extend_from_slice(&address.to_be_bytes());
+}
+
+fn read_fault(r: &mut Reader<'_>) -> Result<Option<MachineError>, StateError> {
+    let kind = r.u8()?;
+    let pc = r.u16()?;
+    let opcode = OpCode::new(r.u16()?);
+    let address = u32::from_be_bytes(<[u8; 4]>::try_from(r.take(4)?).expect("took 4 bytes"));
+    let fault = match kind {
+        0 => return Ok(None),
+        1 => Fault::StackOverflow,
+        2 => Fault::StackUnderflow,
+        3 => Fault::OutOfBoundsAccess(address as usize),
+        4 => Fault::UnknownOpCode,
+        5 => Fault::Exit,
+        _ => return Err(StateError::Corrupted("invalid fault")),
+    };
+    Ok(Some(MachineError { pc, opcode, fault }))
+}
+
+/// Reads an encoded state front to back
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], StateError> {
+        if self.0.len() < n {
+            return Err(StateError::Truncated);
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, StateError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        loader::Loader,
+        machine::Machine,
+        state::{State, StateError},
+    };
+
+    #[test]
+    fn test_round_trip() {
+        let rom = Loader::new().load_path("./games/tetris.ch8").unwrap();
+        let mut machine = Machine::from_rom(rom);
+        for _ in 0..120 {
+            machine.run_frame().unwrap();
+        }
+
+        let state = machine.save_state();
+        let bytes = state.to_bytes();
+        assert_eq!(state, State::from_bytes(&bytes).unwrap());
+
+        // Running on from a restored state is indistinguishable from carrying on
+        let mut restored = machine.clone();
+        for _ in 0..120 {
+            machine.run_frame().unwrap();
+        }
+        restored.load_state(State::from_bytes(&bytes).unwrap());
+        for _ in 0..120 {
+            restored.run_frame().unwrap();
+        }
+        assert_eq!(machine.save_state(), restored.save_state());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let rom = Loader::new().load_path("./games/tetris.ch8").unwrap();
+        let bytes = Machine::from_rom(rom).save_state().to_bytes();
+
+        assert_eq!(Err(StateError::BadMagic), State::from_bytes(b"nope"));
+        let mut newer = bytes.clone();
+        newer[4] = 2;
+        assert_eq!(
+            Err(StateError::UnsupportedVersion(2)),
+            State::from_bytes(&newer)
+        );
+        assert_eq!(
+            Err(StateError::Truncated),
+            State::from_bytes(&bytes[..bytes.len() - 1])
+        );
+
+        // The stack depth follows the magic, version, V0 to VF, I, PC, DT and ST
+        let mut empty = bytes.clone();
+        empty[27..29].copy_from_slice(&[0, 0]);
+        assert_eq!(
+            Err(StateError::Corrupted("empty stack")),
+            State::from_bytes(&empty)
+        );
+    }
+}
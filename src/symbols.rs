@@ -0,0 +1,333 @@
+//! Symbol tables naming addresses in a CHIP-8 program.
+//!
+//! A symbol file is a plain text file with one entry per line, blank lines and lines starting with
+//! `;` are ignored:
+//!
+//! | Entry                              | Meaning                                          |
+//! | ---------------------------------- | ------------------------------------------------ |
+//! | `label <addr> <name>`              | `name` refers to `addr`                          |
+//! | `data <addr> <length> [name]`      | `length` bytes starting at `addr` are not code   |
+//! | `comment <addr> <text>`            | `text` is shown next to `addr`                   |
+//!
+//! An address may have several labels. The first one is the name shown for the address, the
+//! others are aliases, and the file lists them in that order.
+//!
+//! Octo's symbol output, made of `name value` pairs optionally prefixed by the kind of symbol
+//! (`:const`, `:breakpoint`, `:monitor`...), can be imported with [`SymbolTable::from_octo`].
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SymbolError {
+    #[error("Invalid symbol on line {0}: {1}")]
+    Parse(usize, String),
+}
+
+/// A range of memory holding data rather than code
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DataRegion {
+    pub start: u16,
+    /// The length of the region in bytes
+    pub length: u16,
+    pub name: Option<String>,
+}
+
+impl DataRegion {
+    pub fn contains(&self, addr: u16) -> bool {
+        addr >= self.start && u32::from(addr) < u32::from(self.start) + u32::from(self.length)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    /// The labels of each address, the one shown for the address first, then its aliases in the
+    /// order they were inserted
+    labels: BTreeMap<u16, Vec<String>>,
+    /// The address of every label
+    addresses: HashMap<String, u16>,
+    data: Vec<DataRegion>,
+    comments: BTreeMap<u16, String>,
+}
+
+/// Parse a number in decimal, hexadecimal (`0x`, `#`) or binary (`0b`) notation
+pub(crate) fn parse_number(s: &str) -> Option<u16> {
+    let s = s.trim();
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u16::from_str_radix(hex, 16).ok()
+    } else if let Some(hex) = s.strip_prefix('#') {
+        u16::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = s.strip_prefix("0b").or_else(|| s.strip_prefix("0B")) {
+        u16::from_str_radix(bin, 2).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Name `addr` as `name`. If `addr` already has a label, `name` becomes an alias for it.
+    pub fn insert_label(&mut self, addr: u16, name: &str) {
+        match self.addresses.insert(name.to_string(), addr) {
+            Some(previous) if previous == addr => return,
+            Some(previous) => {
+                let names = self.labels.entry(previous).or_default();
+                names.retain(|n| n != name);
+                if names.is_empty() {
+                    self.labels.remove(&previous);
+                }
+            }
+            None => (),
+        }
+        self.labels.entry(addr).or_default().push(name.to_string());
+    }
+
+    /// The label for `addr`, if any
+    pub fn label(&self, addr: u16) -> Option<&str> {
+        self.labels
+            .get(&addr)
+            .and_then(|names| names.first())
+            .map(String::as_str)
+    }
+
+    /// The address of the label `name`, if it exists
+    pub fn address(&self, name: &str) -> Option<u16> {
+        self.addresses.get(name).copied()
+    }
+
+    pub fn insert_data(&mut self, region: DataRegion) {
+        let idx = self.data.binary_search(&region).unwrap_or_else(|idx| idx);
+        self.data.insert(idx, region);
+    }
+
+    /// The data region `addr` is part of, if any
+    pub fn data(&self, addr: u16) -> Option<&DataRegion> {
+        self.data.iter().find(|region| region.contains(addr))
+    }
+
+    pub fn insert_comment(&mut self, addr: u16, comment: &str) {
+        self.comments.insert(addr, comment.to_string());
+    }
+
+    pub fn comment(&self, addr: u16) -> Option<&str> {
+        self.comments.get(&addr).map(String::as_str)
+    }
+
+    /// Resolve a user supplied location, such as the argument to a `break` command, which may be
+    /// either a label or a numeric address.
+    pub fn resolve(&self, s: &str) -> Option<u16> {
+        self.address(s.trim()).or_else(|| parse_number(s))
+    }
+
+    /// Parse a symbol file in the format described in the module documentation
+    pub fn parse(s: &str) -> Result<Self, SymbolError> {
+        let mut table = Self::new();
+
+        for (idx, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+
+            let error = |reason: &str| SymbolError::Parse(idx + 1, reason.to_string());
+            let mut fields = line.splitn(3, char::is_whitespace);
+            let kind = fields.next().unwrap_or_default();
+            let addr = fields
+                .next()
+                .and_then(parse_number)
+                .ok_or_else(|| error("missing or invalid address"))?;
+            let rest = fields.next().map(str::trim).unwrap_or_default();
+
+            match kind {
+                "label" if !rest.is_empty() && !rest.contains(char::is_whitespace) => {
+                    table.insert_label(addr, rest)
+                }
+                "label" => return Err(error("labels must have a single word name")),
+                "data" => {
+                    let mut fields = rest.splitn(2, char::is_whitespace);
+                    let length = fields
+                        .next()
+                        .and_then(parse_number)
+                        .ok_or_else(|| error("missing or invalid data length"))?;
+                    let name = fields.next().map(str::trim).map(str::to_string);
+                    table.insert_data(DataRegion {
+                        start: addr,
+                        length,
+                        name,
+                    });
+                }
+                "comment" => table.insert_comment(addr, rest),
+                _ => return Err(error("unknown entry kind")),
+            }
+        }
+
+        Ok(table)
+    }
+
+    /// Import the symbols emitted by Octo.
+    ///
+    /// Every line holds a name and a value, optionally prefixed by the kind of symbol. Monitors
+    /// (`:monitor name addr length`) become data regions, and constants (`:const` and `:calc`)
+    /// are skipped as their values are not addresses. Everything else becomes a label.
+    pub fn from_octo(s: &str) -> Result<Self, SymbolError> {
+        let mut table = Self::new();
+
+        for (idx, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = |reason: &str| SymbolError::Parse(idx + 1, reason.to_string());
+            let mut fields: Vec<&str> = line.split_whitespace().collect();
+            let kind = match fields.first() {
+                Some(kind) if kind.starts_with(':') => {
+                    let kind = kind.trim_start_matches(':');
+                    fields.remove(0);
+                    Some(kind)
+                }
+                _ => None,
+            };
+
+            let (name, value) = match fields.as_slice() {
+                [name, value, ..] => (
+                    *name,
+                    parse_number(value).ok_or_else(|| error("invalid value"))?,
+                ),
+                _ => return Err(error("expected a name and a value")),
+            };
+
+            match kind {
+                Some("const") | Some("calc") => (),
+                Some("monitor") => {
+                    let length = fields.get(2).and_then(|l| parse_number(l)).unwrap_or(1);
+                    table.insert_data(DataRegion {
+                        start: value,
+                        length,
+                        name: Some(name.to_string()),
+                    });
+                }
+                _ => table.insert_label(value, name),
+            }
+        }
+
+        Ok(table)
+    }
+}
+
+impl fmt::Display for SymbolTable {
+    /// Writes the table in the format accepted by `SymbolTable::parse`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (addr, names) in &self.labels {
+            for name in names {
+                writeln!(f, "label {:#05X} {}", addr, name)?;
+            }
+        }
+        for region in &self.data {
+            write!(f, "data {:#05X} {:#X}", region.start, region.length)?;
+            match &region.name {
+                Some(name) => writeln!(f, " {}", name)?,
+                None => writeln!(f)?,
+            }
+        }
+        for (addr, comment) in &self.comments {
+            writeln!(f, "comment {:#05X} {}", addr, comment)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::symbols::{DataRegion, SymbolError, SymbolTable};
+
+    const SYMBOLS: &str = "\
+; Tetris
+label 0x200 main
+label 0x2A4 draw_piece
+data 0x300 0x10 pieces
+comment 0x2A4 clobbers V0 to V3
+";
+
+    #[test]
+    fn test_parse() {
+        let table = SymbolTable::parse(SYMBOLS).unwrap();
+        assert_eq!(Some("main"), table.label(0x200));
+        assert_eq!(Some(0x2A4), table.address("draw_piece"));
+        assert_eq!(Some("clobbers V0 to V3"), table.comment(0x2A4));
+        assert_eq!(
+            Some(&DataRegion {
+                start: 0x300,
+                length: 0x10,
+                name: Some("pieces".to_string())
+            }),
+            table.data(0x30F)
+        );
+        assert_eq!(None, table.data(0x310));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let table = SymbolTable::parse(SYMBOLS).unwrap();
+        assert_eq!(table, SymbolTable::parse(&table.to_string()).unwrap());
+    }
+
+    #[test]
+    fn test_aliases() {
+        let mut table = SymbolTable::new();
+        table.insert_label(0x200, "start");
+        table.insert_label(0x200, "main");
+        table.insert_label(0x200, "entry");
+        table.insert_label(0x202, "loop");
+        table.insert_label(0x202, "start");
+        assert_eq!(Some("main"), table.label(0x200));
+        assert_eq!(Some("loop"), table.label(0x202));
+        assert_eq!(Some(0x202), table.address("start"));
+
+        let parsed = SymbolTable::parse(&table.to_string()).unwrap();
+        assert_eq!(table, parsed);
+        assert_eq!(
+            "label 0x200 main\nlabel 0x200 entry\nlabel 0x202 loop\nlabel 0x202 start\n",
+            parsed.to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(
+            Err(SymbolError::Parse(2, "unknown entry kind".to_string())),
+            SymbolTable::parse("label 0x200 main\nlabl 0x202 loop")
+        );
+    }
+
+    #[test]
+    fn test_resolve() {
+        let table = SymbolTable::parse(SYMBOLS).unwrap();
+        assert_eq!(Some(0x2A4), table.resolve("draw_piece"));
+        assert_eq!(Some(0x2A6), table.resolve("0x2A6"));
+        assert_eq!(Some(0x200), table.resolve("512"));
+        assert_eq!(None, table.resolve("nowhere"));
+    }
+
+    #[test]
+    fn test_from_octo() {
+        let table = SymbolTable::from_octo(
+            "main 0x202\n:const SPEED 4\n:calc FAST 8\n:breakpoint wait 0x210\n\
+                 :monitor score 0x300 2\n",
+        )
+        .unwrap();
+        assert_eq!(Some("main"), table.label(0x202));
+        assert_eq!(None, table.address("SPEED"));
+        assert_eq!(None, table.label(0x004));
+        assert_eq!(None, table.address("FAST"));
+        assert_eq!(Some(0x210), table.resolve("wait"));
+        assert_eq!(
+            Some("score"),
+            table.data(0x301).and_then(|r| r.name.as_deref())
+        );
+    }
+}
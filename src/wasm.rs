@@ -0,0 +1,149 @@
+//! A C-ABI for running ROMs on a web page, with no JavaScript glue generator involved.
+//!
+//! Build the module with `cargo build --lib --release --target wasm32-unknown-unknown`. The
+//! functions below are exported from it, and drive a single machine:
+//!
+//! ```js
+//! const { instance } = await WebAssembly.instantiate(wasm);
+//! const chirp = instance.exports;
+//! const ptr = chirp.chirp_alloc(rom.length);
+//! new Uint8Array(chirp.memory.buffer, ptr, rom.length).set(rom);
+//! chirp.chirp_load(ptr, rom.length);
+//! chirp.chirp_free(ptr, rom.length);
+//!
+//! // On every animation frame
+//! chirp.chirp_set_key(0x5, pressed);
+//! chirp.chirp_run_frame();
+//! const pixels = new Uint8Array(
+//!     chirp.memory.buffer,
+//!     chirp.chirp_framebuffer(),
+//!     chirp.chirp_width() * chirp.chirp_height(),
+//! );
+//! ```
+//!
+//! The framebuffer holds one byte per pixel, 1 when the pixel is lit, row by row. Its address
+//! changes when the program switches resolution, so read it again after every frame.
+//!
+//! On other targets the functions are not exported, so that they don't clash with the symbols of
+//! programs embedding the library, but they can still be called from Rust.
+use crate::{loader::Loader, machine::Machine};
+use std::sync::Mutex;
+
+/// The machine driven by the page, a web page only runs one
+static MACHINE: Mutex<Option<Machine>> = Mutex::new(None);
+
+fn with_machine<T>(default: T, f: impl FnOnce(&mut Machine) -> T) -> T {
+    let mut machine = MACHINE.lock().expect("machine lock poisoned");
+    machine.as_mut().map_or(default, f)
+}
+
+/// Allocate `len` bytes in the module's memory, for the page to copy a ROM into
+#[cfg_attr(target_arch = "wasm32", no_mangle)]
+pub extern "C" fn chirp_alloc(len: usize) -> *mut u8 {
+    let mut buf = vec![0u8; len].into_boxed_slice();
+    let ptr = buf.as_mut_ptr();
+    std::mem::forget(buf);
+    ptr
+}
+
+/// Free `len` bytes at `ptr`, allocated by `chirp_alloc`
+///
+/// # Safety
+///
+/// `ptr` and `len` must come from a single call to `chirp_alloc`, and must not be used again.
+#[cfg_attr(target_arch = "wasm32", no_mangle)]
+pub unsafe extern "C" fn chirp_free(ptr: *mut u8, len: usize) {
+    drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)));
+}
+
+/// Load the `len` bytes at `ptr` as a ROM in a fresh machine, with its recommended settings.
+/// Returns 0 on success, and -1 if the ROM does not fit in memory or is an invalid Octo cartridge.
+///
+/// # Safety
+///
+/// `ptr` must point to `len` readable bytes.
+#[cfg_attr(target_arch = "wasm32", no_mangle)]
+pub unsafe extern "C" fn chirp_load(ptr: *const u8, len: usize) -> i32 {
+    let bytes = std::slice::from_raw_parts(ptr, len);
+    match Loader::new().load(bytes) {
+        Ok(rom) => {
+            *MACHINE.lock().expect("machine lock poisoned") = Some(Machine::from_rom(rom));
+            0
+        }
+        Err(e) => {
+            log::warn!("Failed to load the ROM: {}", e);
+            -1
+        }
+    }
+}
+
+/// Run a 60Hz frame. Returns 0 if the machine is running, 1 if it halted, and -1 if no ROM was
+/// loaded.
+#[cfg_attr(target_arch = "wasm32", no_mangle)]
+pub extern "C" fn chirp_run_frame() -> i32 {
+    with_machine(-1, |machine| match machine.run_frame() {
+        Ok(()) => 0,
+        Err(_) => 1,
+    })
+}
+
+/// The address of the framebuffer, or null if no ROM was loaded
+#[cfg_attr(target_arch = "wasm32", no_mangle)]
+pub extern "C" fn chirp_framebuffer() -> *const u8 {
+    with_machine(std::ptr::null(), |machine| {
+        machine.display().pixels().as_ptr()
+    })
+}
+
+/// The width of the framebuffer in pixels, 0 if no ROM was loaded
+#[cfg_attr(target_arch = "wasm32", no_mangle)]
+pub extern "C" fn chirp_width() -> u32 {
+    with_machine(0, |machine| machine.display().width() as u32)
+}
+
+/// The height of the framebuffer in pixels, 0 if no ROM was loaded
+#[cfg_attr(target_arch = "wasm32", no_mangle)]
+pub extern "C" fn chirp_height() -> u32 {
+    with_machine(0, |machine| machine.display().height() as u32)
+}
+
+/// Press `key` (0 through F) if `pressed` is non-zero, release it otherwise
+#[cfg_attr(target_arch = "wasm32", no_mangle)]
+pub extern "C" fn chirp_set_key(key: u32, pressed: u32) {
+    with_machine((), |machine| {
+        machine.keypad_mut().set((key & 0xF) as u8, pressed != 0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::wasm::*;
+
+    #[test]
+    fn test_api() {
+        assert_eq!(-1, chirp_run_frame());
+        assert!(chirp_framebuffer().is_null());
+
+        let rom = std::fs::read("./games/tetris.ch8").unwrap();
+        let ptr = chirp_alloc(rom.len());
+        unsafe {
+            std::slice::from_raw_parts_mut(ptr, rom.len()).copy_from_slice(&rom);
+            assert_eq!(0, chirp_load(ptr, rom.len()));
+            chirp_free(ptr, rom.len());
+        }
+
+        chirp_set_key(0x5, 1);
+        for _ in 0..60 {
+            assert_eq!(0, chirp_run_frame());
+        }
+        let (width, height) = (chirp_width() as usize, chirp_height() as usize);
+        assert_eq!((64, 32), (width, height));
+        let pixels = unsafe { std::slice::from_raw_parts(chirp_framebuffer(), width * height) };
+        assert!(pixels.contains(&1));
+
+        let too_large = vec![0; 0x1000];
+        assert_eq!(-1, unsafe {
+            chirp_load(too_large.as_ptr(), too_large.len())
+        });
+    }
+}
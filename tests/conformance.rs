@@ -0,0 +1,155 @@
+//! Runs test ROMs and compares the screen they end on with golden screenshots.
+//!
+//! `conformance/suite.json` lists the ROMs to run, the platforms to run each of them on, for how
+//! many frames and which keys to press along the way, e.g. to pick an entry from a menu. Each run
+//! ends with a screenshot which has to match `conformance/golden/<name>-<platform>.pbm`.
+//!
+//! The suite runs the bundled games. Other ROMs can be listed too: put them in
+//! `tests/conformance/roms`, or point `CHIRP_TEST_ROMS` at a directory holding them. ROMs that
+//! can't be found are skipped, but a ROM that was found has to have golden screenshots.
+//!
+//! The golden screenshots are not references from another interpreter: the six checked in were
+//! taken with `CHIRP_BLESS` on this emulator. They catch changes in behaviour, not deviations
+//! from other implementations, so check the screens by hand before blessing new ones.
+//!
+//! A failing run leaves the screenshot it took and an image of the differences, lit pixels
+//! missing in red and extra ones in green, in `target/conformance`. After checking a change of
+//! screen is intended, update the golden screenshots with:
+//!
+//! ```sh
+//! CHIRP_BLESS=1 cargo test --test conformance
+//! ```
+use chirp::{
+    loader::Profile,
+    machine::Machine,
+    memory::Memory,
+    quirks::Platform,
+    screenshot::{self, Screenshot},
+};
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+const SUITE: &str = "./tests/conformance/suite.json";
+const GOLDEN: &str = "./tests/conformance/golden";
+const OUTPUT: &str = "./target/conformance";
+
+#[derive(Deserialize)]
+struct Test {
+    name: String,
+    rom: String,
+    platforms: Vec<Platform>,
+    frames: u32,
+    #[serde(default)]
+    input: Vec<Press>,
+}
+
+/// Hold `key` down for `hold` frames, starting on frame `frame`
+#[derive(Deserialize)]
+struct Press {
+    frame: u32,
+    key: u8,
+    #[serde(default = "Press::default_hold")]
+    hold: u32,
+}
+
+impl Press {
+    fn default_hold() -> u32 {
+        5
+    }
+}
+
+/// The directories ROMs are looked up in, in order
+fn rom_dirs() -> Vec<PathBuf> {
+    let roms = std::env::var_os("CHIRP_TEST_ROMS")
+        .map_or_else(|| PathBuf::from("./tests/conformance/roms"), PathBuf::from);
+    vec![roms, PathBuf::from("./games")]
+}
+
+fn platform_name(platform: Platform) -> String {
+    serde_json::to_value(platform)
+        .unwrap()
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+/// Run `rom` on `platform` following the script of `test`, returning the final screen
+fn run(test: &Test, rom: &[u8], platform: Platform) -> Screenshot {
+    let mut memory = Memory::new();
+    memory.load(rom, 0x200).unwrap();
+    let mut machine = Machine::new(memory, 0x200, &Profile::for_platform(platform));
+    for frame in 0..test.frames {
+        let keys = test
+            .input
+            .iter()
+            .filter(|press| (press.frame..press.frame + press.hold).contains(&frame))
+            .fold(0, |keys, press| keys | 1 << press.key);
+        machine.keypad_mut().set_bits(keys);
+        if machine.run_frame().is_err() {
+            break;
+        }
+    }
+    Screenshot::of(machine.display())
+}
+
+/// Compare `actual` with the golden screenshot at `golden`, returning what went wrong
+fn compare(golden: &Path, actual: &Screenshot, id: &str) -> Result<(), String> {
+    let pbm = fs::read_to_string(golden).map_err(|e| format!("{}: {}", id, e))?;
+    let expected = Screenshot::from_pbm(&pbm).map_err(|e| format!("{}: {}", id, e))?;
+    if expected.sha1() == actual.sha1() {
+        return Ok(());
+    }
+
+    let output = Path::new(OUTPUT);
+    fs::create_dir_all(output).unwrap();
+    let actual_path = output.join(format!("{}.pbm", id));
+    let diff_path = output.join(format!("{}.diff.ppm", id));
+    screenshot::write_pbm(actual, fs::File::create(&actual_path).unwrap()).unwrap();
+    screenshot::write_diff(&expected, actual, fs::File::create(&diff_path).unwrap()).unwrap();
+    Err(format!(
+        "{}: screen {} differs from golden {}, see {}",
+        id,
+        actual.sha1(),
+        expected.sha1(),
+        diff_path.display()
+    ))
+}
+
+#[test]
+fn test_conformance() {
+    let suite: Vec<Test> = serde_json::from_str(&fs::read_to_string(SUITE).unwrap()).unwrap();
+    let bless = std::env::var_os("CHIRP_BLESS").is_some();
+    let dirs = rom_dirs();
+
+    let mut failures = Vec::new();
+    for test in &suite {
+        let rom = match dirs
+            .iter()
+            .find_map(|dir| fs::read(dir.join(&test.rom)).ok())
+        {
+            Some(rom) => rom,
+            None => {
+                eprintln!("Skipping {}: {} not found", test.name, test.rom);
+                continue;
+            }
+        };
+
+        for &platform in &test.platforms {
+            let id = format!("{}-{}", test.name, platform_name(platform));
+            let actual = run(test, &rom, platform);
+            let golden = Path::new(GOLDEN).join(format!("{}.pbm", id));
+            if bless {
+                screenshot::write_pbm(&actual, fs::File::create(&golden).unwrap()).unwrap();
+            } else if !golden.exists() {
+                failures.push(format!("{}: no golden screenshot, bless it first", id));
+            } else if let Err(failure) = compare(&golden, &actual, &id) {
+                failures.push(failure);
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
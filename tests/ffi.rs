@@ -0,0 +1,62 @@
+//! The C API, as seen from C.
+//!
+//! `cargo test` does not build the shared library, so the C program is ignored by default:
+//!
+//! ```sh
+//! cargo build --lib
+//! cargo test --test ffi -- --ignored
+//! ```
+use std::{path::PathBuf, process::Command};
+
+fn header() -> String {
+    let config = cbindgen::Config::from_file("cbindgen.toml").unwrap();
+    let mut header = Vec::new();
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src("src/ffi.rs")
+        .generate()
+        .unwrap()
+        .write(&mut header);
+    String::from_utf8(header).unwrap()
+}
+
+#[test]
+fn test_header_up_to_date() {
+    assert_eq!(
+        include_str!("../include/chirp.h"),
+        header(),
+        "regenerate with `cbindgen --config cbindgen.toml --output include/chirp.h src/ffi.rs`"
+    );
+}
+
+#[test]
+#[ignore]
+fn test_c_program() {
+    // The test binary lives in target/<profile>/deps, next to which the library is built
+    let exe = std::env::current_exe().unwrap();
+    let lib_dir = exe.parent().and_then(|deps| deps.parent()).unwrap();
+    let program: PathBuf = exe.with_file_name("ffi-tetris");
+
+    let status = Command::new("cc")
+        .args(["tests/ffi/tetris.c", "-Iinclude", "-o"])
+        .arg(&program)
+        .arg("-L")
+        .arg(lib_dir)
+        .arg("-lchirp")
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(status.success());
+
+    let output = Command::new(&program)
+        .arg("games/tetris.ch8")
+        .env("LD_LIBRARY_PATH", lib_dir)
+        .env("DYLD_LIBRARY_PATH", lib_dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!("ok\n", String::from_utf8_lossy(&output.stdout));
+}
@@ -0,0 +1,62 @@
+/* Runs Tetris through the C API, saving and restoring a state along the way */
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "chirp.h"
+
+#define CHECK(call)                                                                \
+    do {                                                                           \
+        ChirpStatus status = (call);                                               \
+        if (status != CHIRP_STATUS_OK) {                                           \
+            fprintf(stderr, "%s: %d %s\n", #call, status,                          \
+                    chirp_machine_last_error(m));                                  \
+            return 1;                                                              \
+        }                                                                          \
+    } while (0)
+
+int main(int argc, char **argv) {
+    unsigned char rom[4096];
+    FILE *f = fopen(argv[1], "rb");
+    if (argc != 2 || f == NULL) {
+        fprintf(stderr, "usage: %s <rom>\n", argv[0]);
+        return 1;
+    }
+    size_t len = fread(rom, 1, sizeof(rom), f);
+    fclose(f);
+
+    ChirpMachine *m = chirp_machine_new();
+    CHECK(chirp_machine_load(m, rom, len));
+    CHECK(chirp_machine_set_key(m, 5, 1));
+    CHECK(chirp_machine_run_cycles(m, 100));
+
+    size_t size = chirp_machine_state_size(m);
+    unsigned char *state = malloc(size);
+    CHECK(chirp_machine_save_state(m, state, size));
+
+    uint32_t width, height;
+    const uint8_t *pixels = chirp_machine_framebuffer(m, &width, &height);
+    uint8_t *saved = malloc(width * height);
+    memcpy(saved, pixels, width * height);
+
+    for (int i = 0; i < 60; i++) {
+        CHECK(chirp_machine_run_frame(m));
+    }
+    CHECK(chirp_machine_load_state(m, state, size));
+    pixels = chirp_machine_framebuffer(m, NULL, NULL);
+    if (memcmp(saved, pixels, width * height) != 0) {
+        fprintf(stderr, "restoring the state did not restore the screen\n");
+        return 1;
+    }
+
+    if (chirp_machine_set_key(m, 16, 1) != CHIRP_STATUS_INVALID_KEY) {
+        fprintf(stderr, "key 16 was accepted\n");
+        return 1;
+    }
+
+    free(saved);
+    free(state);
+    chirp_machine_free(m);
+    printf("ok\n");
+    return 0;
+}
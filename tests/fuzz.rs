@@ -0,0 +1,90 @@
+//! Replays the fuzzing corpus through the checks of the fuzz targets.
+//!
+//! Fuzzing proper needs cargo-fuzz and a nightly toolchain:
+//!
+//! ```sh
+//! cargo +nightly fuzz run execute
+//! ```
+//!
+//! This runs every seed in `fuzz/corpus`, plus a fixed set of random mutations of each, on the
+//! stable toolchain. Set `CHIRP_FUZZ_MUTATIONS` to try more mutations per seed.
+use chirp::fuzz;
+use std::{fs, path::Path};
+
+/// Every file in the corpus of `target`
+fn corpus(target: &str) -> Vec<Vec<u8>> {
+    let dir = Path::new("./fuzz/corpus").join(target);
+    let mut seeds: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    seeds.sort();
+    seeds.iter().map(|path| fs::read(path).unwrap()).collect()
+}
+
+/// A xorshift random number generator, so that the mutations are the same on every run
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound.max(1) as u64) as usize
+    }
+}
+
+/// Overwrite, insert, remove or truncate a few bytes of `seed`
+fn mutate(seed: &[u8], rng: &mut Rng) -> Vec<u8> {
+    let mut data = seed.to_vec();
+    for _ in 0..1 + rng.next(8) {
+        let at = rng.next(data.len());
+        match rng.next(4) {
+            0 if at < data.len() => data[at] = rng.next(0x100) as u8,
+            1 => data.insert(at, rng.next(0x100) as u8),
+            2 if at < data.len() => {
+                data.remove(at);
+            }
+            3 => data.truncate(at),
+            _ => (),
+        }
+    }
+    data
+}
+
+fn replay(target: &str, check: fn(&[u8])) {
+    let mutations = std::env::var("CHIRP_FUZZ_MUTATIONS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(16);
+    let seeds = corpus(target);
+    assert!(!seeds.is_empty());
+
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    for seed in &seeds {
+        check(seed);
+        for _ in 0..mutations {
+            check(&mutate(seed, &mut rng));
+        }
+    }
+}
+
+#[test]
+fn test_decode() {
+    replay("decode", fuzz::decode);
+}
+
+#[test]
+fn test_disassemble() {
+    replay("disassemble", fuzz::disassemble);
+}
+
+#[test]
+fn test_state() {
+    replay("state", fuzz::state);
+}
+
+#[test]
+fn test_execute() {
+    replay("execute", fuzz::execute);
+}
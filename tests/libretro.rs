@@ -0,0 +1,118 @@
+//! Loads the shared library as a libretro core, the way a frontend does.
+//!
+//! `cargo test` does not build the shared library, so the test is ignored by default:
+//!
+//! ```sh
+//! cargo build --lib
+//! cargo test --test libretro -- --ignored
+//! ```
+use chirp::libretro::{
+    RetroAudioSampleBatch, RetroEnvironment, RetroGameInfo, RetroInputPoll, RetroInputState,
+    RetroSystemAvInfo, RetroSystemInfo, RetroVideoRefresh,
+};
+use libloading::{Library, Symbol};
+use std::{
+    ffi::CStr,
+    mem::MaybeUninit,
+    os::raw::{c_uint, c_void},
+    ptr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+static FRAMES: AtomicUsize = AtomicUsize::new(0);
+static LIT: AtomicUsize = AtomicUsize::new(0);
+static SAMPLES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe extern "C" fn environment(cmd: c_uint, _data: *mut c_void) -> bool {
+    // Accept the pixel format and input descriptors, know no core option
+    cmd == 10 || cmd == 11
+}
+
+unsafe extern "C" fn video_refresh(data: *const c_void, width: c_uint, height: c_uint, _: usize) {
+    let pixels = std::slice::from_raw_parts(data as *const u32, (width * height) as usize);
+    FRAMES.fetch_add(1, Ordering::SeqCst);
+    LIT.store(pixels.iter().filter(|&&p| p != 0).count(), Ordering::SeqCst);
+}
+
+unsafe extern "C" fn audio_sample_batch(_: *const i16, frames: usize) -> usize {
+    SAMPLES.fetch_add(frames, Ordering::SeqCst);
+    frames
+}
+
+unsafe extern "C" fn input_poll() {}
+
+unsafe extern "C" fn input_state(_: c_uint, _: c_uint, _: c_uint, _: c_uint) -> i16 {
+    0
+}
+
+#[test]
+#[ignore]
+fn test_core() {
+    // The test binary lives in target/<profile>/deps, next to which the library is built
+    let exe = std::env::current_exe().unwrap();
+    let dir = exe.parent().and_then(|deps| deps.parent()).unwrap();
+    let path = dir.join(libloading::library_filename("chirp"));
+
+    unsafe {
+        let core = Library::new(&path).expect("build the shared library first");
+        macro_rules! sym {
+            ($name:literal: $ty:ty) => {{
+                let sym: Symbol<$ty> = core.get($name).unwrap();
+                sym
+            }};
+        }
+
+        assert_eq!(1, sym!(b"retro_api_version": extern "C" fn() -> c_uint)());
+        let mut info = MaybeUninit::<RetroSystemInfo>::uninit();
+        sym!(b"retro_get_system_info": unsafe extern "C" fn(*mut RetroSystemInfo))(
+            info.as_mut_ptr(),
+        );
+        let info = info.assume_init();
+        assert_eq!("chirp", CStr::from_ptr(info.library_name).to_str().unwrap());
+
+        type Set<T> = unsafe extern "C" fn(T);
+        sym!(b"retro_set_environment": Set<RetroEnvironment>)(environment);
+        sym!(b"retro_set_video_refresh": Set<RetroVideoRefresh>)(video_refresh);
+        sym!(b"retro_set_audio_sample_batch": Set<RetroAudioSampleBatch>)(audio_sample_batch);
+        sym!(b"retro_set_input_poll": Set<RetroInputPoll>)(input_poll);
+        sym!(b"retro_set_input_state": Set<RetroInputState>)(input_state);
+        sym!(b"retro_init": extern "C" fn())();
+
+        let rom = std::fs::read("./games/tetris.ch8").unwrap();
+        let game = RetroGameInfo {
+            path: ptr::null(),
+            data: rom.as_ptr() as *const c_void,
+            size: rom.len(),
+            meta: ptr::null(),
+        };
+        assert!(
+            sym!(b"retro_load_game": unsafe extern "C" fn(*const RetroGameInfo) -> bool)(&game)
+        );
+
+        let mut av = MaybeUninit::<RetroSystemAvInfo>::uninit();
+        sym!(b"retro_get_system_av_info": unsafe extern "C" fn(*mut RetroSystemAvInfo))(
+            av.as_mut_ptr(),
+        );
+        let av = av.assume_init();
+        assert_eq!((64, 32), (av.geometry.base_width, av.geometry.base_height));
+
+        let run = sym!(b"retro_run": extern "C" fn());
+        for _ in 0..60 {
+            run();
+        }
+        assert_eq!(60, FRAMES.load(Ordering::SeqCst));
+        assert!(LIT.load(Ordering::SeqCst) > 0);
+        assert_eq!(60 * 735, SAMPLES.load(Ordering::SeqCst));
+
+        let size = sym!(b"retro_serialize_size": extern "C" fn() -> usize)();
+        let mut state = vec![0u8; size];
+        let serialize = sym!(b"retro_serialize": unsafe extern "C" fn(*mut c_void, usize) -> bool);
+        assert!(serialize(state.as_mut_ptr() as *mut c_void, size));
+        let unserialize =
+            sym!(b"retro_unserialize": unsafe extern "C" fn(*const c_void, usize) -> bool);
+        assert!(unserialize(state.as_ptr() as *const c_void, size));
+
+        sym!(b"retro_unload_game": extern "C" fn())();
+        sym!(b"retro_deinit": extern "C" fn())();
+    }
+}
@@ -0,0 +1,80 @@
+//! The recompiled ROMs must behave exactly like the interpreter.
+use chirp::{loader::Loader, machine::Machine, recompiler};
+
+#[rustfmt::skip]
+#[path = "recompiled/tetris.rs"]
+mod tetris;
+
+#[rustfmt::skip]
+#[path = "recompiled/patched.rs"]
+mod patched;
+
+/// Run `frames` frames of a ROM with `run_frame`, pressing a few keys along the way
+fn replay<F>(mut machine: Machine, mut run_frame: F, frames: u32) -> Machine
+where
+    F: FnMut(&mut Machine) -> Result<(), chirp::machine::MachineError>,
+{
+    for frame in 0..frames {
+        // Move left, right, rotate and drop, holding each key for a few frames
+        let key = [4, 6, 5, 7][(frame / 40 % 4) as usize];
+        for k in [4, 5, 6, 7].iter() {
+            machine.keypad_mut().set(*k, *k == key && frame % 40 < 5);
+        }
+        run_frame(&mut machine).unwrap();
+    }
+    machine
+}
+
+/// Check that the module recompiled from `path` is the one `chirp recompile` writes today
+fn assert_up_to_date(path: &str, source: &str) {
+    let rom = Loader::new().load_path(path).unwrap();
+    let name = path.rsplit('/').next().unwrap();
+    let mut recompiled = Vec::new();
+    recompiler::write_module(&rom, name, &mut recompiled).unwrap();
+    assert_eq!(
+        source,
+        String::from_utf8(recompiled).unwrap(),
+        "regenerate with `chirp recompile {} -o {}`",
+        path.trim_start_matches("./"),
+        path.trim_start_matches("./").replace(".ch8", ".rs")
+    );
+}
+
+#[test]
+fn test_tetris_replay() {
+    let rom = Loader::new().load_path("./games/tetris.ch8").unwrap();
+    let interpreted = replay(Machine::from_rom(rom), Machine::run_frame, 1200);
+    let recompiled = replay(tetris::machine(), tetris::run_frame, 1200);
+
+    assert_eq!(interpreted.register(), recompiled.register());
+    assert_eq!(interpreted.memory(), recompiled.memory());
+    assert_eq!(interpreted.display(), recompiled.display());
+}
+
+#[test]
+fn test_tetris_up_to_date() {
+    assert_up_to_date("./games/tetris.ch8", include_str!("recompiled/tetris.rs"));
+}
+
+#[test]
+fn test_patched_replay() {
+    // The ROM rewrites its own `ADD V1, 0x01` into `ADD V1, 0x02`, so the recompiled block must
+    // give way to the interpreter from then on
+    let rom = Loader::new()
+        .load_path("./tests/recompiled/patched.ch8")
+        .unwrap();
+    let interpreted = replay(Machine::from_rom(rom), Machine::run_frame, 10);
+    let recompiled = replay(patched::machine(), patched::run_frame, 10);
+
+    assert_eq!(interpreted.register(), recompiled.register());
+    assert_eq!(interpreted.memory(), recompiled.memory());
+    assert!(recompiled.register().vs()[1] > 2);
+}
+
+#[test]
+fn test_patched_up_to_date() {
+    assert_up_to_date(
+        "./tests/recompiled/patched.ch8",
+        include_str!("recompiled/patched.rs"),
+    );
+}
@@ -0,0 +1,151 @@
+//! `patched.ch8`, recompiled by `chirp recompile`.
+//!
+//! SHA-1:  d6bfd9d5e80cae126a507e875d65c3ffe456c2fa
+//! Quirks: vblank, logic, the machine must run with the same quirks
+#![allow(dead_code)]
+
+use chirp::{
+    loader::Loader,
+    machine::{Machine, MachineError},
+    memory::Memory,
+};
+
+/// The ROM image
+#[rustfmt::skip]
+pub const ROM: &[u8] = &[
+    0x60, 0x02, 0x71, 0x01, 0xA2, 0x03, 0xF0, 0x55, 0xB2, 0x00,
+];
+
+/// The address the ROM is loaded at
+const ORIGIN: u16 = 0x200;
+
+/// A machine with the ROM loaded, using its recommended settings
+pub fn machine() -> Machine {
+    let rom = Loader::new()
+        .address(ORIGIN)
+        .load(ROM)
+        .expect("the ROM fits in memory");
+    Machine::from_rom(rom)
+}
+
+/// Run the block holding the PC, returns false when the instruction at the PC is left to
+/// the interpreter
+fn run_block(m: &mut Machine, frame: &mut Frame) -> bool {
+    let pc = m.pc();
+    match pc {
+        0x200..=0x206 if pc & 1 == 0 => block_200(m, pc, frame),
+        _ => false,
+    }
+}
+
+/// 0x200 to 0x206
+fn block_200(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x208) {
+        return false;
+    }
+    if pc <= 0x200 {
+        // LD V0, 0x02
+        let v = m.register_mut().vs_mut();
+        v[0x0] = 0x02;
+        if frame.next(m, 0x202) {
+            return true;
+        }
+    }
+    if pc <= 0x202 {
+        // ADD V1, 0x01
+        let v = m.register_mut().vs_mut();
+        v[0x1] = v[0x1].wrapping_add(0x01);
+        if frame.next(m, 0x204) {
+            return true;
+        }
+    }
+    if pc <= 0x204 {
+        // LD I, 0x203
+        m.register_mut().set_i(0x203);
+        if frame.next(m, 0x206) {
+            return true;
+        }
+    }
+    // LD [I], V0
+    let i = m.register().i();
+    if !in_memory(i, 1) {
+        return interpret(m, 0x206);
+    }
+    let v = *m.register().vs();
+    write(m, i, &v[..1]);
+    m.register_mut().set_i(i.wrapping_add(1));
+    frame.jump(m, 0x208)
+}
+
+/// The instructions left to run in the current frame
+struct Frame {
+    budget: u32,
+    /// Set when a draw waits for the vertical blank, which ends the frame
+    vblank: bool,
+}
+
+impl Frame {
+    /// Count an executed instruction, returns whether the frame is over, with the PC at `next`
+    fn next(&mut self, m: &mut Machine, next: u16) -> bool {
+        self.budget -= 1;
+        let over = self.budget == 0 || self.vblank;
+        if over {
+            m.register_mut().set_pc(next);
+        }
+        over
+    }
+
+    /// Count an executed instruction and carry on at `target`, returns true to leave the block
+    fn jump(&mut self, m: &mut Machine, target: u16) -> bool {
+        self.budget -= 1;
+        m.register_mut().set_pc(target & 0xFFF);
+        true
+    }
+}
+
+/// Leave the instruction at `pc` to the interpreter
+fn interpret(m: &mut Machine, pc: u16) -> bool {
+    m.register_mut().set_pc(pc);
+    false
+}
+
+/// Whether the code from `pc` to `end` is still the code that was recompiled
+fn unmodified(m: &Machine, pc: u16, end: u16) -> bool {
+    let (pc, end, origin) = (usize::from(pc), usize::from(end), usize::from(ORIGIN));
+    m.memory().as_bytes()[pc..end] == ROM[pc - origin..end - origin]
+}
+
+/// Whether the `len` bytes from `address` are in memory
+fn in_memory(address: u16, len: usize) -> bool {
+    usize::from(address) + len <= Memory::MEMORY_SIZE
+}
+
+/// Write `values` from `address`, which is checked to be in memory
+fn write(m: &mut Machine, address: u16, values: &[u8]) {
+    let memory = m.memory_mut();
+    for (offset, value) in values.iter().enumerate() {
+        memory
+            .poke(usize::from(address) + offset, *value)
+            .expect("the address is in memory");
+    }
+}
+
+/// Run a 60Hz frame, see `Machine::run_frame`
+pub fn run_frame(m: &mut Machine) -> Result<(), MachineError> {
+    if let Some(fault) = m.fault() {
+        return Err(*fault);
+    }
+    let mut frame = Frame {
+        budget: m.tickrate(),
+        vblank: false,
+    };
+    while frame.budget > 0 && !frame.vblank {
+        if !run_block(m, &mut frame) {
+            m.step()?;
+            frame.budget -= 1;
+            frame.vblank = m.waiting_for_vblank();
+        }
+    }
+    m.end_frame();
+    Ok(())
+}
@@ -0,0 +1,2292 @@
+//! `tetris.ch8`, recompiled by `chirp recompile`.
+//!
+//! SHA-1:  5f518084744bf3cb8733f6e5454dfd1634320563
+//! Quirks: vblank, logic, the machine must run with the same quirks
+#![allow(dead_code)]
+
+use chirp::{
+    loader::Loader,
+    machine::{Machine, MachineError},
+    memory::Memory,
+};
+
+/// The ROM image
+#[rustfmt::skip]
+pub const ROM: &[u8] = &[
+    0xA2, 0xB4, 0x23, 0xE6, 0x22, 0xB6, 0x70, 0x01, 0xD0, 0x11, 0x30, 0x25,
+    0x12, 0x06, 0x71, 0xFF, 0xD0, 0x11, 0x60, 0x1A, 0xD0, 0x11, 0x60, 0x25,
+    0x31, 0x00, 0x12, 0x0E, 0xC4, 0x70, 0x44, 0x70, 0x12, 0x1C, 0xC3, 0x03,
+    0x60, 0x1E, 0x61, 0x03, 0x22, 0x5C, 0xF5, 0x15, 0xD0, 0x14, 0x3F, 0x01,
+    0x12, 0x3C, 0xD0, 0x14, 0x71, 0xFF, 0xD0, 0x14, 0x23, 0x40, 0x12, 0x1C,
+    0xE7, 0xA1, 0x22, 0x72, 0xE8, 0xA1, 0x22, 0x84, 0xE9, 0xA1, 0x22, 0x96,
+    0xE2, 0x9E, 0x12, 0x50, 0x66, 0x00, 0xF6, 0x15, 0xF6, 0x07, 0x36, 0x00,
+    0x12, 0x3C, 0xD0, 0x14, 0x71, 0x01, 0x12, 0x2A, 0xA2, 0xC4, 0xF4, 0x1E,
+    0x66, 0x00, 0x43, 0x01, 0x66, 0x04, 0x43, 0x02, 0x66, 0x08, 0x43, 0x03,
+    0x66, 0x0C, 0xF6, 0x1E, 0x00, 0xEE, 0xD0, 0x14, 0x70, 0xFF, 0x23, 0x34,
+    0x3F, 0x01, 0x00, 0xEE, 0xD0, 0x14, 0x70, 0x01, 0x23, 0x34, 0x00, 0xEE,
+    0xD0, 0x14, 0x70, 0x01, 0x23, 0x34, 0x3F, 0x01, 0x00, 0xEE, 0xD0, 0x14,
+    0x70, 0xFF, 0x23, 0x34, 0x00, 0xEE, 0xD0, 0x14, 0x73, 0x01, 0x43, 0x04,
+    0x63, 0x00, 0x22, 0x5C, 0x23, 0x34, 0x3F, 0x01, 0x00, 0xEE, 0xD0, 0x14,
+    0x73, 0xFF, 0x43, 0xFF, 0x63, 0x03, 0x22, 0x5C, 0x23, 0x34, 0x00, 0xEE,
+    0x80, 0x00, 0x67, 0x05, 0x68, 0x06, 0x69, 0x04, 0x61, 0x1F, 0x65, 0x10,
+    0x62, 0x07, 0x00, 0xEE, 0x40, 0xE0, 0x00, 0x00, 0x40, 0xC0, 0x40, 0x00,
+    0x00, 0xE0, 0x40, 0x00, 0x40, 0x60, 0x40, 0x00, 0x40, 0x40, 0x60, 0x00,
+    0x20, 0xE0, 0x00, 0x00, 0xC0, 0x40, 0x40, 0x00, 0x00, 0xE0, 0x80, 0x00,
+    0x40, 0x40, 0xC0, 0x00, 0x00, 0xE0, 0x20, 0x00, 0x60, 0x40, 0x40, 0x00,
+    0x80, 0xE0, 0x00, 0x00, 0x40, 0xC0, 0x80, 0x00, 0xC0, 0x60, 0x00, 0x00,
+    0x40, 0xC0, 0x80, 0x00, 0xC0, 0x60, 0x00, 0x00, 0x80, 0xC0, 0x40, 0x00,
+    0x00, 0x60, 0xC0, 0x00, 0x80, 0xC0, 0x40, 0x00, 0x00, 0x60, 0xC0, 0x00,
+    0xC0, 0xC0, 0x00, 0x00, 0xC0, 0xC0, 0x00, 0x00, 0xC0, 0xC0, 0x00, 0x00,
+    0xC0, 0xC0, 0x00, 0x00, 0x40, 0x40, 0x40, 0x40, 0x00, 0xF0, 0x00, 0x00,
+    0x40, 0x40, 0x40, 0x40, 0x00, 0xF0, 0x00, 0x00, 0xD0, 0x14, 0x66, 0x35,
+    0x76, 0xFF, 0x36, 0x00, 0x13, 0x38, 0x00, 0xEE, 0xA2, 0xB4, 0x8C, 0x10,
+    0x3C, 0x1E, 0x7C, 0x01, 0x3C, 0x1E, 0x7C, 0x01, 0x3C, 0x1E, 0x7C, 0x01,
+    0x23, 0x5E, 0x4B, 0x0A, 0x23, 0x72, 0x91, 0xC0, 0x00, 0xEE, 0x71, 0x01,
+    0x13, 0x50, 0x60, 0x1B, 0x6B, 0x00, 0xD0, 0x11, 0x3F, 0x00, 0x7B, 0x01,
+    0xD0, 0x11, 0x70, 0x01, 0x30, 0x25, 0x13, 0x62, 0x00, 0xEE, 0x60, 0x1B,
+    0xD0, 0x11, 0x70, 0x01, 0x30, 0x25, 0x13, 0x74, 0x8E, 0x10, 0x8D, 0xE0,
+    0x7E, 0xFF, 0x60, 0x1B, 0x6B, 0x00, 0xD0, 0xE1, 0x3F, 0x00, 0x13, 0x90,
+    0xD0, 0xE1, 0x13, 0x94, 0xD0, 0xD1, 0x7B, 0x01, 0x70, 0x01, 0x30, 0x25,
+    0x13, 0x86, 0x4B, 0x00, 0x13, 0xA6, 0x7D, 0xFF, 0x7E, 0xFF, 0x3D, 0x01,
+    0x13, 0x82, 0x23, 0xC0, 0x3F, 0x01, 0x23, 0xC0, 0x7A, 0x01, 0x23, 0xC0,
+    0x80, 0xA0, 0x6D, 0x07, 0x80, 0xD2, 0x40, 0x04, 0x75, 0xFE, 0x45, 0x02,
+    0x65, 0x04, 0x00, 0xEE, 0xA7, 0x00, 0xF2, 0x55, 0xA8, 0x04, 0xFA, 0x33,
+    0xF2, 0x65, 0xF0, 0x29, 0x6D, 0x32, 0x6E, 0x00, 0xDD, 0xE5, 0x7D, 0x05,
+    0xF1, 0x29, 0xDD, 0xE5, 0x7D, 0x05, 0xF2, 0x29, 0xDD, 0xE5, 0xA7, 0x00,
+    0xF2, 0x65, 0xA2, 0xB4, 0x00, 0xEE, 0x6A, 0x00, 0x60, 0x19, 0x00, 0xEE,
+    0x37, 0x23,
+];
+
+/// The address the ROM is loaded at
+const ORIGIN: u16 = 0x200;
+
+/// A machine with the ROM loaded, using its recommended settings
+pub fn machine() -> Machine {
+    let rom = Loader::new()
+        .address(ORIGIN)
+        .load(ROM)
+        .expect("the ROM fits in memory");
+    Machine::from_rom(rom)
+}
+
+/// Run the block holding the PC, returns false when the instruction at the PC is left to
+/// the interpreter
+fn run_block(m: &mut Machine, frame: &mut Frame) -> bool {
+    let pc = m.pc();
+    match pc {
+        0x200..=0x202 if pc & 1 == 0 => block_200(m, pc, frame),
+        0x204 => block_204(m, pc, frame),
+        0x206..=0x20A if pc & 1 == 0 => block_206(m, pc, frame),
+        0x20C => block_20c(m, pc, frame),
+        0x20E..=0x218 if pc & 1 == 0 => block_20e(m, pc, frame),
+        0x21A => block_21a(m, pc, frame),
+        0x21C..=0x21E if pc & 1 == 0 => block_21c(m, pc, frame),
+        0x220 => block_220(m, pc, frame),
+        0x222..=0x228 if pc & 1 == 0 => block_222(m, pc, frame),
+        0x22A..=0x22E if pc & 1 == 0 => block_22a(m, pc, frame),
+        0x230 => block_230(m, pc, frame),
+        0x232..=0x238 if pc & 1 == 0 => block_232(m, pc, frame),
+        0x23A => block_23a(m, pc, frame),
+        0x23C => block_23c(m, pc, frame),
+        0x23E => block_23e(m, pc, frame),
+        0x240 => block_240(m, pc, frame),
+        0x242 => block_242(m, pc, frame),
+        0x244 => block_244(m, pc, frame),
+        0x246 => block_246(m, pc, frame),
+        0x248 => block_248(m, pc, frame),
+        0x24A => block_24a(m, pc, frame),
+        0x24C..=0x24E if pc & 1 == 0 => block_24c(m, pc, frame),
+        0x250..=0x252 if pc & 1 == 0 => block_250(m, pc, frame),
+        0x254 => block_254(m, pc, frame),
+        0x256..=0x25A if pc & 1 == 0 => block_256(m, pc, frame),
+        0x25C..=0x262 if pc & 1 == 0 => block_25c(m, pc, frame),
+        0x264 => block_264(m, pc, frame),
+        0x266 => block_266(m, pc, frame),
+        0x268 => block_268(m, pc, frame),
+        0x26A => block_26a(m, pc, frame),
+        0x26C => block_26c(m, pc, frame),
+        0x26E..=0x270 if pc & 1 == 0 => block_26e(m, pc, frame),
+        0x272..=0x276 if pc & 1 == 0 => block_272(m, pc, frame),
+        0x278 => block_278(m, pc, frame),
+        0x27A => block_27a(m, pc, frame),
+        0x27C..=0x280 if pc & 1 == 0 => block_27c(m, pc, frame),
+        0x282 => block_282(m, pc, frame),
+        0x284..=0x288 if pc & 1 == 0 => block_284(m, pc, frame),
+        0x28A => block_28a(m, pc, frame),
+        0x28C => block_28c(m, pc, frame),
+        0x28E..=0x292 if pc & 1 == 0 => block_28e(m, pc, frame),
+        0x294 => block_294(m, pc, frame),
+        0x296..=0x29A if pc & 1 == 0 => block_296(m, pc, frame),
+        0x29C => block_29c(m, pc, frame),
+        0x29E => block_29e(m, pc, frame),
+        0x2A0 => block_2a0(m, pc, frame),
+        0x2A2 => block_2a2(m, pc, frame),
+        0x2A4 => block_2a4(m, pc, frame),
+        0x2A6..=0x2AA if pc & 1 == 0 => block_2a6(m, pc, frame),
+        0x2AC => block_2ac(m, pc, frame),
+        0x2AE => block_2ae(m, pc, frame),
+        0x2B0 => block_2b0(m, pc, frame),
+        0x2B2 => block_2b2(m, pc, frame),
+        0x2B6..=0x2C2 if pc & 1 == 0 => block_2b6(m, pc, frame),
+        0x334..=0x336 if pc & 1 == 0 => block_334(m, pc, frame),
+        0x338..=0x33A if pc & 1 == 0 => block_338(m, pc, frame),
+        0x33C => block_33c(m, pc, frame),
+        0x33E => block_33e(m, pc, frame),
+        0x340..=0x344 if pc & 1 == 0 => block_340(m, pc, frame),
+        0x346 => block_346(m, pc, frame),
+        0x348 => block_348(m, pc, frame),
+        0x34A => block_34a(m, pc, frame),
+        0x34C => block_34c(m, pc, frame),
+        0x34E => block_34e(m, pc, frame),
+        0x350 => block_350(m, pc, frame),
+        0x352 => block_352(m, pc, frame),
+        0x354 => block_354(m, pc, frame),
+        0x356 => block_356(m, pc, frame),
+        0x358 => block_358(m, pc, frame),
+        0x35A..=0x35C if pc & 1 == 0 => block_35a(m, pc, frame),
+        0x35E..=0x360 if pc & 1 == 0 => block_35e(m, pc, frame),
+        0x362..=0x364 if pc & 1 == 0 => block_362(m, pc, frame),
+        0x366 => block_366(m, pc, frame),
+        0x368..=0x36C if pc & 1 == 0 => block_368(m, pc, frame),
+        0x36E => block_36e(m, pc, frame),
+        0x370 => block_370(m, pc, frame),
+        0x372 => block_372(m, pc, frame),
+        0x374..=0x378 if pc & 1 == 0 => block_374(m, pc, frame),
+        0x37A => block_37a(m, pc, frame),
+        0x37C..=0x380 if pc & 1 == 0 => block_37c(m, pc, frame),
+        0x382..=0x384 if pc & 1 == 0 => block_382(m, pc, frame),
+        0x386..=0x388 if pc & 1 == 0 => block_386(m, pc, frame),
+        0x38A => block_38a(m, pc, frame),
+        0x38C..=0x38E if pc & 1 == 0 => block_38c(m, pc, frame),
+        0x390..=0x392 if pc & 1 == 0 => block_390(m, pc, frame),
+        0x394..=0x396 if pc & 1 == 0 => block_394(m, pc, frame),
+        0x398 => block_398(m, pc, frame),
+        0x39A => block_39a(m, pc, frame),
+        0x39C => block_39c(m, pc, frame),
+        0x39E..=0x3A2 if pc & 1 == 0 => block_39e(m, pc, frame),
+        0x3A4 => block_3a4(m, pc, frame),
+        0x3A6 => block_3a6(m, pc, frame),
+        0x3A8 => block_3a8(m, pc, frame),
+        0x3AA => block_3aa(m, pc, frame),
+        0x3AC..=0x3AE if pc & 1 == 0 => block_3ac(m, pc, frame),
+        0x3B0..=0x3B6 if pc & 1 == 0 => block_3b0(m, pc, frame),
+        0x3B8 => block_3b8(m, pc, frame),
+        0x3BA => block_3ba(m, pc, frame),
+        0x3BC => block_3bc(m, pc, frame),
+        0x3BE => block_3be(m, pc, frame),
+        0x3C0..=0x3C2 if pc & 1 == 0 => block_3c0(m, pc, frame),
+        0x3C4..=0x3C6 if pc & 1 == 0 => block_3c4(m, pc, frame),
+        0x3C8..=0x3E4 if pc & 1 == 0 => block_3c8(m, pc, frame),
+        0x3E6..=0x3EA if pc & 1 == 0 => block_3e6(m, pc, frame),
+        _ => false,
+    }
+}
+
+/// 0x200 to 0x202
+fn block_200(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x204) {
+        return false;
+    }
+    if pc <= 0x200 {
+        // LD I, 0x2B4
+        m.register_mut().set_i(0x2B4);
+        if frame.next(m, 0x202) {
+            return true;
+        }
+    }
+    // CALL 0x3E6
+    if m.register_mut().push(0x204).is_err() {
+        return interpret(m, 0x202);
+    }
+    frame.jump(m, 0x3E6)
+}
+
+/// 0x204 to 0x204
+fn block_204(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x206) {
+        return false;
+    }
+    // CALL 0x2B6
+    if m.register_mut().push(0x206).is_err() {
+        return interpret(m, 0x204);
+    }
+    frame.jump(m, 0x2B6)
+}
+
+/// 0x206 to 0x20A
+fn block_206(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x20C) {
+        return false;
+    }
+    if pc <= 0x206 {
+        // ADD V0, 0x01
+        let v = m.register_mut().vs_mut();
+        v[0x0] = v[0x0].wrapping_add(0x01);
+        if frame.next(m, 0x208) {
+            return true;
+        }
+    }
+    if pc <= 0x208 {
+        // DRW V0, V1, 0x1
+        let i = m.register().i();
+        if !in_memory(i, 1) {
+            return interpret(m, 0x208);
+        }
+        let mut sprite = [0; 1];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..1]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0x1]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x20A) {
+            return true;
+        }
+    }
+    // SE V0, 0x25
+    let skip = m.register().vs()[0x0] == 0x25;
+    frame.jump(m, if skip { 0x20E } else { 0x20C })
+}
+
+/// 0x20C to 0x20C
+fn block_20c(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x20E) {
+        return false;
+    }
+    // JP 0x206
+    frame.jump(m, 0x206)
+}
+
+/// 0x20E to 0x218
+fn block_20e(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x21A) {
+        return false;
+    }
+    if pc <= 0x20E {
+        // ADD V1, 0xFF
+        let v = m.register_mut().vs_mut();
+        v[0x1] = v[0x1].wrapping_add(0xFF);
+        if frame.next(m, 0x210) {
+            return true;
+        }
+    }
+    if pc <= 0x210 {
+        // DRW V0, V1, 0x1
+        let i = m.register().i();
+        if !in_memory(i, 1) {
+            return interpret(m, 0x210);
+        }
+        let mut sprite = [0; 1];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..1]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0x1]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x212) {
+            return true;
+        }
+    }
+    if pc <= 0x212 {
+        // LD V0, 0x1A
+        let v = m.register_mut().vs_mut();
+        v[0x0] = 0x1A;
+        if frame.next(m, 0x214) {
+            return true;
+        }
+    }
+    if pc <= 0x214 {
+        // DRW V0, V1, 0x1
+        let i = m.register().i();
+        if !in_memory(i, 1) {
+            return interpret(m, 0x214);
+        }
+        let mut sprite = [0; 1];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..1]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0x1]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x216) {
+            return true;
+        }
+    }
+    if pc <= 0x216 {
+        // LD V0, 0x25
+        let v = m.register_mut().vs_mut();
+        v[0x0] = 0x25;
+        if frame.next(m, 0x218) {
+            return true;
+        }
+    }
+    // SE V1, 0x00
+    let skip = m.register().vs()[0x1] == 0x00;
+    frame.jump(m, if skip { 0x21C } else { 0x21A })
+}
+
+/// 0x21A to 0x21A
+fn block_21a(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x21C) {
+        return false;
+    }
+    // JP 0x20E
+    frame.jump(m, 0x20E)
+}
+
+/// 0x21C to 0x21E
+fn block_21c(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x220) {
+        return false;
+    }
+    if pc <= 0x21C {
+        // RND V4, 0x70
+        let random = m.random();
+        m.register_mut().vs_mut()[0x4] = random & 0x70;
+        if frame.next(m, 0x21E) {
+            return true;
+        }
+    }
+    // SNE V4, 0x70
+    let skip = m.register().vs()[0x4] != 0x70;
+    frame.jump(m, if skip { 0x222 } else { 0x220 })
+}
+
+/// 0x220 to 0x220
+fn block_220(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x222) {
+        return false;
+    }
+    // JP 0x21C
+    frame.jump(m, 0x21C)
+}
+
+/// 0x222 to 0x228
+fn block_222(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x22A) {
+        return false;
+    }
+    if pc <= 0x222 {
+        // RND V3, 0x03
+        let random = m.random();
+        m.register_mut().vs_mut()[0x3] = random & 0x03;
+        if frame.next(m, 0x224) {
+            return true;
+        }
+    }
+    if pc <= 0x224 {
+        // LD V0, 0x1E
+        let v = m.register_mut().vs_mut();
+        v[0x0] = 0x1E;
+        if frame.next(m, 0x226) {
+            return true;
+        }
+    }
+    if pc <= 0x226 {
+        // LD V1, 0x03
+        let v = m.register_mut().vs_mut();
+        v[0x1] = 0x03;
+        if frame.next(m, 0x228) {
+            return true;
+        }
+    }
+    // CALL 0x25C
+    if m.register_mut().push(0x22A).is_err() {
+        return interpret(m, 0x228);
+    }
+    frame.jump(m, 0x25C)
+}
+
+/// 0x22A to 0x22E
+fn block_22a(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x230) {
+        return false;
+    }
+    if pc <= 0x22A {
+        // LD DT, V5
+        let r = m.register_mut();
+        let vx = r.vs()[0x5];
+        r.set_dt(vx);
+        if frame.next(m, 0x22C) {
+            return true;
+        }
+    }
+    if pc <= 0x22C {
+        // DRW V0, V1, 0x4
+        let i = m.register().i();
+        if !in_memory(i, 4) {
+            return interpret(m, 0x22C);
+        }
+        let mut sprite = [0; 4];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..4]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0x1]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x22E) {
+            return true;
+        }
+    }
+    // SE VF, 0x01
+    let skip = m.register().vs()[0xF] == 0x01;
+    frame.jump(m, if skip { 0x232 } else { 0x230 })
+}
+
+/// 0x230 to 0x230
+fn block_230(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x232) {
+        return false;
+    }
+    // JP 0x23C
+    frame.jump(m, 0x23C)
+}
+
+/// 0x232 to 0x238
+fn block_232(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x23A) {
+        return false;
+    }
+    if pc <= 0x232 {
+        // DRW V0, V1, 0x4
+        let i = m.register().i();
+        if !in_memory(i, 4) {
+            return interpret(m, 0x232);
+        }
+        let mut sprite = [0; 4];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..4]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0x1]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x234) {
+            return true;
+        }
+    }
+    if pc <= 0x234 {
+        // ADD V1, 0xFF
+        let v = m.register_mut().vs_mut();
+        v[0x1] = v[0x1].wrapping_add(0xFF);
+        if frame.next(m, 0x236) {
+            return true;
+        }
+    }
+    if pc <= 0x236 {
+        // DRW V0, V1, 0x4
+        let i = m.register().i();
+        if !in_memory(i, 4) {
+            return interpret(m, 0x236);
+        }
+        let mut sprite = [0; 4];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..4]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0x1]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x238) {
+            return true;
+        }
+    }
+    // CALL 0x340
+    if m.register_mut().push(0x23A).is_err() {
+        return interpret(m, 0x238);
+    }
+    frame.jump(m, 0x340)
+}
+
+/// 0x23A to 0x23A
+fn block_23a(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x23C) {
+        return false;
+    }
+    // JP 0x21C
+    frame.jump(m, 0x21C)
+}
+
+/// 0x23C to 0x23C
+fn block_23c(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x23E) {
+        return false;
+    }
+    // SNKP V7
+    let skip = !m.keypad().is_pressed(m.register().vs()[0x7]);
+    frame.jump(m, if skip { 0x240 } else { 0x23E })
+}
+
+/// 0x23E to 0x23E
+fn block_23e(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x240) {
+        return false;
+    }
+    // CALL 0x272
+    if m.register_mut().push(0x240).is_err() {
+        return interpret(m, 0x23E);
+    }
+    frame.jump(m, 0x272)
+}
+
+/// 0x240 to 0x240
+fn block_240(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x242) {
+        return false;
+    }
+    // SNKP V8
+    let skip = !m.keypad().is_pressed(m.register().vs()[0x8]);
+    frame.jump(m, if skip { 0x244 } else { 0x242 })
+}
+
+/// 0x242 to 0x242
+fn block_242(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x244) {
+        return false;
+    }
+    // CALL 0x284
+    if m.register_mut().push(0x244).is_err() {
+        return interpret(m, 0x242);
+    }
+    frame.jump(m, 0x284)
+}
+
+/// 0x244 to 0x244
+fn block_244(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x246) {
+        return false;
+    }
+    // SNKP V9
+    let skip = !m.keypad().is_pressed(m.register().vs()[0x9]);
+    frame.jump(m, if skip { 0x248 } else { 0x246 })
+}
+
+/// 0x246 to 0x246
+fn block_246(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x248) {
+        return false;
+    }
+    // CALL 0x296
+    if m.register_mut().push(0x248).is_err() {
+        return interpret(m, 0x246);
+    }
+    frame.jump(m, 0x296)
+}
+
+/// 0x248 to 0x248
+fn block_248(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x24A) {
+        return false;
+    }
+    // SKP V2
+    let skip = m.keypad().is_pressed(m.register().vs()[0x2]);
+    frame.jump(m, if skip { 0x24C } else { 0x24A })
+}
+
+/// 0x24A to 0x24A
+fn block_24a(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x24C) {
+        return false;
+    }
+    // JP 0x250
+    frame.jump(m, 0x250)
+}
+
+/// 0x24C to 0x24E
+fn block_24c(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x250) {
+        return false;
+    }
+    if pc <= 0x24C {
+        // LD V6, 0x00
+        let v = m.register_mut().vs_mut();
+        v[0x6] = 0x00;
+        if frame.next(m, 0x24E) {
+            return true;
+        }
+    }
+    // LD DT, V6
+    let r = m.register_mut();
+    let vx = r.vs()[0x6];
+    r.set_dt(vx);
+    frame.jump(m, 0x250)
+}
+
+/// 0x250 to 0x252
+fn block_250(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x254) {
+        return false;
+    }
+    if pc <= 0x250 {
+        // LD V6, DT
+        let r = m.register_mut();
+        let dt = r.dt();
+        r.vs_mut()[0x6] = dt;
+        if frame.next(m, 0x252) {
+            return true;
+        }
+    }
+    // SE V6, 0x00
+    let skip = m.register().vs()[0x6] == 0x00;
+    frame.jump(m, if skip { 0x256 } else { 0x254 })
+}
+
+/// 0x254 to 0x254
+fn block_254(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x256) {
+        return false;
+    }
+    // JP 0x23C
+    frame.jump(m, 0x23C)
+}
+
+/// 0x256 to 0x25A
+fn block_256(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x25C) {
+        return false;
+    }
+    if pc <= 0x256 {
+        // DRW V0, V1, 0x4
+        let i = m.register().i();
+        if !in_memory(i, 4) {
+            return interpret(m, 0x256);
+        }
+        let mut sprite = [0; 4];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..4]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0x1]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x258) {
+            return true;
+        }
+    }
+    if pc <= 0x258 {
+        // ADD V1, 0x01
+        let v = m.register_mut().vs_mut();
+        v[0x1] = v[0x1].wrapping_add(0x01);
+        if frame.next(m, 0x25A) {
+            return true;
+        }
+    }
+    // JP 0x22A
+    frame.jump(m, 0x22A)
+}
+
+/// 0x25C to 0x262
+fn block_25c(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x264) {
+        return false;
+    }
+    if pc <= 0x25C {
+        // LD I, 0x2C4
+        m.register_mut().set_i(0x2C4);
+        if frame.next(m, 0x25E) {
+            return true;
+        }
+    }
+    if pc <= 0x25E {
+        // ADD I, V4
+        let r = m.register_mut();
+        let vx = r.vs()[0x4];
+        r.set_i(r.i().wrapping_add(u16::from(vx)));
+        if frame.next(m, 0x260) {
+            return true;
+        }
+    }
+    if pc <= 0x260 {
+        // LD V6, 0x00
+        let v = m.register_mut().vs_mut();
+        v[0x6] = 0x00;
+        if frame.next(m, 0x262) {
+            return true;
+        }
+    }
+    // SNE V3, 0x01
+    let skip = m.register().vs()[0x3] != 0x01;
+    frame.jump(m, if skip { 0x266 } else { 0x264 })
+}
+
+/// 0x264 to 0x264
+fn block_264(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x266) {
+        return false;
+    }
+    // LD V6, 0x04
+    let v = m.register_mut().vs_mut();
+    v[0x6] = 0x04;
+    frame.jump(m, 0x266)
+}
+
+/// 0x266 to 0x266
+fn block_266(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x268) {
+        return false;
+    }
+    // SNE V3, 0x02
+    let skip = m.register().vs()[0x3] != 0x02;
+    frame.jump(m, if skip { 0x26A } else { 0x268 })
+}
+
+/// 0x268 to 0x268
+fn block_268(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x26A) {
+        return false;
+    }
+    // LD V6, 0x08
+    let v = m.register_mut().vs_mut();
+    v[0x6] = 0x08;
+    frame.jump(m, 0x26A)
+}
+
+/// 0x26A to 0x26A
+fn block_26a(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x26C) {
+        return false;
+    }
+    // SNE V3, 0x03
+    let skip = m.register().vs()[0x3] != 0x03;
+    frame.jump(m, if skip { 0x26E } else { 0x26C })
+}
+
+/// 0x26C to 0x26C
+fn block_26c(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x26E) {
+        return false;
+    }
+    // LD V6, 0x0C
+    let v = m.register_mut().vs_mut();
+    v[0x6] = 0x0C;
+    frame.jump(m, 0x26E)
+}
+
+/// 0x26E to 0x270
+fn block_26e(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x272) {
+        return false;
+    }
+    if pc <= 0x26E {
+        // ADD I, V6
+        let r = m.register_mut();
+        let vx = r.vs()[0x6];
+        r.set_i(r.i().wrapping_add(u16::from(vx)));
+        if frame.next(m, 0x270) {
+            return true;
+        }
+    }
+    // RET
+    let next = match m.register_mut().pop() {
+        Some(next) => next,
+        None => return interpret(m, 0x270),
+    };
+    frame.jump(m, next)
+}
+
+/// 0x272 to 0x276
+fn block_272(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x278) {
+        return false;
+    }
+    if pc <= 0x272 {
+        // DRW V0, V1, 0x4
+        let i = m.register().i();
+        if !in_memory(i, 4) {
+            return interpret(m, 0x272);
+        }
+        let mut sprite = [0; 4];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..4]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0x1]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x274) {
+            return true;
+        }
+    }
+    if pc <= 0x274 {
+        // ADD V0, 0xFF
+        let v = m.register_mut().vs_mut();
+        v[0x0] = v[0x0].wrapping_add(0xFF);
+        if frame.next(m, 0x276) {
+            return true;
+        }
+    }
+    // CALL 0x334
+    if m.register_mut().push(0x278).is_err() {
+        return interpret(m, 0x276);
+    }
+    frame.jump(m, 0x334)
+}
+
+/// 0x278 to 0x278
+fn block_278(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x27A) {
+        return false;
+    }
+    // SE VF, 0x01
+    let skip = m.register().vs()[0xF] == 0x01;
+    frame.jump(m, if skip { 0x27C } else { 0x27A })
+}
+
+/// 0x27A to 0x27A
+fn block_27a(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x27C) {
+        return false;
+    }
+    // RET
+    let next = match m.register_mut().pop() {
+        Some(next) => next,
+        None => return interpret(m, 0x27A),
+    };
+    frame.jump(m, next)
+}
+
+/// 0x27C to 0x280
+fn block_27c(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x282) {
+        return false;
+    }
+    if pc <= 0x27C {
+        // DRW V0, V1, 0x4
+        let i = m.register().i();
+        if !in_memory(i, 4) {
+            return interpret(m, 0x27C);
+        }
+        let mut sprite = [0; 4];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..4]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0x1]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x27E) {
+            return true;
+        }
+    }
+    if pc <= 0x27E {
+        // ADD V0, 0x01
+        let v = m.register_mut().vs_mut();
+        v[0x0] = v[0x0].wrapping_add(0x01);
+        if frame.next(m, 0x280) {
+            return true;
+        }
+    }
+    // CALL 0x334
+    if m.register_mut().push(0x282).is_err() {
+        return interpret(m, 0x280);
+    }
+    frame.jump(m, 0x334)
+}
+
+/// 0x282 to 0x282
+fn block_282(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x284) {
+        return false;
+    }
+    // RET
+    let next = match m.register_mut().pop() {
+        Some(next) => next,
+        None => return interpret(m, 0x282),
+    };
+    frame.jump(m, next)
+}
+
+/// 0x284 to 0x288
+fn block_284(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x28A) {
+        return false;
+    }
+    if pc <= 0x284 {
+        // DRW V0, V1, 0x4
+        let i = m.register().i();
+        if !in_memory(i, 4) {
+            return interpret(m, 0x284);
+        }
+        let mut sprite = [0; 4];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..4]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0x1]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x286) {
+            return true;
+        }
+    }
+    if pc <= 0x286 {
+        // ADD V0, 0x01
+        let v = m.register_mut().vs_mut();
+        v[0x0] = v[0x0].wrapping_add(0x01);
+        if frame.next(m, 0x288) {
+            return true;
+        }
+    }
+    // CALL 0x334
+    if m.register_mut().push(0x28A).is_err() {
+        return interpret(m, 0x288);
+    }
+    frame.jump(m, 0x334)
+}
+
+/// 0x28A to 0x28A
+fn block_28a(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x28C) {
+        return false;
+    }
+    // SE VF, 0x01
+    let skip = m.register().vs()[0xF] == 0x01;
+    frame.jump(m, if skip { 0x28E } else { 0x28C })
+}
+
+/// 0x28C to 0x28C
+fn block_28c(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x28E) {
+        return false;
+    }
+    // RET
+    let next = match m.register_mut().pop() {
+        Some(next) => next,
+        None => return interpret(m, 0x28C),
+    };
+    frame.jump(m, next)
+}
+
+/// 0x28E to 0x292
+fn block_28e(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x294) {
+        return false;
+    }
+    if pc <= 0x28E {
+        // DRW V0, V1, 0x4
+        let i = m.register().i();
+        if !in_memory(i, 4) {
+            return interpret(m, 0x28E);
+        }
+        let mut sprite = [0; 4];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..4]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0x1]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x290) {
+            return true;
+        }
+    }
+    if pc <= 0x290 {
+        // ADD V0, 0xFF
+        let v = m.register_mut().vs_mut();
+        v[0x0] = v[0x0].wrapping_add(0xFF);
+        if frame.next(m, 0x292) {
+            return true;
+        }
+    }
+    // CALL 0x334
+    if m.register_mut().push(0x294).is_err() {
+        return interpret(m, 0x292);
+    }
+    frame.jump(m, 0x334)
+}
+
+/// 0x294 to 0x294
+fn block_294(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x296) {
+        return false;
+    }
+    // RET
+    let next = match m.register_mut().pop() {
+        Some(next) => next,
+        None => return interpret(m, 0x294),
+    };
+    frame.jump(m, next)
+}
+
+/// 0x296 to 0x29A
+fn block_296(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x29C) {
+        return false;
+    }
+    if pc <= 0x296 {
+        // DRW V0, V1, 0x4
+        let i = m.register().i();
+        if !in_memory(i, 4) {
+            return interpret(m, 0x296);
+        }
+        let mut sprite = [0; 4];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..4]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0x1]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x298) {
+            return true;
+        }
+    }
+    if pc <= 0x298 {
+        // ADD V3, 0x01
+        let v = m.register_mut().vs_mut();
+        v[0x3] = v[0x3].wrapping_add(0x01);
+        if frame.next(m, 0x29A) {
+            return true;
+        }
+    }
+    // SNE V3, 0x04
+    let skip = m.register().vs()[0x3] != 0x04;
+    frame.jump(m, if skip { 0x29E } else { 0x29C })
+}
+
+/// 0x29C to 0x29C
+fn block_29c(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x29E) {
+        return false;
+    }
+    // LD V3, 0x00
+    let v = m.register_mut().vs_mut();
+    v[0x3] = 0x00;
+    frame.jump(m, 0x29E)
+}
+
+/// 0x29E to 0x29E
+fn block_29e(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x2A0) {
+        return false;
+    }
+    // CALL 0x25C
+    if m.register_mut().push(0x2A0).is_err() {
+        return interpret(m, 0x29E);
+    }
+    frame.jump(m, 0x25C)
+}
+
+/// 0x2A0 to 0x2A0
+fn block_2a0(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x2A2) {
+        return false;
+    }
+    // CALL 0x334
+    if m.register_mut().push(0x2A2).is_err() {
+        return interpret(m, 0x2A0);
+    }
+    frame.jump(m, 0x334)
+}
+
+/// 0x2A2 to 0x2A2
+fn block_2a2(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x2A4) {
+        return false;
+    }
+    // SE VF, 0x01
+    let skip = m.register().vs()[0xF] == 0x01;
+    frame.jump(m, if skip { 0x2A6 } else { 0x2A4 })
+}
+
+/// 0x2A4 to 0x2A4
+fn block_2a4(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x2A6) {
+        return false;
+    }
+    // RET
+    let next = match m.register_mut().pop() {
+        Some(next) => next,
+        None => return interpret(m, 0x2A4),
+    };
+    frame.jump(m, next)
+}
+
+/// 0x2A6 to 0x2AA
+fn block_2a6(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x2AC) {
+        return false;
+    }
+    if pc <= 0x2A6 {
+        // DRW V0, V1, 0x4
+        let i = m.register().i();
+        if !in_memory(i, 4) {
+            return interpret(m, 0x2A6);
+        }
+        let mut sprite = [0; 4];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..4]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0x1]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x2A8) {
+            return true;
+        }
+    }
+    if pc <= 0x2A8 {
+        // ADD V3, 0xFF
+        let v = m.register_mut().vs_mut();
+        v[0x3] = v[0x3].wrapping_add(0xFF);
+        if frame.next(m, 0x2AA) {
+            return true;
+        }
+    }
+    // SNE V3, 0xFF
+    let skip = m.register().vs()[0x3] != 0xFF;
+    frame.jump(m, if skip { 0x2AE } else { 0x2AC })
+}
+
+/// 0x2AC to 0x2AC
+fn block_2ac(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x2AE) {
+        return false;
+    }
+    // LD V3, 0x03
+    let v = m.register_mut().vs_mut();
+    v[0x3] = 0x03;
+    frame.jump(m, 0x2AE)
+}
+
+/// 0x2AE to 0x2AE
+fn block_2ae(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x2B0) {
+        return false;
+    }
+    // CALL 0x25C
+    if m.register_mut().push(0x2B0).is_err() {
+        return interpret(m, 0x2AE);
+    }
+    frame.jump(m, 0x25C)
+}
+
+/// 0x2B0 to 0x2B0
+fn block_2b0(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x2B2) {
+        return false;
+    }
+    // CALL 0x334
+    if m.register_mut().push(0x2B2).is_err() {
+        return interpret(m, 0x2B0);
+    }
+    frame.jump(m, 0x334)
+}
+
+/// 0x2B2 to 0x2B2
+fn block_2b2(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x2B4) {
+        return false;
+    }
+    // RET
+    let next = match m.register_mut().pop() {
+        Some(next) => next,
+        None => return interpret(m, 0x2B2),
+    };
+    frame.jump(m, next)
+}
+
+/// 0x2B6 to 0x2C2
+fn block_2b6(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x2C4) {
+        return false;
+    }
+    if pc <= 0x2B6 {
+        // LD V7, 0x05
+        let v = m.register_mut().vs_mut();
+        v[0x7] = 0x05;
+        if frame.next(m, 0x2B8) {
+            return true;
+        }
+    }
+    if pc <= 0x2B8 {
+        // LD V8, 0x06
+        let v = m.register_mut().vs_mut();
+        v[0x8] = 0x06;
+        if frame.next(m, 0x2BA) {
+            return true;
+        }
+    }
+    if pc <= 0x2BA {
+        // LD V9, 0x04
+        let v = m.register_mut().vs_mut();
+        v[0x9] = 0x04;
+        if frame.next(m, 0x2BC) {
+            return true;
+        }
+    }
+    if pc <= 0x2BC {
+        // LD V1, 0x1F
+        let v = m.register_mut().vs_mut();
+        v[0x1] = 0x1F;
+        if frame.next(m, 0x2BE) {
+            return true;
+        }
+    }
+    if pc <= 0x2BE {
+        // LD V5, 0x10
+        let v = m.register_mut().vs_mut();
+        v[0x5] = 0x10;
+        if frame.next(m, 0x2C0) {
+            return true;
+        }
+    }
+    if pc <= 0x2C0 {
+        // LD V2, 0x07
+        let v = m.register_mut().vs_mut();
+        v[0x2] = 0x07;
+        if frame.next(m, 0x2C2) {
+            return true;
+        }
+    }
+    // RET
+    let next = match m.register_mut().pop() {
+        Some(next) => next,
+        None => return interpret(m, 0x2C2),
+    };
+    frame.jump(m, next)
+}
+
+/// 0x334 to 0x336
+fn block_334(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x338) {
+        return false;
+    }
+    if pc <= 0x334 {
+        // DRW V0, V1, 0x4
+        let i = m.register().i();
+        if !in_memory(i, 4) {
+            return interpret(m, 0x334);
+        }
+        let mut sprite = [0; 4];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..4]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0x1]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x336) {
+            return true;
+        }
+    }
+    // LD V6, 0x35
+    let v = m.register_mut().vs_mut();
+    v[0x6] = 0x35;
+    frame.jump(m, 0x338)
+}
+
+/// 0x338 to 0x33A
+fn block_338(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x33C) {
+        return false;
+    }
+    if pc <= 0x338 {
+        // ADD V6, 0xFF
+        let v = m.register_mut().vs_mut();
+        v[0x6] = v[0x6].wrapping_add(0xFF);
+        if frame.next(m, 0x33A) {
+            return true;
+        }
+    }
+    // SE V6, 0x00
+    let skip = m.register().vs()[0x6] == 0x00;
+    frame.jump(m, if skip { 0x33E } else { 0x33C })
+}
+
+/// 0x33C to 0x33C
+fn block_33c(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x33E) {
+        return false;
+    }
+    // JP 0x338
+    frame.jump(m, 0x338)
+}
+
+/// 0x33E to 0x33E
+fn block_33e(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x340) {
+        return false;
+    }
+    // RET
+    let next = match m.register_mut().pop() {
+        Some(next) => next,
+        None => return interpret(m, 0x33E),
+    };
+    frame.jump(m, next)
+}
+
+/// 0x340 to 0x344
+fn block_340(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x346) {
+        return false;
+    }
+    if pc <= 0x340 {
+        // LD I, 0x2B4
+        m.register_mut().set_i(0x2B4);
+        if frame.next(m, 0x342) {
+            return true;
+        }
+    }
+    if pc <= 0x342 {
+        // LD VC, V1
+        let v = m.register_mut().vs_mut();
+        v[0xC] = v[0x1];
+        if frame.next(m, 0x344) {
+            return true;
+        }
+    }
+    // SE VC, 0x1E
+    let skip = m.register().vs()[0xC] == 0x1E;
+    frame.jump(m, if skip { 0x348 } else { 0x346 })
+}
+
+/// 0x346 to 0x346
+fn block_346(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x348) {
+        return false;
+    }
+    // ADD VC, 0x01
+    let v = m.register_mut().vs_mut();
+    v[0xC] = v[0xC].wrapping_add(0x01);
+    frame.jump(m, 0x348)
+}
+
+/// 0x348 to 0x348
+fn block_348(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x34A) {
+        return false;
+    }
+    // SE VC, 0x1E
+    let skip = m.register().vs()[0xC] == 0x1E;
+    frame.jump(m, if skip { 0x34C } else { 0x34A })
+}
+
+/// 0x34A to 0x34A
+fn block_34a(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x34C) {
+        return false;
+    }
+    // ADD VC, 0x01
+    let v = m.register_mut().vs_mut();
+    v[0xC] = v[0xC].wrapping_add(0x01);
+    frame.jump(m, 0x34C)
+}
+
+/// 0x34C to 0x34C
+fn block_34c(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x34E) {
+        return false;
+    }
+    // SE VC, 0x1E
+    let skip = m.register().vs()[0xC] == 0x1E;
+    frame.jump(m, if skip { 0x350 } else { 0x34E })
+}
+
+/// 0x34E to 0x34E
+fn block_34e(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x350) {
+        return false;
+    }
+    // ADD VC, 0x01
+    let v = m.register_mut().vs_mut();
+    v[0xC] = v[0xC].wrapping_add(0x01);
+    frame.jump(m, 0x350)
+}
+
+/// 0x350 to 0x350
+fn block_350(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x352) {
+        return false;
+    }
+    // CALL 0x35E
+    if m.register_mut().push(0x352).is_err() {
+        return interpret(m, 0x350);
+    }
+    frame.jump(m, 0x35E)
+}
+
+/// 0x352 to 0x352
+fn block_352(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x354) {
+        return false;
+    }
+    // SNE VB, 0x0A
+    let skip = m.register().vs()[0xB] != 0x0A;
+    frame.jump(m, if skip { 0x356 } else { 0x354 })
+}
+
+/// 0x354 to 0x354
+fn block_354(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x356) {
+        return false;
+    }
+    // CALL 0x372
+    if m.register_mut().push(0x356).is_err() {
+        return interpret(m, 0x354);
+    }
+    frame.jump(m, 0x372)
+}
+
+/// 0x356 to 0x356
+fn block_356(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x358) {
+        return false;
+    }
+    // SNE V1, VC
+    let skip = m.register().vs()[0x1] != m.register().vs()[0xC];
+    frame.jump(m, if skip { 0x35A } else { 0x358 })
+}
+
+/// 0x358 to 0x358
+fn block_358(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x35A) {
+        return false;
+    }
+    // RET
+    let next = match m.register_mut().pop() {
+        Some(next) => next,
+        None => return interpret(m, 0x358),
+    };
+    frame.jump(m, next)
+}
+
+/// 0x35A to 0x35C
+fn block_35a(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x35E) {
+        return false;
+    }
+    if pc <= 0x35A {
+        // ADD V1, 0x01
+        let v = m.register_mut().vs_mut();
+        v[0x1] = v[0x1].wrapping_add(0x01);
+        if frame.next(m, 0x35C) {
+            return true;
+        }
+    }
+    // JP 0x350
+    frame.jump(m, 0x350)
+}
+
+/// 0x35E to 0x360
+fn block_35e(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x362) {
+        return false;
+    }
+    if pc <= 0x35E {
+        // LD V0, 0x1B
+        let v = m.register_mut().vs_mut();
+        v[0x0] = 0x1B;
+        if frame.next(m, 0x360) {
+            return true;
+        }
+    }
+    // LD VB, 0x00
+    let v = m.register_mut().vs_mut();
+    v[0xB] = 0x00;
+    frame.jump(m, 0x362)
+}
+
+/// 0x362 to 0x364
+fn block_362(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x366) {
+        return false;
+    }
+    if pc <= 0x362 {
+        // DRW V0, V1, 0x1
+        let i = m.register().i();
+        if !in_memory(i, 1) {
+            return interpret(m, 0x362);
+        }
+        let mut sprite = [0; 1];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..1]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0x1]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x364) {
+            return true;
+        }
+    }
+    // SE VF, 0x00
+    let skip = m.register().vs()[0xF] == 0x00;
+    frame.jump(m, if skip { 0x368 } else { 0x366 })
+}
+
+/// 0x366 to 0x366
+fn block_366(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x368) {
+        return false;
+    }
+    // ADD VB, 0x01
+    let v = m.register_mut().vs_mut();
+    v[0xB] = v[0xB].wrapping_add(0x01);
+    frame.jump(m, 0x368)
+}
+
+/// 0x368 to 0x36C
+fn block_368(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x36E) {
+        return false;
+    }
+    if pc <= 0x368 {
+        // DRW V0, V1, 0x1
+        let i = m.register().i();
+        if !in_memory(i, 1) {
+            return interpret(m, 0x368);
+        }
+        let mut sprite = [0; 1];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..1]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0x1]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x36A) {
+            return true;
+        }
+    }
+    if pc <= 0x36A {
+        // ADD V0, 0x01
+        let v = m.register_mut().vs_mut();
+        v[0x0] = v[0x0].wrapping_add(0x01);
+        if frame.next(m, 0x36C) {
+            return true;
+        }
+    }
+    // SE V0, 0x25
+    let skip = m.register().vs()[0x0] == 0x25;
+    frame.jump(m, if skip { 0x370 } else { 0x36E })
+}
+
+/// 0x36E to 0x36E
+fn block_36e(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x370) {
+        return false;
+    }
+    // JP 0x362
+    frame.jump(m, 0x362)
+}
+
+/// 0x370 to 0x370
+fn block_370(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x372) {
+        return false;
+    }
+    // RET
+    let next = match m.register_mut().pop() {
+        Some(next) => next,
+        None => return interpret(m, 0x370),
+    };
+    frame.jump(m, next)
+}
+
+/// 0x372 to 0x372
+fn block_372(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x374) {
+        return false;
+    }
+    // LD V0, 0x1B
+    let v = m.register_mut().vs_mut();
+    v[0x0] = 0x1B;
+    frame.jump(m, 0x374)
+}
+
+/// 0x374 to 0x378
+fn block_374(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x37A) {
+        return false;
+    }
+    if pc <= 0x374 {
+        // DRW V0, V1, 0x1
+        let i = m.register().i();
+        if !in_memory(i, 1) {
+            return interpret(m, 0x374);
+        }
+        let mut sprite = [0; 1];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..1]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0x1]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x376) {
+            return true;
+        }
+    }
+    if pc <= 0x376 {
+        // ADD V0, 0x01
+        let v = m.register_mut().vs_mut();
+        v[0x0] = v[0x0].wrapping_add(0x01);
+        if frame.next(m, 0x378) {
+            return true;
+        }
+    }
+    // SE V0, 0x25
+    let skip = m.register().vs()[0x0] == 0x25;
+    frame.jump(m, if skip { 0x37C } else { 0x37A })
+}
+
+/// 0x37A to 0x37A
+fn block_37a(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x37C) {
+        return false;
+    }
+    // JP 0x374
+    frame.jump(m, 0x374)
+}
+
+/// 0x37C to 0x380
+fn block_37c(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x382) {
+        return false;
+    }
+    if pc <= 0x37C {
+        // LD VE, V1
+        let v = m.register_mut().vs_mut();
+        v[0xE] = v[0x1];
+        if frame.next(m, 0x37E) {
+            return true;
+        }
+    }
+    if pc <= 0x37E {
+        // LD VD, VE
+        let v = m.register_mut().vs_mut();
+        v[0xD] = v[0xE];
+        if frame.next(m, 0x380) {
+            return true;
+        }
+    }
+    // ADD VE, 0xFF
+    let v = m.register_mut().vs_mut();
+    v[0xE] = v[0xE].wrapping_add(0xFF);
+    frame.jump(m, 0x382)
+}
+
+/// 0x382 to 0x384
+fn block_382(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x386) {
+        return false;
+    }
+    if pc <= 0x382 {
+        // LD V0, 0x1B
+        let v = m.register_mut().vs_mut();
+        v[0x0] = 0x1B;
+        if frame.next(m, 0x384) {
+            return true;
+        }
+    }
+    // LD VB, 0x00
+    let v = m.register_mut().vs_mut();
+    v[0xB] = 0x00;
+    frame.jump(m, 0x386)
+}
+
+/// 0x386 to 0x388
+fn block_386(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x38A) {
+        return false;
+    }
+    if pc <= 0x386 {
+        // DRW V0, VE, 0x1
+        let i = m.register().i();
+        if !in_memory(i, 1) {
+            return interpret(m, 0x386);
+        }
+        let mut sprite = [0; 1];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..1]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0xE]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x388) {
+            return true;
+        }
+    }
+    // SE VF, 0x00
+    let skip = m.register().vs()[0xF] == 0x00;
+    frame.jump(m, if skip { 0x38C } else { 0x38A })
+}
+
+/// 0x38A to 0x38A
+fn block_38a(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x38C) {
+        return false;
+    }
+    // JP 0x390
+    frame.jump(m, 0x390)
+}
+
+/// 0x38C to 0x38E
+fn block_38c(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x390) {
+        return false;
+    }
+    if pc <= 0x38C {
+        // DRW V0, VE, 0x1
+        let i = m.register().i();
+        if !in_memory(i, 1) {
+            return interpret(m, 0x38C);
+        }
+        let mut sprite = [0; 1];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..1]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0xE]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x38E) {
+            return true;
+        }
+    }
+    // JP 0x394
+    frame.jump(m, 0x394)
+}
+
+/// 0x390 to 0x392
+fn block_390(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x394) {
+        return false;
+    }
+    if pc <= 0x390 {
+        // DRW V0, VD, 0x1
+        let i = m.register().i();
+        if !in_memory(i, 1) {
+            return interpret(m, 0x390);
+        }
+        let mut sprite = [0; 1];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..1]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0x0]), usize::from(v[0xD]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x392) {
+            return true;
+        }
+    }
+    // ADD VB, 0x01
+    let v = m.register_mut().vs_mut();
+    v[0xB] = v[0xB].wrapping_add(0x01);
+    frame.jump(m, 0x394)
+}
+
+/// 0x394 to 0x396
+fn block_394(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x398) {
+        return false;
+    }
+    if pc <= 0x394 {
+        // ADD V0, 0x01
+        let v = m.register_mut().vs_mut();
+        v[0x0] = v[0x0].wrapping_add(0x01);
+        if frame.next(m, 0x396) {
+            return true;
+        }
+    }
+    // SE V0, 0x25
+    let skip = m.register().vs()[0x0] == 0x25;
+    frame.jump(m, if skip { 0x39A } else { 0x398 })
+}
+
+/// 0x398 to 0x398
+fn block_398(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x39A) {
+        return false;
+    }
+    // JP 0x386
+    frame.jump(m, 0x386)
+}
+
+/// 0x39A to 0x39A
+fn block_39a(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x39C) {
+        return false;
+    }
+    // SNE VB, 0x00
+    let skip = m.register().vs()[0xB] != 0x00;
+    frame.jump(m, if skip { 0x39E } else { 0x39C })
+}
+
+/// 0x39C to 0x39C
+fn block_39c(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x39E) {
+        return false;
+    }
+    // JP 0x3A6
+    frame.jump(m, 0x3A6)
+}
+
+/// 0x39E to 0x3A2
+fn block_39e(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x3A4) {
+        return false;
+    }
+    if pc <= 0x39E {
+        // ADD VD, 0xFF
+        let v = m.register_mut().vs_mut();
+        v[0xD] = v[0xD].wrapping_add(0xFF);
+        if frame.next(m, 0x3A0) {
+            return true;
+        }
+    }
+    if pc <= 0x3A0 {
+        // ADD VE, 0xFF
+        let v = m.register_mut().vs_mut();
+        v[0xE] = v[0xE].wrapping_add(0xFF);
+        if frame.next(m, 0x3A2) {
+            return true;
+        }
+    }
+    // SE VD, 0x01
+    let skip = m.register().vs()[0xD] == 0x01;
+    frame.jump(m, if skip { 0x3A6 } else { 0x3A4 })
+}
+
+/// 0x3A4 to 0x3A4
+fn block_3a4(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x3A6) {
+        return false;
+    }
+    // JP 0x382
+    frame.jump(m, 0x382)
+}
+
+/// 0x3A6 to 0x3A6
+fn block_3a6(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x3A8) {
+        return false;
+    }
+    // CALL 0x3C0
+    if m.register_mut().push(0x3A8).is_err() {
+        return interpret(m, 0x3A6);
+    }
+    frame.jump(m, 0x3C0)
+}
+
+/// 0x3A8 to 0x3A8
+fn block_3a8(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x3AA) {
+        return false;
+    }
+    // SE VF, 0x01
+    let skip = m.register().vs()[0xF] == 0x01;
+    frame.jump(m, if skip { 0x3AC } else { 0x3AA })
+}
+
+/// 0x3AA to 0x3AA
+fn block_3aa(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x3AC) {
+        return false;
+    }
+    // CALL 0x3C0
+    if m.register_mut().push(0x3AC).is_err() {
+        return interpret(m, 0x3AA);
+    }
+    frame.jump(m, 0x3C0)
+}
+
+/// 0x3AC to 0x3AE
+fn block_3ac(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x3B0) {
+        return false;
+    }
+    if pc <= 0x3AC {
+        // ADD VA, 0x01
+        let v = m.register_mut().vs_mut();
+        v[0xA] = v[0xA].wrapping_add(0x01);
+        if frame.next(m, 0x3AE) {
+            return true;
+        }
+    }
+    // CALL 0x3C0
+    if m.register_mut().push(0x3B0).is_err() {
+        return interpret(m, 0x3AE);
+    }
+    frame.jump(m, 0x3C0)
+}
+
+/// 0x3B0 to 0x3B6
+fn block_3b0(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x3B8) {
+        return false;
+    }
+    if pc <= 0x3B0 {
+        // LD V0, VA
+        let v = m.register_mut().vs_mut();
+        v[0x0] = v[0xA];
+        if frame.next(m, 0x3B2) {
+            return true;
+        }
+    }
+    if pc <= 0x3B2 {
+        // LD VD, 0x07
+        let v = m.register_mut().vs_mut();
+        v[0xD] = 0x07;
+        if frame.next(m, 0x3B4) {
+            return true;
+        }
+    }
+    if pc <= 0x3B4 {
+        // AND V0, VD
+        let v = m.register_mut().vs_mut();
+        v[0x0] &= v[0xD];
+        v[0xF] = 0;
+        if frame.next(m, 0x3B6) {
+            return true;
+        }
+    }
+    // SNE V0, 0x04
+    let skip = m.register().vs()[0x0] != 0x04;
+    frame.jump(m, if skip { 0x3BA } else { 0x3B8 })
+}
+
+/// 0x3B8 to 0x3B8
+fn block_3b8(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x3BA) {
+        return false;
+    }
+    // ADD V5, 0xFE
+    let v = m.register_mut().vs_mut();
+    v[0x5] = v[0x5].wrapping_add(0xFE);
+    frame.jump(m, 0x3BA)
+}
+
+/// 0x3BA to 0x3BA
+fn block_3ba(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x3BC) {
+        return false;
+    }
+    // SNE V5, 0x02
+    let skip = m.register().vs()[0x5] != 0x02;
+    frame.jump(m, if skip { 0x3BE } else { 0x3BC })
+}
+
+/// 0x3BC to 0x3BC
+fn block_3bc(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x3BE) {
+        return false;
+    }
+    // LD V5, 0x04
+    let v = m.register_mut().vs_mut();
+    v[0x5] = 0x04;
+    frame.jump(m, 0x3BE)
+}
+
+/// 0x3BE to 0x3BE
+fn block_3be(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x3C0) {
+        return false;
+    }
+    // RET
+    let next = match m.register_mut().pop() {
+        Some(next) => next,
+        None => return interpret(m, 0x3BE),
+    };
+    frame.jump(m, next)
+}
+
+/// 0x3C0 to 0x3C2
+fn block_3c0(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x3C4) {
+        return false;
+    }
+    if pc <= 0x3C0 {
+        // LD I, 0x700
+        m.register_mut().set_i(0x700);
+        if frame.next(m, 0x3C2) {
+            return true;
+        }
+    }
+    // LD [I], V2
+    let i = m.register().i();
+    if !in_memory(i, 3) {
+        return interpret(m, 0x3C2);
+    }
+    let v = *m.register().vs();
+    write(m, i, &v[..3]);
+    m.register_mut().set_i(i.wrapping_add(3));
+    frame.jump(m, 0x3C4)
+}
+
+/// 0x3C4 to 0x3C6
+fn block_3c4(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x3C8) {
+        return false;
+    }
+    if pc <= 0x3C4 {
+        // LD I, 0x804
+        m.register_mut().set_i(0x804);
+        if frame.next(m, 0x3C6) {
+            return true;
+        }
+    }
+    // LD B, VA
+    let (vx, i) = (m.register().vs()[0xA], m.register().i());
+    if !in_memory(i, 3) {
+        return interpret(m, 0x3C6);
+    }
+    write(m, i, &[vx / 100, vx / 10 % 10, vx % 10]);
+    frame.jump(m, 0x3C8)
+}
+
+/// 0x3C8 to 0x3E4
+fn block_3c8(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x3E6) {
+        return false;
+    }
+    if pc <= 0x3C8 {
+        // LD V2, [I]
+        let i = m.register().i();
+        if !in_memory(i, 3) {
+            return interpret(m, 0x3C8);
+        }
+        let mut values = [0; 3];
+        values.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..3]);
+        let r = m.register_mut();
+        r.vs_mut()[..3].copy_from_slice(&values);
+        r.set_i(i.wrapping_add(3));
+        if frame.next(m, 0x3CA) {
+            return true;
+        }
+    }
+    if pc <= 0x3CA {
+        // LD F, V0
+        let r = m.register_mut();
+        let vx = r.vs()[0x0];
+        r.set_i(0x050 + u16::from(vx & 0xF) * 5);
+        if frame.next(m, 0x3CC) {
+            return true;
+        }
+    }
+    if pc <= 0x3CC {
+        // LD VD, 0x32
+        let v = m.register_mut().vs_mut();
+        v[0xD] = 0x32;
+        if frame.next(m, 0x3CE) {
+            return true;
+        }
+    }
+    if pc <= 0x3CE {
+        // LD VE, 0x00
+        let v = m.register_mut().vs_mut();
+        v[0xE] = 0x00;
+        if frame.next(m, 0x3D0) {
+            return true;
+        }
+    }
+    if pc <= 0x3D0 {
+        // DRW VD, VE, 0x5
+        let i = m.register().i();
+        if !in_memory(i, 5) {
+            return interpret(m, 0x3D0);
+        }
+        let mut sprite = [0; 5];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..5]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0xD]), usize::from(v[0xE]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x3D2) {
+            return true;
+        }
+    }
+    if pc <= 0x3D2 {
+        // ADD VD, 0x05
+        let v = m.register_mut().vs_mut();
+        v[0xD] = v[0xD].wrapping_add(0x05);
+        if frame.next(m, 0x3D4) {
+            return true;
+        }
+    }
+    if pc <= 0x3D4 {
+        // LD F, V1
+        let r = m.register_mut();
+        let vx = r.vs()[0x1];
+        r.set_i(0x050 + u16::from(vx & 0xF) * 5);
+        if frame.next(m, 0x3D6) {
+            return true;
+        }
+    }
+    if pc <= 0x3D6 {
+        // DRW VD, VE, 0x5
+        let i = m.register().i();
+        if !in_memory(i, 5) {
+            return interpret(m, 0x3D6);
+        }
+        let mut sprite = [0; 5];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..5]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0xD]), usize::from(v[0xE]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x3D8) {
+            return true;
+        }
+    }
+    if pc <= 0x3D8 {
+        // ADD VD, 0x05
+        let v = m.register_mut().vs_mut();
+        v[0xD] = v[0xD].wrapping_add(0x05);
+        if frame.next(m, 0x3DA) {
+            return true;
+        }
+    }
+    if pc <= 0x3DA {
+        // LD F, V2
+        let r = m.register_mut();
+        let vx = r.vs()[0x2];
+        r.set_i(0x050 + u16::from(vx & 0xF) * 5);
+        if frame.next(m, 0x3DC) {
+            return true;
+        }
+    }
+    if pc <= 0x3DC {
+        // DRW VD, VE, 0x5
+        let i = m.register().i();
+        if !in_memory(i, 5) {
+            return interpret(m, 0x3DC);
+        }
+        let mut sprite = [0; 5];
+        sprite.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..5]);
+        let v = m.register().vs();
+        let (x, y) = (usize::from(v[0xD]), usize::from(v[0xE]));
+        let collision = m.display_mut().draw(x, y, &sprite, 8, false);
+        m.register_mut().vs_mut()[0xF] = collision as u8;
+        frame.vblank = true;
+        if frame.next(m, 0x3DE) {
+            return true;
+        }
+    }
+    if pc <= 0x3DE {
+        // LD I, 0x700
+        m.register_mut().set_i(0x700);
+        if frame.next(m, 0x3E0) {
+            return true;
+        }
+    }
+    if pc <= 0x3E0 {
+        // LD V2, [I]
+        let i = m.register().i();
+        if !in_memory(i, 3) {
+            return interpret(m, 0x3E0);
+        }
+        let mut values = [0; 3];
+        values.copy_from_slice(&m.memory().as_bytes()[usize::from(i)..][..3]);
+        let r = m.register_mut();
+        r.vs_mut()[..3].copy_from_slice(&values);
+        r.set_i(i.wrapping_add(3));
+        if frame.next(m, 0x3E2) {
+            return true;
+        }
+    }
+    if pc <= 0x3E2 {
+        // LD I, 0x2B4
+        m.register_mut().set_i(0x2B4);
+        if frame.next(m, 0x3E4) {
+            return true;
+        }
+    }
+    // RET
+    let next = match m.register_mut().pop() {
+        Some(next) => next,
+        None => return interpret(m, 0x3E4),
+    };
+    frame.jump(m, next)
+}
+
+/// 0x3E6 to 0x3EA
+fn block_3e6(m: &mut Machine, pc: u16, frame: &mut Frame) -> bool {
+    if !unmodified(m, pc, 0x3EC) {
+        return false;
+    }
+    if pc <= 0x3E6 {
+        // LD VA, 0x00
+        let v = m.register_mut().vs_mut();
+        v[0xA] = 0x00;
+        if frame.next(m, 0x3E8) {
+            return true;
+        }
+    }
+    if pc <= 0x3E8 {
+        // LD V0, 0x19
+        let v = m.register_mut().vs_mut();
+        v[0x0] = 0x19;
+        if frame.next(m, 0x3EA) {
+            return true;
+        }
+    }
+    // RET
+    let next = match m.register_mut().pop() {
+        Some(next) => next,
+        None => return interpret(m, 0x3EA),
+    };
+    frame.jump(m, next)
+}
+
+/// The instructions left to run in the current frame
+struct Frame {
+    budget: u32,
+    /// Set when a draw waits for the vertical blank, which ends the frame
+    vblank: bool,
+}
+
+impl Frame {
+    /// Count an executed instruction, returns whether the frame is over, with the PC at `next`
+    fn next(&mut self, m: &mut Machine, next: u16) -> bool {
+        self.budget -= 1;
+        let over = self.budget == 0 || self.vblank;
+        if over {
+            m.register_mut().set_pc(next);
+        }
+        over
+    }
+
+    /// Count an executed instruction and carry on at `target`, returns true to leave the block
+    fn jump(&mut self, m: &mut Machine, target: u16) -> bool {
+        self.budget -= 1;
+        m.register_mut().set_pc(target & 0xFFF);
+        true
+    }
+}
+
+/// Leave the instruction at `pc` to the interpreter
+fn interpret(m: &mut Machine, pc: u16) -> bool {
+    m.register_mut().set_pc(pc);
+    false
+}
+
+/// Whether the code from `pc` to `end` is still the code that was recompiled
+fn unmodified(m: &Machine, pc: u16, end: u16) -> bool {
+    let (pc, end, origin) = (usize::from(pc), usize::from(end), usize::from(ORIGIN));
+    m.memory().as_bytes()[pc..end] == ROM[pc - origin..end - origin]
+}
+
+/// Whether the `len` bytes from `address` are in memory
+fn in_memory(address: u16, len: usize) -> bool {
+    usize::from(address) + len <= Memory::MEMORY_SIZE
+}
+
+/// Write `values` from `address`, which is checked to be in memory
+fn write(m: &mut Machine, address: u16, values: &[u8]) {
+    let memory = m.memory_mut();
+    for (offset, value) in values.iter().enumerate() {
+        memory
+            .poke(usize::from(address) + offset, *value)
+            .expect("the address is in memory");
+    }
+}
+
+/// Run a 60Hz frame, see `Machine::run_frame`
+pub fn run_frame(m: &mut Machine) -> Result<(), MachineError> {
+    if let Some(fault) = m.fault() {
+        return Err(*fault);
+    }
+    let mut frame = Frame {
+        budget: m.tickrate(),
+        vblank: false,
+    };
+    while frame.budget > 0 && !frame.vblank {
+        if !run_block(m, &mut frame) {
+            m.step()?;
+            frame.budget -= 1;
+            frame.vblank = m.waiting_for_vblank();
+        }
+    }
+    m.end_frame();
+    Ok(())
+}
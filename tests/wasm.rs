@@ -0,0 +1,62 @@
+//! Runs the web build of the library in a WebAssembly runtime, driving it like a page would.
+//!
+//! The module has to be built first, so the test is ignored by default:
+//!
+//! ```sh
+//! cargo build --lib --release --target wasm32-unknown-unknown
+//! cargo test --test wasm -- --ignored
+//! ```
+use wasmi::{Engine, Instance, Linker, Module, Store, TypedFunc, WasmParams, WasmResults};
+
+const MODULE: &str = "./target/wasm32-unknown-unknown/release/chirp.wasm";
+
+fn export<P, R>(instance: &Instance, store: &Store<()>, name: &str) -> TypedFunc<P, R>
+where
+    P: WasmParams,
+    R: WasmResults,
+{
+    instance.get_typed_func(store, name).unwrap()
+}
+
+#[test]
+#[ignore]
+fn test_tetris() {
+    let wasm = std::fs::read(MODULE).expect("build the web module first");
+    let engine = Engine::default();
+    let module = Module::new(&engine, &wasm[..]).unwrap();
+    let mut store = Store::new(&engine, ());
+    let instance = <Linker<()>>::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap()
+        .start(&mut store)
+        .unwrap();
+    let memory = instance.get_memory(&store, "memory").unwrap();
+    let alloc = export::<i32, i32>(&instance, &store, "chirp_alloc");
+    let free = export::<(i32, i32), ()>(&instance, &store, "chirp_free");
+    let load = export::<(i32, i32), i32>(&instance, &store, "chirp_load");
+    let set_key = export::<(i32, i32), ()>(&instance, &store, "chirp_set_key");
+    let run_frame = export::<(), i32>(&instance, &store, "chirp_run_frame");
+    let framebuffer = export::<(), i32>(&instance, &store, "chirp_framebuffer");
+    let width = export::<(), i32>(&instance, &store, "chirp_width");
+    let height = export::<(), i32>(&instance, &store, "chirp_height");
+
+    let rom = std::fs::read("./games/tetris.ch8").unwrap();
+    let len = rom.len() as i32;
+    let ptr = alloc.call(&mut store, len).unwrap();
+    memory.write(&mut store, ptr as usize, &rom).unwrap();
+    assert_eq!(0, load.call(&mut store, (ptr, len)).unwrap());
+    free.call(&mut store, (ptr, len)).unwrap();
+
+    set_key.call(&mut store, (0x5, 1)).unwrap();
+    for _ in 0..60 {
+        assert_eq!(0, run_frame.call(&mut store, ()).unwrap());
+    }
+
+    let width = width.call(&mut store, ()).unwrap() as usize;
+    let height = height.call(&mut store, ()).unwrap() as usize;
+    assert_eq!((64, 32), (width, height));
+    let ptr = framebuffer.call(&mut store, ()).unwrap();
+    let mut pixels = vec![0; width * height];
+    memory.read(&store, ptr as usize, &mut pixels).unwrap();
+    assert!(pixels.contains(&1));
+}